@@ -0,0 +1,94 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+
+//!
+//! Measure the time to parse the lines of a label file with the plain fast path of `parse_label`
+//! and with the regular expression of `parse_label_regex`, which `parse_label` falls back to for
+//! lines with short labels, names or tags:
+//!
+//! ```text
+//! cargo bench --bench parse
+//! ```
+//!
+
+extern crate rand;
+extern crate rt_datastructure;
+
+use std::time::{Duration, Instant};
+
+use rand::{Rng, SeedableRng, StdRng};
+
+use rt_datastructure::error::Error;
+use rt_datastructure::input::parse;
+use rt_datastructure::primitives::label::Label;
+
+const LINES: usize = 200_000;
+const RUNS: usize = 5;
+
+fn main() {
+    let seed: &[_] = &[4, 2];
+    let mut rng: StdRng = SeedableRng::from_seed(seed);
+
+    let lines: Vec<String> = (0..LINES)
+        .map(|i| {
+                 format!("{} {} {} {} {} {} {} 'Label {}'",
+                         rng.gen_range(53., 53.25),
+                         rng.gen_range(8.5, 9.),
+                         i,
+                         rng.gen_range(0, 20000),
+                         rng.gen_range(0., 1.),
+                         rng.gen_range(100., 5000.),
+                         rng.gen_range(8., 16.),
+                         i)
+             })
+        .collect();
+
+    let plain = measure(&lines, parse::parse_label);
+    let regex = measure(&lines, parse::parse_label_regex);
+
+    println!("{} lines parsed in {:?} ({:?} per line) by parse_label",
+             LINES,
+             plain,
+             plain / LINES as u32);
+    println!("{} lines parsed in {:?} ({:?} per line) by parse_label_regex",
+             LINES,
+             regex,
+             regex / LINES as u32);
+}
+
+// Parse all lines with the given function and return the fastest of several runs to reduce the
+// noise.
+fn measure<F>(lines: &[String], parse: F) -> Duration
+    where F: Fn(&str) -> Result<Label, Error>
+{
+    let mut best: Option<Duration> = None;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let parsed = lines.iter().filter(|l| parse(l).is_ok()).count();
+        let elapsed = start.elapsed();
+
+        assert!(parsed == lines.len(), "Only {} of {} lines parsed", parsed, lines.len());
+        best = Some(best.map_or(elapsed, |b| b.min(elapsed)));
+    }
+
+    best.unwrap_or_default()
+}
@@ -0,0 +1,87 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//!
+//! Measure the query time of the 3D PST on dense viewports, where most of the time is spent in
+//! the bounding box tests. Compare the results with and without the `simd` feature:
+//!
+//! ```text
+//! cargo bench --bench queries
+//! cargo bench --bench queries --features simd
+//! ```
+//!
+
+extern crate rand;
+extern crate rt_datastructure;
+
+use rand::{Rng, SeedableRng, StdRng};
+
+use rt_datastructure::benchmark;
+use rt_datastructure::primitives::bbox::BBox;
+use rt_datastructure::primitives::label::Label;
+use rt_datastructure::pst_3d::Pst3d;
+
+const LABELS: usize = 200_000;
+const QUERIES: usize = 10_000;
+const RUNS: usize = 5;
+
+fn main() {
+    let seed: &[_] = &[4, 2];
+    let mut rng: StdRng = SeedableRng::from_seed(seed);
+
+    // a dense city sized region, the queries are small viewports within it
+    let labels: Vec<Label> = (0..LABELS)
+        .map(|i| {
+                 Label::new(rng.gen_range(8.5, 9.),
+                            rng.gen_range(53., 53.25),
+                            rng.gen_range(0., 1.),
+                            i as i64,
+                            1,
+                            1.5,
+                            "L".to_string())
+             })
+        .collect();
+    let queries: Vec<(BBox, f64)> = (0..QUERIES)
+        .map(|_| {
+                 let x = rng.gen_range(8.5, 8.95);
+                 let y = rng.gen_range(53., 53.2);
+                 (BBox::new(x, y, x + 0.05, y + 0.05), rng.gen_range(0.9, 1.))
+             })
+        .collect();
+
+    // report the fastest of several runs to reduce the noise
+    let mut best = None;
+    let mut reported = 0;
+    for _ in 0..RUNS {
+        let r = benchmark::run::<Pst3d>(labels.clone(), &queries);
+        reported = r.get_reported();
+        best = Some(best.map_or(r.get_query_time(), |b| r.get_query_time().min(b)));
+    }
+
+    if let Some(best) = best {
+        println!("simd: {}, {} queries in {:?} ({:?} per query), {} labels reported",
+                 cfg!(feature = "simd"),
+                 QUERIES,
+                 best,
+                 best / QUERIES as u32,
+                 reported);
+    }
+}
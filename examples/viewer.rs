@@ -0,0 +1,212 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//!
+//! A minimal viewer rendering the query results of the library on a Leaflet map.
+//!
+//! The viewer serves a single page and answers the Web Mercator tile requests of the page while
+//! panning and zooming. Each tile is queried by `GeoPst3d::get_tile` and thinned by the query
+//! options: labels closer than the merge distance are merged and only the `limit` labels with
+//! the largest t values of a tile are rendered.
+//!
+//! ```text
+//! cargo run --features viewer --example viewer -- <input_path> [port] [limit]
+//! ```
+//!
+//! Afterwards open http://localhost:8080/ (or the given port) in a browser.
+//!
+
+extern crate rt_datastructure;
+
+use rt_datastructure::input;
+use rt_datastructure::output;
+use rt_datastructure::primitives::label::Label;
+use rt_datastructure::pst_3d::GeoPst3d;
+use rt_datastructure::pst_3d::query;
+
+use std::cmp::Ordering;
+use std::env;
+use std::error::Error;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process;
+
+// The size of a map tile in pixels.
+const TILE_SIZE: f64 = 256.;
+// Labels closer than this number of pixels are merged into one.
+const MERGE_PIXELS: f64 = 16.;
+
+const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <title>runtime datastructure viewer</title>
+  <link rel="stylesheet" href="https://unpkg.com/leaflet@1.9.4/dist/leaflet.css">
+  <script src="https://unpkg.com/leaflet@1.9.4/dist/leaflet.js"></script>
+  <style>html, body, #map { height: 100%; margin: 0; }</style>
+</head>
+<body>
+  <div id="map"></div>
+  <script>
+    var map = L.map('map').setView([53.08, 8.81], 12);
+    L.tileLayer('https://{s}.tile.openstreetmap.org/{z}/{x}/{y}.png', {
+      attribution: '&copy; OpenStreetMap contributors'
+    }).addTo(map);
+
+    // the labels of each tile are kept in a layer group removed with the tile
+    var tiles = {};
+    var labels = L.gridLayer();
+    labels.createTile = function (coords, done) {
+      var key = coords.z + '/' + coords.x + '/' + coords.y;
+      var tile = document.createElement('div');
+      tiles[key] = null;
+      fetch('/tiles/' + key).then(function (r) { return r.json(); }).then(function (json) {
+        var group = L.layerGroup();
+        json.features.forEach(function (f) {
+          var c = f.geometry.coordinates;
+          L.marker([c[1], c[0]], {
+            icon: L.divIcon({ className: '', html: f.properties.name, iconSize: null })
+          }).addTo(group);
+        });
+        if (tiles[key] === null) {
+          tiles[key] = group.addTo(map);
+        }
+      }).finally(function () { done(null, tile); });
+
+      return tile;
+    };
+    labels.on('tileunload', function (e) {
+      var key = e.coords.z + '/' + e.coords.x + '/' + e.coords.y;
+      if (tiles[key]) {
+        map.removeLayer(tiles[key]);
+      }
+      delete tiles[key];
+    });
+    labels.addTo(map);
+  </script>
+</body>
+</html>
+"#;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        println!("Usage: {} <input_path> [port] [limit]", args[0]);
+        process::exit(1);
+    }
+
+    let port: u16 = args.get(2).and_then(|p| p.parse().ok()).unwrap_or(8080);
+    let limit: usize = args.get(3).and_then(|l| l.parse().ok()).unwrap_or(50);
+
+    let options = input::ImportOptions::new();
+    let report = input::import_labels(&args[1], &options).unwrap_or_else(|err| {
+        println!("Could not read the given input file {}: {}", args[1], err);
+        process::exit(1);
+    });
+    for e in report.get_skipped() {
+        println!("Skipped a malformed line: {}", e);
+    }
+    let labels = report.into_labels();
+    println!("Successfully imported {} labels", labels.len());
+
+    let pst = GeoPst3d::new(labels);
+
+    let listener = TcpListener::bind(("127.0.0.1", port)).unwrap_or_else(|err| {
+        println!("Could not listen on port {}: {}", port, err);
+        process::exit(1);
+    });
+    println!("Serving the viewer on http://localhost:{}/", port);
+
+    for stream in listener.incoming() {
+        let result = stream.map_err(From::from).and_then(|s| handle(s, &pst, limit));
+        if let Err(err) = result {
+            println!("Could not answer request: {}", err);
+        }
+    }
+}
+
+// Answer a single http request: Either the viewer page or the labels of a tile.
+fn handle(mut stream: TcpStream, pst: &GeoPst3d, limit: usize) -> Result<(), Box<dyn Error>> {
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+
+    let target = request_line.split_whitespace().nth(1).unwrap_or("/");
+
+    let (status, content_type, body) = if target == "/" {
+        ("200 OK", "text/html", PAGE.to_string())
+    } else if let Some(tile) = target.strip_prefix("/tiles/") {
+        match parse_tile(tile) {
+            Some((z, x, y)) => {
+                match tile_labels(pst, z, x, y, limit) {
+                    Ok(json) => ("200 OK", "application/json", json),
+                    Err(err) => ("400 Bad Request", "text/plain", err.to_string()),
+                }
+            }
+            None => ("400 Bad Request", "text/plain", "invalid tile".to_string()),
+        }
+    } else {
+        ("404 Not Found", "text/plain", "not found".to_string())
+    };
+
+    write!(stream,
+           "HTTP/1.1 {}\r\nContent-Type: {}; charset=utf-8\r\nContent-Length: {}\r\n\
+            Connection: close\r\n\r\n{}",
+           status,
+           content_type,
+           body.len(),
+           body)?;
+
+    Ok(())
+}
+
+// Parse the tile coordinates from a path of the form z/x/y.
+fn parse_tile(path: &str) -> Option<(u32, u32, u32)> {
+    let mut it = path.split('/').map(|v| v.parse::<u32>().ok());
+    match (it.next(), it.next(), it.next(), it.next()) {
+        (Some(Some(z)), Some(Some(x)), Some(Some(y)), None) => Some((z, x, y)),
+        _ => None,
+    }
+}
+
+// Query the labels of the tile z/x/y and encode them as GeoJSON. The labels are eliminated at a
+// scale given in degrees per pixel, the tile query returns the labels still present at the zoom
+// level of the tile.
+fn tile_labels(pst: &GeoPst3d,
+               z: u32,
+               x: u32,
+               y: u32,
+               limit: usize)
+               -> Result<String, Box<dyn Error>> {
+    let pixel_size = 360. / (TILE_SIZE * (1u64 << z) as f64);
+
+    let mut labels = pst.get_tile(z, x, y, pixel_size)?;
+    // the limit keeps the labels with the largest t values
+    labels.sort_by(|a, b| b.get_t().partial_cmp(&a.get_t()).unwrap_or(Ordering::Equal));
+
+    let mut options = query::QueryOptions::new();
+    options.set_merge_distance(MERGE_PIXELS, pixel_size);
+    options.set_limit(Some(limit));
+
+    let results = query::apply_options(labels, pixel_size, &options);
+    let labels: Vec<&Label> = results.iter().map(|r| r.get_label()).collect();
+
+    Ok(output::to_geojson(&labels, &output::EncodeOptions::new()))
+}
@@ -44,6 +44,7 @@ typedef struct C_Label {
 	int64_t osm_id;
 	int32_t prio;
 	double lbl_fac;
+	double label_length;
 	char* label;
 } C_Label;
 
@@ -51,9 +52,13 @@ typedef struct C_Label {
 /// A struct represents a basic C_Label vector, i.e. its size and the data (the contained C_Label
 /// objects).
 ///
+/// The generation id identifies the queried dataset. Results of the same dataset have the same
+/// generation id. For an invalid data structure the generation id is 0.
+///
 typedef struct C_Result {
 	uint64_t size;
 	C_Label* data;
+	uint64_t generation;
 } C_Result;
 
 ///
@@ -65,20 +70,127 @@ typedef struct C_Result {
 ///
 Datastructure *init(char const* input_path);
 
+///
+/// Set the memory budget in bytes for all following calls of `init`.
+///
+/// If the imported labels exceed the budget, the labels with the lowest t values are dropped
+/// until the remaining labels fit. The dropped labels are reported on stdout. A budget of 0
+/// disables the budget.
+///
+void set_memory_budget(uint64_t bytes);
+
+///
+/// Set the import options for all following calls of `init` and `append_labels`.
+///
+/// In strict mode the first malformed record fails the import, otherwise malformed records are
+/// skipped. The lines of label files are parsed on the given number of threads, 0 is treated as
+/// 1. By default the import is lenient and single threaded.
+///
+void set_import_options(bool strict, uint32_t threads);
+
 ///
 /// Check if the initialization was successfull and the returned DataStructure object is valid.
 ///
 bool is_good(Datastructure *ds);
 
+///
+/// Get the numeric code of the error that occured during the initialization or 0 if the
+/// initialization was successfull.
+///
+/// The codes are 1: Io, 2: Parse, 3: Validation, 4: Query, 5: Serialization, 6: Ffi,
+/// 7: Shared, 8: Invariant.
+///
+int32_t get_error_code(Datastructure *ds);
+
+///
+/// Load the labels of the file defined by input_path into a valid data structure, compare
+/// `DataStructure::append`. Labels of the file replace the labels with the same osm id.
+///
+/// Returns 0 on success or the numeric code of the error, compare `get_error_code`. On failure
+/// the data structure is left unchanged.
+///
+int32_t append_labels(Datastructure *ds, char const* input_path);
+
+///
+/// Enable caching of query results.
+///
+/// Up to capacity results of identical queries are answered from the cache for ttl_ms
+/// milliseconds. Enabling the cache again discards all cached results.
+///
+void enable_cache(Datastructure *ds, uint64_t capacity, uint64_t ttl_ms);
+
+///
+/// Disable caching of query results and discard all cached results.
+///
+void disable_cache(Datastructure *ds);
+
+///
+/// Invalidate all cached query results, e.g. after the underlying data has changed.
+///
+void invalidate_cache(Datastructure *ds);
+
+///
+/// Limit the number of labels in all following results of `get_data`. Only the labels with the
+/// largest t values are returned. A limit of 0 disables the limit.
+///
+void set_result_limit(Datastructure *ds, uint64_t limit);
+
+///
+/// Round the coordinates of all following results of `get_data` to the given number of decimal
+/// places. For a negative number of decimals the coordinates are returned unchanged.
+///
+void set_coordinate_precision(Datastructure *ds, int32_t decimals);
+
+///
+/// Snap the coordinates of all following results of `get_data` to the nearest multiple of
+/// grid_size. For a grid_size <= 0 the coordinates are returned unchanged.
+///
+void set_coordinate_grid(Datastructure *ds, double grid_size);
+
+///
+/// Set the name fallback chain used to resolve the label strings of all following results of
+/// `get_data` as a comma separated list of name keys, e.g. "name:de,name:en,name".
+///
+/// The first key a label has a name for is used. Labels without a name for any of the keys are
+/// returned with their label string.
+///
+void set_name_fallback(Datastructure *ds, char const* fallback);
+
 ///
 /// Get the labels contained in the specified bounding box with a t value >= min_t.
 ///
+/// The label strings are resolved with the name fallback chain set by `set_name_fallback`.
+/// Interior NUL bytes of the label strings are removed.
+///
 /// The ownership of the result returned by this function is passed to the caller.
 /// To safely deallocate the result pass it to the function `free_result`.
 C_Result get_data(Datastructure *ds, double min_t, double min_x, double max_x, double min_y, double max_y);
 
+///
+/// Get the labels contained in the specified bounding box with a t value >= min_t.
+///
+/// The names of the labels are resolved with the given comma separated name fallback chain,
+/// e.g. "name:de,name:en,name", instead of the one configured by `set_name_fallback`.
+///
+/// The ownership of the result returned by this function is passed to the caller.
+/// To safely deallocate the result pass it to the function `free_result`.
+C_Result get_data_localized(Datastructure *ds, double min_t, double min_x, double max_x, double min_y, double max_y, char const* fallback);
+
+///
+/// Get the labels contained in the specified bounding box with a t value >= min_t.
+///
+/// For requests with min_t >= short_min_t the label string of labels having a short label is
+/// replaced by their short label.
+///
+/// The ownership of the result returned by this function is passed to the caller.
+/// To safely deallocate the result pass it to the function `free_result`.
+C_Result get_data_abbreviated(Datastructure *ds, double min_t, double min_x, double max_x, double min_y, double max_y, double short_min_t);
+
 
 ///
-/// Deallocate a result returned by `get_data`.
+/// Deallocate a result returned by `get_data` or one of its variants, i.e. the label array and
+/// the label strings of the result.
+///
+/// Each result must be passed exactly once, the labels must not be accessed afterwards.
 ///
 void free_result(C_Result result);
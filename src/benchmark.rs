@@ -0,0 +1,176 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::time::{Duration, Instant};
+
+use primitives::bbox::BBox;
+use primitives::label::Label;
+use pst_3d::Pst3d;
+
+///
+/// The "3-sided" priority range reporting query: report all labels within a bounding box with a
+/// t value larger than a given min_t.
+///
+/// Implement the trait for a data structure to compare it with the 3D PST on identical data.
+///
+pub trait RangeReporting {
+    ///
+    /// Construct the data structure from the given labels.
+    ///
+    fn build(labels: Vec<Label>) -> Self where Self: Sized;
+
+    ///
+    /// A short name of the data structure used in benchmark results.
+    ///
+    fn name(&self) -> String;
+
+    ///
+    /// Report all labels contained in bbox with a t value > min_t.
+    ///
+    fn report<'a>(&'a self, bbox: &BBox, min_t: f64) -> Vec<&'a Label>;
+}
+
+impl RangeReporting for Pst3d {
+    fn build(labels: Vec<Label>) -> Self {
+        Pst3d::new(labels)
+    }
+
+    fn name(&self) -> String {
+        "3D PST".to_string()
+    }
+
+    fn report<'a>(&'a self, bbox: &BBox, min_t: f64) -> Vec<&'a Label> {
+        self.get(bbox, min_t)
+    }
+}
+
+///
+/// A simple baseline: the labels are sorted by decreasing t and each query scans the prefix of
+/// labels with a sufficiently large t value.
+///
+pub struct SortedScan {
+    m_labels: Vec<Label>,
+}
+
+impl RangeReporting for SortedScan {
+    fn build(mut labels: Vec<Label>) -> Self {
+        labels.sort_by(|first, second| Label::order_t(second, first));
+
+        SortedScan { m_labels: labels }
+    }
+
+    fn name(&self) -> String {
+        "sorted scan".to_string()
+    }
+
+    fn report<'a>(&'a self, bbox: &BBox, min_t: f64) -> Vec<&'a Label> {
+        self.m_labels
+            .iter()
+            .take_while(|l| l.get_t() > min_t)
+            .filter(|l| bbox.is_contained(l))
+            .collect()
+    }
+}
+
+///
+/// The result of a benchmark run of a single data structure.
+///
+pub struct BenchmarkResult {
+    m_name: String,
+    m_build_time: Duration,
+    m_query_time: Duration,
+    m_reported: usize,
+}
+
+impl BenchmarkResult {
+    ///
+    /// Get the name of the benchmarked data structure.
+    ///
+    pub fn get_name(&self) -> &String {
+        &self.m_name
+    }
+
+    ///
+    /// Get the time required to build the data structure.
+    ///
+    pub fn get_build_time(&self) -> Duration {
+        self.m_build_time
+    }
+
+    ///
+    /// Get the total time required to answer all queries.
+    ///
+    pub fn get_query_time(&self) -> Duration {
+        self.m_query_time
+    }
+
+    ///
+    /// Get the total number of reported labels over all queries.
+    ///
+    pub fn get_reported(&self) -> usize {
+        self.m_reported
+    }
+}
+
+///
+/// Build the data structure R from the labels and answer all queries (bbox, min_t).
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::benchmark::{self, SortedScan};
+/// use rt_datastructure::primitives::{bbox, label};
+/// use rt_datastructure::pst_3d::Pst3d;
+///
+/// let mut v = Vec::new();
+/// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
+/// v.push(label::Label::new(2., 3., 9., 2, 1, 1.5, "T2".to_string()));
+/// v.push(label::Label::new(3., 4., 8., 3, 1, 1.5, "T3".to_string()));
+/// v.push(label::Label::new(4., 5., 7., 4, 1, 1.5, "T4".to_string()));
+///
+/// let queries = vec![(bbox::BBox::new(0., 0., 10., 10.), 7.5),
+///                    (bbox::BBox::new(2., 3., 4., 4.), 0.)];
+///
+/// let pst = benchmark::run::<Pst3d>(v.clone(), &queries);
+/// let scan = benchmark::run::<SortedScan>(v, &queries);
+///
+/// assert!(pst.get_reported() == 5);
+/// assert!(scan.get_reported() == pst.get_reported());
+/// ```
+///
+pub fn run<R: RangeReporting>(labels: Vec<Label>, queries: &[(BBox, f64)]) -> BenchmarkResult {
+    let start = Instant::now();
+    let structure = R::build(labels);
+    let build_time = start.elapsed();
+
+    let mut reported = 0;
+    let start = Instant::now();
+    for &(ref bbox, min_t) in queries {
+        reported += structure.report(bbox, min_t).len();
+    }
+    let query_time = start.elapsed();
+
+    BenchmarkResult {
+        m_name: structure.name(),
+        m_build_time: build_time,
+        m_query_time: query_time,
+        m_reported: reported,
+    }
+}
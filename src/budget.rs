@@ -0,0 +1,169 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::mem;
+
+use primitives::label::Label;
+use primitives::point::Point;
+use pst_3d::Pst3d;
+
+///
+/// A report of the labels dropped to fit a label set into a memory budget.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct DownsampleReport {
+    m_dropped: usize,
+    m_max_dropped_t: Option<f64>,
+    m_estimated_size: usize,
+}
+
+impl DownsampleReport {
+    ///
+    /// Get the number of dropped labels.
+    ///
+    pub fn get_dropped(&self) -> usize {
+        self.m_dropped
+    }
+
+    ///
+    /// Get the largest t value of the dropped labels or None if no label was dropped.
+    ///
+    /// All labels with a t value below this value were dropped.
+    ///
+    pub fn get_max_dropped_t(&self) -> Option<f64> {
+        self.m_max_dropped_t
+    }
+
+    ///
+    /// Get the estimated memory size in bytes of the remaining labels.
+    ///
+    pub fn get_estimated_size(&self) -> usize {
+        self.m_estimated_size
+    }
+}
+
+impl fmt::Display for DownsampleReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.m_max_dropped_t {
+            Some(t) => {
+                write!(f,
+                       "dropped {} labels with t <= {} to fit {} bytes",
+                       self.m_dropped,
+                       t,
+                       self.m_estimated_size)
+            }
+            None => write!(f, "no labels dropped, {} bytes used", self.m_estimated_size),
+        }
+    }
+}
+
+///
+/// Estimate the memory in bytes a label occupies when stored in a 3D PST.
+///
+/// The estimate contains the tree node and the heap allocations of the label, i.e. the label
+/// strings, the names, the tags and the geometry.
+///
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::budget;
+/// use rt_datastructure::primitives::label;
+///
+/// let mut l = label::Label::new(1., 2., 9., 1, 1, 1.5, "T1".to_string());
+/// let size = budget::estimate_size(&l);
+///
+/// l.set_tag("place".to_string(), "city".to_string());
+/// assert!(budget::estimate_size(&l) >= size + "placecity".len());
+/// ```
+///
+pub fn estimate_size(l: &Label) -> usize {
+    let short_label = l.get_short_label().map_or(0, |s| s.capacity());
+    let pairs = |v: &Vec<(String, String)>| {
+        v.capacity() * mem::size_of::<(String, String)>() +
+        v.iter().map(|(k, v)| k.capacity() + v.capacity()).sum::<usize>()
+    };
+    let geometry = l.get_geometry()
+        .map_or(0, |g| g.get_points().capacity() * mem::size_of::<Point>());
+
+    Pst3d::<Label>::get_node_size() + l.get_label().capacity() + short_label +
+    pairs(l.get_names()) + pairs(l.get_tags()) + geometry
+}
+
+///
+/// Drop the labels with the lowest t values until the estimated memory size of the remaining
+/// labels fits into budget bytes.
+///
+/// The order of the remaining labels is kept. Labels with equal t values are dropped in reverse
+/// input order.
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::budget;
+/// use rt_datastructure::primitives::label;
+///
+/// let mut v = Vec::new();
+/// v.push(label::Label::new(1., 2., 9., 1, 1, 1.5, "T1".to_string()));
+/// v.push(label::Label::new(2., 3., 3., 2, 1, 1.5, "T2".to_string()));
+/// v.push(label::Label::new(3., 4., 5., 3, 1, 1.5, "T3".to_string()));
+///
+/// let size = budget::estimate_size(&v[0]);
+/// let report = budget::fit_to_budget(&mut v, 2 * size);
+///
+/// assert!(v.len() == 2);
+/// assert!(v[0].get_osm_id() == 1 && v[1].get_osm_id() == 3);
+/// assert!(report.get_dropped() == 1);
+/// assert!(report.get_max_dropped_t() == Some(3.));
+/// assert!(report.get_estimated_size() <= 2 * size);
+/// ```
+///
+pub fn fit_to_budget(labels: &mut Vec<Label>, budget: usize) -> DownsampleReport {
+    let mut order: Vec<usize> = (0..labels.len()).collect();
+    order.sort_by(|a, b| {
+                      let t_a = labels[*a].get_t();
+                      let t_b = labels[*b].get_t();
+                      t_b.partial_cmp(&t_a).unwrap_or(Ordering::Equal)
+                  });
+
+    let mut keep = vec![false; labels.len()];
+    let mut size = 0;
+    let mut max_dropped_t: Option<f64> = None;
+    for idx in order {
+        let l_size = estimate_size(&labels[idx]);
+        if max_dropped_t.is_none() && size + l_size <= budget {
+            size += l_size;
+            keep[idx] = true;
+        } else if max_dropped_t.is_none() {
+            max_dropped_t = Some(labels[idx].get_t());
+        }
+    }
+
+    let count = labels.len();
+    let mut keep_iter = keep.iter();
+    labels.retain(|_| *keep_iter.next().unwrap_or(&false));
+
+    DownsampleReport {
+        m_dropped: count - labels.len(),
+        m_max_dropped_t: max_dropped_t,
+        m_estimated_size: size,
+    }
+}
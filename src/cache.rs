@@ -0,0 +1,283 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use primitives::bbox::BBox;
+
+///
+/// The cache key consists of the bit patterns of the (rounded) bounding box and min_t.
+///
+type CacheKey = (u64, u64, u64, u64, u64);
+
+///
+/// A cached query result together with its creation time, the time of its last use and the cache
+/// generation it belongs to.
+///
+struct CacheEntry {
+    m_created: Instant,
+    m_last_used: Cell<Instant>,
+    m_generation: u64,
+    m_indices: Vec<u32>,
+}
+
+///
+/// A small in-memory cache for query results.
+///
+/// The cache stores the label indices of a query result for the (bbox, min_t) pair. An entry is
+/// valid for the given time to live and as long as the cache generation did not change.
+/// Invalidating the cache starts a new generation, so does a change of the generation of the
+/// queried data, see `sync_data_generation`. If the cache is full, the least recently used entry
+/// is replaced.
+///
+/// By default only identical queries share an entry. With a key grid the bounding box is rounded
+/// to the grid before the lookup, so nearly identical queries (e.g. of a panning map) are
+/// answered by the same entry.
+///
+pub struct ResultCache {
+    m_capacity: usize,
+    m_ttl: Duration,
+    m_key_grid: f64,
+    m_generation: u64,
+    m_data_generation: Option<u64>,
+    m_entries: HashMap<CacheKey, CacheEntry>,
+}
+
+impl ResultCache {
+    ///
+    /// Initialize a new empty cache that holds at most capacity results for the given time.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::Duration;
+    /// use rt_datastructure::cache;
+    /// use rt_datastructure::primitives::bbox;
+    ///
+    /// let mut c = cache::ResultCache::new(2, Duration::from_secs(60));
+    /// let bb = bbox::BBox::new(0., 0., 1., 1.);
+    ///
+    /// assert!(c.get(&bb, 1.).is_none());
+    /// c.insert(&bb, 1., vec![1, 2, 3]);
+    /// assert!(*c.get(&bb, 1.).unwrap() == vec![1, 2, 3]);
+    /// assert!(c.get(&bb, 2.).is_none());
+    ///
+    /// c.invalidate();
+    /// assert!(c.get(&bb, 1.).is_none());
+    /// assert!(c.get_generation() == 1);
+    /// ```
+    ///
+    /// Entries expire after the time to live:
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use rt_datastructure::cache;
+    /// use rt_datastructure::primitives::bbox;
+    ///
+    /// let mut c = cache::ResultCache::new(2, Duration::from_secs(0));
+    /// let bb = bbox::BBox::new(0., 0., 1., 1.);
+    ///
+    /// c.insert(&bb, 1., vec![1, 2, 3]);
+    /// assert!(c.get(&bb, 1.).is_none());
+    /// ```
+    ///
+    pub fn new(capacity: usize, ttl: Duration) -> ResultCache {
+        ResultCache {
+            m_capacity: capacity,
+            m_ttl: ttl,
+            m_key_grid: 0.,
+            m_generation: 0,
+            m_data_generation: None,
+            m_entries: HashMap::new(),
+        }
+    }
+
+    ///
+    /// Get the cached label indices for the given query if there is a valid entry.
+    ///
+    pub fn get(&self, bbox: &BBox, min_t: f64) -> Option<&Vec<u32>> {
+        match self.m_entries.get(&cache_key(bbox, min_t, self.m_key_grid)) {
+            Some(e) if self.is_valid(e) => {
+                e.m_last_used.set(Instant::now());
+                Some(&e.m_indices)
+            }
+            _ => None,
+        }
+    }
+
+    ///
+    /// Round the bounding boxes of queries to the nearest multiple of grid before the lookup. For
+    /// a grid <= 0 only identical bounding boxes share an entry. Changing the grid invalidates
+    /// the cache.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::Duration;
+    /// use rt_datastructure::cache;
+    /// use rt_datastructure::primitives::bbox;
+    ///
+    /// let mut c = cache::ResultCache::new(2, Duration::from_secs(60));
+    /// c.set_key_grid(0.01);
+    ///
+    /// c.insert(&bbox::BBox::new(0., 0., 1., 1.), 1., vec![1, 2, 3]);
+    /// assert!(c.get(&bbox::BBox::new(0.001, 0., 1.002, 1.), 1.).is_some());
+    /// assert!(c.get(&bbox::BBox::new(0.1, 0., 1., 1.), 1.).is_none());
+    /// ```
+    ///
+    pub fn set_key_grid(&mut self, grid: f64) {
+        self.m_key_grid = grid;
+        self.invalidate();
+    }
+
+    ///
+    /// Get the grid the bounding boxes of queries are rounded to.
+    ///
+    pub fn get_key_grid(&self) -> f64 {
+        self.m_key_grid
+    }
+
+    ///
+    /// Store the label indices of the given query.
+    ///
+    /// If the cache is full, expired entries are removed first. If this does not free any space
+    /// the least recently used entry is replaced.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::Duration;
+    /// use rt_datastructure::cache;
+    /// use rt_datastructure::primitives::bbox;
+    ///
+    /// let mut c = cache::ResultCache::new(2, Duration::from_secs(60));
+    /// let bb = bbox::BBox::new(0., 0., 1., 1.);
+    ///
+    /// c.insert(&bb, 1., vec![1]);
+    /// c.insert(&bb, 2., vec![2]);
+    /// assert!(c.get(&bb, 1.).is_some());
+    ///
+    /// // the entry of min_t 2 is the least recently used one
+    /// c.insert(&bb, 3., vec![3]);
+    /// assert!(c.get(&bb, 1.).is_some());
+    /// assert!(c.get(&bb, 2.).is_none());
+    /// ```
+    ///
+    pub fn insert(&mut self, bbox: &BBox, min_t: f64, indices: Vec<u32>) {
+        if self.m_capacity == 0 {
+            return;
+        }
+
+        if self.m_entries.len() >= self.m_capacity {
+            let generation = self.m_generation;
+            let ttl = self.m_ttl;
+            self.m_entries
+                .retain(|_, e| e.m_generation == generation && e.m_created.elapsed() < ttl);
+        }
+
+        if self.m_entries.len() >= self.m_capacity {
+            let least_recent = self.m_entries
+                .iter()
+                .min_by_key(|&(_, e)| e.m_last_used.get())
+                .map(|(k, _)| *k);
+            if let Some(k) = least_recent {
+                self.m_entries.remove(&k);
+            }
+        }
+
+        let now = Instant::now();
+        self.m_entries.insert(cache_key(bbox, min_t, self.m_key_grid),
+                              CacheEntry {
+                                  m_created: now,
+                                  m_last_used: Cell::new(now),
+                                  m_generation: self.m_generation,
+                                  m_indices: indices,
+                              });
+    }
+
+    ///
+    /// Invalidate all cached results by starting a new generation.
+    ///
+    pub fn invalidate(&mut self) {
+        self.m_generation += 1;
+        self.m_entries.clear();
+    }
+
+    ///
+    /// Invalidate the cache if the given generation of the queried data differs from the one the
+    /// cached results were computed for, e.g. after the tree was rebuilt.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::Duration;
+    /// use rt_datastructure::cache;
+    /// use rt_datastructure::primitives::bbox;
+    ///
+    /// let mut c = cache::ResultCache::new(2, Duration::from_secs(60));
+    /// let bb = bbox::BBox::new(0., 0., 1., 1.);
+    ///
+    /// c.sync_data_generation(7);
+    /// c.insert(&bb, 1., vec![1, 2, 3]);
+    /// c.sync_data_generation(7);
+    /// assert!(c.get(&bb, 1.).is_some());
+    ///
+    /// c.sync_data_generation(8);
+    /// assert!(c.get(&bb, 1.).is_none());
+    /// ```
+    ///
+    pub fn sync_data_generation(&mut self, generation: u64) {
+        if self.m_data_generation.is_some_and(|g| g != generation) {
+            self.invalidate();
+        }
+        self.m_data_generation = Some(generation);
+    }
+
+    ///
+    /// Get the current cache generation.
+    ///
+    pub fn get_generation(&self) -> u64 {
+        self.m_generation
+    }
+
+    ///
+    /// Check if the entry belongs to the current generation and is not expired.
+    ///
+    fn is_valid(&self, e: &CacheEntry) -> bool {
+        e.m_generation == self.m_generation && e.m_created.elapsed() < self.m_ttl
+    }
+}
+
+///
+/// Create the cache key of a query. For a grid > 0 the bounding box is rounded to the grid.
+///
+fn cache_key(bbox: &BBox, min_t: f64, grid: f64) -> CacheKey {
+    // adding 0 maps -0 to 0, both must result in the same key
+    let round = |v: f64| if grid > 0. { (v / grid).round() * grid + 0. } else { v };
+
+    (round(bbox.get_min_x()).to_bits(),
+     round(bbox.get_min_y()).to_bits(),
+     round(bbox.get_max_x()).to_bits(),
+     round(bbox.get_max_y()).to_bits(),
+     min_t.to_bits())
+}
@@ -0,0 +1,193 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::error;
+use std::fmt;
+use std::io;
+
+///
+/// The error type of the crate.
+///
+/// Each kind of error is mapped to a stable numeric code that is reported at the C interface,
+/// see `get_code`.
+///
+#[derive(Debug)]
+pub enum Error {
+    /// Reading a file failed
+    Io(io::Error),
+    /// A line of an input file could not be parsed, line and column numbers start at 1. A column
+    /// of 0 means the position within the line is unknown
+    Parse {
+        line: usize,
+        column: usize,
+        message: String,
+        source: Option<Box<dyn error::Error + Send + Sync>>,
+    },
+    /// The data violates a requirement of the data structure, e.g. invalid coordinates
+    Validation(String),
+    /// A query could not be answered, e.g. due to invalid query parameters
+    Query(String),
+    /// A data structure could not be serialized or deserialized
+    Serialization(String),
+    /// Invalid arguments were passed to the C interface
+    Ffi(String),
+    /// The tree could not be modified since its nodes are still shared with a snapshot
+    Shared(String),
+    /// A tree violates an invariant of the 3D PST. The node is the index of the element the
+    /// violation was found at, if any
+    Invariant { node: Option<usize>, reason: String },
+}
+
+impl Error {
+    ///
+    /// Construct a parse error without a line and column number.
+    ///
+    /// The line number is set by the caller that knows the line via `at_line`, the column via
+    /// `at_column`.
+    ///
+    pub fn parse<E>(message: String, source: Option<E>) -> Error
+        where E: Into<Box<dyn error::Error + Send + Sync>>
+    {
+        Error::Parse {
+            line: 0,
+            column: 0,
+            message,
+            source: source.map(Into::into),
+        }
+    }
+
+    ///
+    /// Set the line number of a parse error. Other errors are returned unchanged.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::error::Error;
+    ///
+    /// let e = Error::parse::<String>("Invalid label".to_string(), None).at_line(3);
+    /// assert!(e.to_string() == "line 3: Invalid label");
+    /// assert!(e.get_code() == 2);
+    /// ```
+    ///
+    pub fn at_line(self, line: usize) -> Error {
+        match self {
+            Error::Parse { column, message, source, .. } => {
+                Error::Parse {
+                    line,
+                    column,
+                    message,
+                    source,
+                }
+            }
+            e => e,
+        }
+    }
+
+    ///
+    /// Set the column number of a parse error. Other errors are returned unchanged.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::error::Error;
+    ///
+    /// let e = Error::parse::<String>("Invalid label".to_string(), None).at_column(7).at_line(3);
+    /// assert!(e.to_string() == "line 3, column 7: Invalid label");
+    /// ```
+    ///
+    pub fn at_column(self, column: usize) -> Error {
+        match self {
+            Error::Parse { line, message, source, .. } => {
+                Error::Parse {
+                    line,
+                    column,
+                    message,
+                    source,
+                }
+            }
+            e => e,
+        }
+    }
+
+    ///
+    /// Get the stable numeric code of the error.
+    ///
+    /// The codes are:
+    ///
+    /// ```text
+    /// 1: Io, 2: Parse, 3: Validation, 4: Query, 5: Serialization, 6: Ffi, 7: Shared,
+    /// 8: Invariant
+    /// ```
+    ///
+    /// The code 0 is reserved for "no error".
+    ///
+    pub fn get_code(&self) -> i32 {
+        match *self {
+            Error::Io(_) => 1,
+            Error::Parse { .. } => 2,
+            Error::Validation(_) => 3,
+            Error::Query(_) => 4,
+            Error::Serialization(_) => 5,
+            Error::Ffi(_) => 6,
+            Error::Shared(_) => 7,
+            Error::Invariant { .. } => 8,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref e) => write!(f, "I/O error: {}", e),
+            Error::Parse { line, column: 0, ref message, .. } => {
+                write!(f, "line {}: {}", line, message)
+            }
+            Error::Parse { line, column, ref message, .. } => {
+                write!(f, "line {}, column {}: {}", line, column, message)
+            }
+            Error::Validation(ref message) => write!(f, "invalid data: {}", message),
+            Error::Query(ref message) => write!(f, "invalid query: {}", message),
+            Error::Serialization(ref message) => write!(f, "serialization failed: {}", message),
+            Error::Ffi(ref message) => write!(f, "invalid argument: {}", message),
+            Error::Shared(ref message) => write!(f, "shared with a snapshot: {}", message),
+            Error::Invariant { node: Some(node), ref reason } => {
+                write!(f, "invariant violated at node {}: {}", node, reason)
+            }
+            Error::Invariant { node: None, ref reason } => {
+                write!(f, "invariant violated: {}", reason)
+            }
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io(ref e) => Some(e),
+            Error::Parse { source: Some(ref e), .. } => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
@@ -0,0 +1,178 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::io::{BufRead, Read};
+
+use error::Error;
+use input::ImportOptions;
+use input::format::{ImportReport, InputFormat};
+use pack::codec;
+use primitives::label::Label;
+
+///
+/// The magic bytes at the beginning of a binary label file.
+///
+pub const BINARY_MAGIC: &[u8; 8] = b"RTLBIN\0\0";
+
+///
+/// The version of the binary label file format.
+///
+pub const BINARY_VERSION: u32 = 1;
+
+///
+/// The size of a single label record in bytes.
+///
+pub const RECORD_SIZE: usize = 64;
+
+// Corrupted counts must not allocate huge vectors upfront, larger files grow while reading.
+const MAX_RESERVED: u64 = 1 << 20;
+
+///
+/// The binary label file format.
+///
+/// In contrast to the text formats no values need to be parsed, loading a file is bound by the
+/// I/O. A file consists of fixed size label records followed by a string table:
+///
+/// ```text
+/// magic     8 bytes   "RTLBIN\0\0"
+/// version   u32       1
+/// count     u64       the number of labels
+/// records   count x 64 bytes
+///             f64 x, f64 y, f64 t, i64 osm_id, i32 prio, u32 category, f64 lbl_fac,
+///             f64 label_length, u32 label, u32 short label + 1 (0 if there is none)
+/// strings   varint count, count x (varint length, utf-8 bytes)
+/// ```
+///
+/// All integers and floats are little endian, the label strings are indices into the string
+/// table. Names, tags and geometries are not stored, use label packs to keep them.
+///
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::input::ImportOptions;
+/// use rt_datastructure::input::binary::BinaryFormat;
+/// use rt_datastructure::input::format::InputFormat;
+/// use rt_datastructure::output;
+/// use rt_datastructure::primitives::label::Label;
+///
+/// let mut l = Label::new(8.80, 53.08, 3.5, 62559, 1, 11., "Bremen".to_string());
+/// l.set_short_label(Some("HB".to_string()));
+/// l.set_label_length(3300.);
+///
+/// let mut buf: Vec<u8> = Vec::new();
+/// output::write_binary(&mut buf, &[&l]).unwrap();
+/// assert!(BinaryFormat.detect(&buf));
+///
+/// let r = BinaryFormat.read(&mut buf.as_slice(), &ImportOptions::new()).unwrap();
+/// assert!(r.get_labels()[0].get_osm_id() == 62559);
+/// assert!(r.get_labels()[0].get_label_length() == 3300.);
+/// assert!(*r.get_labels()[0].get_short_label().unwrap() == "HB".to_string());
+/// ```
+///
+pub struct BinaryFormat;
+
+impl InputFormat for BinaryFormat {
+    fn get_name(&self) -> &str {
+        "binary label file"
+    }
+
+    fn detect(&self, head: &[u8]) -> bool {
+        head.starts_with(BINARY_MAGIC)
+    }
+
+    fn read(&self,
+            reader: &mut dyn BufRead,
+            _: &ImportOptions)
+            -> Result<ImportReport, Error> {
+        Ok(ImportReport::new(read_binary(reader)?, Vec::new()))
+    }
+}
+
+///
+/// Read the labels of a binary label file as described by `BinaryFormat`.
+///
+/// # Errors
+///   * Io: if the input ends early
+///   * Serialization: if the input is not a binary label file of a supported version or a string
+///     index is out of range
+///
+pub fn read_binary<R: Read + ?Sized>(mut r: &mut R) -> Result<Vec<Label>, Error> {
+    let mut magic = [0u8; 8];
+    r.read_exact(&mut magic)?;
+    if &magic != BINARY_MAGIC {
+        return Err(Error::Serialization("Not a binary label file".to_string()));
+    }
+    let version = codec::read_u32(&mut r)?;
+    if version != BINARY_VERSION {
+        return Err(Error::Serialization(format!("Unsupported binary label file version {}",
+                                                version)));
+    }
+
+    let count = codec::read_u64(&mut r)?;
+    let mut records: Vec<u8> = Vec::with_capacity(count.min(MAX_RESERVED) as usize * RECORD_SIZE);
+    let mut record = [0u8; RECORD_SIZE];
+    for _ in 0..count {
+        r.read_exact(&mut record)?;
+        records.extend_from_slice(&record);
+    }
+
+    let string_count = codec::read_count(&mut r, u64::from(u32::MAX))?;
+    let mut strings: Vec<String> = Vec::with_capacity(string_count.min(MAX_RESERVED as usize));
+    for _ in 0..string_count {
+        strings.push(codec::read_string(&mut r)?);
+    }
+
+    records.chunks(RECORD_SIZE).map(|rec| read_record(rec, &strings)).collect()
+}
+
+// Decode a single label record, the strings are resolved in the string table.
+fn read_record(mut rec: &[u8], strings: &[String]) -> Result<Label, Error> {
+    let x = codec::read_f64(&mut rec)?;
+    let y = codec::read_f64(&mut rec)?;
+    let t = codec::read_f64(&mut rec)?;
+    let osm_id = codec::read_u64(&mut rec)? as i64;
+    let prio = codec::read_u32(&mut rec)? as i32;
+    let category = codec::read_u32(&mut rec)?;
+    let lbl_fac = codec::read_f64(&mut rec)?;
+    let label_length = codec::read_f64(&mut rec)?;
+    let label = codec::read_u32(&mut rec)?;
+    let short_label = codec::read_u32(&mut rec)?;
+
+    if category > u32::from(u16::MAX) {
+        return Err(Error::Serialization(format!("Invalid category {} of label {}",
+                                                category,
+                                                osm_id)));
+    }
+    let string = |idx: u32| {
+        strings.get(idx as usize).cloned().ok_or_else(|| {
+            Error::Serialization(format!("String index {} of label {} out of range", idx, osm_id))
+        })
+    };
+
+    let mut l = Label::new(x, y, t, osm_id, prio, lbl_fac, string(label)?);
+    l.set_category(category as u16);
+    l.set_label_length(label_length);
+    if short_label != 0 {
+        l.set_short_label(Some(string(short_label - 1)?));
+    }
+
+    Ok(l)
+}
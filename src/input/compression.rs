@@ -0,0 +1,154 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+#[cfg(feature = "gzip")]
+use flate2::bufread::MultiGzDecoder;
+
+use error::Error;
+
+///
+/// The compression of an input file.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    /// The file is not compressed.
+    None,
+    /// The file is gzip compressed (.gz).
+    Gzip,
+    /// The file is zstd compressed (.zst).
+    Zstd,
+}
+
+impl Compression {
+    ///
+    /// Detect the compression of a file from its first bytes, or from the extension of its path
+    /// if the first bytes do not belong to a known compression format.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::input::compression::Compression;
+    ///
+    /// assert!(Compression::detect(&[0x1f, 0x8b, 0x08], "labels.txt") == Compression::Gzip);
+    /// assert!(Compression::detect(&[0x28, 0xb5, 0x2f, 0xfd], "labels") == Compression::Zstd);
+    /// assert!(Compression::detect(b"3\n", "labels.zst") == Compression::Zstd);
+    /// assert!(Compression::detect(b"3\n", "labels.txt") == Compression::None);
+    /// ```
+    ///
+    pub fn detect<P: AsRef<Path>>(head: &[u8], path: P) -> Compression {
+        if head.starts_with(&[0x1f, 0x8b]) {
+            return Compression::Gzip;
+        }
+        if head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            return Compression::Zstd;
+        }
+
+        match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+}
+
+///
+/// Open the file at 'path' for reading and decompress it transparently, compare
+/// `Compression::detect`.
+///
+/// Gzip files require the `gzip` feature and zstd files the `zstd` feature of the crate.
+///
+/// # Errors
+///   * Io: if the file path does not match any file in the file system
+///   * Parse: if the file is compressed in a format whose feature is not enabled
+///
+pub fn open(path: &String) -> Result<Box<dyn BufRead>, Error> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let compression = Compression::detect(reader.fill_buf()?, path);
+
+    decompress(reader, compression)
+}
+
+///
+/// Wrap the reader into a decoder of the given compression.
+///
+///
+/// # Examples
+/// ```
+/// use std::io::Read;
+///
+/// use rt_datastructure::input::compression::{self, Compression};
+///
+/// // "0\n" compressed by gzip
+/// let gz: Vec<u8> = vec![0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x33,
+///                        0xe0, 0x02, 0x00, 0x12, 0xcd, 0x4a, 0x7e, 0x02, 0x00, 0x00, 0x00];
+///
+/// let r = compression::decompress(std::io::Cursor::new(gz), Compression::Gzip);
+/// # if cfg!(feature = "gzip") {
+/// let mut content = String::new();
+/// r.unwrap().read_to_string(&mut content).unwrap();
+/// assert!(content == "0\n");
+/// # } else {
+/// # assert!(r.is_err());
+/// # }
+/// ```
+///
+/// # Errors
+///   * Parse: if the feature of the compression is not enabled
+///   * Io: if the zstd decoder could not be initialized
+///
+pub fn decompress<R: BufRead + 'static>(reader: R,
+                                        compression: Compression)
+                                        -> Result<Box<dyn BufRead>, Error> {
+    match compression {
+        Compression::None => Ok(Box::new(reader)),
+        Compression::Gzip => gzip(reader),
+        Compression::Zstd => zstd(reader),
+    }
+}
+
+#[cfg(feature = "gzip")]
+fn gzip<R: BufRead + 'static>(reader: R) -> Result<Box<dyn BufRead>, Error> {
+    // concatenated gzip members are read as a single stream like gzip -d does
+    Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+}
+
+#[cfg(not(feature = "gzip"))]
+fn gzip<R: BufRead + 'static>(_reader: R) -> Result<Box<dyn BufRead>, Error> {
+    Err(Error::parse::<String>("Reading gzip compressed input requires the gzip feature"
+                                   .to_string(),
+                               None))
+}
+
+#[cfg(feature = "zstd")]
+fn zstd<R: BufRead + 'static>(reader: R) -> Result<Box<dyn BufRead>, Error> {
+    Ok(Box::new(BufReader::new(::zstd::stream::read::Decoder::with_buffer(reader)?)))
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd<R: BufRead + 'static>(_reader: R) -> Result<Box<dyn BufRead>, Error> {
+    Err(Error::parse::<String>("Reading zstd compressed input requires the zstd feature"
+                                   .to_string(),
+                               None))
+}
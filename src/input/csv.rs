@@ -0,0 +1,421 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::error;
+use std::io::BufRead;
+use std::str::FromStr;
+
+use error::Error;
+use input::{ImportMode, ImportOptions};
+use input::format::{ImportReport, InputFormat};
+use input::parse;
+use primitives::label::Label;
+
+// The byte order mark some editors write at the start of UTF-8 files.
+const BOM: char = '\u{feff}';
+
+///
+/// A column of a CSV file containing labels.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CsvColumn {
+    /// The latitude, i.e. the y coordinate (required)
+    Lat,
+    /// The longitude, i.e. the x coordinate (required)
+    Lon,
+    /// The osm id, 0 if not given
+    OsmId,
+    /// The priority, 0 if not given
+    Prio,
+    /// The elimination time t (required)
+    T,
+    /// The label length, 0 if not given
+    LabelLength,
+    /// The label (size) factor, 1 if not given
+    LabelFactor,
+    /// The label string (required)
+    Label,
+    /// An optional short label string, empty values mean no short label
+    ShortLabel,
+    /// The category, 0 if not given
+    Category,
+    /// A column that is not read
+    Ignore,
+}
+
+///
+/// The settings to read labels from CSV files.
+///
+/// By default fields are separated by commas, may be quoted by double quotes (a quote within a
+/// quoted field is written twice) and the first line is a header that is skipped. The default
+/// column order is the one of the label file format:
+///
+/// ```text
+/// lat,lon,osm_id,priority,collision_time,label_length,size_factor,label
+/// ```
+///
+/// Each record is a single line, quoted fields cannot contain line breaks.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct CsvOptions {
+    m_delimiter: char,
+    m_quote: Option<char>,
+    m_header: bool,
+    m_columns: Vec<CsvColumn>,
+}
+
+impl CsvOptions {
+    ///
+    /// Initialize the default settings.
+    ///
+    pub fn new() -> CsvOptions {
+        CsvOptions {
+            m_delimiter: ',',
+            m_quote: Some('"'),
+            m_header: true,
+            m_columns: vec![CsvColumn::Lat,
+                            CsvColumn::Lon,
+                            CsvColumn::OsmId,
+                            CsvColumn::Prio,
+                            CsvColumn::T,
+                            CsvColumn::LabelLength,
+                            CsvColumn::LabelFactor,
+                            CsvColumn::Label],
+        }
+    }
+
+    ///
+    /// Set the character separating the fields of a record, e.g. ';' or '\t'.
+    ///
+    pub fn set_delimiter(&mut self, delimiter: char) {
+        self.m_delimiter = delimiter;
+    }
+
+    ///
+    /// Get the character separating the fields of a record.
+    ///
+    pub fn get_delimiter(&self) -> char {
+        self.m_delimiter
+    }
+
+    ///
+    /// Set the character quoting fields or None if fields are never quoted.
+    ///
+    pub fn set_quote(&mut self, quote: Option<char>) {
+        self.m_quote = quote;
+    }
+
+    ///
+    /// Get the character quoting fields.
+    ///
+    pub fn get_quote(&self) -> Option<char> {
+        self.m_quote
+    }
+
+    ///
+    /// Set whether the first line is a header that is skipped.
+    ///
+    pub fn set_header(&mut self, header: bool) {
+        self.m_header = header;
+    }
+
+    ///
+    /// Check whether the first line is a header that is skipped.
+    ///
+    pub fn get_header(&self) -> bool {
+        self.m_header
+    }
+
+    ///
+    /// Set the order of the columns. Records may have further columns that are not read.
+    ///
+    pub fn set_columns(&mut self, columns: Vec<CsvColumn>) {
+        self.m_columns = columns;
+    }
+
+    ///
+    /// Get the order of the columns.
+    ///
+    pub fn get_columns(&self) -> &Vec<CsvColumn> {
+        &self.m_columns
+    }
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+///
+/// The CSV input format with the given settings.
+///
+/// Records that could not be read are skipped and reported with their line and column.
+///
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::input::{ImportMode, ImportOptions};
+/// use rt_datastructure::input::csv::{CsvColumn, CsvFormat, CsvOptions};
+/// use rt_datastructure::input::format::InputFormat;
+///
+/// let mut o = CsvOptions::new();
+/// o.set_delimiter(';');
+/// o.set_columns(vec![CsvColumn::Label, CsvColumn::Lon, CsvColumn::Lat, CsvColumn::T]);
+///
+/// let data = "name;lon;lat;t\n\
+///             \"Bremen; Hansestadt\";8.75;53.125;3.5\n\
+///             Bremerhaven;8.58;x;2.5\n\
+///             \"Schloss \"\"Schönebeck\"\"\";8.66;53.18;1.5\n";
+///
+/// let f = CsvFormat::new(o);
+/// let mut import = ImportOptions::new();
+/// let r = f.read(&mut data.as_bytes(), &import).unwrap();
+/// assert!(r.get_labels().len() == 2);
+/// assert!(r.get_labels()[0].get_label() == "Bremen; Hansestadt");
+/// assert!(r.get_labels()[0].get_x() == 8.75);
+/// assert!(r.get_labels()[1].get_label() == "Schloss \"Schönebeck\"");
+/// assert!(r.get_skipped()[0].to_string().starts_with("line 3, column 18: "));
+///
+/// import.set_mode(ImportMode::Strict);
+/// let e = f.read(&mut data.as_bytes(), &import).unwrap_err();
+/// assert!(e.to_string().starts_with("line 3, column 18: "));
+/// ```
+///
+/// A byte order mark at the start of the file is ignored. Like in the label file format, a label
+/// that never disappears has the t value `inf`, which is stored as the largest finite t:
+///
+/// ```
+/// use rt_datastructure::input::ImportOptions;
+/// use rt_datastructure::input::csv::{CsvFormat, CsvOptions};
+/// use rt_datastructure::input::format::InputFormat;
+/// use rt_datastructure::primitives::coord::Coord;
+///
+/// let mut o = CsvOptions::new();
+/// o.set_header(false);
+///
+/// let data = "\u{feff}53.14,8.93,3627273522,1,inf,3300,11,Timmersloh\n";
+/// let r = CsvFormat::new(o).read(&mut data.as_bytes(), &ImportOptions::new()).unwrap();
+/// assert!(r.get_skipped().is_empty());
+/// assert!(r.get_labels()[0].get_y() == 53.14);
+/// assert!(r.get_labels()[0].get_t() == Coord::MAX as f64);
+/// ```
+///
+pub struct CsvFormat {
+    m_options: CsvOptions,
+}
+
+impl CsvFormat {
+    ///
+    /// Initialize the CSV format with the given settings.
+    ///
+    pub fn new(options: CsvOptions) -> CsvFormat {
+        CsvFormat { m_options: options }
+    }
+
+    ///
+    /// Get the settings of the format.
+    ///
+    pub fn get_options(&self) -> &CsvOptions {
+        &self.m_options
+    }
+
+    // Create the label of a single record.
+    fn read_record(&self, line: &str) -> Result<Label, Error> {
+        let fields = split_record(line, self.m_options.m_delimiter, self.m_options.m_quote)?;
+        if fields.len() < self.m_options.m_columns.len() {
+            let message = format!("Expected {} fields, found {}",
+                                  self.m_options.m_columns.len(),
+                                  fields.len());
+            return Err(Error::parse::<String>(message, None).at_column(line.chars().count() + 1));
+        }
+
+        let mut b = Label::builder();
+        let (mut x, mut y) = (0., 0.);
+        for (&column, &(ref value, start)) in self.m_options.m_columns.iter().zip(fields.iter()) {
+            let at = |e: Error| e.at_column(start);
+            b = match column {
+                CsvColumn::Lat => {
+                    y = parse_value(value, "lat").map_err(at)?;
+                    b
+                }
+                CsvColumn::Lon => {
+                    x = parse_value(value, "lon").map_err(at)?;
+                    b
+                }
+                CsvColumn::OsmId => b.osm_id(parse_value(value, "osm_id").map_err(at)?),
+                CsvColumn::Prio => b.prio(parse_value(value, "priority").map_err(at)?),
+                CsvColumn::T => b.t(parse::normalize_t(parse_value(value, "t").map_err(at)?)),
+                CsvColumn::LabelLength => b.length(parse_value(value, "label_length").map_err(at)?),
+                CsvColumn::LabelFactor => b.factor(parse_value(value, "size_factor").map_err(at)?),
+                CsvColumn::Label => b.text(value.as_str()),
+                CsvColumn::ShortLabel if !value.is_empty() => b.short_text(value.as_str()),
+                CsvColumn::Category => b.category(parse_value(value, "category").map_err(at)?),
+                CsvColumn::ShortLabel | CsvColumn::Ignore => b,
+            };
+        }
+
+        Ok(b.pos(x, y).build())
+    }
+}
+
+impl InputFormat for CsvFormat {
+    fn get_name(&self) -> &str {
+        "csv"
+    }
+
+    // A CSV file is detected by a header line with at least one field per column whose first
+    // field is not a number.
+    fn detect(&self, head: &[u8]) -> bool {
+        let first = head.split(|&b| b == b'\n').next().unwrap_or(&[]);
+        let header = match ::std::str::from_utf8(first) {
+            Ok(s) => s.trim_start_matches(BOM).trim_end_matches('\r'),
+            Err(_) => return false,
+        };
+
+        match split_record(header, self.m_options.m_delimiter, self.m_options.m_quote) {
+            Ok(fields) => {
+                self.m_options.m_header && fields.len() >= self.m_options.m_columns.len() &&
+                fields.first().is_some_and(|f| f.0.trim().parse::<f64>().is_err())
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn read(&self,
+            reader: &mut dyn BufRead,
+            options: &ImportOptions)
+            -> Result<ImportReport, Error> {
+        for required in &[CsvColumn::Lat, CsvColumn::Lon, CsvColumn::T, CsvColumn::Label] {
+            if !self.m_options.m_columns.contains(required) {
+                return Err(Error::Validation(format!("The CSV columns lack the column {:?}",
+                                                     required)));
+            }
+        }
+
+        let mut labels: Vec<Label> = Vec::new();
+        let mut skipped: Vec<Error> = Vec::new();
+        for (idx, line_res) in reader.lines().enumerate() {
+            let line = line_res?;
+            let mut line = line.trim_end_matches('\r');
+            if idx == 0 {
+                line = line.trim_start_matches(BOM);
+            }
+            if (idx == 0 && self.m_options.m_header) || line.is_empty() {
+                continue;
+            }
+
+            match self.read_record(line) {
+                Ok(label) => labels.push(label),
+                Err(e) => {
+                    let e = e.at_line(idx + 1);
+                    if options.get_mode() == ImportMode::Strict {
+                        return Err(e);
+                    }
+                    skipped.push(e);
+                }
+            }
+        }
+
+        Ok(ImportReport::new(labels, skipped))
+    }
+}
+
+// Split the record into its fields. Each field is returned with the column it starts at.
+fn split_record(line: &str, delimiter: char, quote: Option<char>) -> Result<Vec<(String, usize)>,
+                                                                          Error> {
+    let mut fields: Vec<(String, usize)> = Vec::new();
+    let mut chars = line.chars().peekable();
+    let mut column = 1;
+
+    loop {
+        let start = column;
+        let mut value = String::new();
+
+        if quote.is_some() && chars.peek().cloned() == quote {
+            chars.next();
+            column += 1;
+            loop {
+                match chars.next() {
+                    None => {
+                        let message = "Unterminated quoted field".to_string();
+                        return Err(Error::parse::<String>(message, None).at_column(start));
+                    }
+                    Some(c) if Some(c) == quote => {
+                        column += 1;
+                        if chars.peek().cloned() != quote {
+                            break;
+                        }
+                        chars.next();
+                        column += 1;
+                        value.push(c);
+                    }
+                    Some(c) => {
+                        column += 1;
+                        value.push(c);
+                    }
+                }
+            }
+
+            match chars.next() {
+                None => {
+                    fields.push((value, start));
+                    return Ok(fields);
+                }
+                Some(c) if c == delimiter => column += 1,
+                Some(_) => {
+                    let message = "Unexpected character after a quoted field".to_string();
+                    return Err(Error::parse::<String>(message, None).at_column(column));
+                }
+            }
+            fields.push((value, start));
+            continue;
+        }
+
+        loop {
+            match chars.next() {
+                None => {
+                    fields.push((value, start));
+                    return Ok(fields);
+                }
+                Some(c) if c == delimiter => {
+                    column += 1;
+                    break;
+                }
+                Some(c) => {
+                    column += 1;
+                    value.push(c);
+                }
+            }
+        }
+        fields.push((value, start));
+    }
+}
+
+// Parse the value of the field with the given name.
+fn parse_value<F>(value: &str, name: &str) -> Result<F, Error>
+    where F: FromStr,
+          F::Err: Into<Box<dyn error::Error + Send + Sync>>
+{
+    value.trim()
+        .parse()
+        .map_err(|e| Error::parse(format!("Invalid {} value: {}", name, value), Some(e)))
+}
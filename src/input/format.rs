@@ -0,0 +1,532 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::BufRead;
+use std::sync::{Arc, RwLock};
+
+use error::Error;
+use input::binary::BinaryFormat;
+use input::{ImportOptions, LabelReader};
+use primitives::bbox::BBox;
+use primitives::label::Label;
+
+///
+/// The quantiles of the t values given by `ImportSummary::get_t_quantiles`.
+///
+pub const T_QUANTILES: [f64; 5] = [0.1, 0.25, 0.5, 0.75, 0.9];
+
+///
+/// The result of reading an input file.
+///
+/// Lines or records that could not be read are skipped. Their errors are collected in the
+/// report, so the import pipeline can report them uniformly for all input formats. The library
+/// does not print anything, the caller decides how to present the report.
+///
+#[derive(Debug)]
+pub struct ImportReport {
+    m_labels: Vec<Label>,
+    m_skipped: Vec<Error>,
+    m_rejected: Vec<Rejection>,
+    m_duplicates: usize,
+    m_format: Option<String>,
+}
+
+impl ImportReport {
+    ///
+    /// Initialize a new report of the given labels and the errors of the skipped records.
+    ///
+    pub fn new(labels: Vec<Label>, skipped: Vec<Error>) -> ImportReport {
+        ImportReport {
+            m_labels: labels,
+            m_skipped: skipped,
+            m_rejected: Vec::new(),
+            m_duplicates: 0,
+            m_format: None,
+        }
+    }
+
+    ///
+    /// Set the name of the input format the labels were read in.
+    ///
+    pub fn set_format(&mut self, name: &str) {
+        self.m_format = Some(name.to_string());
+    }
+
+    ///
+    /// Get the name of the input format the labels were read in, if it was detected by
+    /// `import_auto`.
+    ///
+    pub fn get_format(&self) -> Option<&str> {
+        self.m_format.as_deref()
+    }
+
+    ///
+    /// Set the labels rejected by the validation of the import.
+    ///
+    pub fn set_rejected(&mut self, rejected: Vec<Rejection>) {
+        self.m_rejected = rejected;
+    }
+
+    ///
+    /// Get the labels rejected by the validation of the import. Rejected labels are not part of
+    /// the imported labels.
+    ///
+    pub fn get_rejected(&self) -> &Vec<Rejection> {
+        &self.m_rejected
+    }
+
+    ///
+    /// Set the number of records dropped as duplicates of another record's osm id.
+    ///
+    pub fn set_duplicates(&mut self, duplicates: usize) {
+        self.m_duplicates = duplicates;
+    }
+
+    ///
+    /// Get the number of records dropped as duplicates of another record's osm id.
+    ///
+    pub fn get_duplicates(&self) -> usize {
+        self.m_duplicates
+    }
+
+    ///
+    /// Get the imported labels.
+    ///
+    pub fn get_labels(&self) -> &Vec<Label> {
+        &self.m_labels
+    }
+
+    ///
+    /// Get the errors of the skipped records.
+    ///
+    pub fn get_skipped(&self) -> &Vec<Error> {
+        &self.m_skipped
+    }
+
+    ///
+    /// Compute the summary statistics of the imported labels, e.g. to sanity check a new
+    /// dataset before it is deployed.
+    ///
+    pub fn get_summary(&self) -> ImportSummary {
+        ImportSummary::new(&self.m_labels)
+    }
+
+    ///
+    /// Consume the report and return the imported labels.
+    ///
+    pub fn into_labels(self) -> Vec<Label> {
+        self.m_labels
+    }
+}
+
+///
+/// The summary statistics of a set of labels.
+///
+/// The t statistics only consider finite t values.
+///
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::input::format::ImportSummary;
+/// use rt_datastructure::primitives::label::Label;
+///
+/// let labels: Vec<Label> = (0..100)
+///     .map(|i| Label::new(i as f64 / 8., 50., i as f64, i, (i % 3) as i32, 1., "L".to_string()))
+///     .collect();
+///
+/// let s = ImportSummary::new(&labels);
+/// assert!(s.get_count() == 100);
+/// assert!(s.get_bbox().get_max_x() == 12.375);
+/// assert!(s.get_min_t() == Some(0.) && s.get_max_t() == Some(99.));
+/// assert!(s.get_t_quantiles()[2] == (0.5, 50.));
+/// assert!(s.get_prio_histogram() == &vec![(0, 34), (1, 33), (2, 33)]);
+/// ```
+///
+#[derive(Clone, Debug)]
+pub struct ImportSummary {
+    m_count: usize,
+    m_bbox: BBox,
+    m_min_t: Option<f64>,
+    m_max_t: Option<f64>,
+    m_t_quantiles: Vec<(f64, f64)>,
+    m_prio_histogram: Vec<(i32, usize)>,
+}
+
+impl ImportSummary {
+    ///
+    /// Compute the summary statistics of the labels.
+    ///
+    pub fn new(labels: &[Label]) -> ImportSummary {
+        let mut ts: Vec<f64> = labels.iter().map(|l| l.get_t()).filter(|t| t.is_finite()).collect();
+        ts.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+        // nearest rank quantiles
+        let t_quantiles = match ts.len() {
+            0 => Vec::new(),
+            n => {
+                T_QUANTILES.iter()
+                    .map(|&q| (q, ts[((n - 1) as f64 * q).round() as usize]))
+                    .collect()
+            }
+        };
+
+        let mut histogram: BTreeMap<i32, usize> = BTreeMap::new();
+        for l in labels {
+            *histogram.entry(l.get_prio()).or_insert(0) += 1;
+        }
+
+        ImportSummary {
+            m_count: labels.len(),
+            m_bbox: BBox::from_labels(labels),
+            m_min_t: ts.first().cloned(),
+            m_max_t: ts.last().cloned(),
+            m_t_quantiles: t_quantiles,
+            m_prio_histogram: histogram.into_iter().collect(),
+        }
+    }
+
+    ///
+    /// Get the number of labels.
+    ///
+    pub fn get_count(&self) -> usize {
+        self.m_count
+    }
+
+    ///
+    /// Get the bounding box of the label positions, it is empty if there are no labels.
+    ///
+    pub fn get_bbox(&self) -> &BBox {
+        &self.m_bbox
+    }
+
+    ///
+    /// Get the minimal t value or None if there is no finite t value.
+    ///
+    pub fn get_min_t(&self) -> Option<f64> {
+        self.m_min_t
+    }
+
+    ///
+    /// Get the maximal t value or None if there is no finite t value.
+    ///
+    pub fn get_max_t(&self) -> Option<f64> {
+        self.m_max_t
+    }
+
+    ///
+    /// Get the (quantile, t value) pairs of the quantiles in `T_QUANTILES`, empty if there is no
+    /// finite t value.
+    ///
+    pub fn get_t_quantiles(&self) -> &Vec<(f64, f64)> {
+        &self.m_t_quantiles
+    }
+
+    ///
+    /// Get the number of labels per priority as (priority, count) pairs by increasing priority.
+    ///
+    pub fn get_prio_histogram(&self) -> &Vec<(i32, usize)> {
+        &self.m_prio_histogram
+    }
+}
+
+impl fmt::Display for ImportSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} labels within {}", self.m_count, self.m_bbox)?;
+        if let (Some(min), Some(max)) = (self.m_min_t, self.m_max_t) {
+            write!(f, ", t in [{}, {}]", min, max)?;
+        }
+        for &(q, t) in &self.m_t_quantiles {
+            write!(f, ", p{} {}", (q * 100.).round(), t)?;
+        }
+        for &(prio, count) in &self.m_prio_histogram {
+            write!(f, ", prio {}: {}", prio, count)?;
+        }
+
+        Ok(())
+    }
+}
+
+///
+/// The reason a label was rejected by the validation of an import.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RejectReason {
+    /// The latitude is not within [-90, 90].
+    Latitude,
+    /// The longitude is not within [-180, 180].
+    Longitude,
+    /// The elimination time t is NaN or infinite.
+    T,
+}
+
+///
+/// A label rejected by the validation of an import.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rejection {
+    m_index: usize,
+    m_osm_id: i64,
+    m_reason: RejectReason,
+    m_value: f64,
+}
+
+impl Rejection {
+    ///
+    /// Check the coordinates and the t value of the label, the index is the position of its
+    /// record in the input. Returns None if the label is valid.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::input::format::{RejectReason, Rejection};
+    /// use rt_datastructure::primitives::label::Label;
+    ///
+    /// let l = Label::new(8.75, 53.125, 3.5, 1, 1, 11., "Bremen".to_string());
+    /// assert!(Rejection::check(0, &l).is_none());
+    ///
+    /// let l = Label::new(8.75, 153.125, 3.5, 2, 1, 11., "Bremen".to_string());
+    /// let r = Rejection::check(7, &l).unwrap();
+    /// assert!(r.get_reason() == RejectReason::Latitude && r.get_index() == 7);
+    /// assert!(r.to_string() == "record 7 (osm id 2): latitude 153.125 out of range");
+    /// ```
+    ///
+    pub fn check(index: usize, l: &Label) -> Option<Rejection> {
+        let (reason, value) = if !(l.get_y() >= -90. && l.get_y() <= 90.) {
+            (RejectReason::Latitude, l.get_y())
+        } else if !(l.get_x() >= -180. && l.get_x() <= 180.) {
+            (RejectReason::Longitude, l.get_x())
+        } else if !l.get_t().is_finite() {
+            (RejectReason::T, l.get_t())
+        } else {
+            return None;
+        };
+
+        Some(Rejection {
+            m_index: index,
+            m_osm_id: l.get_osm_id(),
+            m_reason: reason,
+            m_value: value,
+        })
+    }
+
+    ///
+    /// Get the position of the record in the input, malformed records are counted.
+    ///
+    pub fn get_index(&self) -> usize {
+        self.m_index
+    }
+
+    ///
+    /// Get the osm id of the rejected label.
+    ///
+    pub fn get_osm_id(&self) -> i64 {
+        self.m_osm_id
+    }
+
+    ///
+    /// Get the reason the label was rejected.
+    ///
+    pub fn get_reason(&self) -> RejectReason {
+        self.m_reason
+    }
+
+    ///
+    /// Get the invalid value.
+    ///
+    pub fn get_value(&self) -> f64 {
+        self.m_value
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let what = match self.m_reason {
+            RejectReason::Latitude => "latitude",
+            RejectReason::Longitude => "longitude",
+            RejectReason::T => "t",
+        };
+        write!(f,
+               "record {} (osm id {}): {} {} out of range",
+               self.m_index,
+               self.m_osm_id,
+               what,
+               self.m_value)
+    }
+}
+
+///
+/// An input format the labels can be imported from.
+///
+/// Custom formats are made available to `import_auto` by `register_format`.
+///
+///
+/// # Examples
+/// ```
+/// use std::io::BufRead;
+/// use rt_datastructure::error::Error;
+/// use rt_datastructure::input::ImportOptions;
+/// use rt_datastructure::input::format::{self, ImportReport, InputFormat};
+/// use rt_datastructure::primitives::label::Label;
+///
+/// // a format with one "x;y;t;name" record per line
+/// struct Csv;
+///
+/// impl InputFormat for Csv {
+///     fn get_name(&self) -> &str {
+///         "csv"
+///     }
+///
+///     fn detect(&self, head: &[u8]) -> bool {
+///         head.starts_with(b"csv\n")
+///     }
+///
+///     fn read(&self,
+///             reader: &mut dyn BufRead,
+///             _: &ImportOptions)
+///             -> Result<ImportReport, Error> {
+///         let mut labels = Vec::new();
+///         for (idx, line) in reader.lines().enumerate().skip(1) {
+///             let line = line?;
+///             let f: Vec<&str> = line.split(';').collect();
+///             let t: f64 = f[2].parse().unwrap();
+///             labels.push(Label::new(f[0].parse().unwrap(), f[1].parse().unwrap(), t,
+///                                    idx as i64, 1, 1., f[3].to_string()));
+///         }
+///         Ok(ImportReport::new(labels, Vec::new()))
+///     }
+/// }
+///
+/// format::register_format(Box::new(Csv));
+///
+/// let f = format::find_format(b"csv\n1;2;3;Bremen\n").unwrap();
+/// assert!(f.get_name() == "csv");
+///
+/// let r = f.read(&mut "csv\n1;2;3;Bremen\n".as_bytes(), &ImportOptions::new()).unwrap();
+/// assert!(r.get_labels()[0].get_label() == "Bremen");
+/// ```
+///
+pub trait InputFormat: Send + Sync {
+    ///
+    /// Get a short name of the format for messages.
+    ///
+    fn get_name(&self) -> &str;
+
+    ///
+    /// Check if the given beginning of an input file is in this format.
+    ///
+    fn detect(&self, head: &[u8]) -> bool;
+
+    ///
+    /// Read all labels from the reader with the given options.
+    ///
+    /// In strict mode the first record that could not be read fails the import, in lenient mode
+    /// it is skipped and its error is collected in the report. Formats may ignore the remaining
+    /// options, e.g. the number of threads.
+    ///
+    fn read(&self,
+            reader: &mut dyn BufRead,
+            options: &ImportOptions)
+            -> Result<ImportReport, Error>;
+}
+
+///
+/// The label file format of the crate described in the [Input Module](../index.html).
+///
+/// Malformed lines are skipped and reported with their line and column. The lines are parsed on
+/// the number of threads of the import options.
+///
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::input::{ImportMode, ImportOptions};
+/// use rt_datastructure::input::format::{InputFormat, LabelFileFormat};
+///
+/// let data = "3\n\
+///             lat lon osm_id priority collision_time label_length size_factor label\n\
+///             53.08 8.80 1 1 3.5 3300.0 11.0 'Bremen'\n\
+///             53.55 x 2 1 2.5 3300.0 11.0 'Bremerhaven'\n\
+///             53.14 8.21 3 1 1.5 3300.0 11.0 'Oldenburg'\n";
+///
+/// let mut o = ImportOptions::new();
+/// let r = LabelFileFormat.read(&mut data.as_bytes(), &o).unwrap();
+/// assert!(r.get_labels().len() == 2);
+/// assert!(r.get_skipped()[0].to_string().starts_with("line 4, column 7: "));
+///
+/// o.set_mode(ImportMode::Strict);
+/// assert!(LabelFileFormat.read(&mut data.as_bytes(), &o).is_err());
+/// ```
+///
+pub struct LabelFileFormat;
+
+impl InputFormat for LabelFileFormat {
+    fn get_name(&self) -> &str {
+        "label file"
+    }
+
+    // The first line of a label file contains the number of labels, possibly preceded by a UTF-8
+    // byte order mark.
+    fn detect(&self, head: &[u8]) -> bool {
+        let head = if head.starts_with(b"\xef\xbb\xbf") { &head[3..] } else { head };
+        let first = head.split(|&b| b == b'\n').next().unwrap_or(&[]);
+        match ::std::str::from_utf8(first) {
+            Ok(s) => s.trim().parse::<usize>().is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    fn read(&self,
+            reader: &mut dyn BufRead,
+            options: &ImportOptions)
+            -> Result<ImportReport, Error> {
+        LabelReader::new(reader)?.read_all(options)
+    }
+}
+
+// The registered custom input formats.
+static FORMATS: RwLock<Vec<Arc<dyn InputFormat>>> = RwLock::new(Vec::new());
+
+///
+/// Register a custom input format used by `import_auto`.
+///
+/// Formats registered later are tried first, the binary and the label file format of the crate
+/// are tried last.
+///
+pub fn register_format(format: Box<dyn InputFormat>) {
+    let mut formats = FORMATS.write().unwrap_or_else(|e| e.into_inner());
+    formats.push(Arc::from(format));
+}
+
+///
+/// Find the input format of a file starting with head or None if no format matches.
+///
+pub fn find_format(head: &[u8]) -> Option<Arc<dyn InputFormat>> {
+    let formats = FORMATS.read().unwrap_or_else(|e| e.into_inner());
+    let builtin: [Arc<dyn InputFormat>; 2] = [Arc::new(BinaryFormat), Arc::new(LabelFileFormat)];
+
+    formats.iter()
+        .rev()
+        .cloned()
+        .chain(builtin.iter().cloned())
+        .find(|f| f.detect(head))
+}
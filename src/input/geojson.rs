@@ -0,0 +1,591 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::error;
+use std::io::BufRead;
+use std::str::FromStr;
+
+use error::Error;
+use input::{ImportMode, ImportOptions};
+use input::format::{ImportReport, InputFormat};
+use primitives::label::Label;
+
+// The maximum nesting depth of json values.
+const MAX_DEPTH: usize = 128;
+
+///
+/// The settings to read labels from GeoJSON files.
+///
+/// The settings define the names of the feature properties the label data is read from. The
+/// defaults are the property names written by `output::to_geojson`:
+///
+/// ```text
+/// osm_id, prio, t, name, lbl_fac
+/// ```
+///
+/// The properties label_length, category and tags are read if present.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeoJsonOptions {
+    m_osm_id_key: String,
+    m_prio_key: String,
+    m_t_key: String,
+    m_label_key: String,
+    m_factor_key: String,
+}
+
+impl GeoJsonOptions {
+    ///
+    /// Initialize the default settings.
+    ///
+    pub fn new() -> GeoJsonOptions {
+        GeoJsonOptions {
+            m_osm_id_key: "osm_id".to_string(),
+            m_prio_key: "prio".to_string(),
+            m_t_key: "t".to_string(),
+            m_label_key: "name".to_string(),
+            m_factor_key: "lbl_fac".to_string(),
+        }
+    }
+
+    ///
+    /// Set the name of the property containing the osm id, the osm id is 0 if it is missing.
+    ///
+    pub fn set_osm_id_key(&mut self, key: String) {
+        self.m_osm_id_key = key;
+    }
+
+    ///
+    /// Get the name of the property containing the osm id.
+    ///
+    pub fn get_osm_id_key(&self) -> &String {
+        &self.m_osm_id_key
+    }
+
+    ///
+    /// Set the name of the property containing the priority, the priority is 0 if it is missing.
+    ///
+    pub fn set_prio_key(&mut self, key: String) {
+        self.m_prio_key = key;
+    }
+
+    ///
+    /// Get the name of the property containing the priority.
+    ///
+    pub fn get_prio_key(&self) -> &String {
+        &self.m_prio_key
+    }
+
+    ///
+    /// Set the name of the property containing the elimination time t (required).
+    ///
+    pub fn set_t_key(&mut self, key: String) {
+        self.m_t_key = key;
+    }
+
+    ///
+    /// Get the name of the property containing the elimination time t.
+    ///
+    pub fn get_t_key(&self) -> &String {
+        &self.m_t_key
+    }
+
+    ///
+    /// Set the name of the property containing the label string (required).
+    ///
+    pub fn set_label_key(&mut self, key: String) {
+        self.m_label_key = key;
+    }
+
+    ///
+    /// Get the name of the property containing the label string.
+    ///
+    pub fn get_label_key(&self) -> &String {
+        &self.m_label_key
+    }
+
+    ///
+    /// Set the name of the property containing the label factor, the factor is 1 if it is
+    /// missing.
+    ///
+    pub fn set_factor_key(&mut self, key: String) {
+        self.m_factor_key = key;
+    }
+
+    ///
+    /// Get the name of the property containing the label factor.
+    ///
+    pub fn get_factor_key(&self) -> &String {
+        &self.m_factor_key
+    }
+}
+
+impl Default for GeoJsonOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+///
+/// The GeoJSON input format with the given settings.
+///
+/// The file must contain a FeatureCollection of Point features. Features that could not be
+/// read, e.g. features of other geometry types, are skipped and reported with the line they
+/// start at. Label strings may also be indices into a top level `strings` array as written by
+/// `output::to_geojson` with the string table enabled.
+///
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::input::ImportOptions;
+/// use rt_datastructure::input::format::InputFormat;
+/// use rt_datastructure::input::geojson::{GeoJsonFormat, GeoJsonOptions};
+///
+/// let data = r#"{"type": "FeatureCollection", "features": [
+///     {"type": "Feature", "geometry": {"type": "Point", "coordinates": [8.75, 53.125]},
+///      "properties": {"id": 62559, "elim_t": 3.5, "label": "Bremen",
+///                     "tags": {"place": "city"}}},
+///     {"type": "Feature", "geometry": {"type": "LineString", "coordinates": [[8, 53], [9, 53]]},
+///      "properties": {"id": 2, "elim_t": 1.5, "label": "Weser"}}
+/// ]}"#;
+///
+/// let mut o = GeoJsonOptions::new();
+/// o.set_osm_id_key("id".to_string());
+/// o.set_t_key("elim_t".to_string());
+/// o.set_label_key("label".to_string());
+///
+/// let r = GeoJsonFormat::new(o).read(&mut data.as_bytes(), &ImportOptions::new()).unwrap();
+/// assert!(r.get_labels().len() == 1);
+/// assert!(r.get_labels()[0].get_osm_id() == 62559);
+/// assert!(r.get_labels()[0].get_x() == 8.75 && r.get_labels()[0].get_t() == 3.5);
+/// assert!(*r.get_labels()[0].get_tag("place").unwrap() == "city".to_string());
+/// assert!(r.get_skipped()[0].to_string().starts_with("line 5: "));
+/// ```
+///
+pub struct GeoJsonFormat {
+    m_options: GeoJsonOptions,
+}
+
+impl GeoJsonFormat {
+    ///
+    /// Initialize the GeoJSON format with the given settings.
+    ///
+    pub fn new(options: GeoJsonOptions) -> GeoJsonFormat {
+        GeoJsonFormat { m_options: options }
+    }
+
+    ///
+    /// Get the settings of the format.
+    ///
+    pub fn get_options(&self) -> &GeoJsonOptions {
+        &self.m_options
+    }
+
+    // Create the label of a single feature.
+    fn read_feature(&self, feature: &Json, strings: &[String]) -> Result<Label, Error> {
+        let geometry = feature.get("geometry").ok_or_else(|| invalid("Missing geometry"))?;
+        match geometry.get("type") {
+            Some(Json::Str(kind)) if kind == "Point" => {}
+            Some(Json::Str(kind)) => {
+                return Err(invalid(&format!("Unsupported geometry type {}", kind)))
+            }
+            _ => return Err(invalid("Missing geometry type")),
+        }
+        let (x, y) = match geometry.get("coordinates") {
+            Some(Json::Array(c)) if c.len() >= 2 => {
+                (number(&c[0].1, "x coordinate")?, number(&c[1].1, "y coordinate")?)
+            }
+            _ => return Err(invalid("Invalid point coordinates")),
+        };
+
+        let properties = feature.get("properties").ok_or_else(|| invalid("Missing properties"))?;
+        let property = |key: &String| {
+            properties.get(key).ok_or_else(|| invalid(&format!("Missing property {}", key)))
+        };
+
+        let o = &self.m_options;
+        let text = match *property(&o.m_label_key)? {
+            Json::Str(ref s) => s.clone(),
+            ref idx @ Json::Number(_) => {
+                let idx: usize = number(idx, &o.m_label_key)?;
+                strings.get(idx)
+                    .cloned()
+                    .ok_or_else(|| invalid(&format!("Invalid string index {}", idx)))?
+            }
+            _ => return Err(invalid(&format!("Invalid {} value", o.m_label_key))),
+        };
+
+        let mut b = Label::builder()
+            .pos(x, y)
+            .t(number(property(&o.m_t_key)?, &o.m_t_key)?)
+            .text(text);
+        if let Some(v) = properties.get(&o.m_osm_id_key) {
+            b = b.osm_id(number(v, &o.m_osm_id_key)?);
+        }
+        if let Some(v) = properties.get(&o.m_prio_key) {
+            b = b.prio(number(v, &o.m_prio_key)?);
+        }
+        if let Some(v) = properties.get(&o.m_factor_key) {
+            b = b.factor(number(v, &o.m_factor_key)?);
+        }
+        if let Some(v) = properties.get("label_length") {
+            b = b.length(number(v, "label_length")?);
+        }
+        if let Some(v) = properties.get("category") {
+            b = b.category(number(v, "category")?);
+        }
+        if let Some(Json::Object(tags)) = properties.get("tags") {
+            for (key, value) in tags {
+                if let Json::Str(value) = value {
+                    b = b.tag(key.as_str(), value.as_str());
+                }
+            }
+        }
+
+        Ok(b.build())
+    }
+}
+
+impl InputFormat for GeoJsonFormat {
+    fn get_name(&self) -> &str {
+        "GeoJSON"
+    }
+
+    // A GeoJSON file is detected by a json object declaring a FeatureCollection at its beginning.
+    fn detect(&self, head: &[u8]) -> bool {
+        let head = String::from_utf8_lossy(head);
+        head.trim_start().starts_with('{') && head.contains("\"FeatureCollection\"")
+    }
+
+    fn read(&self,
+            reader: &mut dyn BufRead,
+            options: &ImportOptions)
+            -> Result<ImportReport, Error> {
+        let mut input = String::new();
+        reader.read_to_string(&mut input)?;
+
+        let root = Parser::new(&input).parse()?;
+        let features = match (root.get("type"), root.get("features")) {
+            (Some(Json::Str(kind)), Some(Json::Array(features)))
+                if kind == "FeatureCollection" => features,
+            _ => return Err(invalid("Not a GeoJSON FeatureCollection").at_line(1)),
+        };
+        let strings: Vec<String> = match root.get("strings") {
+            Some(Json::Array(strings)) => {
+                strings.iter()
+                    .map(|s| match s.1 {
+                             Json::Str(ref s) => s.clone(),
+                             _ => String::new(),
+                         })
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+
+        let mut labels: Vec<Label> = Vec::new();
+        let mut skipped: Vec<Error> = Vec::new();
+        for &(line, ref feature) in features {
+            match self.read_feature(feature, &strings) {
+                Ok(label) => labels.push(label),
+                Err(e) => {
+                    let e = e.at_line(line);
+                    if options.get_mode() == ImportMode::Strict {
+                        return Err(e);
+                    }
+                    skipped.push(e);
+                }
+            }
+        }
+
+        Ok(ImportReport::new(labels, skipped))
+    }
+}
+
+// Construct a parse error of a feature.
+fn invalid(message: &str) -> Error {
+    Error::parse::<String>(message.to_string(), None)
+}
+
+// Parse the json number with the given name.
+fn number<F>(v: &Json, name: &str) -> Result<F, Error>
+    where F: FromStr,
+          F::Err: Into<Box<dyn error::Error + Send + Sync>>
+{
+    match *v {
+        Json::Number(ref n) => {
+            n.parse().map_err(|e| Error::parse(format!("Invalid {} value: {}", name, n), Some(e)))
+        }
+        _ => Err(invalid(&format!("Invalid {} value", name))),
+    }
+}
+
+// A json value. Numbers keep their text, so integers are read without loss of precision. The
+// elements of arrays are stored with the line they start at. The literals true, false and null
+// are not distinguished since no label data is read from them.
+enum Json {
+    Literal,
+    Number(String),
+    Str(String),
+    Array(Vec<(usize, Json)>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    // Get the member of an object with the given key.
+    fn get(&self, key: &str) -> Option<&Json> {
+        match *self {
+            Json::Object(ref members) => members.iter().find(|m| m.0 == key).map(|m| &m.1),
+            _ => None,
+        }
+    }
+}
+
+// A recursive descent parser of json documents. Line breaks may only occur in whitespace, so the
+// current line is tracked by skip_whitespace.
+struct Parser<'a> {
+    m_input: &'a str,
+    m_pos: usize,
+    m_line: usize,
+    m_line_start: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Parser<'a> {
+        Parser {
+            m_input: input,
+            m_pos: 0,
+            m_line: 1,
+            m_line_start: 0,
+        }
+    }
+
+    // Parse the whole input as a single json value.
+    fn parse(&mut self) -> Result<Json, Error> {
+        let v = self.value(0)?;
+        self.skip_whitespace();
+        if self.m_pos < self.m_input.len() {
+            return Err(self.error("Unexpected data after the json value"));
+        }
+
+        Ok(v)
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Json, Error> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("Json values are nested too deeply"));
+        }
+
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.object(depth),
+            Some(b'[') => self.array(depth),
+            Some(b'"') => self.string().map(Json::Str),
+            Some(b't') => self.literal("true"),
+            Some(b'f') => self.literal("false"),
+            Some(b'n') => self.literal("null"),
+            Some(b'-') | Some(b'0'..=b'9') => self.number(),
+            _ => Err(self.error("Expected a json value")),
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<Json, Error> {
+        self.m_pos += 1;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.m_pos += 1;
+            return Ok(Json::Object(members));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("Expected an object key"));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.consume(b':')?;
+            members.push((key, self.value(depth + 1)?));
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.m_pos += 1,
+                Some(b'}') => {
+                    self.m_pos += 1;
+                    return Ok(Json::Object(members));
+                }
+                _ => return Err(self.error("Expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<Json, Error> {
+        self.m_pos += 1;
+        let mut elements = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.m_pos += 1;
+            return Ok(Json::Array(elements));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let line = self.m_line;
+            elements.push((line, self.value(depth + 1)?));
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.m_pos += 1,
+                Some(b']') => {
+                    self.m_pos += 1;
+                    return Ok(Json::Array(elements));
+                }
+                _ => return Err(self.error("Expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        self.m_pos += 1;
+        let mut s = String::new();
+        loop {
+            let rest = &self.m_input[self.m_pos..];
+            let end = match rest.find(|c| c == '"' || c == '\\' || c < ' ') {
+                Some(end) => end,
+                None => return Err(self.error("Unterminated string")),
+            };
+            s.push_str(&rest[..end]);
+            self.m_pos += end;
+
+            match self.peek() {
+                Some(b'"') => {
+                    self.m_pos += 1;
+                    return Ok(s);
+                }
+                Some(b'\\') => {
+                    self.m_pos += 1;
+                    let c = self.escape()?;
+                    s.push(c);
+                }
+                _ => return Err(self.error("Control character in string")),
+            }
+        }
+    }
+
+    // Read the escape sequence following a backslash.
+    fn escape(&mut self) -> Result<char, Error> {
+        let c = match self.peek() {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{8}',
+            Some(b'f') => '\u{c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                self.m_pos += 1;
+                let mut code = self.hex()?;
+                // characters outside of the basic multilingual plane are written as surrogate pair
+                let pair = self.m_input[self.m_pos..].starts_with("\\u");
+                if pair && (0xd800..0xdc00).contains(&code) {
+                    self.m_pos += 2;
+                    let low = self.hex()?;
+                    if (0xdc00..0xe000).contains(&low) {
+                        code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                    }
+                }
+                return Ok(::std::char::from_u32(code).unwrap_or('\u{fffd}'));
+            }
+            _ => return Err(self.error("Invalid escape sequence")),
+        };
+        self.m_pos += 1;
+
+        Ok(c)
+    }
+
+    // Read 4 hexadecimal digits.
+    fn hex(&mut self) -> Result<u32, Error> {
+        let digits = self.m_input.get(self.m_pos..self.m_pos + 4).unwrap_or("");
+        match u32::from_str_radix(digits, 16) {
+            Ok(v) if digits.len() == 4 => {
+                self.m_pos += 4;
+                Ok(v)
+            }
+            _ => Err(self.error("Invalid unicode escape sequence")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, Error> {
+        let rest = &self.m_input[self.m_pos..];
+        let end = rest.find(|c: char| !(c.is_ascii_digit() || "+-.eE".contains(c)))
+            .unwrap_or(rest.len());
+        let text = &rest[..end];
+        if text.parse::<f64>().is_err() {
+            return Err(self.error("Invalid number"));
+        }
+        self.m_pos += end;
+
+        Ok(Json::Number(text.to_string()))
+    }
+
+    fn literal(&mut self, word: &str) -> Result<Json, Error> {
+        if !self.m_input[self.m_pos..].starts_with(word) {
+            return Err(self.error("Expected a json value"));
+        }
+        self.m_pos += word.len();
+
+        Ok(Json::Literal)
+    }
+
+    fn consume(&mut self, b: u8) -> Result<(), Error> {
+        if self.peek() != Some(b) {
+            return Err(self.error(&format!("Expected '{}'", b as char)));
+        }
+        self.m_pos += 1;
+
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b) = self.peek() {
+            match b {
+                b' ' | b'\t' | b'\r' => {}
+                b'\n' => {
+                    self.m_line += 1;
+                    self.m_line_start = self.m_pos + 1;
+                }
+                _ => return,
+            }
+            self.m_pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.m_input.as_bytes().get(self.m_pos).cloned()
+    }
+
+    // Construct a parse error at the current position.
+    fn error(&self, message: &str) -> Error {
+        let column = self.m_input[self.m_line_start..self.m_pos].chars().count() + 1;
+        invalid(message).at_column(column).at_line(self.m_line)
+    }
+}
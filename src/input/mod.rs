@@ -26,51 +26,929 @@
 ///
 pub mod parse;
 
-use std::error::Error;
+///
+/// A module providing the input formats labels can be imported from.
+///
+/// Besides the label file format of the crate, custom formats can be registered and are then
+/// detected by [import_auto](fn.import_auto.html).
+///
+pub mod format;
+
+///
+/// A module providing the import of labels from CSV files with configurable delimiter, quoting
+/// and column order.
+///
+pub mod csv;
+
+///
+/// A module providing the import of labels from GeoJSON FeatureCollections of Point features.
+///
+pub mod geojson;
+
+///
+/// A module providing the import of labels from the compact binary label file format written by
+/// [export_binary](../output/fn.export_binary.html).
+///
+pub mod binary;
+
+///
+/// A module providing the transparent decompression of gzip and zstd compressed input files.
+///
+pub mod compression;
+
+///
+/// A module providing the import of labels synthesized from the place nodes of OSM PBF files.
+///
+/// The module requires the `osm-pbf` feature.
+///
+#[cfg(feature = "osm-pbf")]
+pub mod pbf;
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::error;
 use std::fs::File;
+use std::io;
 use std::io::BufRead;
 use std::io::BufReader;
+use std::io::Lines;
+use std::panic;
+use std::rc::Rc;
+use std::thread;
+use std::str::FromStr;
 
+use error::Error;
+use primitives::bbox::BBox;
 use primitives::label::Label;
+use pst_3d::overlay::PriorityOverlay;
+
+use self::compression::Compression;
+use self::csv::{CsvFormat, CsvOptions};
+use self::format::{ImportReport, InputFormat, Rejection};
+use self::geojson::{GeoJsonFormat, GeoJsonOptions};
 
 ///
-/// import the label elimination data given by the file at 'path' into a vector.
+/// A reader of the label file format yielding the labels one by one.
+///
+/// In contrast to `import_labels` the labels are not collected, so they can be filtered or
+/// inserted into other structures without holding the whole file in memory. Malformed lines
+/// yield a Parse error with their line and column and the reader continues with the next line.
+/// An I/O error ends the iteration. If the number of lines does not match the number of labels
+/// specified in the first line, a Validation error is yielded after the last line.
 ///
-/// # Errors
-///   * if the file path does not match any file in the file system
-///   * if the number of labels does not match the specified number of labels
 ///
-pub fn import_labels(path: &String) -> Result<Vec<Label>, Box<Error>> {
-    let mut result: Vec<Label> = Vec::new();
+/// # Examples
+/// ```
+/// use rt_datastructure::input::LabelReader;
+///
+/// let data = "3\n\
+///             lat lon osm_id priority collision_time label_length size_factor label\n\
+///             53.08 8.80 1 1 3.5 3300.0 11.0 'Bremen'\n\
+///             53.55 x 2 1 2.5 3300.0 11.0 'Bremerhaven'\n\
+///             53.14 8.21 3 1 1.5 3300.0 11.0 'Oldenburg'\n";
+///
+/// let reader = LabelReader::new(data.as_bytes()).unwrap();
+/// assert!(reader.get_total() == 3);
+///
+/// let results: Vec<_> = reader.collect();
+/// assert!(results.len() == 3);
+/// assert!(results[1].is_err());
+///
+/// let reader = LabelReader::new(data.as_bytes()).unwrap();
+/// let late: Vec<_> = reader.filter_map(|r| r.ok()).filter(|l| l.get_t() > 2.).collect();
+/// assert!(late.len() == 1 && late[0].get_label() == "Bremen");
+/// ```
+///
+pub struct LabelReader<R: BufRead> {
+    m_lines: Lines<R>,
+    m_line: usize,
+    m_total: usize,
+    m_count: usize,
+    m_done: bool,
+}
 
-    let input_file = File::open(path)?;
-    let reader = BufReader::new(input_file);
+impl<R: BufRead> LabelReader<R> {
+    ///
+    /// Initialize a new reader and read the number of labels and the header line.
+    ///
+    /// A leading UTF-8 byte order mark is skipped and lines may end with CRLF as in files written
+    /// on Windows.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::input::LabelReader;
+    ///
+    /// let data = "\u{feff}1\r\n\
+    ///             lat lon osm_id priority collision_time label_length size_factor label\r\n\
+    ///             53.08 8.80 1 1 3.5 3300.0 11.0 'Bremen'\r";
+    ///
+    /// let mut reader = LabelReader::new(data.as_bytes()).unwrap();
+    /// assert!(reader.get_total() == 1);
+    /// assert!(reader.next().unwrap().unwrap().get_label() == "Bremen");
+    /// assert!(reader.next().is_none());
+    /// ```
+    ///
+    /// # Errors
+    ///   * Io: if the reader fails
+    ///   * Parse: if the first line does not contain the number of labels
+    ///
+    pub fn new(reader: R) -> Result<LabelReader<R>, Error> {
+        let mut lines = reader.lines();
 
-    let mut total: usize = 0;
-    for (idx, line_res) in reader.lines().enumerate() {
-        let line = line_res.unwrap().to_string();
-        if idx == 0 {
-            total = line.parse()?;
-            println!("Reading {} labels from the file", total);
-            continue;
-        } else if idx == 1 {
-            // skip the header line
-            continue;
+        let total = match lines.next() {
+            Some(line) => {
+                let line = line?;
+                line.trim_start_matches('\u{feff}')
+                    .trim()
+                    .parse()
+                    .map_err(|e| Error::parse(format!("Invalid value: {}", line), Some(e)))?
+            }
+            None => 0,
+        };
+        // skip the header line
+        if let Some(line) = lines.next() {
+            line?;
         }
 
-        match parse::parse_label(&line) {
-            Ok(label) => result.push(label),
+        Ok(LabelReader {
+            m_lines: lines,
+            m_line: 2,
+            m_total: total,
+            m_count: 0,
+            m_done: false,
+        })
+    }
+
+    ///
+    /// Get the number of labels specified in the first line.
+    ///
+    pub fn get_total(&self) -> usize {
+        self.m_total
+    }
+
+    ///
+    /// Read all remaining labels with the given options.
+    ///
+    /// The lines are read in batches, each batch is split into one chunk per thread and the
+    /// parsed chunks are merged in the order of the file. In lenient mode malformed lines are
+    /// skipped and their errors are collected in the report.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::input::{ImportMode, ImportOptions, LabelReader};
+    ///
+    /// let data = "3\n\
+    ///             lat lon osm_id priority collision_time label_length size_factor label\n\
+    ///             53.08 8.80 1 1 3.5 3300.0 11.0 'Bremen'\n\
+    ///             53.55 x 2 1 2.5 3300.0 11.0 'Bremerhaven'\n\
+    ///             53.14 8.21 3 1 1.5 3300.0 11.0 'Oldenburg'\n";
+    ///
+    /// let mut o = ImportOptions::new();
+    /// o.set_threads(4);
+    /// let r = LabelReader::new(data.as_bytes()).unwrap().read_all(&o).unwrap();
+    /// assert!(r.get_labels().len() == 2 && r.get_labels()[1].get_label() == "Oldenburg");
+    /// assert!(r.get_skipped()[0].to_string().starts_with("line 4, column 7: "));
+    ///
+    /// o.set_mode(ImportMode::Strict);
+    /// let e = LabelReader::new(data.as_bytes()).unwrap().read_all(&o).unwrap_err();
+    /// assert!(e.to_string().starts_with("line 4, column 7: "));
+    /// ```
+    ///
+    /// # Errors
+    ///   * Io: if the reader fails
+    ///   * Parse: if a line is malformed in strict mode
+    ///   * Validation: if the number of lines does not match the specified number of labels
+    ///
+    pub fn read_all(mut self, options: &ImportOptions) -> Result<ImportReport, Error> {
+        let threads = options.get_threads().max(1);
+        let mut collector = Collector::new();
+
+        loop {
+            let first_line = self.m_line + 1;
+            let first_record = self.m_count;
+            let mut batch: Vec<String> = Vec::with_capacity(threads * PARSE_BATCH);
+            for line in self.m_lines.by_ref().take(threads * PARSE_BATCH) {
+                batch.push(line?);
+            }
+            if batch.is_empty() {
+                break;
+            }
+            self.m_line += batch.len();
+            self.m_count += batch.len();
+
+            let chunk_size = batch.len().div_ceil(threads);
+            let parsed: Vec<Vec<Result<Label, Error>>> = if threads == 1 {
+                vec![parse_lines(&batch, first_line)]
+            } else {
+                thread::scope(|s| {
+                    let handles: Vec<_> = batch.chunks(chunk_size)
+                        .enumerate()
+                        .map(|(i, c)| s.spawn(move || parse_lines(c, first_line + i * chunk_size)))
+                        .collect();
+                    handles.into_iter()
+                        .map(|h| h.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+                        .collect()
+                })
+            };
+
+            let results = parsed.into_iter().flat_map(|chunk| chunk.into_iter());
+            for (idx, res) in results.enumerate() {
+                collector.add(res, first_record + idx, first_line + idx, options)?;
+            }
+        }
+
+        // skipped lines count as labels, so a malformed line does not fail the whole import
+        if self.m_count != self.m_total {
+            return Err(Error::Validation(format!("Specified number of labels ({}) does not \
+                                                  match real label size ({})!",
+                                                 self.m_total,
+                                                 self.m_count)));
+        }
+
+        let mut report = ImportReport::new(collector.m_labels, collector.m_skipped);
+        report.set_rejected(collector.m_rejected);
+        report.set_duplicates(collector.m_duplicates);
+        Ok(report)
+    }
+}
+
+// The results of the parsed lines of `LabelReader::read_all` filtered by the import options.
+struct Collector {
+    m_labels: Vec<Label>,
+    m_skipped: Vec<Error>,
+    m_rejected: Vec<Rejection>,
+    // the positions of the labels by osm id, only filled if duplicates are handled
+    m_positions: HashMap<i64, usize>,
+    m_duplicates: usize,
+}
+
+impl Collector {
+    fn new() -> Collector {
+        Collector {
+            m_labels: Vec::new(),
+            m_skipped: Vec::new(),
+            m_rejected: Vec::new(),
+            m_positions: HashMap::new(),
+            m_duplicates: 0,
+        }
+    }
+
+    // Add the result of the record at the given index and line, strict mode returns the errors.
+    fn add(&mut self,
+           res: Result<Label, Error>,
+           record: usize,
+           line: usize,
+           options: &ImportOptions)
+           -> Result<(), Error> {
+        let strict = options.get_mode() == ImportMode::Strict;
+        let label = match res {
+            Ok(label) => label,
+            Err(e) if strict => return Err(e),
             Err(e) => {
-                println!("Line {} could not be parsed!\nRepored error was: {}", line, e);
-                continue;
-            },
+                self.m_skipped.push(e);
+                return Ok(());
+            }
+        };
+
+        if options.get_validate_coordinates() {
+            match Rejection::check(record, &label) {
+                Some(r) if strict => {
+                    return Err(Error::Validation(format!("line {}: {}", line, r)));
+                }
+                Some(r) => {
+                    self.m_rejected.push(r);
+                    return Ok(());
+                }
+                None => {}
+            }
+        }
+
+        let policy = options.get_duplicates();
+        if policy == DuplicatePolicy::Keep {
+            self.m_labels.push(label);
+            return Ok(());
+        }
+        match self.m_positions.get(&label.get_osm_id()) {
+            Some(_) if policy == DuplicatePolicy::Reject => {
+                let message = format!("line {}: Duplicate osm id {}", line, label.get_osm_id());
+                return Err(Error::Validation(message));
+            }
+            Some(&pos) => {
+                self.m_duplicates += 1;
+                if label.get_t() > self.m_labels[pos].get_t() {
+                    self.m_labels[pos] = label;
+                }
+            }
+            None => {
+                self.m_positions.insert(label.get_osm_id(), self.m_labels.len());
+                self.m_labels.push(label);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// The number of lines each thread parses per batch of `LabelReader::read_all`.
+const PARSE_BATCH: usize = 16 * 1024;
+
+// Parse the lines of a chunk, the first line of the chunk has the given line number.
+fn parse_lines(lines: &[String], first_line: usize) -> Vec<Result<Label, Error>> {
+    lines.iter()
+        .enumerate()
+        .map(|(i, line)| parse::parse_label(line).map_err(|e| e.at_line(first_line + i)))
+        .collect()
+}
+
+impl LabelReader<Box<dyn BufRead>> {
+    ///
+    /// Open the label file at 'path', a gzip or zstd compression is removed transparently.
+    ///
+    /// # Errors
+    ///   * Io: if the file path does not match any file in the file system
+    ///   * Parse: if the first line does not contain the number of labels or the file is
+    ///     compressed in a format whose feature is not enabled
+    ///
+    pub fn open(path: &String) -> Result<LabelReader<Box<dyn BufRead>>, Error> {
+        LabelReader::new(compression::open(path)?)
+    }
+}
+
+impl<R: BufRead> Iterator for LabelReader<R> {
+    type Item = Result<Label, Error>;
+
+    fn next(&mut self) -> Option<Result<Label, Error>> {
+        if self.m_done {
+            return None;
+        }
+
+        match self.m_lines.next() {
+            Some(Ok(line)) => {
+                self.m_line += 1;
+                self.m_count += 1;
+                Some(parse::parse_label(&line).map_err(|e| e.at_line(self.m_line)))
+            }
+            Some(Err(e)) => {
+                self.m_done = true;
+                Some(Err(Error::from(e)))
+            }
+            None => {
+                self.m_done = true;
+                if self.m_count == self.m_total {
+                    return None;
+                }
+
+                Some(Err(Error::Validation(format!("Specified number of labels ({}) does not \
+                                                    match real label size ({})!",
+                                                   self.m_total,
+                                                   self.m_count))))
+            }
         }
     }
+}
+
+///
+/// import the labels of the binary label file at 'path' into a vector, compare
+/// [BinaryFormat](binary/struct.BinaryFormat.html).
+///
+/// # Errors
+///   * Io: if the file path does not match any file in the file system or the file ends early
+///   * Serialization: if the file is not a binary label file of a supported version
+///
+pub fn import_binary(path: &String) -> Result<Vec<Label>, Error> {
+    let mut reader = compression::open(path)?;
+
+    binary::read_binary(&mut reader)
+}
+
+///
+/// The handling of malformed lines during an import.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImportMode {
+    /// The first malformed line fails the import with its line and column.
+    Strict,
+    /// Malformed lines are skipped, their errors are collected in the import report.
+    Lenient,
+}
 
-    if total != result.len() {
-        return Err(From::from("Specified number of labels does not match real label size!"));
+///
+/// The handling of records sharing the osm id of an earlier record during an import.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DuplicatePolicy {
+    /// All records are imported.
+    Keep,
+    /// Only the record with the highest t is imported, at the position of the first record.
+    KeepHighestT,
+    /// The first duplicate fails the import.
+    Reject,
+}
+
+///
+/// The settings of `import_labels`.
+///
+/// A default constructed ImportOptions object imports leniently on a single thread.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportOptions {
+    m_mode: ImportMode,
+    m_threads: usize,
+    m_validate_coordinates: bool,
+    m_duplicates: DuplicatePolicy,
+}
+
+impl ImportOptions {
+    ///
+    /// Initialize new options importing leniently on a single thread.
+    ///
+    pub fn new() -> ImportOptions {
+        ImportOptions {
+            m_mode: ImportMode::Lenient,
+            m_threads: 1,
+            m_validate_coordinates: false,
+            m_duplicates: DuplicatePolicy::Keep,
+        }
+    }
+
+    ///
+    /// Set the handling of malformed lines.
+    ///
+    pub fn set_mode(&mut self, mode: ImportMode) {
+        self.m_mode = mode;
+    }
+
+    ///
+    /// Get the handling of malformed lines.
+    ///
+    pub fn get_mode(&self) -> ImportMode {
+        self.m_mode
     }
 
+    ///
+    /// Set the number of threads the lines are parsed on, 0 is treated as 1.
+    ///
+    pub fn set_threads(&mut self, threads: usize) {
+        self.m_threads = threads;
+    }
+
+    ///
+    /// Get the number of threads the lines are parsed on.
+    ///
+    pub fn get_threads(&self) -> usize {
+        self.m_threads
+    }
+
+    ///
+    /// Validate the latitude, longitude and t value of each label. Invalid labels are collected
+    /// in the report as rejected, in strict mode the first invalid label fails the import.
+    ///
+    /// Without validation, labels outside of the WGS84 extent are clamped by `GeoPst3d::new` or
+    /// fail its validation.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::input::{self, ImportMode, ImportOptions};
+    ///
+    /// let data = "2\n\
+    ///             lat lon osm_id priority collision_time label_length size_factor label\n\
+    ///             153.08 8.80 1 1 3.5 3300.0 11.0 'Bremen'\n\
+    ///             53.55 8.58 2 1 2.5 3300.0 11.0 'Bremerhaven'\n";
+    ///
+    /// let mut o = ImportOptions::new();
+    /// o.set_validate_coordinates(true);
+    /// let r = input::import_from_reader(data.as_bytes(), &o).unwrap();
+    /// assert!(r.get_labels().len() == 1);
+    /// assert!(r.get_rejected()[0].get_index() == 0 && r.get_rejected()[0].get_osm_id() == 1);
+    ///
+    /// o.set_mode(ImportMode::Strict);
+    /// let e = input::import_from_reader(data.as_bytes(), &o).unwrap_err();
+    /// assert!(e.to_string().contains("line 3: record 0 (osm id 1): latitude 153.08"));
+    /// ```
+    ///
+    pub fn set_validate_coordinates(&mut self, validate: bool) {
+        self.m_validate_coordinates = validate;
+    }
+
+    ///
+    /// Check if the coordinates of the labels are validated.
+    ///
+    pub fn get_validate_coordinates(&self) -> bool {
+        self.m_validate_coordinates
+    }
+
+    ///
+    /// Set the handling of records with the osm id of an earlier record, e.g. the overlapping
+    /// border objects of concatenated regional extracts. The number of dropped records is given
+    /// by `ImportReport::get_duplicates`.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::input::{self, DuplicatePolicy, ImportOptions};
+    ///
+    /// let data = "3\n\
+    ///             lat lon osm_id priority collision_time label_length size_factor label\n\
+    ///             53.08 8.80 1 1 3.5 3300.0 11.0 'Bremen'\n\
+    ///             53.55 8.58 2 1 2.5 3300.0 11.0 'Bremerhaven'\n\
+    ///             53.08 8.80 1 1 4.5 3300.0 11.0 'Bremen'\n";
+    ///
+    /// let mut o = ImportOptions::new();
+    /// o.set_duplicates(DuplicatePolicy::KeepHighestT);
+    /// let r = input::import_from_reader(data.as_bytes(), &o).unwrap();
+    /// assert!(r.get_labels().len() == 2 && r.get_duplicates() == 1);
+    /// assert!(r.get_labels()[0].get_t() == 4.5);
+    ///
+    /// o.set_duplicates(DuplicatePolicy::Reject);
+    /// let e = input::import_from_reader(data.as_bytes(), &o).unwrap_err();
+    /// assert!(e.to_string().contains("line 5: Duplicate osm id 1"));
+    /// ```
+    ///
+    pub fn set_duplicates(&mut self, policy: DuplicatePolicy) {
+        self.m_duplicates = policy;
+    }
+
+    ///
+    /// Get the handling of records with the osm id of an earlier record.
+    ///
+    pub fn get_duplicates(&self) -> DuplicatePolicy {
+        self.m_duplicates
+    }
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+///
+/// import the label elimination data given by the file at 'path' with the given options.
+///
+/// In lenient mode the report contains the errors of the skipped lines, in strict mode the
+/// import fails at the first malformed line. Nothing is printed to stdout.
+///
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::input::{self, ImportMode, ImportOptions};
+///
+/// let path = "resources/bremen-latest.osm.pbf.ce".to_string();
+/// let mut o = ImportOptions::new();
+/// o.set_mode(ImportMode::Strict);
+/// o.set_threads(2);
+///
+/// let r = input::import_labels(&path, &o).unwrap();
+/// assert!(r.get_labels().len() == 133 && r.get_skipped().is_empty());
+/// ```
+///
+/// # Errors
+///   * Io: if the file path does not match any file in the file system
+///   * Parse: if the first line does not contain the number of labels, the file is compressed
+///     in a format whose feature is not enabled or, in strict mode, a line is malformed
+///   * Validation: if the number of lines does not match the specified number of labels.
+///     Malformed lines count as labels.
+///
+pub fn import_labels(path: &String, options: &ImportOptions) -> Result<ImportReport, Error> {
+    LabelReader::open(path)?.read_all(options)
+}
+
+///
+/// import the label elimination data read from the reader with the given options, e.g. from
+/// stdin or an in-memory buffer. Compressed streams can be wrapped by
+/// [decompress](compression/fn.decompress.html) first.
+///
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::input::{self, ImportOptions};
+///
+/// let data = "1\n\
+///             lat lon osm_id priority collision_time label_length size_factor label\n\
+///             53.08 8.80 1 1 3.5 3300.0 11.0 'Bremen'\n";
+///
+/// let r = input::import_from_reader(data.as_bytes(), &ImportOptions::new()).unwrap();
+/// assert!(r.get_labels()[0].get_label() == "Bremen");
+/// ```
+///
+/// # Errors
+///   * Io: if the reader fails
+///   * Parse: if the first line does not contain the number of labels or, in strict mode, a line
+///     is malformed
+///   * Validation: if the number of lines does not match the specified number of labels.
+///     Malformed lines count as labels.
+///
+pub fn import_from_reader<R: BufRead>(reader: R,
+                                      options: &ImportOptions)
+                                      -> Result<ImportReport, Error> {
+    LabelReader::new(reader)?.read_all(options)
+}
+
+///
+/// Parse the labels of a whole label file given as string, i.e. the number of labels, the header
+/// and the records, e.g. where no file system is available. The records are parsed strictly.
+///
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::input;
+///
+/// let data = "2\n\
+///             lat lon osm_id priority collision_time label_length size_factor label\n\
+///             53.08 8.80 1 1 3.5 3300.0 11.0 'Bremen'\n\
+///             53.55 8.58 2 1 2.5 3300.0 11.0 'Bremerhaven'\n";
+///
+/// let labels = input::parse_all(data).unwrap();
+/// assert!(labels.len() == 2 && labels[1].get_label() == "Bremerhaven");
+///
+/// let e = input::parse_all(&data.replace("8.58", "x")).unwrap_err();
+/// assert!(e.to_string().starts_with("line 4, column 7: "));
+/// ```
+///
+/// # Errors
+///   * Parse: if the first line does not contain the number of labels or a line is malformed
+///   * Validation: if the number of lines does not match the specified number of labels
+///
+pub fn parse_all(text: &str) -> Result<Vec<Label>, Error> {
+    let mut options = ImportOptions::new();
+    options.set_mode(ImportMode::Strict);
+
+    import_from_reader(text.as_bytes(), &options).map(|report| report.into_labels())
+}
+
+///
+/// The number of label lines after which `import_labels_with_progress` reports the progress.
+///
+pub const PROGRESS_INTERVAL: usize = 100_000;
+
+///
+/// The progress of a running import.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportProgress {
+    m_lines: usize,
+    m_bytes: u64,
+    m_labels: usize,
+    m_total: usize,
+}
+
+impl ImportProgress {
+    ///
+    /// Get the number of lines read including the two header lines.
+    ///
+    pub fn get_lines(&self) -> usize {
+        self.m_lines
+    }
+
+    ///
+    /// Get the number of bytes read from the file. For compressed files these are the compressed
+    /// bytes, so the progress can always be related to the file size.
+    ///
+    pub fn get_bytes(&self) -> u64 {
+        self.m_bytes
+    }
+
+    ///
+    /// Get the number of labels accepted so far, malformed lines are not counted.
+    ///
+    pub fn get_labels(&self) -> usize {
+        self.m_labels
+    }
+
+    ///
+    /// Get the number of labels specified in the first line of the file.
+    ///
+    pub fn get_total(&self) -> usize {
+        self.m_total
+    }
+}
+
+///
+/// import the label elimination data given by the file at 'path' and report the progress to the
+/// callback.
+///
+/// The callback is invoked every `PROGRESS_INTERVAL` label lines and once after the last line.
+/// Malformed lines are skipped and collected in the report.
+///
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::input;
+///
+/// let mut reports = Vec::new();
+/// let path = "resources/bremen-latest.osm.pbf.ce".to_string();
+/// let r = input::import_labels_with_progress(&path, |p| reports.push(p.clone())).unwrap();
+///
+/// let last = reports.last().unwrap();
+/// assert!(last.get_labels() == r.get_labels().len() && last.get_total() == 133);
+/// assert!(last.get_lines() == 135);
+/// assert!(last.get_bytes() == std::fs::metadata(&path).unwrap().len());
+/// ```
+///
+/// # Errors
+///   * Io: if the file path does not match any file in the file system
+///   * Parse: if the first line does not contain the number of labels or the file is compressed
+///     in a format whose feature is not enabled
+///   * Validation: if the number of lines does not match the specified number of labels.
+///     Malformed lines count as labels, they are skipped and collected in the report.
+///
+pub fn import_labels_with_progress<F>(path: &String, mut callback: F) -> Result<ImportReport, Error>
+    where F: FnMut(&ImportProgress)
+{
+    let bytes = Rc::new(Cell::new(0));
+    let mut input = BufReader::new(CountingReader {
+        m_inner: File::open(path)?,
+        m_count: bytes.clone(),
+    });
+    let compression = Compression::detect(input.fill_buf()?, path);
+    let reader = LabelReader::new(compression::decompress(input, compression)?)?;
+
+    let mut progress = ImportProgress {
+        m_lines: 2,
+        m_bytes: 0,
+        m_labels: 0,
+        m_total: reader.get_total(),
+    };
+    let mut labels: Vec<Label> = Vec::new();
+    let mut skipped: Vec<Error> = Vec::new();
+    let mut unreported: usize = 0;
+    for res in reader {
+        match res {
+            Ok(label) => labels.push(label),
+            Err(e @ Error::Parse { .. }) => skipped.push(e),
+            Err(e) => return Err(e),
+        }
+
+        progress.m_lines += 1;
+        unreported += 1;
+        if unreported == PROGRESS_INTERVAL {
+            unreported = 0;
+            progress.m_bytes = bytes.get();
+            progress.m_labels = labels.len();
+            callback(&progress);
+        }
+    }
+    progress.m_bytes = bytes.get();
+    progress.m_labels = labels.len();
+    callback(&progress);
+
+    Ok(ImportReport::new(labels, skipped))
+}
+
+// A reader counting the bytes read from the inner reader.
+struct CountingReader<R: io::Read> {
+    m_inner: R,
+    m_count: Rc<Cell<u64>>,
+}
+
+impl<R: io::Read> io::Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.m_inner.read(buf)?;
+        self.m_count.set(self.m_count.get() + n as u64);
+        Ok(n)
+    }
+}
+
+///
+/// import the labels given by the CSV file at 'path' read with the given options.
+///
+/// Records that could not be read are skipped and collected in the report.
+///
+/// # Errors
+///   * Io: if the file path does not match any file in the file system
+///   * Validation: if the columns of the options lack the lat, lon, t or label column
+///
+pub fn import_csv(path: &String, options: CsvOptions) -> Result<ImportReport, Error> {
+    let mut reader = compression::open(path)?;
+
+    CsvFormat::new(options).read(&mut reader, &ImportOptions::new())
+}
+
+///
+/// import the labels given by the GeoJSON file at 'path' read with the given options.
+///
+/// Features that could not be read are skipped and collected in the report.
+///
+/// # Errors
+///   * Io: if the file path does not match any file in the file system
+///   * Parse: if the file is not a valid json document or not a FeatureCollection
+///
+pub fn import_geojson(path: &String, options: GeoJsonOptions) -> Result<ImportReport, Error> {
+    let mut reader = compression::open(path)?;
+
+    GeoJsonFormat::new(options).read(&mut reader, &ImportOptions::new())
+}
+
+///
+/// import the labels synthesized from the place nodes of the OSM PBF file at 'path', compare
+/// [OsmPbfFormat](pbf/struct.OsmPbfFormat.html).
+///
+/// # Errors
+///   * Io: if the file path does not match any file in the file system or the file ends early
+///   * Serialization: if the file is not a valid OSM PBF file or requires unsupported features
+///
+#[cfg(feature = "osm-pbf")]
+pub fn import_osm_pbf(path: &String, options: pbf::OsmOptions) -> Result<Vec<Label>, Error> {
+    let mut reader = compression::open(path)?;
+
+    let report = pbf::OsmPbfFormat::new(options).read(&mut reader, &ImportOptions::new())?;
+    Ok(report.into_labels())
+}
+
+///
+/// import the labels given by the file at 'path' in any registered input format with the given
+/// options.
+///
+/// The format is detected from the beginning of the file, compare
+/// [find_format](format/fn.find_format.html), after a gzip or zstd compression has been removed.
+/// The name of the detected format and the errors of skipped records are part of the report.
+///
+/// # Errors
+///   * Io: if the file path does not match any file in the file system
+///   * Parse: if no input format matches the file, the file could not be read in its format or,
+///     in strict mode, a record is malformed
+///   * Validation: if the data read violates the requirements of the format
+///
+pub fn import_auto(path: &String, options: &ImportOptions) -> Result<ImportReport, Error> {
+    let mut reader = compression::open(path)?;
+
+    let detected = format::find_format(reader.fill_buf()?);
+    match detected {
+        Some(f) => {
+            let mut report = f.read(&mut reader, options)?;
+            report.set_format(f.get_name());
+            Ok(report)
+        }
+        None => {
+            let message = format!("Unknown input format of {}", path);
+            Err(Error::parse::<String>(message, None))
+        }
+    }
+}
+
+///
+/// import a priority overlay from the file at 'path'.
+///
+/// Each line of the file defines a single override of one of the forms:
+///
+/// ```text
+/// id <osm_id> <delta>
+/// region <min_x> <min_y> <max_x> <max_y> <delta>
+/// ```
+///
+/// Empty lines and lines starting with '#' are ignored, e.g.:
+///
+/// ```text
+/// # promote a sponsor POI
+/// id 3627273522 2.5
+/// # demote all labels in a city center
+/// region 8.7 53.0 8.9 53.1 -1.0
+/// ```
+///
+/// # Errors
+///   * Io: if the file path does not match any file in the file system
+///   * Parse: if a line does not match one of the forms above
+///
+pub fn import_overlay(path: &String) -> Result<PriorityOverlay, Error> {
+    let mut result = PriorityOverlay::new();
+
+    let input_file = File::open(path)?;
+    let reader = BufReader::new(input_file);
+
+    for (idx, line_res) in reader.lines().enumerate() {
+        let line = line_res?;
+        let nr = idx + 1;
+        let fields: Vec<&str> = line.trim_start_matches('\u{feff}').split_whitespace().collect();
+
+        match fields.first() {
+            None => continue,
+            Some(f) if f.starts_with('#') => continue,
+            Some(&"id") if fields.len() == 3 => {
+                result.add_id(parse_value(fields[1], nr)?, parse_value(fields[2], nr)?);
+            }
+            Some(&"region") if fields.len() == 6 => {
+                let region = BBox::new(parse_value(fields[1], nr)?,
+                                       parse_value(fields[2], nr)?,
+                                       parse_value(fields[3], nr)?,
+                                       parse_value(fields[4], nr)?);
+                result.add_region(region, parse_value(fields[5], nr)?);
+            }
+            Some(_) => {
+                let message = format!("Invalid overlay line: {}", line);
+                return Err(Error::parse::<String>(message, None).at_line(nr));
+            }
+        }
+    }
 
     Ok(result)
 }
+
+// Parse a single value of the given line of an input file.
+fn parse_value<F>(s: &str, line: usize) -> Result<F, Error>
+    where F: FromStr,
+          F::Err: Into<Box<dyn error::Error + Send + Sync>>
+{
+    s.parse().map_err(|e| Error::parse(format!("Invalid value: {}", s), Some(e)).at_line(line))
+}
@@ -25,9 +25,12 @@
 /// The strings must be of the form defined in the [Module description](index.html)
 ///
 
-use regex::Regex;
-use std::error::Error;
+use regex::{Captures, Regex};
+use std::error;
+use std::str::FromStr;
 
+use error::Error;
+use primitives::coord::Coord;
 use primitives::label::Label;
 
 ///
@@ -42,6 +45,9 @@ use primitives::label::Label;
 ///          'Timmersloh'".to_string();
 /// let v = parse::validate_label(&s);
 /// assert!(v);
+///
+/// let s = "42.35 13.40 123 1 1.5 3300.0 11.0 'L\\'Aquila'".to_string();
+/// assert!(parse::validate_label(&s));
 /// ```
 ///
 /// ```
@@ -55,25 +61,33 @@ use primitives::label::Label;
 ///
 pub fn validate_label(s_input: &String) -> bool {
     lazy_static! {
-        static ref RE : Regex = Regex::new("\
+        static ref RE : Option<Regex> = Regex::new("\
         ^-?\\d{1,3}(\\.\\d+(e[+-]?\\d+)?)? \
         -?\\d{1,3}(\\.\\d+(e[+-]?\\d+)?)? \
-        \\d+ \\d+ \
-        \\d+(\\.\\d+(e[+-]?\\d+)?)? \
+        \\d+ -?\\d+ \
+        (inf|\\d+(\\.\\d+(e[+-]?\\d+)?)?) \
         \\d+(\\.\\d+(e[+-]?\\d+)?)? \
         \\d+(\\.\\d+(e[+-]?\\d+)?)? \
-        '.*'\
-        ").unwrap();
+        '(?:[^\\\\]|\\\\.)*'\
+        ").ok();
     }
 
-    RE.is_match(s_input)
+    match *RE {
+        Some(ref re) => re.is_match(s_input),
+        None => false,
+    }
 }
 
 ///
-/// Parse a string reference and create a corresponding label
+/// Parse a string reference and create a corresponding label, a trailing carriage return of a
+/// CRLF line ending is ignored.
 ///
-/// # Panics
-/// * Panics if the string does not match the required format.
+/// Lines consisting of the numeric fields and a plain label string are split by hand, all other
+/// lines are parsed by `parse_label_regex`. Both ways yield the same labels and errors.
+///
+/// # Errors
+///   * Parse: if the string does not match the required format. The column of the error is the
+///     position of the first field that could not be read, the line is left to the caller.
 ///
 /// # Examples
 /// ```
@@ -82,7 +96,10 @@ pub fn validate_label(s_input: &String) -> bool {
 ///
 /// let s = "53.143155300000004 8.9351249 3627273522 1 1.4922737369836614 3300.0 11.0 \
 ///          'Timmersloh'".to_string();
-/// let l = parse::parse_label(&s);
+/// let l = parse::parse_label(&s).unwrap();
+///
+/// assert!(l.get_label_length() == 3300.);
+/// assert!(l.get_label_factor() == 11.);
 /// ```
 ///
 /// ```
@@ -95,32 +112,414 @@ pub fn validate_label(s_input: &String) -> bool {
 /// assert!(l.is_err())
 /// ```
 ///
-pub fn parse_label(s_input: &String) -> Result<Label, Box<Error>> {
+/// ```
+/// use rt_datastructure::input::parse;
+///
+/// // the priority is not an integer
+/// let s = "53.14 8.93 3627273522 x 1.49 3300.0 11.0 'Timmersloh'".to_string();
+/// let e = parse::parse_label(&s).unwrap_err().at_line(12);
+///
+/// assert!(e.to_string().starts_with("line 12, column 23: "));
+///
+/// // the label string is missing
+/// let s = "53.14 8.93 3627273522 1 1.49 3300.0 11.0".to_string();
+/// let e = parse::parse_label(&s).unwrap_err().at_line(1);
+///
+/// assert!(e.to_string().starts_with("line 1, column 41: "));
+/// ```
+///
+/// An optional short label may follow the label string:
+///
+/// ```
+/// use rt_datastructure::input::parse;
+///
+/// let s = "59.93 30.31 123 1 1.5 3300.0 11.0 'Sankt Petersburg' 'St. Pete'".to_string();
+/// let l = parse::parse_label(&s).unwrap();
+///
+/// assert!(*l.get_label() == "Sankt Petersburg".to_string());
+/// assert!(*l.get_short_label().unwrap() == "St. Pete".to_string());
+/// ```
+///
+/// An optional category id may follow the short label:
+///
+/// ```
+/// use rt_datastructure::input::parse;
+///
+/// let s = "47.42 10.98 123 1 1.5 3300.0 11.0 'Zugspitze' category:2".to_string();
+/// assert!(parse::parse_label(&s).unwrap().get_category() == 2);
+///
+/// let s = "47.42 10.98 123 1 1.5 3300.0 11.0 'Zugspitze' category:70000".to_string();
+/// assert!(parse::parse_label(&s).is_err());
+/// ```
+///
+/// Names in further languages may follow as key value pairs:
+///
+/// ```
+/// use rt_datastructure::input::parse;
+///
+/// let s = "48.14 11.58 123 1 1.5 3300.0 11.0 'München' name:en='Munich' name:it='Monaco'"
+///     .to_string();
+/// let l = parse::parse_label(&s).unwrap();
+///
+/// assert!(*l.get_label() == "München".to_string());
+/// assert!(l.get_short_label().is_none());
+/// assert!(*l.get_name("name:en").unwrap() == "Munich".to_string());
+/// assert!(*l.get_name("name:it").unwrap() == "Monaco".to_string());
+/// ```
+///
+/// Tags may follow the names as key value pairs with the prefix "tag:":
+///
+/// ```
+/// use rt_datastructure::input::parse;
+///
+/// let s = "52.52 13.40 123 1 1.5 3300.0 11.0 'Berlin' name:en='Berlin' tag:place='city' \
+///          tag:wikidata='Q64'"
+///     .to_string();
+/// let l = parse::parse_label(&s).unwrap();
+///
+/// assert!(*l.get_name("name:en").unwrap() == "Berlin".to_string());
+/// assert!(*l.get_tag("place").unwrap() == "city".to_string());
+/// assert!(*l.get_tag("wikidata").unwrap() == "Q64".to_string());
+/// ```
+///
+/// Single quotes within the strings are escaped by a backslash or doubled, a backslash is escaped
+/// by a second one:
+///
+/// ```
+/// use rt_datastructure::input::parse;
+///
+/// let s = "42.35 13.40 123 1 1.5 3300.0 11.0 'L\\'Aquila' 'L''AQ' name:fr='L\\'Aquila'"
+///     .to_string();
+/// let l = parse::parse_label(&s).unwrap();
+///
+/// assert!(*l.get_label() == "L'Aquila".to_string());
+/// assert!(*l.get_short_label().unwrap() == "L'AQ".to_string());
+/// assert!(*l.get_name("name:fr").unwrap() == "L'Aquila".to_string());
+/// ```
+///
+/// Priorities may be negative and float fields may be given as plain integers. A label that never
+/// disappears has the t value `inf`, which is stored as the largest finite t:
+///
+/// ```
+/// use rt_datastructure::input::parse;
+/// use rt_datastructure::primitives::coord::Coord;
+///
+/// let s = "53.14 8.93 3627273522 -2 inf 3300 11 'Timmersloh'".to_string();
+/// assert!(parse::validate_label(&s));
+///
+/// let l = parse::parse_label(&s).unwrap();
+///
+/// assert!(l.get_prio() == -2);
+/// assert!(l.get_t() == Coord::MAX as f64);
+/// assert!(l.get_label_length() == 3300.);
+///
+/// // infinity is only accepted for t
+/// let s = "53.14 8.93 3627273522 1 1.49 inf 11.0 'Timmersloh'".to_string();
+/// let e = parse::parse_label(&s).unwrap_err().at_line(1);
+///
+/// assert!(e.to_string().starts_with("line 1, column 30: "));
+/// ```
+///
+pub fn parse_label(s_input: &str) -> Result<Label, Error> {
+    let s_input = s_input.trim_end_matches('\r');
+    match parse_plain(s_input) {
+        Some(label) => Ok(label),
+        None => parse_label_regex(s_input),
+    }
+}
+
+///
+/// Parse a string reference by a regular expression and create a corresponding label, compare
+/// `parse_label`.
+///
+/// # Errors
+///   * Parse: if the string does not match the required format, as for `parse_label`
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::input::parse;
+///
+/// let s = "53.14 8.93 3627273522 1 1.49 3300.0 11.0 'Timmersloh' 'Ti.'";
+/// let l = parse::parse_label_regex(s).unwrap();
+///
+/// assert!(l.get_label() == parse::parse_label(s).unwrap().get_label());
+/// assert!(*l.get_short_label().unwrap() == "Ti.".to_string());
+/// ```
+///
+pub fn parse_label_regex(s_input: &str) -> Result<Label, Error> {
     lazy_static! {
-        static ref RE2 : Regex = Regex::new("\
+        static ref RE2 : Option<Regex> = Regex::new("\
         ^(?P<y>-?\\d{1,3}(\\.\\d+(e[+-]?\\d+)?)?) \
         (?P<x>-?\\d{1,3}(\\.\\d+(e[+-]?\\d+)?)?) \
         (?P<osmId>\\d+) \
-        (?P<prio>\\d+) \
-        (?P<elimT>\\d+(\\.\\d+(e[+-]?\\d+)?)?) \
+        (?P<prio>-?\\d+) \
+        (?P<elimT>inf|\\d+(\\.\\d+(e[+-]?\\d+)?)?) \
         (?P<rad>\\d+(\\.\\d+(e[+-]?\\d+)?)?) \
         (?P<lblFac>\\d+(\\.\\d+(e[+-]?\\d+)?)?) \
-        '(?P<lbl>.*)'\
-        ").unwrap();
+        '(?P<lbl>(?:[^\\\\]|\\\\.)*?)'\
+        ( '(?P<shortLbl>(?:[^\\\\]|\\\\.)*?)')?\
+        ( category:(?P<category>\\d+))?\
+        (?P<names>( name:[a-zA-Z_-]+='(?:[^'\\\\]|\\\\.|'')*')*)\
+        (?P<tags>( tag:[a-zA-Z0-9_:-]+='(?:[^'\\\\]|\\\\.|'')*')*)$\
+        ").ok();
+        static ref NAMES : Option<Regex> =
+            Regex::new(" (?P<key>name:[a-zA-Z_-]+)='(?P<name>(?:[^'\\\\]|\\\\.|'')*)'").ok();
+        static ref TAGS : Option<Regex> =
+            Regex::new(" tag:(?P<key>[a-zA-Z0-9_:-]+)='(?P<value>(?:[^'\\\\]|\\\\.|'')*)'")
+                .ok();
     }
 
-    let fields = match RE2.captures(s_input) {
+    let re = match *RE2 {
+        Some(ref re) => re,
+        None => {
+            return Err(Error::parse::<String>("Invalid label regular expression".to_string(), None))
+        }
+    };
+
+    // a carriage return is left over from a CRLF line ending
+    let s_input = s_input.trim_end_matches('\r');
+    let fields = match re.captures(s_input) {
         Some(capture) => capture,
-        None => return Err(From::from(format!("Could not evaulate poi: {}", s_input))),
+        None => {
+            return Err(Error::parse::<String>(format!("Could not evaulate poi: {}", s_input), None)
+                           .at_column(mismatch_column(s_input)))
+        }
     };
 
-    let x: f64 = fields["x"].parse()?;
-    let y: f64 = fields["y"].parse()?;
-    let elim_t: f64 = fields["elimT"].parse()?;
-    let osm_id: i64 = fields["osmId"].parse()?;
-    let prio: i32 = fields["prio"].parse()?;
-    let lbl_f: f64 = fields["lblFac"].parse()?;
-    let label: String = fields["lbl"].to_string();
+    let x: f64 = parse_field(&fields, "x")?;
+    let y: f64 = parse_field(&fields, "y")?;
+    let elim_t: f64 = normalize_t(parse_field(&fields, "elimT")?);
+    let osm_id: i64 = parse_field(&fields, "osmId")?;
+    let prio: i32 = parse_field(&fields, "prio")?;
+    let lbl_f: f64 = parse_field(&fields, "lblFac")?;
+    let label_length: f64 = parse_field(&fields, "rad")?;
+    let label: String = unescape(&fields["lbl"]);
+
+    let mut result = Label::new(x, y, elim_t, osm_id, prio, lbl_f, label);
+    result.set_label_length(label_length);
+    result.set_short_label(fields.name("shortLbl").map(|m| unescape(m.as_str())));
+    if fields.name("category").is_some() {
+        result.set_category(parse_field(&fields, "category")?);
+    }
+
+    if let (Some(names), &Some(ref re)) = (fields.name("names"), &*NAMES) {
+        for n in re.captures_iter(names.as_str()) {
+            result.set_name(n["key"].to_string(), unescape(&n["name"]));
+        }
+    }
+
+    if let (Some(tags), Some(re)) = (fields.name("tags"), TAGS.as_ref()) {
+        for t in re.captures_iter(tags.as_str()) {
+            result.set_tag(t["key"].to_string(), unescape(&t["value"]));
+        }
+    }
+
+    Ok(result)
+}
+
+///
+/// Escape a string for the use as quoted string of the label file format. Single quotes and
+/// backslashes are escaped by a backslash.
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::input::parse;
+///
+/// assert!(parse::escape("L'Aquila") == "L\\'Aquila");
+///
+/// let s = format!("42.35 13.40 123 1 1.5 3300.0 11.0 '{}' name:it='{}'",
+///                 parse::escape("L'Aquila"),
+///                 parse::escape("L'Aquila \\ Abruzzo"));
+/// let l = parse::parse_label(&s).unwrap();
+/// assert!(*l.get_label() == "L'Aquila".to_string());
+/// assert!(*l.get_name("name:it").unwrap() == "L'Aquila \\ Abruzzo".to_string());
+/// ```
+///
+pub fn escape(s: &str) -> String {
+    let mut r = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c == '\'' || c == '\\' {
+            r.push('\\');
+        }
+        r.push(c);
+    }
+
+    r
+}
+
+///
+/// Normalize the t value of a label read from an input file: a label that never disappears, i.e.
+/// with the t value infinity, is stored with the largest finite t.
+///
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::input::parse;
+/// use rt_datastructure::primitives::coord::Coord;
+///
+/// assert!(parse::normalize_t(f64::INFINITY) == Coord::MAX as f64);
+/// assert!(parse::normalize_t(3.5) == 3.5);
+/// ```
+///
+pub fn normalize_t(t: f64) -> f64 {
+    t.min(Coord::MAX as f64)
+}
+
+// Resolve the escapes of a quoted string: \' and '' are a single quote and \\ is a backslash.
+// Other backslashes are kept.
+fn unescape(s: &str) -> String {
+    let mut r = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some(&next)) if next == '\'' || next == '\\' => {
+                r.push(next);
+                chars.next();
+            }
+            ('\'', Some(&'\'')) => {
+                r.push('\'');
+                chars.next();
+            }
+            _ => r.push(c),
+        }
+    }
+
+    r
+}
+
+// Parse a line consisting of the numeric fields and a label string without quotes. None is
+// returned for all other lines, they are left to the regular expression.
+fn parse_plain(s: &str) -> Option<Label> {
+    let mut fields = s.splitn(FIELDS.len() + 1, ' ');
+    let mut values = [""; 7];
+    for (value, format) in values.iter_mut().zip(FIELDS.iter()) {
+        let field = fields.next()?;
+        if !is_field(field, format) {
+            return None;
+        }
+        *value = field;
+    }
+
+    let rest = fields.next()?;
+    if rest.len() < 2 || !rest.starts_with('\'') || !rest.ends_with('\'') {
+        return None;
+    }
+    let label = &rest[1..rest.len() - 1];
+    let mut escaped = false;
+    let mut chars = label.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => return None,
+            // a backslash escapes the next character except for a line break
+            '\\' => {
+                match chars.next() {
+                    Some(n) if n != '\n' => escaped = true,
+                    _ => return None,
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let elim_t = normalize_t(values[4].parse().ok()?);
+    let mut result = Label::new(values[1].parse().ok()?,
+                                values[0].parse().ok()?,
+                                elim_t,
+                                values[2].parse().ok()?,
+                                values[3].parse().ok()?,
+                                values[6].parse().ok()?,
+                                if escaped { unescape(label) } else { label.to_string() });
+    result.set_label_length(values[5].parse().ok()?);
+
+    Some(result)
+}
+
+// Parse the captured field with the given name.
+fn parse_field<F>(fields: &Captures, name: &str) -> Result<F, Error>
+    where F: FromStr,
+          F::Err: Into<Box<dyn error::Error + Send + Sync>>
+{
+    fields[name]
+        .parse()
+        .map_err(|e| {
+            let column = fields.name(name).map_or(0, |m| column_of(&fields[0], m.start()));
+            Error::parse(format!("Invalid {} value: {}", name, &fields[name]), Some(e))
+                .at_column(column)
+        })
+}
+
+// Find the column of the first of the leading space separated fields of the string that does not
+// have the required format. If all of them are valid, the column of the label string is returned.
+fn mismatch_column(s: &str) -> usize {
+    let mut start = 0;
+    for format in FIELDS.iter() {
+        let end = s[start..].find(' ').map_or(s.len(), |offset| start + offset);
+        if !is_field(&s[start..end], format) {
+            return column_of(s, start);
+        }
+        if end == s.len() {
+            return column_of(s, end);
+        }
+        start = end + 1;
+    }
+
+    column_of(s, start)
+}
+
+// (signed, maximum number of integer digits, fraction, infinity) of y, x, osm id, prio, t, label
+// length and label factor as in the regular expression of parse_label_regex
+const FIELDS: [(bool, usize, bool, bool); 7] = [(true, 3, true, false),
+                                                (true, 3, true, false),
+                                                (false, usize::MAX, false, false),
+                                                (true, usize::MAX, false, false),
+                                                (false, usize::MAX, true, true),
+                                                (false, usize::MAX, true, false),
+                                                (false, usize::MAX, true, false)];
+
+// Check if the field has the given format of FIELDS.
+fn is_field(field: &str, &(signed, max_digits, fraction, infinity): &(bool, usize, bool, bool))
+            -> bool {
+    is_number(field, signed, max_digits, fraction) || infinity && field == "inf"
+}
+
+// Check if the field is a number of the form -?\d{1,max_digits}(\.\d+(e[+-]?\d+)?)? where the sign
+// is only allowed if signed and the fractional part only if fraction is set.
+fn is_number(field: &str, signed: bool, max_digits: usize, fraction: bool) -> bool {
+    let digits = |s: &str| s.bytes().take_while(|b| b.is_ascii_digit()).count();
+
+    let field = if signed && field.starts_with('-') { &field[1..] } else { field };
+    let int = digits(field);
+    let rest = &field[int..];
+    if int == 0 || int > max_digits {
+        return false;
+    }
+    if rest.is_empty() {
+        return true;
+    }
+    if !fraction || !rest.starts_with('.') {
+        return false;
+    }
+
+    let frac = digits(&rest[1..]);
+    let rest = &rest[1 + frac..];
+    if frac == 0 {
+        return false;
+    }
+    if rest.is_empty() {
+        return true;
+    }
+    if !rest.starts_with('e') {
+        return false;
+    }
+
+    let rest = &rest[1..];
+    let rest = if rest.starts_with('+') || rest.starts_with('-') { &rest[1..] } else { rest };
+    let exp = digits(rest);
+
+    exp > 0 && exp == rest.len()
+}
 
-    Ok(Label::new(x, y, elim_t, osm_id, prio, lbl_f, label))
+// Get the column (starting at 1) of the character at the given byte offset of the string.
+fn column_of(s: &str, offset: usize) -> usize {
+    s[..offset].chars().count() + 1
 }
@@ -0,0 +1,517 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::io::{BufRead, Read};
+use std::str;
+
+use flate2::read::ZlibDecoder;
+
+use error::Error;
+use input::ImportOptions;
+use input::format::{ImportReport, InputFormat};
+use pack::codec;
+use primitives::label::Label;
+
+// The maximum sizes of blob headers and blobs given by the OSM PBF specification.
+const MAX_HEADER_SIZE: usize = 64 * 1024;
+const MAX_BLOB_SIZE: usize = 32 * 1024 * 1024;
+
+// The required features of an OSMHeader block the importer understands.
+const SUPPORTED_FEATURES: [&str; 2] = ["OsmSchema-V0.6", "DenseNodes"];
+
+///
+/// The settings to synthesize labels from the place nodes of an OSM PBF file.
+///
+/// Only nodes with a name and a place tag of one of the configured place values become labels.
+/// The priority of a label is the priority of its place value, the defaults are:
+///
+/// ```text
+/// city 5, town 4, village 3, suburb 2, hamlet 1
+/// ```
+///
+/// The elimination time t of a label is its priority plus a fraction in [0, 1) growing with the
+/// population, so larger places of the same kind are eliminated later. It is a synthesized order,
+/// not a computed collision time.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct OsmOptions {
+    m_places: Vec<(String, i32)>,
+    m_label_factor: f64,
+}
+
+impl OsmOptions {
+    ///
+    /// Initialize the default settings.
+    ///
+    pub fn new() -> OsmOptions {
+        OsmOptions {
+            m_places: vec![("city".to_string(), 5),
+                           ("town".to_string(), 4),
+                           ("village".to_string(), 3),
+                           ("suburb".to_string(), 2),
+                           ("hamlet".to_string(), 1)],
+            m_label_factor: 1.,
+        }
+    }
+
+    ///
+    /// Set the priority of the labels of the place value, the place value is imported from now
+    /// on. An existing priority is replaced.
+    ///
+    pub fn set_place_priority(&mut self, place: String, prio: i32) {
+        match self.m_places.iter_mut().find(|p| p.0 == place) {
+            Some(p) => p.1 = prio,
+            None => self.m_places.push((place, prio)),
+        }
+    }
+
+    ///
+    /// Get the priority of the labels of the place value or None if it is not imported.
+    ///
+    pub fn get_place_priority(&self, place: &str) -> Option<i32> {
+        self.m_places.iter().find(|p| p.0 == place).map(|p| p.1)
+    }
+
+    ///
+    /// Stop importing the place value.
+    ///
+    pub fn remove_place(&mut self, place: &str) {
+        self.m_places.retain(|p| p.0 != place);
+    }
+
+    ///
+    /// Set the label size factor of the imported labels.
+    ///
+    pub fn set_label_factor(&mut self, factor: f64) {
+        self.m_label_factor = factor;
+    }
+
+    ///
+    /// Get the label size factor of the imported labels.
+    ///
+    pub fn get_label_factor(&self) -> f64 {
+        self.m_label_factor
+    }
+
+    ///
+    /// Synthesize the label of a node from its tags, or None if the node is not an imported
+    /// place or has no name.
+    ///
+    /// The tags place and population are kept as label tags, name:* tags become names in further
+    /// languages and the short_name tag becomes the short label.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::input::pbf::OsmOptions;
+    ///
+    /// let o = OsmOptions::new();
+    /// let tags = [("name", "Bremen"), ("place", "city"), ("population", "569352")];
+    /// let l = o.place_label(20982927, 8.81, 53.08, &tags).unwrap();
+    ///
+    /// assert!(l.get_prio() == 5);
+    /// assert!(l.get_t() > 5. && l.get_t() < 6.);
+    /// assert!(l.get_tag("population").unwrap() == "569352");
+    /// assert!(o.place_label(1, 8.81, 53.08, &[("place", "city")]).is_none());
+    /// assert!(o.place_label(1, 8.81, 53.08, &[("name", "Weser"), ("place", "river")]).is_none());
+    /// ```
+    ///
+    pub fn place_label(&self,
+                       osm_id: i64,
+                       lon: f64,
+                       lat: f64,
+                       tags: &[(&str, &str)])
+                       -> Option<Label> {
+        let tag = |key: &str| tags.iter().find(|t| t.0 == key).map(|t| t.1);
+
+        let place = tag("place")?;
+        let prio = self.get_place_priority(place)?;
+        let name = tag("name")?;
+
+        let population = tag("population").and_then(|p| p.trim().parse::<u64>().ok()).unwrap_or(0);
+        let t = f64::from(prio) + 1. - 1. / (1. + (population as f64 + 1.).log10());
+
+        let mut l = Label::new(lon, lat, t, osm_id, prio, self.m_label_factor, name.to_string());
+        l.set_short_label(tag("short_name").map(|s| s.to_string()));
+        for &(key, value) in tags {
+            if key.starts_with("name:") {
+                l.set_name(key.to_string(), value.to_string());
+            } else if key == "place" || key == "population" {
+                l.set_tag(key.to_string(), value.to_string());
+            }
+        }
+
+        Some(l)
+    }
+}
+
+impl Default for OsmOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+///
+/// The OSM PBF file format, labels are synthesized from the place nodes of the file as defined
+/// by the `OsmOptions`.
+///
+/// Nodes and dense nodes of raw and zlib compressed blobs are read, ways and relations are
+/// ignored. Files requiring features other than `OsmSchema-V0.6` and `DenseNodes` are rejected.
+///
+pub struct OsmPbfFormat {
+    m_options: OsmOptions,
+}
+
+impl OsmPbfFormat {
+    ///
+    /// Initialize the format with the given settings.
+    ///
+    pub fn new(options: OsmOptions) -> OsmPbfFormat {
+        OsmPbfFormat { m_options: options }
+    }
+}
+
+impl InputFormat for OsmPbfFormat {
+    fn get_name(&self) -> &str {
+        "OSM PBF file"
+    }
+
+    fn detect(&self, head: &[u8]) -> bool {
+        // the first blob header starts with its type string "OSMHeader"
+        head.len() >= 15 && head[4] == 0x0a && head[5] == 9 && &head[6..15] == b"OSMHeader"
+    }
+
+    fn read(&self,
+            reader: &mut dyn BufRead,
+            _: &ImportOptions)
+            -> Result<ImportReport, Error> {
+        let mut labels: Vec<Label> = Vec::new();
+        while let Some((kind, data)) = read_blob(reader)? {
+            match kind.as_str() {
+                "OSMHeader" => check_header(&data)?,
+                "OSMData" => read_block(&data, &self.m_options, &mut labels)?,
+                // unknown blob types are skipped as required by the specification
+                _ => {}
+            }
+        }
+
+        Ok(ImportReport::new(labels, Vec::new()))
+    }
+}
+
+// The error of invalid or unsupported PBF data.
+fn invalid(message: &str) -> Error {
+    Error::Serialization(format!("Invalid OSM PBF data: {}", message))
+}
+
+// A value of a protobuf field. Fixed size values are skipped, the format does not use them.
+enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+// A protobuf message read field by field.
+struct Message<'a> {
+    m_data: &'a [u8],
+}
+
+impl<'a> Message<'a> {
+    fn new(data: &'a [u8]) -> Message<'a> {
+        Message { m_data: data }
+    }
+
+    // Read the next field number and value or None at the end of the message.
+    fn next_field(&mut self) -> Result<Option<(u64, Field<'a>)>, Error> {
+        if self.m_data.is_empty() {
+            return Ok(None);
+        }
+
+        let key = varint(&mut self.m_data)?;
+        let value = match key & 7 {
+            0 => Field::Varint(varint(&mut self.m_data)?),
+            1 => {
+                self.take(8)?;
+                Field::Fixed
+            }
+            2 => {
+                let len = varint(&mut self.m_data)?;
+                Field::Bytes(self.take(len)?)
+            }
+            5 => {
+                self.take(4)?;
+                Field::Fixed
+            }
+            _ => return Err(invalid("unsupported protobuf wire type")),
+        };
+
+        Ok(Some((key >> 3, value)))
+    }
+
+    fn take(&mut self, len: u64) -> Result<&'a [u8], Error> {
+        if len > self.m_data.len() as u64 {
+            return Err(invalid("truncated protobuf message"));
+        }
+
+        let (head, tail) = self.m_data.split_at(len as usize);
+        self.m_data = tail;
+        Ok(head)
+    }
+}
+
+fn varint(data: &mut &[u8]) -> Result<u64, Error> {
+    codec::read_varint(data).map_err(|_| invalid("truncated protobuf varint"))
+}
+
+fn zigzag(v: u64) -> i64 {
+    ((v >> 1) as i64) ^ -((v & 1) as i64)
+}
+
+// The values of a repeated integer field, either packed or a single value.
+fn values(field: Field) -> Result<Vec<u64>, Error> {
+    match field {
+        Field::Varint(v) => Ok(vec![v]),
+        Field::Bytes(mut data) => {
+            let mut r = Vec::new();
+            while !data.is_empty() {
+                r.push(varint(&mut data)?);
+            }
+            Ok(r)
+        }
+        Field::Fixed => Err(invalid("unexpected fixed size value")),
+    }
+}
+
+fn utf8(data: &[u8]) -> Result<&str, Error> {
+    str::from_utf8(data).map_err(|_| invalid("invalid utf-8 string"))
+}
+
+// Read the next blob of the file as its type and decompressed data, None at the end of the file.
+fn read_blob(r: &mut dyn BufRead) -> Result<Option<(String, Vec<u8>)>, Error> {
+    if r.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+
+    let mut size = [0u8; 4];
+    r.read_exact(&mut size)?;
+    let header_size = u32::from_be_bytes(size) as usize;
+    if header_size > MAX_HEADER_SIZE {
+        return Err(invalid("blob header exceeds 64 KiB"));
+    }
+    let mut header = vec![0u8; header_size];
+    r.read_exact(&mut header)?;
+
+    let mut kind = String::new();
+    let mut data_size = 0;
+    let mut m = Message::new(&header);
+    while let Some((nr, field)) = m.next_field()? {
+        match (nr, field) {
+            (1, Field::Bytes(b)) => kind = utf8(b)?.to_string(),
+            (3, Field::Varint(v)) => data_size = v as usize,
+            _ => {}
+        }
+    }
+    if data_size > MAX_BLOB_SIZE {
+        return Err(invalid("blob exceeds 32 MiB"));
+    }
+    let mut blob = vec![0u8; data_size];
+    r.read_exact(&mut blob)?;
+
+    let mut m = Message::new(&blob);
+    while let Some((nr, field)) = m.next_field()? {
+        match (nr, field) {
+            (1, Field::Bytes(raw)) => return Ok(Some((kind, raw.to_vec()))),
+            (3, Field::Bytes(zlib)) => {
+                let mut data = Vec::new();
+                ZlibDecoder::new(zlib).take(MAX_BLOB_SIZE as u64).read_to_end(&mut data)?;
+                return Ok(Some((kind, data)));
+            }
+            (4..=7, _) => return Err(invalid("unsupported blob compression")),
+            _ => {}
+        }
+    }
+
+    Err(invalid("blob without data"))
+}
+
+// Check that the importer supports the required features of the OSMHeader block.
+fn check_header(data: &[u8]) -> Result<(), Error> {
+    let mut m = Message::new(data);
+    while let Some((nr, field)) = m.next_field()? {
+        if let (4, Field::Bytes(b)) = (nr, field) {
+            let feature = utf8(b)?;
+            if !SUPPORTED_FEATURES.contains(&feature) {
+                return Err(invalid(&format!("unsupported required feature {}", feature)));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// The string table and coordinate encoding of a primitive block.
+struct Block<'a> {
+    m_strings: Vec<&'a str>,
+    m_granularity: i64,
+    m_lat_offset: i64,
+    m_lon_offset: i64,
+}
+
+impl<'a> Block<'a> {
+    fn string(&self, idx: u64) -> Result<&'a str, Error> {
+        self.m_strings
+            .get(idx as usize)
+            .cloned()
+            .ok_or_else(|| invalid("string index out of range"))
+    }
+
+    fn lat(&self, v: i64) -> f64 {
+        1e-9 * (self.m_lat_offset + self.m_granularity * v) as f64
+    }
+
+    fn lon(&self, v: i64) -> f64 {
+        1e-9 * (self.m_lon_offset + self.m_granularity * v) as f64
+    }
+}
+
+// Read the labels of the place nodes of a primitive block.
+fn read_block(data: &[u8], options: &OsmOptions, labels: &mut Vec<Label>) -> Result<(), Error> {
+    let mut block = Block {
+        m_strings: Vec::new(),
+        m_granularity: 100,
+        m_lat_offset: 0,
+        m_lon_offset: 0,
+    };
+    let mut groups: Vec<&[u8]> = Vec::new();
+
+    let mut m = Message::new(data);
+    while let Some((nr, field)) = m.next_field()? {
+        match (nr, field) {
+            (1, Field::Bytes(table)) => {
+                let mut t = Message::new(table);
+                while let Some((nr, field)) = t.next_field()? {
+                    if let (1, Field::Bytes(s)) = (nr, field) {
+                        block.m_strings.push(utf8(s)?);
+                    }
+                }
+            }
+            (2, Field::Bytes(group)) => groups.push(group),
+            (17, Field::Varint(v)) => block.m_granularity = v as i64,
+            (19, Field::Varint(v)) => block.m_lat_offset = v as i64,
+            (20, Field::Varint(v)) => block.m_lon_offset = v as i64,
+            _ => {}
+        }
+    }
+
+    for group in groups {
+        let mut g = Message::new(group);
+        while let Some((nr, field)) = g.next_field()? {
+            match (nr, field) {
+                (1, Field::Bytes(node)) => read_node(node, &block, options, labels)?,
+                (2, Field::Bytes(dense)) => read_dense_nodes(dense, &block, options, labels)?,
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn read_node(data: &[u8],
+             block: &Block,
+             options: &OsmOptions,
+             labels: &mut Vec<Label>)
+             -> Result<(), Error> {
+    let (mut id, mut lat, mut lon) = (0, 0, 0);
+    let (mut keys, mut vals) = (Vec::new(), Vec::new());
+
+    let mut m = Message::new(data);
+    while let Some((nr, field)) = m.next_field()? {
+        match (nr, field) {
+            (1, Field::Varint(v)) => id = zigzag(v),
+            (2, f) => keys.extend(values(f)?),
+            (3, f) => vals.extend(values(f)?),
+            (8, Field::Varint(v)) => lat = zigzag(v),
+            (9, Field::Varint(v)) => lon = zigzag(v),
+            _ => {}
+        }
+    }
+    if keys.len() != vals.len() {
+        return Err(invalid("node with unequal number of keys and values"));
+    }
+
+    let mut tags: Vec<(&str, &str)> = Vec::with_capacity(keys.len());
+    for (k, v) in keys.into_iter().zip(vals) {
+        tags.push((block.string(k)?, block.string(v)?));
+    }
+    labels.extend(options.place_label(id, block.lon(lon), block.lat(lat), &tags));
+
+    Ok(())
+}
+
+fn read_dense_nodes(data: &[u8],
+                    block: &Block,
+                    options: &OsmOptions,
+                    labels: &mut Vec<Label>)
+                    -> Result<(), Error> {
+    let (mut ids, mut lats, mut lons, mut keys_vals) =
+        (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+
+    let mut m = Message::new(data);
+    while let Some((nr, field)) = m.next_field()? {
+        match nr {
+            1 => ids = values(field)?,
+            8 => lats = values(field)?,
+            9 => lons = values(field)?,
+            10 => keys_vals = values(field)?,
+            _ => {}
+        }
+    }
+    if ids.len() != lats.len() || ids.len() != lons.len() {
+        return Err(invalid("dense nodes with unequal number of ids and coordinates"));
+    }
+    // without any tags in the block no node is a place
+    if keys_vals.is_empty() {
+        return Ok(());
+    }
+
+    // ids and coordinates are delta coded, the tags of the nodes are separated by a 0
+    let (mut id, mut lat, mut lon) = (0i64, 0i64, 0i64);
+    let mut kv = keys_vals.into_iter();
+    let mut tags: Vec<(&str, &str)> = Vec::new();
+    for ((&d_id, &d_lat), &d_lon) in ids.iter().zip(&lats).zip(&lons) {
+        id = id.wrapping_add(zigzag(d_id));
+        lat = lat.wrapping_add(zigzag(d_lat));
+        lon = lon.wrapping_add(zigzag(d_lon));
+
+        tags.clear();
+        while let Some(k) = kv.next() {
+            if k == 0 {
+                break;
+            }
+            let v = kv.next().ok_or_else(|| invalid("dense node key without value"))?;
+            tags.push((block.string(k)?, block.string(v)?));
+        }
+        labels.extend(options.place_label(id, block.lon(lon), block.lat(lat), &tags));
+    }
+
+    Ok(())
+}
@@ -22,10 +22,32 @@
 #[macro_use]
 extern crate lazy_static;
 
+#[cfg(any(feature = "pack", feature = "gzip", feature = "osm-pbf"))]
+extern crate flate2;
+
 extern crate rand;
 
 extern crate regex;
 
+#[cfg(feature = "zstd")]
+extern crate zstd;
+
+// Validate a condition in the library code.
+//
+// By default a violated condition panics like assert!. With the `panic-free` feature the check
+// is compiled out and the caller has to recover from the violated condition itself.
+#[cfg(not(feature = "panic-free"))]
+macro_rules! validate {
+    ($cond:expr) => { assert!($cond) };
+    ($cond:expr, $($arg:tt)+) => { assert!($cond, $($arg)+) };
+}
+
+#[cfg(feature = "panic-free")]
+macro_rules! validate {
+    ($cond:expr) => { let _ = $cond; };
+    ($cond:expr, $($arg:tt)+) => { let _ = $cond; };
+}
+
 ///
 /// A module providing some primitive geo types.
 ///
@@ -75,9 +97,71 @@ pub mod pst_3d;
 ///  * its length<br>
 ///  * its size factor<br>
 ///  * the label string<br>
+///  * optionally a short label string, e.g. `'Sankt Petersburg' 'St. Pete'`<br>
+///  * optionally names in further languages, e.g. `'München' name:en='Munich'`<br>
 ///
 pub mod input;
 
+///
+/// A module to sanitize label data before it is shared.
+///
+/// Datasets derived from licensed data must not leak the original osm ids. The module provides a
+/// pass to replace the osm ids by running numbers or by salted hashes and to remove configured
+/// tag and name fields.
+///
+pub mod sanitize;
+
+///
+/// A module providing a small in-memory cache for query results.
+///
+/// User interfaces often repeat the same query without changing the viewport. The cache answers
+/// such identical queries without traversing the tree.
+///
+pub mod cache;
+
+///
+/// A module to compare the 3D PST with other data structures for priority range reporting.
+///
+/// The RangeReporting trait captures the "3-sided" query of the 3D PST. Third-party data
+/// structures implementing the trait can be benchmarked on identical data and queries.
+///
+pub mod benchmark;
+
+///
+/// A module to fit label sets into a memory budget.
+///
+/// Devices with hard memory ceilings cannot hold arbitrary large label sets. The module drops the
+/// labels with the lowest t values until the estimated size of the remaining labels fits.
+///
+pub mod budget;
+
+///
+/// A module providing the error type of the crate.
+///
+/// All fallible functions of the library report an Error. At the C interface the errors are
+/// mapped to stable numeric codes.
+///
+pub mod error;
+
+///
+/// A module providing encoders for query results and writers of label files.
+///
+/// The labels of a query result are encoded as GeoJSON. Repeated label strings can optionally be
+/// written once into a shared string table to keep large payloads small. Label sets, e.g. the
+/// contents of a tree, are written back to the text or the binary label file format.
+///
+pub mod output;
+
+///
+/// A module implementing the label pack format.
+///
+/// A label pack is a single file containing the metadata, the labels, the serialized 3D PST and
+/// optionally a name index of a dataset. It can be loaded without constructing the tree again.
+/// Reading and writing packs requires the `pack` feature, which is enabled by default.
+///
+pub mod pack;
+
+use std::collections::HashMap;
 use std::ffi::CStr;
 use std::ffi::CString;
 use std::os::raw::c_char;
@@ -85,6 +169,16 @@ use std::os::raw::c_char;
 use std::error::Error;
 use std::io::prelude::*;
 use std::fs::File;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+// The memory budget in bytes applied by `init`. A budget of 0 disables the budget.
+static MEMORY_BUDGET: AtomicUsize = AtomicUsize::new(0);
+
+// The import mode and the number of parsing threads applied by `init` and `append_labels`.
+static IMPORT_STRICT: AtomicBool = AtomicBool::new(false);
+static IMPORT_THREADS: AtomicUsize = AtomicUsize::new(1);
 
 ///
 /// C representation of a pst instance.
@@ -97,6 +191,74 @@ use std::fs::File;
 #[repr(C)]
 pub struct DataStructure {
     pst: Option<pst_3d::GeoPst3d>,
+    precision: pst_3d::query::CoordinatePrecision,
+    limit: Option<usize>,
+    cache: Mutex<Option<cache::ResultCache>>,
+    error_code: i32,
+}
+
+impl DataStructure {
+    fn new(pst: Option<pst_3d::GeoPst3d>) -> DataStructure {
+        DataStructure {
+            pst,
+            precision: pst_3d::query::CoordinatePrecision::Full,
+            limit: None,
+            cache: Mutex::new(None),
+            error_code: 0,
+        }
+    }
+
+    fn failed(e: &error::Error) -> DataStructure {
+        let mut ds = DataStructure::new(None);
+        ds.error_code = e.get_code();
+        ds
+    }
+
+    ///
+    /// Load the labels of an additional file in any registered input format into the data
+    /// structure, e.g. a regional update of a base dataset.
+    ///
+    /// A label of the file replaces the label with the same osm id, all other labels of the file
+    /// are added. The tree is rebuilt from the merged labels within the memory budget, the name
+    /// fallback chain, the hysteresis and the other settings of the data structure are kept and
+    /// cached query results are discarded. On failure the data structure is left unchanged.
+    ///
+    /// Return the import report of the file, e.g. to present the number of imported labels and
+    /// the skipped records.
+    ///
+    /// # Errors
+    ///   * Io: if the file path does not match any file in the file system
+    ///   * Parse: if no input format matches the file, the file could not be read in its format
+    ///     or, in strict mode, a record is malformed
+    ///   * Validation: if the data structure is not valid, compare `is_good`, or the merged labels
+    ///     do not form a valid tree
+    ///
+    pub fn append(&mut self,
+                  path: &str,
+                  options: &input::ImportOptions)
+                  -> Result<input::format::ImportReport, error::Error> {
+        let (merged, report) = match self.pst {
+            Some(ref pst) => {
+                let report = input::import_auto(&path.to_string(), options)?;
+
+                let mut labels: Vec<primitives::label::Label> =
+                    pst.get_pst().iter().cloned().collect();
+                merge_labels(&mut labels, report.get_labels());
+
+                let mut merged = build_tree(labels, pst.get_crs().clone())?;
+                merged.set_name_fallback(pst.get_name_fallback().clone());
+                merged.set_hysteresis(pst.get_pst().get_hysteresis());
+                (merged, report)
+            }
+            None => {
+                return Err(error::Error::Validation("The data structure is not valid".to_string()))
+            }
+        };
+
+        self.pst = Some(merged);
+        invalidate_cache(self);
+        Ok(report)
+    }
 }
 
 ///
@@ -114,6 +276,7 @@ pub struct C_Label {
     prio: i32,
 
     lbl_fac: f64,
+    label_length: f64,
     label: *mut c_char,
 }
 
@@ -121,11 +284,16 @@ pub struct C_Label {
 /// A struct represents a basic C_Label vector, i.e. its size and the data (the contained C_Label
 /// objects).
 ///
+/// The generation id identifies the queried dataset. Results of the same dataset have the same
+/// generation id. For an invalid data structure the generation id is 0.
+///
 #[repr(C)]
 pub struct C_Result {
     size: u64,
 
     data: *mut C_Label,
+
+    generation: u64,
 }
 
 
@@ -136,13 +304,19 @@ pub struct C_Result {
 ///
 /// The given file must match the format specified in the [Input Module](input/index.html).
 ///
+/// # Safety
+///   input_path must point to a NUL terminated string.
+///
 #[no_mangle]
-pub extern "C" fn init(input_path: *const c_char) -> Box<DataStructure> {
-    let c_string = unsafe { CStr::from_ptr(input_path) };
+pub unsafe extern "C" fn init(input_path: *const c_char) -> Box<DataStructure> {
+    let c_string = CStr::from_ptr(input_path);
 
     let input_path = match c_string.to_str() {
         Ok(path) => path.to_string(),
-        Err(_) => return Box::new(DataStructure { pst: None }),
+        Err(_) => {
+            let e = error::Error::Ffi("The input path is not valid UTF-8".to_string());
+            return Box::new(DataStructure::failed(&e));
+        }
     };
 
     // debug
@@ -151,26 +325,96 @@ pub extern "C" fn init(input_path: *const c_char) -> Box<DataStructure> {
         Err(why) => println!("couldn't create {}: {}", log_path, why.description()),
         Ok(mut file) => {
             match file.write_all(format!("Reading ds from {}", input_path).as_bytes()) {
-                Err(why) => panic!("couldn't write to {}: {}", log_path, why.description()),
+                Err(why) => println!("couldn't write to {}: {}", log_path, why.description()),
                 Ok(_) => println!("successfully wrote to {}", log_path),
             };
         }
     }
 
-    let tree: Option<pst_3d::GeoPst3d> = match input::import_labels(&input_path) {
-        Ok(res) => {
-            println!("Successfully imported {} labels", res.len());
-            Some(pst_3d::GeoPst3d::new(res))
-        }
+    match load_tree(&input_path) {
+        Ok(tree) => Box::new(DataStructure::new(Some(tree))),
         Err(e) => {
-            println!("Could not read the given input file:{}\n\t{:?}\n",
+            println!("Could not read the given input file:{}\n\t{}\n",
                      input_path,
                      e);
-            None
+            Box::new(DataStructure::failed(&e))
         }
-    };
+    }
+}
+
+// Import the labels from the given file and build the tree.
+fn load_tree(input_path: &String) -> Result<pst_3d::GeoPst3d, error::Error> {
+    let labels = input::import_auto(input_path, &import_options())?.into_labels();
+    println!("Successfully imported {} labels", labels.len());
+
+    build_tree(labels, primitives::crs::Crs::Wgs84)
+}
+
+// Build the tree of the labels within the memory budget.
+fn build_tree(mut labels: Vec<primitives::label::Label>,
+              crs: primitives::crs::Crs)
+              -> Result<pst_3d::GeoPst3d, error::Error> {
+    let memory_budget = MEMORY_BUDGET.load(Ordering::SeqCst);
+    if memory_budget > 0 {
+        let report = budget::fit_to_budget(&mut labels, memory_budget);
+        println!("Memory budget of {} bytes: {}", memory_budget, report);
+    }
+
+    pst_3d::GeoPst3d::try_new_with_crs(labels, crs)
+}
+
+// Merge the labels of an update into the labels. An update label replaces the last label with the
+// same osm id, the other update labels are appended.
+fn merge_labels(labels: &mut Vec<primitives::label::Label>,
+                update: &[primitives::label::Label]) {
+    let mut positions: HashMap<i64, usize> =
+        labels.iter().enumerate().map(|(idx, l)| (l.get_osm_id(), idx)).collect();
+
+    for l in update {
+        match positions.get(&l.get_osm_id()) {
+            Some(&idx) => labels[idx] = l.clone(),
+            None => {
+                positions.insert(l.get_osm_id(), labels.len());
+                labels.push(l.clone());
+            }
+        }
+    }
+}
 
-    Box::new(DataStructure { pst: tree })
+///
+/// Set the memory budget in bytes for all following calls of `init`.
+///
+/// If the imported labels exceed the budget, the labels with the lowest t values are dropped
+/// until the remaining labels fit. The dropped labels are reported on stdout. A budget of 0
+/// disables the budget.
+///
+#[no_mangle]
+pub extern "C" fn set_memory_budget(bytes: u64) {
+    MEMORY_BUDGET.store(bytes as usize, Ordering::SeqCst);
+}
+
+///
+/// Set the import options for all following calls of `init` and `append_labels`.
+///
+/// In strict mode the first malformed record fails the import, otherwise malformed records are
+/// skipped. The lines of label files are parsed on the given number of threads, 0 is treated as
+/// 1. By default the import is lenient and single threaded.
+///
+#[no_mangle]
+pub extern "C" fn set_import_options(strict: bool, threads: u32) {
+    IMPORT_STRICT.store(strict, Ordering::SeqCst);
+    IMPORT_THREADS.store(threads as usize, Ordering::SeqCst);
+}
+
+// The import options set by `set_import_options`.
+fn import_options() -> input::ImportOptions {
+    let mut options = input::ImportOptions::new();
+    if IMPORT_STRICT.load(Ordering::SeqCst) {
+        options.set_mode(input::ImportMode::Strict);
+    }
+    options.set_threads(IMPORT_THREADS.load(Ordering::SeqCst));
+
+    options
 }
 
 ///
@@ -181,9 +425,154 @@ pub extern "C" fn is_good(ds: &mut DataStructure) -> bool {
     return ds.pst.is_some();
 }
 
+///
+/// Get the numeric code of the error that occured during the initialization or 0 if the
+/// initialization was successfull.
+///
+/// The codes are 1: Io, 2: Parse, 3: Validation, 4: Query, 5: Serialization, 6: Ffi,
+/// 7: Shared, 8: Invariant.
+///
+#[no_mangle]
+pub extern "C" fn get_error_code(ds: &DataStructure) -> i32 {
+    ds.error_code
+}
+
+///
+/// Load the labels of the file defined by input_path into a valid data structure, compare
+/// `DataStructure::append`. Labels of the file replace the labels with the same osm id.
+///
+/// Returns 0 on success or the numeric code of the error, compare `get_error_code`. On failure
+/// the data structure is left unchanged.
+///
+/// # Safety
+///   input_path must point to a NUL terminated string.
+///
+#[no_mangle]
+pub unsafe extern "C" fn append_labels(ds: &mut DataStructure, input_path: *const c_char) -> i32 {
+    let c_string = CStr::from_ptr(input_path);
+
+    let result = match c_string.to_str() {
+        Ok(path) => ds.append(path, &import_options()),
+        Err(_) => Err(error::Error::Ffi("The input path is not valid UTF-8".to_string())),
+    };
+    match result {
+        Ok(report) => {
+            println!("Successfully imported {} labels", report.get_labels().len());
+            0
+        }
+        Err(e) => {
+            println!("Could not append the given input file:{}\n\t{}\n",
+                     c_string.to_string_lossy(),
+                     e);
+            e.get_code()
+        }
+    }
+}
+
+///
+/// Enable caching of query results.
+///
+/// Up to capacity results of identical queries are answered from the cache for ttl_ms
+/// milliseconds. Enabling the cache again discards all cached results.
+///
+#[no_mangle]
+pub extern "C" fn enable_cache(ds: &mut DataStructure, capacity: u64, ttl_ms: u64) {
+    let c = cache::ResultCache::new(capacity as usize, Duration::from_millis(ttl_ms));
+    if let Ok(mut guard) = ds.cache.lock() {
+        *guard = Some(c);
+    }
+}
+
+///
+/// Disable caching of query results and discard all cached results.
+///
+#[no_mangle]
+pub extern "C" fn disable_cache(ds: &mut DataStructure) {
+    if let Ok(mut guard) = ds.cache.lock() {
+        *guard = None;
+    }
+}
+
+///
+/// Invalidate all cached query results, e.g. after the underlying data has changed.
+///
+#[no_mangle]
+pub extern "C" fn invalidate_cache(ds: &mut DataStructure) {
+    if let Ok(mut guard) = ds.cache.lock() {
+        if let Some(ref mut c) = *guard {
+            c.invalidate();
+        }
+    }
+}
+
+///
+/// Limit the number of labels in all following results of `get_data`. Only the labels with the
+/// largest t values are returned. A limit of 0 disables the limit.
+///
+#[no_mangle]
+pub extern "C" fn set_result_limit(ds: &mut DataStructure, limit: u64) {
+    ds.limit = if limit == 0 {
+        None
+    } else {
+        Some(limit as usize)
+    };
+
+    // cached results might have been computed with another limit
+    invalidate_cache(ds);
+}
+
+///
+/// Round the coordinates of all following results of `get_data` to the given number of decimal
+/// places. For a negative number of decimals the coordinates are returned unchanged.
+///
+#[no_mangle]
+pub extern "C" fn set_coordinate_precision(ds: &mut DataStructure, decimals: i32) {
+    ds.precision = if decimals < 0 {
+        pst_3d::query::CoordinatePrecision::Full
+    } else {
+        pst_3d::query::CoordinatePrecision::Decimals(decimals as u32)
+    };
+}
+
+///
+/// Snap the coordinates of all following results of `get_data` to the nearest multiple of
+/// grid_size. For a grid_size <= 0 the coordinates are returned unchanged.
+///
+#[no_mangle]
+pub extern "C" fn set_coordinate_grid(ds: &mut DataStructure, grid_size: f64) {
+    ds.precision = if grid_size > 0. {
+        pst_3d::query::CoordinatePrecision::Grid(grid_size)
+    } else {
+        pst_3d::query::CoordinatePrecision::Full
+    };
+}
+
+///
+/// Set the name fallback chain used to resolve the label strings of all following results of
+/// `get_data` as a comma separated list of name keys, e.g. "name:de,name:en,name".
+///
+/// The first key a label has a name for is used. Labels without a name for any of the keys are
+/// returned with their label string.
+///
+/// # Safety
+///   fallback must point to a NUL terminated string.
+///
+#[no_mangle]
+pub unsafe extern "C" fn set_name_fallback(ds: &mut DataStructure, fallback: *const c_char) {
+    let c_string = CStr::from_ptr(fallback);
+    let fallback = pst_3d::query::NameFallback::from_list(&c_string.to_string_lossy());
+
+    if let Some(ref mut pst) = ds.pst {
+        pst.set_name_fallback(fallback);
+    }
+}
+
 ///
 /// Get the labels contained in the specified bounding box with a t value >= min_t.
 ///
+/// The label strings are resolved with the name fallback chain set by `set_name_fallback`.
+/// Interior NUL bytes of the label strings are removed.
+///
 /// The ownership of the result returned by this function is passed to the caller.
 /// To safely deallocate the result pass it to the function `free_result`.
 #[no_mangle]
@@ -194,6 +583,87 @@ pub extern "C" fn get_data(ds: &DataStructure,
                            min_y: f64,
                            max_y: f64)
                            -> C_Result {
+    get_data_with(ds,
+                  min_t,
+                  min_x,
+                  max_x,
+                  min_y,
+                  max_y,
+                  |pst, l| pst.get_name(l, None).clone())
+}
+
+///
+/// Get the labels contained in the specified bounding box with a t value >= min_t.
+///
+/// The names of the labels are resolved with the given comma separated name fallback chain,
+/// e.g. "name:de,name:en,name", instead of the one configured by `set_name_fallback`.
+///
+/// The ownership of the result returned by this function is passed to the caller.
+/// To safely deallocate the result pass it to the function `free_result`.
+///
+/// # Safety
+///   fallback must point to a NUL terminated string.
+///
+#[no_mangle]
+pub unsafe extern "C" fn get_data_localized(ds: &DataStructure,
+                                            min_t: f64,
+                                            min_x: f64,
+                                            max_x: f64,
+                                            min_y: f64,
+                                            max_y: f64,
+                                            fallback: *const c_char)
+                                            -> C_Result {
+    let c_string = CStr::from_ptr(fallback);
+    let fallback = pst_3d::query::NameFallback::from_list(&c_string.to_string_lossy());
+
+    get_data_with(ds,
+                  min_t,
+                  min_x,
+                  max_x,
+                  min_y,
+                  max_y,
+                  |pst, l| pst.get_name(l, Some(&fallback)).clone())
+}
+
+///
+/// Get the labels contained in the specified bounding box with a t value >= min_t.
+///
+/// For requests with min_t >= short_min_t the label string of labels having a short label is
+/// replaced by their short label.
+///
+/// The ownership of the result returned by this function is passed to the caller.
+/// To safely deallocate the result pass it to the function `free_result`.
+#[no_mangle]
+pub extern "C" fn get_data_abbreviated(ds: &DataStructure,
+                                       min_t: f64,
+                                       min_x: f64,
+                                       max_x: f64,
+                                       min_y: f64,
+                                       max_y: f64,
+                                       short_min_t: f64)
+                                       -> C_Result {
+    let abbrev = primitives::abbreviation::ShortLabelThreshold::new(short_min_t);
+    get_data_with(ds,
+                  min_t,
+                  min_x,
+                  max_x,
+                  min_y,
+                  max_y,
+                  |_, l| primitives::abbreviation::display_label(l, min_t, &abbrev))
+}
+
+// Query the data structure and convert the result into its C representation. The label string
+// of each result is created by the given function.
+fn get_data_with<F>(ds: &DataStructure,
+                    min_t: f64,
+                    min_x: f64,
+                    max_x: f64,
+                    min_y: f64,
+                    max_y: f64,
+                    label_text: F)
+                    -> C_Result
+    where F: Fn(&pst_3d::GeoPst3d, &primitives::label::Label) -> String
+{
     use std::mem::forget;
     let mut result;
     let pointer;
@@ -209,40 +679,90 @@ pub extern "C" fn get_data(ds: &DataStructure,
             return C_Result {
                        size: len,
                        data: pointer,
+                       generation: 0,
                    };
         }
     };
 
     let bb = primitives::bbox::BBox::new(min_x, min_y, max_x, max_y);
-    let r = pst.get(&bb, min_t);
+    let indices = cached_indices(ds, pst, &bb, min_t);
+    let r: Vec<&primitives::label::Label> =
+        indices.into_iter().filter_map(|idx| pst.get_label(idx)).collect();
 
     result = Vec::with_capacity(r.len());
     for e in &r {
-        let c_label = CString::new(e.get_label().as_str()).unwrap();
+        let c_label = to_c_string(label_text(pst, e));
         result.push(C_Label {
-                        x: e.get_x(),
-                        y: e.get_y(),
+                        x: ds.precision.apply(e.get_x()),
+                        y: ds.precision.apply(e.get_y()),
                         t: e.get_t(),
                         osm_id: e.get_osm_id(),
                         prio: e.get_prio(),
                         lbl_fac: e.get_label_factor(),
+                        label_length: e.get_label_length(),
                         label: c_label.into_raw(),
                     });
     }
-    result.shrink_to_fit();
-    let pointer = result.as_mut_ptr();
-    forget(result);
+    // the capacity must match the size for free_result to reclaim the array
+    let pointer = Box::into_raw(result.into_boxed_slice()) as *mut C_Label;
     C_Result {
         size: r.len() as u64,
         data: pointer,
+        generation: pst.get_generation(),
     }
 }
 
+// Get the label indices of a query from the cache of the data structure if possible. Otherwise
+// the tree is queried and the result is added to the cache.
+fn cached_indices(ds: &DataStructure,
+                  pst: &pst_3d::GeoPst3d,
+                  bb: &primitives::bbox::BBox,
+                  min_t: f64)
+                  -> Vec<u32> {
+    let mut guard = match ds.cache.lock() {
+        Ok(guard) => guard,
+        Err(_) => return pst.get_limited_indices(bb, min_t, ds.limit),
+    };
+
+    match *guard {
+        Some(ref mut c) => {
+            if let Some(indices) = c.get(bb, min_t) {
+                return indices.clone();
+            }
+
+            let indices = pst.get_limited_indices(bb, min_t, ds.limit);
+            c.insert(bb, min_t, indices.clone());
+            indices
+        }
+        None => pst.get_limited_indices(bb, min_t, ds.limit),
+    }
+}
+
+// Convert the label string into a C string. C strings end at the first NUL byte, so interior NUL
+// bytes of the label string are removed instead of truncating the string or failing.
+fn to_c_string(s: String) -> CString {
+    match CString::new(s) {
+        Ok(c) => c,
+        Err(e) => {
+            let mut bytes = e.into_vec();
+            bytes.retain(|&b| b != 0);
+            CString::new(bytes).unwrap_or_default()
+        }
+    }
+}
+
+///
+/// Deallocate a result returned by `get_data` or one of its variants, i.e. the label array and
+/// the label strings of the result.
 ///
-/// Deallocate a result returned by `get_data`.
+/// Each result must be passed exactly once, the labels must not be accessed afterwards.
 ///
 #[no_mangle]
 pub extern "C" fn free_result(res: C_Result) {
+    if res.data.is_null() {
+        return;
+    }
+
     unsafe {
         let vec = Vec::from_raw_parts(res.data, res.size as usize, res.size as usize);
         for label in vec {
@@ -264,9 +784,23 @@ mod tests {
     use rand::{thread_rng, Rng};
 
     use std::collections::HashSet;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::ffi::{CStr, CString};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
 
-    use super::primitives::{bbox, label};
+    use super::cache;
+    use super::input;
+    use super::input::format::InputFormat;
+    use super::input::geojson;
+    use super::output;
+    #[cfg(feature = "pack")]
+    use super::pack;
+    use super::primitives::{bbox, crs, label, point};
     use super::pst_3d;
+    use super::pst_3d::query::{CategorySet, QueryOptions};
 
     // create a random floating point number in the range -180 to 180
     fn rand_lat() -> f64 {
@@ -324,6 +858,766 @@ mod tests {
         res
     }
 
+    // strip doc comments, comments and unit tests from the given source file
+    fn library_code(source: &str) -> String {
+        let code = match source.find("#[test]") {
+            Some(idx) => &source[..idx],
+            None => source,
+        };
+        let code = match code.find("#[cfg(test)]") {
+            Some(idx) => &code[..idx],
+            None => code,
+        };
+
+        code.lines()
+            .map(|l| match l.find("//") {
+                     Some(idx) => &l[..idx],
+                     None => l,
+                 })
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+
+    // collect the rust source files below the given directory
+    fn source_files(dir: &Path, files: &mut Vec<PathBuf>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                source_files(&path, files);
+            } else if path.extension().is_some_and(|e| e == "rs") {
+                files.push(path);
+            }
+        }
+    }
+
+    #[test]
+    fn core_modules_are_panic_free() {
+        // every module of the library except the crate roots, lib.rs holds the validate macro
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let mut modules = Vec::new();
+        source_files(&root, &mut modules);
+        modules.retain(|p| p != &root.join("lib.rs") && p != &root.join("main.rs"));
+        assert!(modules.contains(&root.join("pst_3d").join("root.rs")));
+        let denied = ["panic!", "assert!", "assert_eq!", "assert_ne!", "unreachable!",
+                      "unimplemented!", "todo!", ".unwrap()", ".expect("];
+
+        for path in modules.iter() {
+            let source = fs::read_to_string(path).unwrap();
+            let code = library_code(&source);
+            for call in denied.iter() {
+                assert!(!code.contains(call), "{} contains {}", path.display(), call);
+            }
+
+            // a bounds check is compiled out without panics, so it must not guard an index
+            let lines: Vec<&str> = code.lines().collect();
+            for (idx, pair) in lines.windows(2).enumerate() {
+                let bounds_check = pair[0].contains("validate!(") && pair[0].contains(".len()");
+                assert!(!bounds_check || !pair[1].contains('['),
+                        "{} indexes after the bounds check in line {} of the library code",
+                        path.display(),
+                        idx + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn construction_is_deterministic() {
+        let instance = random_label_instance(TEST_SIZE);
+        let build = |labels: Vec<label::Label>, parallel: bool| {
+            let mut builder = pst_3d::builder::Pst3dBuilder::new();
+            builder.set_parallel(parallel);
+            let t = builder.build(labels);
+            let bbox = bbox::BBox::new(-90., -45., 90., 45.);
+            let ids: Vec<i64> = t.get(&bbox, 0.5).iter().map(|l| l.get_osm_id()).collect();
+            (t.to_string(), ids)
+        };
+        let expected = build(instance.clone(), false);
+        assert!(!expected.1.is_empty());
+
+        // sequential and parallel constructions, run by different numbers of threads at once
+        for threads in [1, 2, 4].iter() {
+            for parallel in [false, true].iter() {
+                let builds: Vec<(String, Vec<i64>)> = (0..*threads)
+                    .map(|_| {
+                             let labels = instance.clone();
+                             let parallel = *parallel;
+                             thread::spawn(move || build(labels, parallel))
+                         })
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .map(|h| h.join().unwrap())
+                    .collect();
+
+                assert!(builds.iter().all(|b| *b == expected));
+            }
+        }
+    }
+
+    #[cfg(feature = "pack")]
+    #[test]
+    fn label_pack_round_trip() {
+        let instance = random_label_instance(TEST_SIZE);
+
+        let mut buf: Vec<u8> = Vec::new();
+        let p = pack::LabelPack::new(pst_3d::GeoPst3d::new(instance.clone()));
+        p.write(&mut buf).unwrap();
+
+        let t = pst_3d::GeoPst3d::new(instance);
+        let loaded = pack::LabelPack::read(&mut buf.as_slice()).unwrap().into_tree();
+
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let x = rng.gen_range(-180., 170.);
+            let y = rng.gen_range(-90., 80.);
+            let bbox = bbox::BBox::new(x, y, x + 10., y + 10.);
+            let min_t = rand::random::<f64>();
+
+            let ids = |r: Vec<&label::Label>| {
+                let mut ids: Vec<i64> = r.iter().map(|l| l.get_osm_id()).collect();
+                ids.sort();
+                ids
+            };
+            assert!(ids(loaded.get(&bbox, min_t)) == ids(t.get(&bbox, min_t)));
+        }
+    }
+
+    #[cfg(feature = "pack")]
+    #[test]
+    fn label_pack_rejects_custom_crs() {
+        let crs = crs::Crs::Custom {
+            extent: bbox::BBox::new(-180., -90., 180., 90.),
+            from_wgs84: |lon, lat| (lon, lat),
+        };
+        let t = pst_3d::GeoPst3d::new_with_crs(random_label_instance(10), crs);
+
+        let mut buf: Vec<u8> = Vec::new();
+        assert!(pack::LabelPack::new(t).write(&mut buf).is_err());
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn leaf_buckets_match_queries() {
+        let instance = random_label_instance(TEST_SIZE);
+
+        let plain = pst_3d::Pst3d::new(instance.clone());
+        let mut builder = pst_3d::builder::Pst3dBuilder::new();
+        builder.set_leaf_bucket_size(16);
+        let mut t = builder.build(instance.clone());
+        assert!(t.validate().is_ok());
+        assert!(t.get_size() == plain.get_size());
+        assert!(t.get_node_count() < plain.get_node_count() / 4);
+        assert!(t.iter().count() == instance.len());
+
+        // the tree structure and the bucket elements are restored from their serialization
+        let mut buf: Vec<u8> = Vec::new();
+        t.write_tree(&mut buf).unwrap();
+        let labels = t.iter().cloned().collect();
+        let loaded: pst_3d::Pst3d = pst_3d::Pst3d::read_tree(&mut buf.as_slice(), labels).unwrap();
+        assert!(loaded.get_node_count() == t.get_node_count());
+
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let x = rng.gen_range(-180., 170.);
+            let y = rng.gen_range(-90., 80.);
+            let bbox = bbox::BBox::new(x, y, x + 10., y + 10.);
+            let min_t = rand::random::<f64>();
+
+            let expected = get_id_set_filtered(&instance, &bbox, min_t);
+            assert!(get_id_set(&t.get(&bbox, min_t)) == expected);
+            assert!(get_id_set(&loaded.get(&bbox, min_t)) == expected);
+            assert!(get_id_set(&t.get_many(&[(bbox.clone(), min_t)])[0]) == expected);
+            assert!(get_id_set(&t.get_approximate(&bbox, min_t, 0.)) == expected);
+            assert!(t.max_t_in(&bbox) == plain.max_t_in(&bbox));
+
+            let top = |r: Vec<&label::Label>| -> Vec<f64> {
+                r.iter().map(|l| l.get_t()).collect()
+            };
+            assert!(top(t.get_limited(&bbox, min_t, Some(5))) ==
+                    top(plain.get_limited(&bbox, min_t, Some(5))));
+
+            let focus = point::Point::new(x + 5., y + 5.);
+            let distance = |r: Vec<&label::Label>| -> Vec<f64> {
+                r.iter().map(|l| focus.squared_distance(*l)).collect()
+            };
+            assert!(distance(t.get_nearest(&focus, min_t, 5)) ==
+                    distance(plain.get_nearest(&focus, min_t, 5)));
+        }
+
+        t.rebuild().unwrap();
+        assert!(t.validate().is_ok());
+        assert!(t.get_size() == instance.len());
+    }
+
+    #[test]
+    fn flattened_cold_regions_match_queries() {
+        let instance = random_label_instance(TEST_SIZE);
+
+        let plain = pst_3d::Pst3d::new(instance.clone());
+        let mut t = pst_3d::Pst3d::new(instance.clone());
+        assert!(t.flatten_cold_regions(0).unwrap() == 0);
+        t.enable_access_tracking();
+
+        // only the queried region keeps its inner nodes
+        let hot = bbox::BBox::new(0., 0., 20., 20.);
+        t.get(&hot, 0.);
+        let generation = t.get_generation();
+
+        // a snapshot blocks the flattening visibly
+        let snapshot = t.snapshot();
+        let e = t.flatten_cold_regions(0).unwrap_err();
+        assert!(e.get_code() == 7);
+        assert!(t.get_generation() == generation);
+        drop(snapshot);
+
+        let released = t.flatten_cold_regions(0).unwrap();
+        assert!(released > 0);
+        assert!(t.get_generation() != generation);
+        assert!(t.validate().is_ok());
+        assert!(t.get_size() == plain.get_size());
+        assert!(t.get_node_count() + released <= plain.get_node_count());
+        assert!(t.get_memory_size() < plain.get_memory_size());
+        assert!(get_id_set(&t.get(&hot, 0.)) == get_id_set_filtered(&instance, &hot, 0.));
+
+        let mut buf: Vec<u8> = Vec::new();
+        t.write_tree(&mut buf).unwrap();
+        let labels = t.iter().cloned().collect();
+        let loaded: pst_3d::Pst3d = pst_3d::Pst3d::read_tree(&mut buf.as_slice(), labels).unwrap();
+        assert!(loaded.get_node_count() == t.get_node_count());
+
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let x = rng.gen_range(-180., 170.);
+            let y = rng.gen_range(-90., 80.);
+            let bbox = bbox::BBox::new(x, y, x + 10., y + 10.);
+            let min_t = rand::random::<f64>();
+
+            let expected = get_id_set_filtered(&instance, &bbox, min_t);
+            assert!(get_id_set(&t.get(&bbox, min_t)) == expected);
+            assert!(get_id_set(&loaded.get(&bbox, min_t)) == expected);
+            assert!(t.max_t_in(&bbox) == plain.max_t_in(&bbox));
+
+            let focus = point::Point::new(x + 5., y + 5.);
+            let distance = |r: Vec<&label::Label>| -> Vec<f64> {
+                r.iter().map(|l| focus.squared_distance(*l)).collect()
+            };
+            assert!(distance(t.get_nearest(&focus, min_t, 5)) ==
+                    distance(plain.get_nearest(&focus, min_t, 5)));
+        }
+    }
+
+    #[test]
+    fn flattening_invalidates_the_cache() {
+        let instance = random_label_instance(TEST_SIZE);
+
+        let mut t = pst_3d::GeoPst3d::new(instance.clone());
+        t.enable_cache(cache::ResultCache::new(64, Duration::from_secs(60)));
+        t.enable_access_tracking();
+
+        let hot = bbox::BBox::new(0., 0., 20., 20.);
+        let other = bbox::BBox::new(-100., -50., -60., -10.);
+        for _ in 0..10 {
+            t.get(&hot, 0.);
+        }
+        let expected = get_id_set_filtered(&instance, &other, 0.);
+        assert!(get_id_set(&t.get_cached(&other, 0.)) == expected);
+
+        // the cached indices of the flattened region are not valid anymore
+        assert!(t.flatten_cold_regions(5).unwrap() > 0);
+        assert!(get_id_set(&t.get_cached(&other, 0.)) == expected);
+        assert!(get_id_set(&t.get_cached(&hot, 0.)) == get_id_set_filtered(&instance, &hot, 0.));
+
+        t.rebuild().unwrap();
+        assert!(get_id_set(&t.get_cached(&other, 0.)) == expected);
+    }
+
+    #[test]
+    fn bbox_from_labels_is_tight() {
+        let instance = random_label_instance(TEST_SIZE);
+
+        for n in 1..TEST_SIZE / 10 {
+            let labels = &instance[..n];
+            let bb = bbox::BBox::from_labels(labels);
+
+            assert!(labels.iter().all(|l| bb.is_contained(l)));
+            assert!(labels.iter().any(|l| l.get_x() == bb.get_min_x()));
+            assert!(labels.iter().any(|l| l.get_x() == bb.get_max_x()));
+            assert!(labels.iter().any(|l| l.get_y() == bb.get_min_y()));
+            assert!(labels.iter().any(|l| l.get_y() == bb.get_max_y()));
+        }
+
+        assert!(bbox::BBox::from_labels(&instance[..0]).area() == 0.);
+    }
+
+    #[cfg(feature = "osm-pbf")]
+    #[test]
+    fn osm_pbf_place_nodes() {
+        use flate2::write::ZlibEncoder;
+        use std::io::Write;
+        use super::input::pbf;
+        use super::pack::codec::write_varint;
+
+        fn bytes(out: &mut Vec<u8>, nr: u64, data: &[u8]) {
+            write_varint(out, nr << 3 | 2).unwrap();
+            write_varint(out, data.len() as u64).unwrap();
+            out.extend_from_slice(data);
+        }
+        fn varint(out: &mut Vec<u8>, nr: u64, v: u64) {
+            write_varint(out, nr << 3).unwrap();
+            write_varint(out, v).unwrap();
+        }
+        fn packed(values: &[u64]) -> Vec<u8> {
+            let mut out = Vec::new();
+            for &v in values {
+                write_varint(&mut out, v).unwrap();
+            }
+            out
+        }
+        fn zigzag(v: i64) -> u64 {
+            ((v << 1) ^ (v >> 63)) as u64
+        }
+        fn blob(file: &mut Vec<u8>, kind: &str, data: &[u8], zlib: bool) {
+            let mut b = Vec::new();
+            if zlib {
+                let mut e = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                e.write_all(data).unwrap();
+                bytes(&mut b, 3, &e.finish().unwrap());
+            } else {
+                bytes(&mut b, 1, data);
+            }
+            let mut h = Vec::new();
+            bytes(&mut h, 1, kind.as_bytes());
+            varint(&mut h, 3, b.len() as u64);
+            file.extend_from_slice(&(h.len() as u32).to_be_bytes());
+            file.extend_from_slice(&h);
+            file.extend_from_slice(&b);
+        }
+
+        let mut header = Vec::new();
+        bytes(&mut header, 4, b"OsmSchema-V0.6");
+        bytes(&mut header, 4, b"DenseNodes");
+
+        let mut table = Vec::new();
+        for s in &["", "name", "place", "population", "Bremen", "city", "Farge", "village",
+                   "name:en", "Brema", "569352"] {
+            bytes(&mut table, 1, s.as_bytes());
+        }
+        // three dense nodes: Bremen, an untagged node and Farge
+        let mut dense = Vec::new();
+        bytes(&mut dense, 1, &packed(&[zigzag(20982927), zigzag(1), zigzag(-20982927)]));
+        bytes(&mut dense, 8, &packed(&[zigzag(530758196), zigzag(-10), zigzag(1244000)]));
+        bytes(&mut dense, 9, &packed(&[zigzag(88071646), zigzag(10), zigzag(-2782000)]));
+        bytes(&mut dense, 10, &packed(&[1, 4, 2, 5, 3, 10, 8, 9, 0, 0, 1, 6, 2, 7, 0]));
+        // a single node with an id, keys, values, lat and lon
+        let mut node = Vec::new();
+        varint(&mut node, 1, zigzag(42));
+        bytes(&mut node, 2, &packed(&[1, 2]));
+        bytes(&mut node, 3, &packed(&[6, 5]));
+        varint(&mut node, 8, zigzag(-100));
+        varint(&mut node, 9, zigzag(200));
+
+        let mut group = Vec::new();
+        bytes(&mut group, 2, &dense);
+        bytes(&mut group, 1, &node);
+        let mut block = Vec::new();
+        bytes(&mut block, 1, &table);
+        bytes(&mut block, 2, &group);
+        varint(&mut block, 17, 10);
+
+        let mut file = Vec::new();
+        blob(&mut file, "OSMHeader", &header, false);
+        blob(&mut file, "OSMData", &block, true);
+
+        let format = pbf::OsmPbfFormat::new(pbf::OsmOptions::new());
+        assert!(format.detect(&file));
+        let labels = format.read(&mut file.as_slice(), &input::ImportOptions::new())
+            .unwrap()
+            .into_labels();
+
+        assert!(labels.len() == 3);
+        assert!(labels[0].get_label() == "Bremen" && labels[0].get_osm_id() == 20982927);
+        assert!((labels[0].get_y() - 5.30758196).abs() < 1e-6);
+        assert!((labels[0].get_x() - 0.88071646).abs() < 1e-6);
+        assert!(labels[0].get_name("name:en").unwrap() == "Brema");
+        assert!(labels[0].get_t() > labels[2].get_t());
+        assert!(labels[1].get_label() == "Farge" && labels[1].get_prio() == 3);
+        assert!(labels[1].get_osm_id() == 1);
+        assert!(labels[2].get_osm_id() == 42 && (labels[2].get_y() + 1e-6).abs() < 1e-12);
+
+        let mut unsupported = Vec::new();
+        bytes(&mut header, 4, b"HistoricalInformation");
+        blob(&mut unsupported, "OSMHeader", &header, false);
+        assert!(format.read(&mut unsupported.as_slice(), &input::ImportOptions::new()).is_err());
+    }
+
+    #[test]
+    fn parallel_import_matches_sequential() {
+        let path = "resources/bremen-latest.osm.pbf.ce".to_string();
+        let mut o = input::ImportOptions::new();
+        let sequential = input::import_labels(&path, &o).unwrap().into_labels();
+
+        for threads in 0..5 {
+            o.set_threads(threads);
+            let parallel = input::import_labels(&path, &o).unwrap().into_labels();
+            assert!(parallel.len() == sequential.len());
+            for (p, s) in parallel.iter().zip(sequential.iter()) {
+                assert!(p.get_osm_id() == s.get_osm_id() && p.get_t() == s.get_t());
+                assert!(p.get_label() == s.get_label());
+            }
+        }
+    }
+
+    #[test]
+    fn plain_parser_matches_regex_parser() {
+        fn check(line: &str) {
+            let plain = input::parse::parse_label(line);
+            let regex = input::parse::parse_label_regex(line);
+            match (plain, regex) {
+                (Ok(p), Ok(r)) => assert!(format!("{:?}", p) == format!("{:?}", r), "{}", line),
+                (Err(p), Err(r)) => assert!(p.to_string() == r.to_string(), "{}", line),
+                _ => panic!("parsers disagree on {}", line),
+            }
+        }
+
+        let data = include_str!("../resources/bremen-latest.osm.pbf.ce");
+        let lines: Vec<&str> = data.lines().skip(2).collect();
+        for line in lines.iter() {
+            check(line);
+        }
+
+        let special = ["53.14 8.93 1 -2 inf 3300 11 'Timmersloh'",
+                       "53.14 8.93 1 1 1.5e3 3300.0 11.0 ''",
+                       "53.14 8.93 1 1 1.5 3300.0 11.0 'a' 'b'",
+                       "53.14 8.93 1 1 1.5 3300.0 11.0 'L\\'Aquila'",
+                       "53.14 8.93 1 1 1.5 3300.0 11.0 'L''AQ'",
+                       "53.14 8.93 1 1 1.5 3300.0 11.0 'x' category:3",
+                       "53.14 8.93 1 1 1.5 3300.0 11.0 'x'\r",
+                       "53.14  8.93 1 1 1.5 3300.0 11.0 'x'",
+                       "1053.14 8.93 1 1 1.5 3300.0 11.0 'x'",
+                       "53.14 8.93 99999999999999999999 1 1.5 3300.0 11.0 'x'",
+                       "53.14 8.93 1 99999999999 1.5 3300.0 11.0 'x'",
+                       "53.14 8.93 1 1 1.5 3300.0 11.0 '",
+                       "53.14 8.93 1 1 1.5 3300.0 11.0 'a\\nb\\\\'",
+                       "53.14 8.93 1 1 1.5 3300.0 11.0 'a\\'",
+                       "53.14 8.93 1 1 1.5 3300.0 11.0 'a\\\nb'",
+                       "53.14 8.93 1 1 1.5 3300.0 11.0",
+                       "\u{665}3.14 8.93 1 1 1.5 3300.0 11.0 'x'"];
+        for line in special.iter() {
+            check(line);
+        }
+
+        // mutate single characters of the lines to compare the errors of both parsers
+        let alphabet: Vec<char> = " '\\-.e+0159xinf\r".chars().collect();
+        let mut rng = thread_rng();
+        for _ in 0..5000 {
+            let mut chars: Vec<char> = lines[rng.gen_range(0, lines.len())].chars().collect();
+            let pos = rng.gen_range(0, chars.len());
+            chars[pos] = alphabet[rng.gen_range(0, alphabet.len())];
+            check(&chars.into_iter().collect::<String>());
+        }
+    }
+
+    #[test]
+    fn crlf_import_matches_lf() {
+        let o = input::ImportOptions::new();
+        let lf = input::import_labels(&"resources/bremen-latest.osm.pbf.ce".to_string(), &o)
+            .unwrap();
+        let crlf = input::import_labels(&"resources/bremen-latest.osm.pbf.crlf.ce".to_string(),
+                                        &o)
+            .unwrap();
+        assert!(crlf.get_skipped().is_empty());
+        assert!(crlf.get_labels().len() == lf.get_labels().len());
+        for (c, l) in crlf.get_labels().iter().zip(lf.get_labels().iter()) {
+            assert!(c.get_osm_id() == l.get_osm_id() && c.get_t() == l.get_t());
+            assert!(c.get_label() == l.get_label());
+        }
+
+        let auto = input::import_auto(&"resources/bremen-latest.osm.pbf.crlf.ce".to_string(), &o)
+            .unwrap();
+        assert!(auto.get_labels().len() == lf.get_labels().len());
+        assert!(auto.get_format() == Some("label file"));
+    }
+
+    #[test]
+    fn auto_import_applies_options() {
+        let mut lines: Vec<String> = fs::read_to_string("resources/bremen-latest.osm.pbf.ce")
+            .unwrap()
+            .lines()
+            .map(|l| l.to_string())
+            .collect();
+        lines[10] = lines[10].replacen(' ', " x", 1);
+        let path = std::env::temp_dir()
+            .join(format!("rt_datastructure_auto_{}.ce", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        fs::write(&path, lines.join("\n")).unwrap();
+
+        let mut o = input::ImportOptions::new();
+        let lenient = input::import_auto(&path, &o).unwrap();
+        assert!(lenient.get_skipped().len() == 1);
+        assert!(lenient.get_labels().len() == 132);
+
+        o.set_threads(4);
+        let parallel = input::import_auto(&path, &o).unwrap();
+        assert!(get_id_set(&parallel.get_labels().iter().collect()) ==
+                get_id_set(&lenient.get_labels().iter().collect()));
+
+        o.set_mode(input::ImportMode::Strict);
+        let e = input::import_auto(&path, &o).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(e.to_string().starts_with("line 11, "));
+    }
+
+    #[test]
+    fn geojson_round_trip() {
+        let mut instance = random_label_instance(TEST_SIZE / 10);
+        instance[0].set_tag("place".to_string(), "city \"Mitte\"".to_string());
+        instance[1].set_category(3);
+
+        for &string_table in &[false, true] {
+            let mut o = output::EncodeOptions::new();
+            o.set_string_table(string_table);
+            let json = output::to_geojson(&instance.iter().collect::<Vec<_>>(), &o);
+
+            let format = geojson::GeoJsonFormat::new(geojson::GeoJsonOptions::new());
+            let r = format.read(&mut json.as_bytes(), &input::ImportOptions::new()).unwrap();
+            assert!(r.get_skipped().is_empty());
+
+            for (read, l) in r.get_labels().iter().zip(instance.iter()) {
+                assert!(read.get_osm_id() == l.get_osm_id());
+                assert!(read.get_x() == l.get_x() && read.get_y() == l.get_y());
+                assert!(read.get_t() == l.get_t());
+                assert!(read.get_label() == l.get_label());
+                assert!(read.get_category() == l.get_category());
+                assert!(read.get_tags() == l.get_tags());
+            }
+        }
+    }
+
+    #[test]
+    fn antimeridian_results_are_unique() {
+        let mut instance = random_label_instance(TEST_SIZE);
+        for (i, l) in instance.iter_mut().enumerate().take(TEST_SIZE / 5) {
+            let y = l.get_y();
+            l.set_position(if i % 2 == 0 { 180. } else { -180. }, y);
+        }
+
+        let t = pst_3d::GeoPst3d::new(instance.clone());
+
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let min_x = rng.gen_range(0., 180.);
+            let max_x = rng.gen_range(-180., 0.);
+            let min_y = rng.gen_range(-90., 0.);
+            let min_t = rand::random::<f64>();
+
+            let bbox = bbox::BBox::new(min_x, min_y, max_x, min_y + 90.);
+            let r = t.get(&bbox, min_t);
+            let ids = get_id_set(&r);
+            assert!(ids.len() == r.len());
+
+            let expected = get_id_set_filtered(&instance,
+                                               &bbox::BBox::new(min_x, min_y, 180., min_y + 90.),
+                                               min_t)
+                .union(&get_id_set_filtered(&instance,
+                                            &bbox::BBox::new(-180., min_y, max_x, min_y + 90.),
+                                            min_t))
+                .cloned()
+                .collect::<HashSet<i64>>();
+            assert!(ids == expected);
+        }
+    }
+
+    #[test]
+    fn t_interval_contains_both_bounds() {
+        let instance = random_label_instance(TEST_SIZE);
+        let t = pst_3d::GeoPst3d::new(instance.clone());
+        let bbox = bbox::BBox::new(-180., -90., 180., 90.);
+
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let t_low = instance[rng.gen_range(0, instance.len())].get_t();
+            let t_high = instance[rng.gen_range(0, instance.len())].get_t().max(t_low);
+
+            let ids = get_id_set(&t.get_t_interval(&bbox, t_low, t_high));
+            let expected: HashSet<i64> = instance.iter()
+                .filter(|l| t_low <= l.get_t() && l.get_t() <= t_high)
+                .map(|l| l.get_osm_id())
+                .collect();
+            assert!(ids == expected);
+        }
+    }
+
+    #[test]
+    fn options_limit_after_filters() {
+        let mut instance = random_label_instance(TEST_SIZE);
+        for l in instance.iter_mut() {
+            let category = (l.get_osm_id() % 100) as u16;
+            l.set_category(category);
+        }
+        let t = pst_3d::GeoPst3d::new(instance.clone());
+
+        let mut o = QueryOptions::new();
+        o.set_categories(Some(CategorySet::from_list(&[1, 70, 99])));
+        o.set_limit(Some(5));
+
+        let mut expected: Vec<&label::Label> =
+            instance.iter().filter(|l| [1, 70, 99].contains(&l.get_category())).collect();
+        expected.sort_by(|a, b| b.get_t().partial_cmp(&a.get_t()).unwrap());
+        expected.truncate(5);
+
+        let r = t.get_with_options(&bbox::BBox::new(-180., -90., 180., 90.), 0., &o);
+        let ids: Vec<i64> = r.iter().map(|r| r.get_label().get_osm_id()).collect();
+        assert!(ids == expected.iter().map(|l| l.get_osm_id()).collect::<Vec<i64>>());
+    }
+
+    #[test]
+    fn post_processing_uses_tree_priority() {
+        // the priorities of the tree are the reverse of the t values of the labels
+        let v: Vec<label::Label> = (1..11)
+            .map(|i| label::Label::new(1. + i as f64 * 0.01, 1., i as f64, i, 11 - i as i32, 1.5,
+                                       "A".to_string()))
+            .collect();
+        let t = pst_3d::Pst3d::new_by(v, |l| l.get_prio() as f64);
+        let bb = bbox::BBox::new(0., 0., 10., 10.);
+
+        let mut o = QueryOptions::new();
+        o.set_limit(Some(3));
+        let r = t.get_with_options(&bb, 0., &o);
+        let ids: Vec<i64> = r.iter().map(|r| r.get_label().get_osm_id()).collect();
+        assert!(ids == vec![1, 2, 3]);
+
+        o.set_merge_distance(1., 1.);
+        let r = t.get_with_options(&bb, 0., &o);
+        assert!(r.len() == 1);
+        assert!(r[0].get_label().get_osm_id() == 1 && r[0].get_merged_count() == 10);
+
+        // priority 5 is visible before, priority 4 is not
+        let visible: HashSet<i64> = [6].iter().cloned().collect();
+        let r = t.get_with_hysteresis(&bb, 5.5, &visible, Some(2.));
+        let mut ids: Vec<i64> = r.iter().map(|l| l.get_osm_id()).collect();
+        ids.sort();
+        assert!(ids == vec![1, 2, 3, 4, 5, 6]);
+
+        let mut overlay = pst_3d::overlay::PriorityOverlay::new();
+        overlay.add_id(10, 9.5);
+        let r = t.get_with_overlay(&bb, 9.5, &overlay);
+        let mut ids: Vec<i64> = r.iter().map(|l| l.get_osm_id()).collect();
+        ids.sort();
+        assert!(ids == vec![1, 10]);
+    }
+
+    #[test]
+    fn ffi_labels_with_interior_nul() {
+        let v = vec![label::Label::new(1., 2., 10., 1, 1, 1.5, "Bad\0Name".to_string()),
+                     label::Label::new(2., 3., 9., 2, 1, 1.5, "Good".to_string())];
+        let ds = super::DataStructure::new(Some(pst_3d::GeoPst3d::new(v)));
+
+        let res = super::get_data(&ds, 0., 0., 10., 0., 10.);
+        assert!(res.size == 2);
+        let mut names: Vec<String> = (0..res.size as usize)
+            .map(|i| unsafe { CStr::from_ptr((*res.data.add(i)).label) })
+            .map(|s| s.to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert!(names == vec!["BadName".to_string(), "Good".to_string()]);
+
+        super::free_result(res);
+    }
+
+    #[test]
+    fn free_results_of_all_queries() {
+        let v = vec![label::Label::new(1., 2., 10., 1, 1, 1.5, "Eins".to_string()),
+                     label::Label::new(2., 3., 9., 2, 1, 1.5, "Zwei".to_string())];
+        let ds = super::DataStructure::new(Some(pst_3d::GeoPst3d::new(v)));
+        let fallback = CString::new("name:de,name").unwrap();
+
+        for _ in 0..100 {
+            super::free_result(super::get_data(&ds, 0., 0., 10., 0., 10.));
+            let localized = unsafe {
+                super::get_data_localized(&ds, 0., 0., 10., 0., 10., fallback.as_ptr())
+            };
+            super::free_result(localized);
+            super::free_result(super::get_data_abbreviated(&ds, 0., 0., 10., 0., 10., 5.));
+        }
+
+        let invalid = super::DataStructure::new(None);
+        let res = super::get_data(&invalid, 0., 0., 10., 0., 10.);
+        assert!(res.size == 0 && res.generation == 0);
+        super::free_result(res);
+    }
+
+    #[test]
+    fn append_merges_update() {
+        let base = "resources/bremen-latest.osm.pbf.ce".to_string();
+        let mut ds = super::DataStructure::new(Some(super::load_tree(&base).unwrap()));
+        super::enable_cache(&mut ds, 10, 60_000);
+        let before = super::get_data(&ds, 0., -180., 180., -90., 90.);
+        assert!(before.size == 133);
+        super::free_result(before);
+
+        let replaced = label::Label::new(8.84, 53.07, 5000., 3955109710, 1, 11., "Neu".to_string());
+        let added = label::Label::new(8.5, 53.5, 5000., 1, 1, 11., "Neu hinzu".to_string());
+        let path = std::env::temp_dir()
+            .join(format!("rt_datastructure_append_{}.ce", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        output::export_labels(&path, &[&replaced, &added]).unwrap();
+
+        let c_path = CString::new(path.clone()).unwrap();
+        let code = unsafe { super::append_labels(&mut ds, c_path.as_ptr()) };
+        assert!(code == 0);
+        // appending the same update again replaces the labels it added before
+        let report = ds.append(&path, &input::ImportOptions::new()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(report.get_labels().len() == 2 && report.get_skipped().is_empty());
+
+        let res = super::get_data(&ds, 4000., -180., 180., -90., 90.);
+        assert!(res.size == 2);
+        super::free_result(res);
+        let all = super::get_data(&ds, 0., -180., 180., -90., 90.);
+        assert!(all.size == 134);
+        super::free_result(all);
+
+        let missing = CString::new("resources/missing.ce").unwrap();
+        assert!(unsafe { super::append_labels(&mut ds, missing.as_ptr()) } == 1);
+        let mut invalid = super::DataStructure::new(None);
+        assert!(unsafe { super::append_labels(&mut invalid, missing.as_ptr()) } == 3);
+    }
+
+    #[test]
+    fn concurrent_queries() {
+        let instance = random_label_instance(TEST_SIZE);
+        let t = Arc::new(pst_3d::GeoPst3d::new(instance.clone()));
+
+        let queries: Vec<(bbox::BBox, f64)> = (0..100)
+            .map(|i| {
+                let x = (i % 35) as f64 * 10. - 180.;
+                let y = (i % 17) as f64 * 10. - 90.;
+                (bbox::BBox::new(x, y, x + 20., y + 20.), (i % 10) as f64 / 10.)
+            })
+            .collect();
+        let queries = Arc::new(queries);
+
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let t = Arc::clone(&t);
+                let queries = Arc::clone(&queries);
+                thread::spawn(move || {
+                    queries.iter()
+                        .map(|&(ref bbox, min_t)| get_id_set(&t.get(bbox, min_t)))
+                        .collect::<Vec<HashSet<i64>>>()
+                })
+            })
+            .collect();
+
+        for w in workers {
+            let results = w.join().unwrap();
+            for (r, &(ref bbox, min_t)) in results.iter().zip(queries.iter()) {
+                assert!(*r == get_id_set_filtered(&instance, bbox, min_t));
+            }
+        }
+    }
+
     #[test]
     fn randomized_test() {
         let instance = random_label_instance(TEST_SIZE);
@@ -352,6 +1646,12 @@ mod tests {
             let res = pskdt.get(&bbox, t);
 
             assert!(get_id_set(&res) == get_id_set_filtered(&instance, &bbox, t));
+
+            let max_t = instance.iter()
+                .filter(|l| bbox.is_contained(l))
+                .map(|l| l.get_t())
+                .fold(None, |m: Option<f64>, t| Some(m.map_or(t, |m| m.max(t))));
+            assert!(pskdt.max_t_in(&bbox) == max_t);
         }
     }
 }
@@ -19,11 +19,20 @@
     along with this program.  If not, see <http://www.gnu.org/licenses/>.
 */
 
+extern crate rand;
 extern crate rt_datastructure;
 
+use rand::Rng;
+
+use rt_datastructure::benchmark;
+use rt_datastructure::budget;
 use rt_datastructure::input;
+use rt_datastructure::output;
+#[cfg(feature = "pack")]
+use rt_datastructure::pack;
 use rt_datastructure::primitives;
 use rt_datastructure::pst_3d;
+use rt_datastructure::sanitize;
 
 use std::env;
 use std::error::Error;
@@ -33,6 +42,14 @@ fn main() {
     println!("Hallo :-)");
 
     let args: Vec<String> = env::args().collect();
+    if args.len() > 1 && (args[1] == "pack" || args[1] == "unpack") {
+        if let Err(err) = run_pack_command(&args[1..]) {
+            println!("Problem running {}: {}", args[1], err);
+            process::exit(1);
+        }
+        return;
+    }
+
     let config = Config::new(&args).unwrap_or_else(|err| {
                                                        println!("Problem parsing arguments: {}",
                                                                 err);
@@ -47,10 +64,11 @@ fn main() {
 
 
     let testing = false;
-    let labels = match input::import_labels(&config.m_input_path) {
-        Ok(res) => {
-            println!("Successfully imported {} labels", res.len());
-            res
+    let mut labels = match input::import_auto(&config.m_input_path, &config.m_import) {
+        Ok(report) => {
+            print_import(&config.m_input_path, &report);
+            println!("Successfully imported {}", report.get_summary());
+            report.into_labels()
         }
         Err(e) => {
             panic!("Could not read the given input file:\
@@ -60,19 +78,42 @@ fn main() {
         }
     };
 
+    if config.m_memory_budget > 0 {
+        let report = budget::fit_to_budget(&mut labels, config.m_memory_budget);
+        println!("Memory budget of {} bytes: {}", config.m_memory_budget, report);
+    }
+
+    if config.m_sanitize.is_enabled() {
+        println!("Sanitizing the labels: ids {:?}, stripped fields {:?}",
+                 config.m_sanitize.get_id_anonymization(),
+                 config.m_sanitize.get_stripped_fields());
+        sanitize::sanitize(&mut labels, &config.m_sanitize);
+
+        let sanitized: Vec<&primitives::label::Label> = labels.iter().collect();
+        if let Err(err) = output::export_labels(&config.m_output_path, &sanitized) {
+            println!("Could not write the sanitized labels to {}: {}", config.m_output_path, err);
+            process::exit(1);
+        }
+        println!("Wrote {} sanitized labels to {}", labels.len(), config.m_output_path);
+    }
+
     if testing {
         for (idx, l) in labels.iter().enumerate() {
-            println!("Parsed label (#{}):\n{}", idx, l.to_string());
+            println!("Parsed label (#{}):\n{}", idx, l);
         }
     }
 
+    if config.m_benchmark > 0 {
+        run_benchmark(&labels, config.m_benchmark);
+    }
+
     let mut tree = pst_3d::Pst3d::new(labels.clone());
 
     // Testing stuff ...
     if testing {
         let l = primitives::label::Label::new(90., 90., 0.9, 1234567, 16, 1.5, "Test".to_string());
 
-        println!("Test label:\n{}", l.to_string());
+        println!("Test label:\n{}", l);
 
         println!("Starting to create a 3d priority search tree ...");
 
@@ -94,42 +135,217 @@ fn main() {
 
         println!("... finished!");
 
-        println!("\n####    TREE:\n{}", t.to_string());
+        println!("\n####    TREE:\n{}", t);
 
         let bb = primitives::bbox::BBox::new(4., 5., 7., 8.);
         let r = t.get(&bb, config.m_min_t);
 
         println!("Requesting labels with min t: {} in {}",
                  config.m_min_t,
-                 bb.to_string());
+                 bb);
 
         println!("\n####    RESULTS:");
         for elem in r {
-            println!("Result: {}", elem.to_string());
+            println!("Result: {}", elem);
         }
     }
 }
 
+// Run the label pack commands of the form:
+//
+//   pack [--name-index] <input_path> <pack_path>
+//   unpack <pack_path> <output_path>
+//
+// pack imports the labels and writes them together with the constructed tree into a label pack.
+// unpack prints the metadata of a label pack and exports its labels into a label file.
+#[cfg(feature = "pack")]
+fn run_pack_command(args: &[String]) -> Result<(), Box<Error>> {
+    let name_index = args.iter().any(|a| a == "--name-index");
+    let paths: Vec<&String> = args.iter().skip(1).filter(|a| *a != "--name-index").collect();
+    if paths.len() != 2 {
+        return Err(From::from("usage: pack [--name-index] <input_path> <pack_path> | \
+                               unpack <pack_path> <output_path>"));
+    }
+
+    if args[0] == "pack" {
+        let report = input::import_auto(paths[0], &input::ImportOptions::new())?;
+        print_import(paths[0], &report);
+        let labels = report.into_labels();
+        let count = labels.len();
+
+        let mut p = pack::LabelPack::new(pst_3d::GeoPst3d::try_new(labels)?);
+        p.set_metadata("source".to_string(), paths[0].clone());
+        if name_index {
+            p.build_name_index();
+        }
+        p.save(paths[1])?;
+        println!("Packed {} labels into {}", count, paths[1]);
+    } else {
+        let p = pack::LabelPack::open(paths[0])?;
+        for m in p.get_all_metadata() {
+            println!("{}: {}", m.0, m.1);
+        }
+
+        let pst = p.get_tree().get_pst();
+        let labels: Vec<&primitives::label::Label> = (0..pst.get_size() as u32)
+            .filter_map(|idx| pst.get_label(idx))
+            .collect();
+        output::export_labels(paths[1], &labels)?;
+        println!("Unpacked {} labels into {}", labels.len(), paths[1]);
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "pack"))]
+fn run_pack_command(_args: &[String]) -> Result<(), Box<Error>> {
+    Err(From::from("label packs require the pack feature"))
+}
+
+// Print the detected input format of the file at path and the errors of the skipped records.
+fn print_import(path: &str, report: &input::format::ImportReport) {
+    if let Some(format) = report.get_format() {
+        println!("Importing {} as {}", path, format);
+    }
+    for e in report.get_skipped() {
+        println!("Input could not be parsed!\nReported error was: {}", e);
+    }
+}
+
+// Compare the 3D PST with the sorted scan baseline on count random queries within the bounding
+// box of the given labels.
+fn run_benchmark(labels: &[primitives::label::Label], count: usize) {
+    let mut data_box = primitives::bbox::BBox::new_empty();
+    let mut max_t: f64 = 0.;
+    for l in labels {
+        data_box.add_to_box(l);
+        max_t = max_t.max(l.get_t());
+    }
+
+    let mut rng = rand::thread_rng();
+    let mut queries = Vec::with_capacity(count);
+    for _ in 0..count {
+        let x1 = sample(&mut rng, data_box.get_min_x(), data_box.get_max_x());
+        let x2 = sample(&mut rng, data_box.get_min_x(), data_box.get_max_x());
+        let y1 = sample(&mut rng, data_box.get_min_y(), data_box.get_max_y());
+        let y2 = sample(&mut rng, data_box.get_min_y(), data_box.get_max_y());
+        let min_t = sample(&mut rng, 0., max_t);
+
+        queries.push((primitives::bbox::BBox::new(x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2)),
+                      min_t));
+    }
+
+    let results = vec![benchmark::run::<pst_3d::Pst3d>(labels.to_vec(), &queries),
+                       benchmark::run::<benchmark::SortedScan>(labels.to_vec(), &queries)];
+    for r in results {
+        println!("{}: build {:?}, {} queries {:?}, {} labels reported",
+                 r.get_name(),
+                 r.get_build_time(),
+                 count,
+                 r.get_query_time(),
+                 r.get_reported());
+    }
+}
+
+// Draw a uniformly distributed value in [low, high) or low if the range is empty, e.g. if all
+// labels share a coordinate. gen_range panics for an empty range.
+fn sample<R: Rng>(rng: &mut R, low: f64, high: f64) -> f64 {
+    if low < high {
+        rng.gen_range(low, high)
+    } else {
+        low
+    }
+}
+
 struct Config {
     m_input_path: String,
     m_min_t: f64,
+    m_output_path: String,
+    m_sanitize: sanitize::SanitizeOptions,
+    m_benchmark: usize,
+    m_memory_budget: usize,
+    m_import: input::ImportOptions,
 }
 
 impl Config {
+    ///
+    /// Parse the command line arguments of the form:
+    ///
+    /// ```text
+    /// [--anonymize | --anonymize-hash <salt>] [--strip <field>]... [--output <path>]
+    ///     [--benchmark <queries>] [--memory-budget <bytes>] [--strict] [--threads <count>]
+    ///     [min_t [input_path]]
+    /// ```
+    ///
+    /// With --strict the first malformed record fails the import, --threads sets the number of
+    /// threads the lines of a label file are parsed on.
+    ///
+    /// The sanitized labels are written to the output path, which is required by the
+    /// anonymization and strip options.
+    ///
     pub fn new(args: &[String]) -> Result<Config, Box<Error>> {
         let mut min_t = 4.;
         let mut path = "".to_string();
+        let mut output_path = "".to_string();
+        let mut sanitize = sanitize::SanitizeOptions::new();
+        let mut benchmark = 0;
+        let mut memory_budget = 0;
+        let mut import = input::ImportOptions::new();
+
+        let mut positional: Vec<&String> = Vec::new();
+        let mut iter = args.iter().skip(1);
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--anonymize" => {
+                    sanitize.set_id_anonymization(sanitize::IdAnonymization::Renumber)
+                }
+                "--anonymize-hash" => {
+                    let salt = iter.next().ok_or("--anonymize-hash requires a salt")?;
+                    sanitize.set_id_anonymization(sanitize::IdAnonymization::Hash(salt.parse()?));
+                }
+                "--strip" => {
+                    let field = iter.next().ok_or("--strip requires a tag or name key")?;
+                    sanitize.add_stripped_field(field.clone());
+                }
+                "--output" => {
+                    output_path = iter.next().ok_or("--output requires a path")?.clone();
+                }
+                "--benchmark" => {
+                    let count = iter.next().ok_or("--benchmark requires a number of queries")?;
+                    benchmark = count.parse()?;
+                }
+                "--memory-budget" => {
+                    let bytes = iter.next().ok_or("--memory-budget requires a number of bytes")?;
+                    memory_budget = bytes.parse()?;
+                }
+                "--strict" => import.set_mode(input::ImportMode::Strict),
+                "--threads" => {
+                    let count = iter.next().ok_or("--threads requires a number of threads")?;
+                    import.set_threads(count.parse()?);
+                }
+                _ => positional.push(arg),
+            }
+        }
 
-        if args.len() >= 2 {
-            min_t = args[1].parse()?;
+        if !positional.is_empty() {
+            min_t = positional[0].parse()?;
         }
-        if args.len() >= 3 {
-            path = args[2].clone();
+        if positional.len() >= 2 {
+            path = positional[1].clone();
+        }
+
+        if sanitize.is_enabled() && output_path.is_empty() {
+            return Err(From::from("--anonymize, --anonymize-hash and --strip require --output"));
         }
 
         Ok(Config {
                m_input_path: path,
                m_min_t: min_t,
+               m_output_path: output_path,
+               m_sanitize: sanitize,
+               m_benchmark: benchmark,
+               m_memory_budget: memory_budget,
+               m_import: import,
            })
     }
 }
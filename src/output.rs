@@ -0,0 +1,509 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs::File;
+use std::io;
+
+use error::Error;
+use input::binary::{BINARY_MAGIC, BINARY_VERSION};
+use input::parse;
+use pack::codec;
+use primitives::geometry::Geometry;
+use primitives::label::Label;
+use primitives::point::Point;
+use pst_3d::query::CoordinatePrecision;
+
+///
+/// Options of the output encoders.
+///
+/// A default constructed EncodeOptions object writes each label string inline and the coordinates
+/// in full precision.
+///
+pub struct EncodeOptions {
+    m_string_table: bool,
+    m_precision: CoordinatePrecision,
+}
+
+impl EncodeOptions {
+    ///
+    /// Initialize new options writing each label string inline and the coordinates in full
+    /// precision.
+    ///
+    pub fn new() -> EncodeOptions {
+        EncodeOptions {
+            m_string_table: false,
+            m_precision: CoordinatePrecision::Full,
+        }
+    }
+
+    ///
+    /// Write each distinct label string once into a shared string table and reference it by its
+    /// index. Large results with many repeated names become considerably smaller.
+    ///
+    pub fn set_string_table(&mut self, enabled: bool) {
+        self.m_string_table = enabled;
+    }
+
+    ///
+    /// Check if the label strings are written into a shared string table.
+    ///
+    pub fn get_string_table(&self) -> bool {
+        self.m_string_table
+    }
+
+    ///
+    /// Set the precision the label positions and anchor geometries are written with.
+    ///
+    pub fn set_precision(&mut self, precision: CoordinatePrecision) {
+        self.m_precision = precision;
+    }
+
+    ///
+    /// Get the precision the label positions and anchor geometries are written with.
+    ///
+    pub fn get_precision(&self) -> CoordinatePrecision {
+        self.m_precision
+    }
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+///
+/// Encode the labels as a GeoJSON FeatureCollection of points.
+///
+/// Each feature has the properties osm_id, t, prio, lbl_fac, label_length, category and name and,
+/// if the label has a short label, short_name and, if the label has tags, a tags object. The
+/// point geometry of a feature is the label position; labels of line and area features
+/// additionally get their LineString or Polygon as anchor_geometry property. With the string
+/// table option enabled, the collection gets an additional `strings` array containing each
+/// distinct label string once and the name and short_name properties are indices into this
+/// array. The coordinates of the point and the anchor geometry are written with the precision of
+/// the options.
+///
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::primitives::label;
+/// use rt_datastructure::pst_3d::query::CoordinatePrecision;
+/// use rt_datastructure::output;
+///
+/// let mut l1 = label::Label::new(1., 2., 10., 1, 1, 1.5, "Berlin".to_string());
+/// let mut l2 = label::Label::new(3., 4., 9., 2, 1, 1.5, "Berlin".to_string());
+/// l1.set_tag("place".to_string(), "city".to_string());
+/// l2.set_short_label(Some("B".to_string()));
+///
+/// let mut o = output::EncodeOptions::new();
+/// let json = output::to_geojson(&[&l1, &l2], &o);
+/// assert!(json.matches("\"name\":\"Berlin\"").count() == 2);
+/// assert!(json.matches("\"short_name\":\"B\"").count() == 1);
+/// assert!(json.matches("\"tags\":{\"place\":\"city\"}").count() == 1);
+/// assert!(!json.contains("anchor_geometry"));
+///
+/// o.set_string_table(true);
+/// let json = output::to_geojson(&[&l1, &l2], &o);
+/// assert!(json.contains("\"strings\":[\"Berlin\",\"B\"]"));
+/// assert!(json.matches("\"name\":0").count() == 2);
+/// assert!(json.contains("\"short_name\":1"));
+///
+/// let l3 = label::Label::new(8.9351249, 53.125, 9., 3, 1, 1.5, "Bremen".to_string());
+/// o.set_precision(CoordinatePrecision::Decimals(2));
+/// let json = output::to_geojson(&[&l3], &o);
+/// assert!(json.contains("\"coordinates\":[8.94,53.13]"));
+/// ```
+///
+pub fn to_geojson(labels: &[&Label], options: &EncodeOptions) -> String {
+    let mut strings: Vec<&String> = Vec::new();
+    let mut string_idx: HashMap<&String, usize> = HashMap::new();
+
+    let precision = options.get_precision();
+    let mut features: Vec<String> = Vec::with_capacity(labels.len());
+    let mut string = |s| if options.get_string_table() {
+        let next = strings.len();
+        let idx = *string_idx.entry(s).or_insert(next);
+        if idx == next {
+            strings.push(s);
+        }
+        idx.to_string()
+    } else {
+        format!("\"{}\"", escape(s))
+    };
+    for l in labels {
+        let name = string(l.get_label());
+        let short_name = l.get_short_label()
+            .map_or(String::new(), |s| format!(",\"short_name\":{}", string(s)));
+
+        features.push(format!("{{\"type\":\"Feature\",\"geometry\":{{\"type\":\"Point\",\
+                               \"coordinates\":[{},{}]}},\"properties\":{{\"osm_id\":{},\
+                               \"t\":{},\"prio\":{},\"lbl_fac\":{},\"label_length\":{},\
+                               \"category\":{},\"name\":{}{}{}{}}}}}",
+                              number(precision.apply(l.get_x())),
+                              number(precision.apply(l.get_y())),
+                              l.get_osm_id(),
+                              number(l.get_t()),
+                              l.get_prio(),
+                              number(l.get_label_factor()),
+                              number(l.get_label_length()),
+                              l.get_category(),
+                              name,
+                              short_name,
+                              tags(l),
+                              anchor_geometry(l, precision)));
+    }
+
+    let mut r = String::from("{\"type\":\"FeatureCollection\",");
+    if options.get_string_table() {
+        let entries: Vec<String> = strings.iter().map(|s| format!("\"{}\"", escape(s))).collect();
+        let _ = write!(r, "\"strings\":[{}],", entries.join(","));
+    }
+    let _ = write!(r, "\"features\":[{}]}}", features.join(","));
+
+    r
+}
+
+///
+/// Write the labels in the binary label file format, compare
+/// [BinaryFormat](../input/binary/struct.BinaryFormat.html). Equal label strings are stored once.
+///
+/// # Errors
+///   * Io: if the writer fails
+///   * Serialization: if there are more than 2^32 - 1 distinct label strings
+///
+pub fn write_binary<W: io::Write>(w: &mut W, labels: &[&Label]) -> Result<(), Error> {
+    let mut indices: HashMap<&str, u32> = HashMap::new();
+    let mut strings: Vec<&str> = Vec::new();
+
+    let mut records: Vec<(u32, u32)> = Vec::with_capacity(labels.len());
+    for l in labels {
+        let label = string_index(l.get_label(), &mut indices, &mut strings)?;
+        let short_label = match l.get_short_label() {
+            Some(s) => string_index(s, &mut indices, &mut strings)? + 1,
+            None => 0,
+        };
+        records.push((label, short_label));
+    }
+
+    w.write_all(BINARY_MAGIC)?;
+    codec::write_u32(w, BINARY_VERSION)?;
+    codec::write_u64(w, labels.len() as u64)?;
+    for (l, &(label, short_label)) in labels.iter().zip(records.iter()) {
+        codec::write_f64(w, l.get_x())?;
+        codec::write_f64(w, l.get_y())?;
+        codec::write_f64(w, l.get_t())?;
+        codec::write_u64(w, l.get_osm_id() as u64)?;
+        codec::write_u32(w, l.get_prio() as u32)?;
+        codec::write_u32(w, u32::from(l.get_category()))?;
+        codec::write_f64(w, l.get_label_factor())?;
+        codec::write_f64(w, l.get_label_length())?;
+        codec::write_u32(w, label)?;
+        codec::write_u32(w, short_label)?;
+    }
+
+    codec::write_varint(w, strings.len() as u64)?;
+    for s in strings {
+        codec::write_string(w, s)?;
+    }
+
+    Ok(())
+}
+
+///
+/// Write the labels in the label file format read by
+/// [import_labels](../input/fn.import_labels.html), including the count and header lines.
+///
+/// The strings are escaped, so the labels are read back unchanged.
+///
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::input::{self, ImportOptions};
+/// use rt_datastructure::output;
+/// use rt_datastructure::primitives::label::Label;
+/// use rt_datastructure::pst_3d::Pst3d;
+///
+/// let mut v = Vec::new();
+/// v.push(Label::new(8.80, 53.08, 3.5, 1, 1, 11., "Bremen".to_string()));
+/// v.push(Label::new(13.40, 42.35, 2.5, 2, 1, 11., "L'Aquila".to_string()));
+/// let t = Pst3d::new(v);
+///
+/// let mut buf: Vec<u8> = Vec::new();
+/// output::write_labels(&mut buf, &t.iter().collect::<Vec<_>>()).unwrap();
+/// assert!(buf.starts_with(b"2\nlat lon osm_id"));
+///
+/// let r = input::import_from_reader(buf.as_slice(), &ImportOptions::new()).unwrap();
+/// assert!(r.get_labels().len() == 2 && r.get_skipped().is_empty());
+/// assert!(r.get_labels().iter().any(|l| l.get_label() == "L'Aquila"));
+/// ```
+///
+/// # Errors
+///   * Io: if the writer fails
+///
+pub fn write_labels<W: io::Write>(w: &mut W, labels: &[&Label]) -> Result<(), Error> {
+    writeln!(w, "{}", labels.len())?;
+    writeln!(w, "lat lon osm_id priority collision_time label_length size_factor label")?;
+    for l in labels {
+        write!(w,
+               "{} {} {} {} {} {} {} '{}'",
+               l.get_y(),
+               l.get_x(),
+               l.get_osm_id(),
+               l.get_prio(),
+               l.get_t(),
+               l.get_label_length(),
+               l.get_label_factor(),
+               parse::escape(l.get_label()))?;
+        if let Some(s) = l.get_short_label() {
+            write!(w, " '{}'", parse::escape(s))?;
+        }
+        if l.get_category() != 0 {
+            write!(w, " category:{}", l.get_category())?;
+        }
+        for n in l.get_names() {
+            write!(w, " {}='{}'", n.0, parse::escape(&n.1))?;
+        }
+        for t in l.get_tags() {
+            write!(w, " tag:{}='{}'", t.0, parse::escape(&t.1))?;
+        }
+        writeln!(w)?;
+    }
+
+    Ok(())
+}
+
+///
+/// Export the labels into the file at 'path' in the label file format read by
+/// [import_labels](../input/fn.import_labels.html).
+///
+/// # Errors
+///   * Io: if the file could not be written
+///
+pub fn export_labels(path: &String, labels: &[&Label]) -> Result<(), Error> {
+    let mut w = io::BufWriter::new(File::create(path)?);
+    write_labels(&mut w, labels)?;
+    io::Write::flush(&mut w)?;
+
+    Ok(())
+}
+
+///
+/// Write the labels as KML document of placemarks, e.g. to inspect a dataset in Google Earth.
+///
+/// Each placemark is named by the label string and located at the label position. The osm id,
+/// the priority and the elimination time t are stored as extended data.
+///
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::output;
+/// use rt_datastructure::primitives::label::Label;
+///
+/// let l = Label::new(8.75, 53.125, 3.5, 62559, 1, 11., "Bremen & <Umland>".to_string());
+///
+/// let mut buf: Vec<u8> = Vec::new();
+/// output::write_kml(&mut buf, &[&l]).unwrap();
+/// let kml = String::from_utf8(buf).unwrap();
+///
+/// assert!(kml.contains("<name>Bremen &amp; &lt;Umland&gt;</name>"));
+/// assert!(kml.contains("<Data name=\"t\"><value>3.5</value></Data>"));
+/// assert!(kml.contains("<coordinates>8.75,53.125</coordinates>"));
+/// ```
+///
+/// # Errors
+///   * Io: if the writer fails
+///
+pub fn write_kml<W: io::Write>(w: &mut W, labels: &[&Label]) -> Result<(), Error> {
+    writeln!(w, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(w, "<kml xmlns=\"http://www.opengis.net/kml/2.2\">")?;
+    writeln!(w, "<Document>")?;
+    for l in labels {
+        writeln!(w, "<Placemark>")?;
+        writeln!(w, "<name>{}</name>", escape_xml(l.get_label()))?;
+        writeln!(w, "<ExtendedData>")?;
+        writeln!(w, "<Data name=\"osm_id\"><value>{}</value></Data>", l.get_osm_id())?;
+        writeln!(w, "<Data name=\"prio\"><value>{}</value></Data>", l.get_prio())?;
+        writeln!(w, "<Data name=\"t\"><value>{}</value></Data>", l.get_t())?;
+        writeln!(w, "</ExtendedData>")?;
+        writeln!(w,
+                 "<Point><coordinates>{},{}</coordinates></Point>",
+                 l.get_x(),
+                 l.get_y())?;
+        writeln!(w, "</Placemark>")?;
+    }
+    writeln!(w, "</Document>")?;
+    writeln!(w, "</kml>")?;
+
+    Ok(())
+}
+
+///
+/// Export the labels into the KML file at 'path', compare `write_kml`.
+///
+/// # Errors
+///   * Io: if the file could not be written
+///
+pub fn export_kml(path: &String, labels: &[&Label]) -> Result<(), Error> {
+    let mut w = io::BufWriter::new(File::create(path)?);
+    write_kml(&mut w, labels)?;
+    io::Write::flush(&mut w)?;
+
+    Ok(())
+}
+
+///
+/// Export the labels into the file at 'path' in the binary label file format read by
+/// [import_binary](../input/fn.import_binary.html).
+///
+/// # Errors
+///   * Io: if the file could not be written
+///   * Serialization: if there are more than 2^32 - 1 distinct label strings
+///
+pub fn export_binary(path: &String, labels: &[&Label]) -> Result<(), Error> {
+    let mut w = io::BufWriter::new(File::create(path)?);
+    write_binary(&mut w, labels)?;
+    io::Write::flush(&mut w)?;
+
+    Ok(())
+}
+
+///
+/// Get the index of the string in the string table, the string is appended if it is new.
+///
+fn string_index<'a>(s: &'a str,
+                    indices: &mut HashMap<&'a str, u32>,
+                    strings: &mut Vec<&'a str>)
+                    -> Result<u32, Error> {
+    if let Some(idx) = indices.get(s) {
+        return Ok(*idx);
+    }
+    if strings.len() >= u32::MAX as usize {
+        return Err(Error::Serialization("Too many distinct label strings".to_string()));
+    }
+
+    let idx = strings.len() as u32;
+    indices.insert(s, idx);
+    strings.push(s);
+    Ok(idx)
+}
+
+///
+/// Format the tags of the label as json property, or an empty string if the label has no tags.
+///
+fn tags(l: &Label) -> String {
+    if l.get_tags().is_empty() {
+        return String::new();
+    }
+
+    let entries: Vec<String> = l.get_tags()
+        .iter()
+        .map(|t| format!("\"{}\":\"{}\"", escape(&t.0), escape(&t.1)))
+        .collect();
+    format!(",\"tags\":{{{}}}", entries.join(","))
+}
+
+///
+/// Format the geometry of the label as json property, or an empty string if the label has none.
+/// The coordinates are written with the given precision.
+///
+fn anchor_geometry(l: &Label, precision: CoordinatePrecision) -> String {
+    let positions = |points: &Vec<Point>| -> Vec<String> {
+        points.iter()
+            .map(|p| {
+                format!("[{},{}]",
+                        number(precision.apply(p.get_x())),
+                        number(precision.apply(p.get_y())))
+            })
+            .collect()
+    };
+
+    let (kind, coordinates) = match l.get_geometry() {
+        Some(Geometry::Polyline(points)) => {
+            ("LineString", format!("[{}]", positions(points).join(",")))
+        }
+        Some(Geometry::Area(points)) => {
+            // GeoJSON rings are closed explicitly
+            let mut ring = positions(points);
+            if let Some(first) = ring.first().cloned() {
+                ring.push(first);
+            }
+            ("Polygon", format!("[[{}]]", ring.join(",")))
+        }
+        None => return String::new(),
+    };
+
+    format!(",\"anchor_geometry\":{{\"type\":\"{}\",\"coordinates\":{}}}",
+            kind,
+            coordinates)
+}
+
+///
+/// Format a number as json value. Json has no representation of NaN and infinite values, they
+/// are written as null.
+///
+fn number(v: f64) -> String {
+    if v.is_finite() {
+        v.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+///
+/// Escape a string for the use as xml text or attribute value.
+///
+fn escape_xml(s: &str) -> String {
+    let mut r = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => r.push_str("&amp;"),
+            '<' => r.push_str("&lt;"),
+            '>' => r.push_str("&gt;"),
+            '"' => r.push_str("&quot;"),
+            '\'' => r.push_str("&apos;"),
+            c => r.push(c),
+        }
+    }
+
+    r
+}
+
+///
+/// Escape a string for the use in a json string literal.
+///
+fn escape(s: &str) -> String {
+    let mut r = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => r.push_str("\\\""),
+            '\\' => r.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(r, "\\u{:04x}", c as u32);
+            }
+            c => r.push(c),
+        }
+    }
+
+    r
+}
@@ -0,0 +1,192 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::io::{Read, Write};
+
+use error::Error;
+
+///
+/// Write a single byte.
+///
+pub fn write_u8<W: Write>(w: &mut W, v: u8) -> Result<(), Error> {
+    w.write_all(&[v])?;
+    Ok(())
+}
+
+///
+/// Write an unsigned 32 bit integer in little endian byte order.
+///
+pub fn write_u32<W: Write>(w: &mut W, v: u32) -> Result<(), Error> {
+    w.write_all(&v.to_le_bytes())?;
+    Ok(())
+}
+
+///
+/// Write an unsigned 64 bit integer in little endian byte order.
+///
+pub fn write_u64<W: Write>(w: &mut W, v: u64) -> Result<(), Error> {
+    w.write_all(&v.to_le_bytes())?;
+    Ok(())
+}
+
+///
+/// Write a 64 bit floating point number in little endian byte order.
+///
+pub fn write_f64<W: Write>(w: &mut W, v: f64) -> Result<(), Error> {
+    write_u64(w, v.to_bits())
+}
+
+///
+/// Write an unsigned integer as LEB128 variable length integer. Small values take less bytes.
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::pack::codec;
+///
+/// let mut buf: Vec<u8> = Vec::new();
+/// codec::write_varint(&mut buf, 300).unwrap();
+/// assert!(buf == vec![0xac, 0x02]);
+/// assert!(codec::read_varint(&mut buf.as_slice()).unwrap() == 300);
+/// ```
+///
+pub fn write_varint<W: Write>(w: &mut W, mut v: u64) -> Result<(), Error> {
+    loop {
+        let byte = (v & 0x7f) as u8;
+        v >>= 7;
+        if v == 0 {
+            return write_u8(w, byte);
+        }
+        write_u8(w, byte | 0x80)?;
+    }
+}
+
+///
+/// Write a signed integer as zig zag encoded variable length integer. Values close to 0 take
+/// less bytes.
+///
+pub fn write_zigzag<W: Write>(w: &mut W, v: i64) -> Result<(), Error> {
+    write_varint(w, ((v << 1) ^ (v >> 63)) as u64)
+}
+
+///
+/// Write a string as its length followed by its utf-8 bytes.
+///
+pub fn write_string<W: Write>(w: &mut W, s: &str) -> Result<(), Error> {
+    write_varint(w, s.len() as u64)?;
+    w.write_all(s.as_bytes())?;
+    Ok(())
+}
+
+///
+/// Read a single byte.
+///
+pub fn read_u8<R: Read>(r: &mut R) -> Result<u8, Error> {
+    let mut buf = [0u8; 1];
+    r.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+///
+/// Read an unsigned 32 bit integer in little endian byte order.
+///
+pub fn read_u32<R: Read>(r: &mut R) -> Result<u32, Error> {
+    let mut buf = [0u8; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+///
+/// Read an unsigned 64 bit integer in little endian byte order.
+///
+pub fn read_u64<R: Read>(r: &mut R) -> Result<u64, Error> {
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+///
+/// Read a 64 bit floating point number in little endian byte order.
+///
+pub fn read_f64<R: Read>(r: &mut R) -> Result<f64, Error> {
+    Ok(f64::from_bits(read_u64(r)?))
+}
+
+///
+/// Read a LEB128 variable length integer.
+///
+/// # Errors
+///   * Serialization: if the integer does not fit into 64 bits
+///
+pub fn read_varint<R: Read>(r: &mut R) -> Result<u64, Error> {
+    let mut v: u64 = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = read_u8(r)?;
+        v |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(v);
+        }
+    }
+
+    Err(Error::Serialization("Variable length integer exceeds 64 bits".to_string()))
+}
+
+///
+/// Read a zig zag encoded variable length integer.
+///
+pub fn read_zigzag<R: Read>(r: &mut R) -> Result<i64, Error> {
+    let v = read_varint(r)?;
+    Ok(((v >> 1) as i64) ^ -((v & 1) as i64))
+}
+
+///
+/// Read a string written by `write_string`.
+///
+/// # Errors
+///   * Io: if the input ends before the end of the string
+///   * Serialization: if the string is not valid utf-8
+///
+pub fn read_string<R: Read>(r: &mut R) -> Result<String, Error> {
+    let len = read_varint(r)?;
+
+    // read via take, a corrupted length must not allocate huge buffers upfront
+    let mut buf: Vec<u8> = Vec::new();
+    r.take(len).read_to_end(&mut buf)?;
+    if buf.len() as u64 != len {
+        return Err(Error::Serialization("Unexpected end of string".to_string()));
+    }
+
+    String::from_utf8(buf).map_err(|e| Error::Serialization(format!("Invalid string: {}", e)))
+}
+
+///
+/// Read a count of elements and check that it does not exceed max. Corrupted counts must not
+/// lead to huge allocations.
+///
+pub fn read_count<R: Read>(r: &mut R, max: u64) -> Result<usize, Error> {
+    let count = read_varint(r)?;
+    if count > max {
+        return Err(Error::Serialization(format!("Element count {} exceeds the limit {}",
+                                                count,
+                                                max)));
+    }
+
+    Ok(count as usize)
+}
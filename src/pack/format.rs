@@ -0,0 +1,725 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+
+use error::Error;
+use pack::codec;
+use primitives::geometry::Geometry;
+use primitives::crs::Crs;
+use primitives::label::Label;
+use primitives::point::Point;
+use pst_3d::{GeoPst3d, Pst3d};
+
+///
+/// The magic bytes every label pack starts with.
+///
+const MAGIC: &[u8; 8] = b"RTLPACK\0";
+
+///
+/// The version of the label pack format written by this implementation.
+///
+const VERSION: u32 = 1;
+
+///
+/// The maximum number of strings, labels or entries of a section. Corrupted counts must not lead
+/// to huge allocations.
+///
+const MAX_COUNT: u64 = u32::MAX as u64;
+
+///
+/// A section of a label pack consisting of its tag and payload.
+///
+type Section = ([u8; 4], Vec<u8>);
+
+///
+/// A label pack: a queryable dataset in a single file.
+///
+/// A pack contains metadata as key value pairs, the labels, the serialized 3D PST and optionally
+/// a name index. Loading a pack does not construct the tree again. The coordinate reference
+/// system of the tree is restored on loading, trees with a custom reference system cannot be
+/// packed.
+///
+/// # Format
+///
+/// A label pack file consists of a header followed by a sequence of sections:
+///
+/// ```text
+/// magic     8 bytes   "RTLPACK\0"
+/// version   u32       1
+/// sections  each section consists of
+///             tag      4 bytes
+///             length   u64       the number of payload bytes
+///             payload  length bytes
+/// ```
+///
+/// Integers are little endian, `varint` is a LEB128 variable length integer, `zigzag` a zig zag
+/// encoded varint and `string` a varint length followed by utf-8 bytes. Readers skip sections
+/// with unknown tags. The sections are:
+///
+/// ```text
+/// META  metadata: varint count, count x (string key, string value)
+/// CRS   optional coordinate reference system of the tree if it is not WGS84:
+///         u8 kind (1: Web Mercator)
+/// LBLS  deflate compressed labels in tree order:
+///         varint count, count x string          (the string table)
+///         varint count, count x label with
+///           f64 x, f64 y, f64 t, zigzag osm_id, zigzag prio, f64 lbl_fac,
+///           varint label string, varint short label string + 1 (0: none),
+///           varint count, count x (varint key string, varint name string)
+///       where strings are indices into the string table
+/// LENS  optional label lengths of the labels with a label length other than 0:
+///         varint count, count x (varint label index, f64 label length)
+/// CATS  optional categories of the labels with a category other than 0:
+///         varint count, count x (varint label index, varint category)
+/// TAGS  optional deflate compressed tags of the labels with at least one tag:
+///         varint count, count x (varint label index, varint count, count x
+///           (string key, string value))
+/// GEOM  optional deflate compressed geometries of the labels of line and area features:
+///         varint count, count x (varint label index, u8 kind (0: polyline, 1: area),
+///           varint count, count x (f64 x, f64 y))
+/// TREE  deflate compressed tree structure, compare Pst3d::write_tree
+/// NAME  optional deflate compressed name index sorted by name:
+///         varint count, count x (string name, varint label index)
+/// ```
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::pack::LabelPack;
+/// use rt_datastructure::primitives::{label, bbox};
+/// use rt_datastructure::primitives::geometry::Geometry;
+/// use rt_datastructure::primitives::point::Point;
+/// use rt_datastructure::pst_3d;
+///
+/// let mut v = Vec::new();
+/// v.push(label::Label::new(8.8, 53.1, 10., 1, 1, 1.5, "Bremen".to_string()));
+/// v.push(label::Label::new(8.6, 53.5, 9., 2, 1, 1.5, "Bremerhaven".to_string()));
+/// v[0].set_tag("wikidata".to_string(), "Q24879".to_string());
+/// v[1].set_category(2);
+/// v[1].set_label_length(3300.);
+/// v[1].set_geometry(Some(Geometry::Polyline(vec![Point::new(8.5, 53.5), Point::new(8.7, 53.5)])));
+///
+/// let mut p = LabelPack::new(pst_3d::GeoPst3d::new(v));
+/// p.set_metadata("source".to_string(), "example".to_string());
+/// p.build_name_index();
+///
+/// let mut buf: Vec<u8> = Vec::new();
+/// p.write(&mut buf).unwrap();
+///
+/// let loaded = LabelPack::read(&mut buf.as_slice()).unwrap();
+/// assert!(*loaded.get_metadata("source").unwrap() == "example".to_string());
+/// assert!(loaded.find_by_name("Bremerhaven").unwrap()[0].get_osm_id() == 2);
+/// assert!(loaded.find_by_name("Bremerhaven").unwrap()[0].get_category() == 2);
+/// assert!(loaded.find_by_name("Bremerhaven").unwrap()[0].get_label_length() == 3300.);
+/// assert!(loaded.find_by_name("Bremerhaven").unwrap()[0].get_geometry().is_some());
+///
+/// let bb = bbox::BBox::new(8., 53., 9., 54.);
+/// let r = loaded.get_tree().get(&bb, 9.5);
+/// assert!(r.len() == 1);
+/// assert!(*r[0].get_tag("wikidata").unwrap() == "Q24879".to_string());
+/// ```
+///
+pub struct LabelPack {
+    m_metadata: Vec<(String, String)>,
+    m_tree: GeoPst3d,
+    m_name_index: Option<Vec<(String, u32)>>,
+}
+
+impl LabelPack {
+    ///
+    /// Initialize a new pack of the given tree without metadata and name index.
+    ///
+    pub fn new(tree: GeoPst3d) -> LabelPack {
+        LabelPack {
+            m_metadata: Vec::new(),
+            m_tree: tree,
+            m_name_index: None,
+        }
+    }
+
+    ///
+    /// Set the metadata value for the given key. An existing value for the key is replaced.
+    ///
+    pub fn set_metadata(&mut self, key: String, value: String) {
+        match self.m_metadata.iter_mut().find(|m| m.0 == key) {
+            Some(m) => m.1 = value,
+            None => self.m_metadata.push((key, value)),
+        }
+    }
+
+    ///
+    /// Get the metadata value for the given key.
+    ///
+    pub fn get_metadata(&self, key: &str) -> Option<&String> {
+        self.m_metadata.iter().find(|m| m.0 == key).map(|m| &m.1)
+    }
+
+    ///
+    /// Get all metadata as (key, value) pairs.
+    ///
+    pub fn get_all_metadata(&self) -> &Vec<(String, String)> {
+        &self.m_metadata
+    }
+
+    ///
+    /// Build the name index of the pack. It contains the label strings and the names in further
+    /// languages of all labels.
+    ///
+    pub fn build_name_index(&mut self) {
+        let pst = self.m_tree.get_pst();
+        let mut index: Vec<(String, u32)> = Vec::new();
+        for idx in 0..pst.get_size() as u32 {
+            if let Some(l) = pst.get_label(idx) {
+                index.push((l.get_label().clone(), idx));
+                for n in l.get_names() {
+                    index.push((n.1.clone(), idx));
+                }
+            }
+        }
+        index.sort();
+        index.dedup();
+
+        self.m_name_index = Some(index);
+    }
+
+    ///
+    /// Find the labels with the given label string or name in a further language.
+    ///
+    /// Returns None if the pack has no name index.
+    ///
+    pub fn find_by_name(&self, name: &str) -> Option<Vec<&Label>> {
+        let index = match self.m_name_index {
+            Some(ref index) => index,
+            None => return None,
+        };
+
+        let start = index.partition_point(|e| e.0.as_str() < name);
+        let labels = index[start..]
+            .iter()
+            .take_while(|e| e.0 == name)
+            .filter_map(|e| self.m_tree.get_label(e.1))
+            .collect();
+
+        Some(labels)
+    }
+
+    ///
+    /// Get the tree of the pack.
+    ///
+    pub fn get_tree(&self) -> &GeoPst3d {
+        &self.m_tree
+    }
+
+    ///
+    /// Consume the pack and return its tree.
+    ///
+    pub fn into_tree(self) -> GeoPst3d {
+        self.m_tree
+    }
+
+    ///
+    /// Write the pack in the label pack format.
+    ///
+    /// # Errors
+    ///   * Io: if the writer fails
+    ///   * Serialization: if the tree has a custom coordinate reference system
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::pack::LabelPack;
+    /// use rt_datastructure::primitives::crs::Crs;
+    /// use rt_datastructure::primitives::label;
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let v = vec![label::Label::new(981000., 7008000., 10., 1, 1, 1.5, "Bremen".to_string())];
+    /// let p = LabelPack::new(pst_3d::GeoPst3d::new_with_crs(v, Crs::WebMercator));
+    ///
+    /// let mut buf: Vec<u8> = Vec::new();
+    /// p.write(&mut buf).unwrap();
+    ///
+    /// let loaded = LabelPack::read(&mut buf.as_slice()).unwrap();
+    /// match *loaded.get_tree().get_crs() {
+    ///     Crs::WebMercator => {}
+    ///     _ => panic!("the reference system has not been restored"),
+    /// }
+    /// ```
+    ///
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), Error> {
+        // validated before anything is written
+        let crs = match *self.m_tree.get_crs() {
+            Crs::Wgs84 => None,
+            Crs::WebMercator => Some(1u8),
+            Crs::Custom { .. } => {
+                return Err(Error::Serialization("Trees with a custom coordinate reference \
+                                                 system cannot be packed"
+                    .to_string()));
+            }
+        };
+
+        w.write_all(MAGIC)?;
+        codec::write_u32(w, VERSION)?;
+
+        let mut meta: Vec<u8> = Vec::new();
+        codec::write_varint(&mut meta, self.m_metadata.len() as u64)?;
+        for (key, value) in &self.m_metadata {
+            codec::write_string(&mut meta, key)?;
+            codec::write_string(&mut meta, value)?;
+        }
+        write_section(w, b"META", &meta)?;
+        if let Some(kind) = crs {
+            write_section(w, b"CRS ", &[kind])?;
+        }
+
+        let pst = self.m_tree.get_pst();
+        let labels: Vec<&Label> = (0..pst.get_size() as u32)
+            .filter_map(|idx| pst.get_label(idx))
+            .collect();
+        write_section(w, b"LBLS", &compress(|e| write_labels(e, &labels))?)?;
+        if labels.iter().any(|l| l.get_label_length() != 0.) {
+            write_section(w, b"LENS", &write_label_lengths(&labels)?)?;
+        }
+        if labels.iter().any(|l| l.get_category() != 0) {
+            write_section(w, b"CATS", &write_categories(&labels)?)?;
+        }
+        if labels.iter().any(|l| !l.get_tags().is_empty()) {
+            write_section(w, b"TAGS", &compress(|e| write_tags(e, &labels))?)?;
+        }
+        if labels.iter().any(|l| l.get_geometry().is_some()) {
+            write_section(w, b"GEOM", &compress(|e| write_geometries(e, &labels))?)?;
+        }
+        write_section(w, b"TREE", &compress(|e| pst.write_tree(e))?)?;
+
+        if let Some(ref index) = self.m_name_index {
+            let name = compress(|e| {
+                codec::write_varint(e, index.len() as u64)?;
+                for &(ref name, idx) in index {
+                    codec::write_string(e, name)?;
+                    codec::write_varint(e, u64::from(idx))?;
+                }
+                Ok(())
+            })?;
+            write_section(w, b"NAME", &name)?;
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Read a pack in the label pack format.
+    ///
+    /// # Errors
+    ///   * Io: if the reader fails
+    ///   * Serialization: if the data is not a valid label pack of a supported version
+    ///   * Validation: if a label of the pack has invalid coordinates
+    ///
+    pub fn read<R: Read>(r: &mut R) -> Result<LabelPack, Error> {
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(Error::Serialization("Not a label pack".to_string()));
+        }
+        let version = codec::read_u32(r)?;
+        if version != VERSION {
+            return Err(Error::Serialization(format!("Unsupported label pack version {}",
+                                                    version)));
+        }
+
+        let mut sections: HashMap<[u8; 4], Vec<u8>> = HashMap::new();
+        while let Some((tag, payload)) = read_section(r)? {
+            sections.insert(tag, payload);
+        }
+        let section = |tag: &[u8; 4]| {
+            sections.get(tag).ok_or_else(|| {
+                Error::Serialization(format!("Missing section {}", String::from_utf8_lossy(tag)))
+            })
+        };
+
+        let mut metadata: Vec<(String, String)> = Vec::new();
+        if let Some(meta) = sections.get(b"META") {
+            let mut m = meta.as_slice();
+            for _ in 0..codec::read_count(&mut m, MAX_COUNT)? {
+                metadata.push((codec::read_string(&mut m)?, codec::read_string(&mut m)?));
+            }
+        }
+
+        let crs = match sections.get(b"CRS ").map(|c| c.as_slice()) {
+            None => Crs::Wgs84,
+            Some([1]) => Crs::WebMercator,
+            Some(_) => {
+                return Err(Error::Serialization("Unknown coordinate reference system".to_string()))
+            }
+        };
+
+        let mut labels = read_labels(&mut DeflateDecoder::new(section(b"LBLS")?.as_slice()))?;
+        if let Some(lengths) = sections.get(b"LENS") {
+            read_label_lengths(&mut lengths.as_slice(), &mut labels)?;
+        }
+        if let Some(categories) = sections.get(b"CATS") {
+            read_categories(&mut categories.as_slice(), &mut labels)?;
+        }
+        if let Some(tags) = sections.get(b"TAGS") {
+            read_tags(&mut DeflateDecoder::new(tags.as_slice()), &mut labels)?;
+        }
+        if let Some(geometries) = sections.get(b"GEOM") {
+            read_geometries(&mut DeflateDecoder::new(geometries.as_slice()), &mut labels)?;
+        }
+        let pst = Pst3d::read_tree(&mut DeflateDecoder::new(section(b"TREE")?.as_slice()),
+                                   labels)?;
+
+        let mut name_index = None;
+        if let Some(name) = sections.get(b"NAME") {
+            let mut d = DeflateDecoder::new(name.as_slice());
+            let mut index: Vec<(String, u32)> = Vec::new();
+            for _ in 0..codec::read_count(&mut d, MAX_COUNT)? {
+                let name = codec::read_string(&mut d)?;
+                let idx = codec::read_varint(&mut d)?;
+                if idx >= pst.get_size() as u64 {
+                    return Err(Error::Serialization(format!("Invalid label index {}", idx)));
+                }
+                index.push((name, idx as u32));
+            }
+            name_index = Some(index);
+        }
+
+        Ok(LabelPack {
+               m_metadata: metadata,
+               m_tree: GeoPst3d::try_from_pst_with_crs(pst, crs)?,
+               m_name_index: name_index,
+           })
+    }
+
+    ///
+    /// Write the pack to the file at 'path'.
+    ///
+    pub fn save(&self, path: &String) -> Result<(), Error> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write(&mut w)?;
+        w.flush()?;
+        Ok(())
+    }
+
+    ///
+    /// Read the pack from the file at 'path'.
+    ///
+    pub fn open(path: &String) -> Result<LabelPack, Error> {
+        LabelPack::read(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+///
+/// Load the queryable tree of the label pack at 'path' in a single call.
+///
+/// # Errors
+///   * Io: if the file path does not match any file in the file system
+///   * Serialization: if the file is not a valid label pack
+///   * Validation: if a label of the pack has invalid coordinates
+///
+pub fn load(path: &String) -> Result<GeoPst3d, Error> {
+    Ok(LabelPack::open(path)?.into_tree())
+}
+
+// Write a section with the given tag and payload.
+fn write_section<W: Write>(w: &mut W, tag: &[u8; 4], payload: &[u8]) -> Result<(), Error> {
+    w.write_all(tag)?;
+    codec::write_u64(w, payload.len() as u64)?;
+    w.write_all(payload)?;
+    Ok(())
+}
+
+// Read the next section or None at the end of the input.
+fn read_section<R: Read>(r: &mut R) -> Result<Option<Section>, Error> {
+    let mut tag = [0u8; 4];
+    let mut filled = 0;
+    while filled < tag.len() {
+        match r.read(&mut tag[filled..])? {
+            0 if filled == 0 => return Ok(None),
+            0 => return Err(Error::Serialization("Truncated section header".to_string())),
+            n => filled += n,
+        }
+    }
+
+    let len = codec::read_u64(r)?;
+    let mut payload: Vec<u8> = Vec::new();
+    r.take(len).read_to_end(&mut payload)?;
+    if payload.len() as u64 != len {
+        return Err(Error::Serialization("Truncated section".to_string()));
+    }
+
+    Ok(Some((tag, payload)))
+}
+
+// Compress the data written by the given function.
+fn compress<F>(write: F) -> Result<Vec<u8>, Error>
+    where F: FnOnce(&mut DeflateEncoder<Vec<u8>>) -> Result<(), Error>
+{
+    let mut e = DeflateEncoder::new(Vec::new(), Compression::default());
+    write(&mut e)?;
+    Ok(e.finish()?)
+}
+
+// Write the labels with a shared string table.
+fn write_labels<W: Write>(w: &mut W, labels: &[&Label]) -> Result<(), Error> {
+    let mut strings: Vec<&str> = Vec::new();
+    let mut string_idx: HashMap<&str, u64> = HashMap::new();
+    for l in labels {
+        let mut used: Vec<&str> = vec![l.get_label()];
+        used.extend(l.get_short_label().map(|s| s.as_str()));
+        for n in l.get_names() {
+            used.push(&n.0);
+            used.push(&n.1);
+        }
+
+        for s in used {
+            let next = strings.len() as u64;
+            if *string_idx.entry(s).or_insert(next) == next {
+                strings.push(s);
+            }
+        }
+    }
+
+    codec::write_varint(w, strings.len() as u64)?;
+    for s in &strings {
+        codec::write_string(w, s)?;
+    }
+
+    let idx = |s: &str| string_idx.get(s).cloned().unwrap_or(0);
+    codec::write_varint(w, labels.len() as u64)?;
+    for l in labels {
+        codec::write_f64(w, l.get_x())?;
+        codec::write_f64(w, l.get_y())?;
+        codec::write_f64(w, l.get_t())?;
+        codec::write_zigzag(w, l.get_osm_id())?;
+        codec::write_zigzag(w, i64::from(l.get_prio()))?;
+        codec::write_f64(w, l.get_label_factor())?;
+        codec::write_varint(w, idx(l.get_label()))?;
+        codec::write_varint(w, l.get_short_label().map_or(0, |s| idx(s) + 1))?;
+        codec::write_varint(w, l.get_names().len() as u64)?;
+        for n in l.get_names() {
+            codec::write_varint(w, idx(&n.0))?;
+            codec::write_varint(w, idx(&n.1))?;
+        }
+    }
+
+    Ok(())
+}
+
+// Write the label lengths of the labels with a label length other than 0.
+fn write_label_lengths(labels: &[&Label]) -> Result<Vec<u8>, Error> {
+    let measured: Vec<(usize, &&Label)> = labels.iter()
+        .enumerate()
+        .filter(|&(_, l)| l.get_label_length() != 0.)
+        .collect();
+
+    let mut w: Vec<u8> = Vec::new();
+    codec::write_varint(&mut w, measured.len() as u64)?;
+    for (idx, l) in measured {
+        codec::write_varint(&mut w, idx as u64)?;
+        codec::write_f64(&mut w, l.get_label_length())?;
+    }
+
+    Ok(w)
+}
+
+// Read the label lengths written by write_label_lengths into the given labels.
+fn read_label_lengths<R: Read>(r: &mut R, labels: &mut [Label]) -> Result<(), Error> {
+    for _ in 0..codec::read_count(r, MAX_COUNT)? {
+        let idx = codec::read_varint(r)?;
+        let label_length = codec::read_f64(r)?;
+        match labels.get_mut(idx as usize) {
+            Some(l) => l.set_label_length(label_length),
+            None => return Err(Error::Serialization(format!("Invalid label index {}", idx))),
+        }
+    }
+
+    Ok(())
+}
+
+// Write the categories of the labels with a category other than 0.
+fn write_categories(labels: &[&Label]) -> Result<Vec<u8>, Error> {
+    let categorized: Vec<(usize, &&Label)> = labels.iter()
+        .enumerate()
+        .filter(|&(_, l)| l.get_category() != 0)
+        .collect();
+
+    let mut w: Vec<u8> = Vec::new();
+    codec::write_varint(&mut w, categorized.len() as u64)?;
+    for (idx, l) in categorized {
+        codec::write_varint(&mut w, idx as u64)?;
+        codec::write_varint(&mut w, u64::from(l.get_category()))?;
+    }
+
+    Ok(w)
+}
+
+// Read the categories written by write_categories into the given labels.
+fn read_categories<R: Read>(r: &mut R, labels: &mut [Label]) -> Result<(), Error> {
+    for _ in 0..codec::read_count(r, MAX_COUNT)? {
+        let idx = codec::read_varint(r)?;
+        let category = codec::read_varint(r)?;
+        match labels.get_mut(idx as usize) {
+            Some(l) if category <= u64::from(u16::MAX) => l.set_category(category as u16),
+            _ => {
+                return Err(Error::Serialization(format!("Invalid category {} of label {}",
+                                                        category,
+                                                        idx)))
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Write the tags of the labels with at least one tag.
+fn write_tags<W: Write>(w: &mut W, labels: &[&Label]) -> Result<(), Error> {
+    let tagged: Vec<(usize, &&Label)> = labels.iter()
+        .enumerate()
+        .filter(|&(_, l)| !l.get_tags().is_empty())
+        .collect();
+
+    codec::write_varint(w, tagged.len() as u64)?;
+    for (idx, l) in tagged {
+        codec::write_varint(w, idx as u64)?;
+        codec::write_varint(w, l.get_tags().len() as u64)?;
+        for t in l.get_tags() {
+            codec::write_string(w, &t.0)?;
+            codec::write_string(w, &t.1)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Read the tags written by write_tags into the given labels.
+fn read_tags<R: Read>(r: &mut R, labels: &mut [Label]) -> Result<(), Error> {
+    for _ in 0..codec::read_count(r, MAX_COUNT)? {
+        let idx = codec::read_varint(r)?;
+        let count = codec::read_count(r, MAX_COUNT)?;
+        let l = match labels.get_mut(idx as usize) {
+            Some(l) => l,
+            None => return Err(Error::Serialization(format!("Invalid label index {}", idx))),
+        };
+        for _ in 0..count {
+            let key = codec::read_string(r)?;
+            let value = codec::read_string(r)?;
+            l.set_tag(key, value);
+        }
+    }
+
+    Ok(())
+}
+
+// Write the geometries of the labels of line and area features.
+fn write_geometries<W: Write>(w: &mut W, labels: &[&Label]) -> Result<(), Error> {
+    let anchored: Vec<(usize, &Geometry)> = labels.iter()
+        .enumerate()
+        .filter_map(|(idx, l)| l.get_geometry().map(|g| (idx, g)))
+        .collect();
+
+    codec::write_varint(w, anchored.len() as u64)?;
+    for (idx, g) in anchored {
+        codec::write_varint(w, idx as u64)?;
+        codec::write_u8(w, match *g {
+            Geometry::Polyline(_) => 0,
+            Geometry::Area(_) => 1,
+        })?;
+        codec::write_varint(w, g.get_points().len() as u64)?;
+        for p in g.get_points() {
+            codec::write_f64(w, p.get_x())?;
+            codec::write_f64(w, p.get_y())?;
+        }
+    }
+
+    Ok(())
+}
+
+// Read the geometries written by write_geometries into the given labels.
+fn read_geometries<R: Read>(r: &mut R, labels: &mut [Label]) -> Result<(), Error> {
+    for _ in 0..codec::read_count(r, MAX_COUNT)? {
+        let idx = codec::read_varint(r)?;
+        let kind = codec::read_u8(r)?;
+        let mut points: Vec<Point> = Vec::new();
+        for _ in 0..codec::read_count(r, MAX_COUNT)? {
+            let x = codec::read_f64(r)?;
+            points.push(Point::new(x, codec::read_f64(r)?));
+        }
+
+        let geometry = match kind {
+            0 => Geometry::Polyline(points),
+            1 => Geometry::Area(points),
+            _ => return Err(Error::Serialization(format!("Invalid geometry kind {}", kind))),
+        };
+        match labels.get_mut(idx as usize) {
+            Some(l) => l.set_geometry(Some(geometry)),
+            None => return Err(Error::Serialization(format!("Invalid label index {}", idx))),
+        }
+    }
+
+    Ok(())
+}
+
+// Read the labels written by write_labels.
+fn read_labels<R: Read>(r: &mut R) -> Result<Vec<Label>, Error> {
+    let mut strings: Vec<String> = Vec::new();
+    for _ in 0..codec::read_count(r, MAX_COUNT)? {
+        strings.push(codec::read_string(r)?);
+    }
+
+    let string = |r: &mut R, offset: u64| -> Result<Option<String>, Error> {
+        let idx = codec::read_varint(r)?;
+        if idx < offset {
+            return Ok(None);
+        }
+        match strings.get((idx - offset) as usize) {
+            Some(s) => Ok(Some(s.clone())),
+            None => Err(Error::Serialization(format!("Invalid string index {}", idx))),
+        }
+    };
+
+    let mut labels: Vec<Label> = Vec::new();
+    for _ in 0..codec::read_count(r, MAX_COUNT)? {
+        let x = codec::read_f64(r)?;
+        let y = codec::read_f64(r)?;
+        let t = codec::read_f64(r)?;
+        let osm_id = codec::read_zigzag(r)?;
+        let prio = codec::read_zigzag(r)? as i32;
+        let lbl_fac = codec::read_f64(r)?;
+        let label = string(r, 0)?.unwrap_or_default();
+
+        let mut l = Label::new(x, y, t, osm_id, prio, lbl_fac, label);
+        l.set_short_label(string(r, 1)?);
+        for _ in 0..codec::read_count(r, MAX_COUNT)? {
+            let key = string(r, 0)?.unwrap_or_default();
+            let name = string(r, 0)?.unwrap_or_default();
+            l.set_name(key, name);
+        }
+
+        labels.push(l);
+    }
+
+    Ok(labels)
+}
@@ -0,0 +1,38 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+///
+/// A module providing the binary encoding of primitive values used by label packs.
+///
+/// Integers are written in little endian byte order, counts and string lengths as variable
+/// length integers.
+///
+pub mod codec;
+
+///
+/// The label pack file format, compare LabelPack. The sections of a pack are deflate
+/// compressed, so reading and writing packs requires the `pack` feature.
+///
+#[cfg(feature = "pack")]
+mod format;
+
+#[cfg(feature = "pack")]
+pub use self::format::{LabelPack, load};
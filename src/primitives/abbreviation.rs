@@ -0,0 +1,102 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use primitives::label::Label;
+
+///
+/// A hook to select the text that is displayed for a label at a given query min_t.
+///
+/// A larger min_t corresponds to a smaller zoom level, i.e. less space is available for the
+/// label text. Implementations may return an abbreviated version of the label string. If None is
+/// returned the full label string is used.
+///
+pub trait Abbreviation {
+    ///
+    /// Get the abbreviated text for the label l at the query min_t or None if the full label
+    /// string should be used.
+    ///
+    fn abbreviate(&self, l: &Label, min_t: f64) -> Option<String>;
+}
+
+///
+/// Select the short label of a label if the query min_t reaches a given threshold.
+///
+pub struct ShortLabelThreshold {
+    m_min_t: f64,
+}
+
+impl ShortLabelThreshold {
+    ///
+    /// Initialize a new threshold abbreviation. The short label is used for queries with a
+    /// min_t >= the given threshold.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    /// use rt_datastructure::primitives::abbreviation::{self, Abbreviation};
+    ///
+    /// let mut l = label::Label::new(1., 2., 9., 1, 1, 1.5, "Sankt Petersburg".to_string());
+    /// l.set_short_label(Some("St. Pete".to_string()));
+    ///
+    /// let a = abbreviation::ShortLabelThreshold::new(5.);
+    /// assert!(a.abbreviate(&l, 4.).is_none());
+    /// assert!(a.abbreviate(&l, 5.) == Some("St. Pete".to_string()));
+    /// ```
+    ///
+    pub fn new(min_t: f64) -> ShortLabelThreshold {
+        ShortLabelThreshold { m_min_t: min_t }
+    }
+}
+
+impl Abbreviation for ShortLabelThreshold {
+    fn abbreviate(&self, l: &Label, min_t: f64) -> Option<String> {
+        if min_t >= self.m_min_t {
+            l.get_short_label().cloned()
+        } else {
+            None
+        }
+    }
+}
+
+///
+/// Get the text to display for the label l at the query min_t using the given abbreviation hook.
+///
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::primitives::label;
+/// use rt_datastructure::primitives::abbreviation;
+///
+/// let mut l = label::Label::new(1., 2., 9., 1, 1, 1.5, "Sankt Petersburg".to_string());
+/// l.set_short_label(Some("St. Pete".to_string()));
+///
+/// let a = abbreviation::ShortLabelThreshold::new(5.);
+/// assert!(abbreviation::display_label(&l, 1., &a) == "Sankt Petersburg".to_string());
+/// assert!(abbreviation::display_label(&l, 6., &a) == "St. Pete".to_string());
+/// ```
+///
+pub fn display_label<A: Abbreviation + ?Sized>(l: &Label, min_t: f64, abbrev: &A) -> String {
+    match abbrev.abbreviate(l, min_t) {
+        Some(s) => s,
+        None => l.get_label().clone(),
+    }
+}
@@ -20,14 +20,19 @@
 */
 
 use std::f64;
+use std::fmt;
+use std::iter::FromIterator;
 
-use primitives::label::Label;
+use error::Error;
+use primitives::point::Point;
+use primitives::traits::HasPosition;
 
 
 ///
 /// The struct defines an axis aligned rectangular area in 2 dimension via min and max in each
 /// dimension X and Y.
 ///
+#[derive(Clone, Debug)]
 pub struct BBox {
     m_max_x: f64,
     m_max_y: f64,
@@ -63,6 +68,34 @@ impl BBox {
         }
     }
 
+    ///
+    /// Initialize a new Bounding Box with the given values, if all of them are finite.
+    ///
+    /// # Errors
+    ///   * Validation: if a value is NaN or infinite
+    ///
+    /// # Examples
+    /// ```
+    /// use std::f64;
+    /// use rt_datastructure::primitives::bbox;
+    ///
+    /// assert!(bbox::BBox::try_new(0., 0., 1., 1.).is_ok());
+    /// assert!(bbox::BBox::try_new(0., f64::NAN, 1., 1.).is_err());
+    /// assert!(bbox::BBox::try_new(0., 0., f64::INFINITY, 1.).is_err());
+    /// ```
+    ///
+    pub fn try_new(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Result<BBox, Error> {
+        if ![min_x, min_y, max_x, max_y].iter().all(|v| v.is_finite()) {
+            return Err(Error::Validation(format!("Non finite bounding box ({}, {}) - ({}, {})",
+                                                 min_x,
+                                                 min_y,
+                                                 max_x,
+                                                 max_y)));
+        }
+
+        Ok(BBox::new(min_x, min_y, max_x, max_y))
+    }
+
     ///
     /// Initialize a new empty bounding box.
     ///
@@ -111,7 +144,7 @@ impl BBox {
     /// assert!(!bb.is_contained(&not_contained));
     /// ```
     ///
-    pub fn new_from_point(l: &Label) -> BBox {
+    pub fn new_from_point<P: HasPosition + ?Sized>(l: &P) -> BBox {
         BBox {
             m_max_x: l.get_x(),
             m_max_y: l.get_y(),
@@ -120,6 +153,42 @@ impl BBox {
         }
     }
 
+    ///
+    /// Initialize the smallest bounding box that spans all the given labels.
+    ///
+    /// If the iterator is empty, the result is an empty box (see `new_empty`). The same box is
+    /// obtained by collecting an iterator of label references into a `BBox`.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::{bbox, label};
+    ///
+    /// let labels = vec![label::Label::new(1., -2., 0., 0, 0, 1., "A".to_string()),
+    ///                   label::Label::new(-3., 4., 0., 1, 0, 1., "B".to_string())];
+    ///
+    /// let bb = bbox::BBox::from_labels(&labels);
+    /// assert!(bb.get_min_x() == -3.);
+    /// assert!(bb.get_min_y() == -2.);
+    /// assert!(bb.get_max_x() == 1.);
+    /// assert!(bb.get_max_y() == 4.);
+    ///
+    /// let collected: bbox::BBox = labels.iter().collect();
+    /// assert!(collected.get_min_x() == bb.get_min_x());
+    /// ```
+    ///
+    pub fn from_labels<'a, I, P>(labels: I) -> BBox
+        where I: IntoIterator<Item = &'a P>,
+              P: HasPosition + ?Sized + 'a
+    {
+        let mut result = BBox::new_empty();
+        for l in labels {
+            result.add_to_box(l);
+        }
+
+        result
+    }
+
     ///
     /// Adapt the box to also contain the given point.
     ///
@@ -135,11 +204,11 @@ impl BBox {
     /// assert!(bb.is_contained(&tba));
     /// ```
     ///
-    pub fn add_to_box(&mut self, l: &Label) {
+    pub fn add_to_box<P: HasPosition + ?Sized>(&mut self, l: &P) {
         self.m_max_x = self.m_max_x.max(l.get_x());
         self.m_max_y = self.m_max_y.max(l.get_y());
-        self.m_min_x = self.m_max_x.min(l.get_x());
-        self.m_min_y = self.m_max_y.min(l.get_y());
+        self.m_min_x = self.m_min_x.min(l.get_x());
+        self.m_min_y = self.m_min_y.min(l.get_y());
     }
 
     ///
@@ -164,8 +233,8 @@ impl BBox {
     pub fn add_box(&mut self, other_box: &Self) {
         self.m_max_x = self.m_max_x.max(other_box.m_max_x);
         self.m_max_y = self.m_max_y.max(other_box.m_max_y);
-        self.m_min_x = self.m_max_x.min(other_box.m_min_x);
-        self.m_min_y = self.m_max_y.min(other_box.m_min_y);
+        self.m_min_x = self.m_min_x.min(other_box.m_min_x);
+        self.m_min_y = self.m_min_y.min(other_box.m_min_y);
     }
 
     ///
@@ -253,7 +322,7 @@ impl BBox {
     /// assert!(!bb.is_contained(&not_contained));
     /// ```
     ///
-    pub fn is_contained(&self, l: &Label) -> bool {
+    pub fn is_contained<P: HasPosition + ?Sized>(&self, l: &P) -> bool {
         let x_in = l.get_x() <= self.m_max_x && l.get_x() >= self.m_min_x;
         let y_in = l.get_y() <= self.m_max_y && l.get_y() >= self.m_min_y;
 
@@ -261,24 +330,250 @@ impl BBox {
     }
 
     ///
-    /// Output the given bounding box to a human readable string
+    /// Check if the point lies within the box, compare `is_contained`.
     ///
     ///
     /// # Examples
     /// ```
     /// use rt_datastructure::primitives::bbox;
+    /// use rt_datastructure::primitives::point::Point;
     ///
-    /// let bb = bbox::BBox::new(1., 2., 3., 4.);
-    /// let s = bb.to_string();
+    /// let bb = bbox::BBox::new(-1., -1., 1., 1.);
+    ///
+    /// assert!(bb.contains_point(&Point::new(1., 0.)));
+    /// assert!(!bb.contains_point(&Point::new(0., 2.)));
+    /// ```
+    ///
+    pub fn contains_point(&self, p: &Point) -> bool {
+        self.is_contained(p)
+    }
+
+    ///
+    /// Check whether the box overlaps with the other box.
+    ///
+    /// Boxes are closed, i.e. two boxes that only share a border (or a corner) do intersect. An
+    /// empty box does not intersect any box.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::bbox;
+    ///
+    /// let bb = bbox::BBox::new(0., 0., 2., 2.);
+    ///
+    /// assert!(bb.intersects(&bbox::BBox::new(1., 1., 3., 3.)));
+    /// assert!(bb.intersects(&bbox::BBox::new(2., 2., 3., 3.)));
+    /// assert!(!bb.intersects(&bbox::BBox::new(2.5, 0., 3., 2.)));
+    /// assert!(!bb.intersects(&bbox::BBox::new_empty()));
+    /// ```
+    ///
+    pub fn intersects(&self, other: &Self) -> bool {
+        let x_in = self.m_min_x <= other.m_max_x && other.m_min_x <= self.m_max_x;
+        let y_in = self.m_min_y <= other.m_max_y && other.m_min_y <= self.m_max_y;
+
+        x_in && y_in && self.m_min_x <= self.m_max_x && self.m_min_y <= self.m_max_y
+            && other.m_min_x <= other.m_max_x && other.m_min_y <= other.m_max_y
+    }
+
+    ///
+    /// Compute the overlapping part of the box and the other box.
+    ///
+    /// Returns `None` if the boxes do not intersect (see `intersects`). Boxes that only share a
+    /// border yield a degenerate box of zero width or height.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::bbox;
+    ///
+    /// let bb = bbox::BBox::new(0., 0., 2., 2.);
+    ///
+    /// let overlap = bb.intersection(&bbox::BBox::new(1., -1., 3., 1.)).unwrap();
+    /// assert!(overlap.get_min_x() == 1.);
+    /// assert!(overlap.get_min_y() == 0.);
+    /// assert!(overlap.get_max_x() == 2.);
+    /// assert!(overlap.get_max_y() == 1.);
+    ///
+    /// assert!(bb.intersection(&bbox::BBox::new(3., 3., 4., 4.)).is_none());
+    /// ```
+    ///
+    pub fn intersection(&self, other: &Self) -> Option<BBox> {
+        if !self.intersects(other) {
+            return None;
+        }
+
+        Some(BBox {
+            m_max_x: self.m_max_x.min(other.m_max_x),
+            m_max_y: self.m_max_y.min(other.m_max_y),
+            m_min_x: self.m_min_x.max(other.m_min_x),
+            m_min_y: self.m_min_y.max(other.m_min_y),
+        })
+    }
+
+    ///
+    /// Get a copy of the box enlarged by margin_x to the left and right and by margin_y to the
+    /// bottom and top.
+    ///
+    /// The box itself is not geo-aware: the result may exceed the longitude and latitude ranges.
+    /// GeoPst3d queries clamp the latitude and wrap the longitude of such boxes, so a box crossing
+    /// the antimeridian (min x > max x) may be expanded as well. Use `Crs::expand_bbox` to get a
+    /// box clamped to the extent of a coordinate reference system.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::bbox;
+    ///
+    /// let bb = bbox::BBox::new(0., 0., 2., 1.).expanded(1., 0.5);
     ///
-    /// assert!(s == "[x: 1 - 3, y: 2 - 4]".to_string());
+    /// assert!(bb.get_min_x() == -1.);
+    /// assert!(bb.get_min_y() == -0.5);
+    /// assert!(bb.get_max_x() == 3.);
+    /// assert!(bb.get_max_y() == 1.5);
+    /// ```
+    ///
+    pub fn expanded(&self, margin_x: f64, margin_y: f64) -> BBox {
+        BBox {
+            m_max_x: self.m_max_x + margin_x,
+            m_max_y: self.m_max_y + margin_y,
+            m_min_x: self.m_min_x - margin_x,
+            m_min_y: self.m_min_y - margin_y,
+        }
+    }
+
+    ///
+    /// Get a copy of the box scaled by the factor around its center.
+    ///
+    /// A factor of 2 doubles the width and the height of the box. As for `expanded`, the result
+    /// is not clamped to geographic ranges, compare `Crs::scale_bbox`. The center is computed from
+    /// min and max, hence boxes crossing the antimeridian should be padded via `expanded` instead.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::bbox;
+    ///
+    /// let bb = bbox::BBox::new(0., 0., 2., 4.).scaled(1.5);
+    ///
+    /// assert!(bb.get_min_x() == -0.5);
+    /// assert!(bb.get_min_y() == -1.);
+    /// assert!(bb.get_max_x() == 2.5);
+    /// assert!(bb.get_max_y() == 5.);
+    /// ```
+    ///
+    pub fn scaled(&self, factor: f64) -> BBox {
+        let margin_x = (self.m_max_x - self.m_min_x) * (factor - 1.) / 2.;
+        let margin_y = (self.m_max_y - self.m_min_y) * (factor - 1.) / 2.;
+
+        self.expanded(margin_x, margin_y)
+    }
+
+    ///
+    /// Compute the smallest box spanning both the box and the other box.
+    ///
+    /// This is the non-mutating counterpart of `add_box`.
+    ///
+    ///
+    /// # Examples
     /// ```
+    /// use rt_datastructure::primitives::bbox;
+    ///
+    /// let bb = bbox::BBox::new(0., 0., 1., 1.);
+    /// let union = bb.union(&bbox::BBox::new(2., -1., 3., 0.5));
+    ///
+    /// assert!(union.get_min_x() == 0.);
+    /// assert!(union.get_min_y() == -1.);
+    /// assert!(union.get_max_x() == 3.);
+    /// assert!(union.get_max_y() == 1.);
+    /// ```
+    ///
+    pub fn union(&self, other: &Self) -> BBox {
+        let mut result = self.clone();
+        result.add_box(other);
+
+        result
+    }
+
+    ///
+    /// Get the area of the box.
+    ///
+    /// Empty boxes have an area of 0.
+    ///
     ///
-    pub fn to_string(&self) -> String {
-        format!("[x: {} - {}, y: {} - {}]",
-                self.m_min_x,
-                self.m_max_x,
-                self.m_min_y,
-                self.m_max_y)
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::bbox;
+    ///
+    /// assert!(bbox::BBox::new(-1., 0., 1., 3.).area() == 6.);
+    /// assert!(bbox::BBox::new_empty().area() == 0.);
+    /// ```
+    ///
+    pub fn area(&self) -> f64 {
+        if self.m_min_x > self.m_max_x || self.m_min_y > self.m_max_y {
+            return 0.;
+        }
+
+        (self.m_max_x - self.m_min_x) * (self.m_max_y - self.m_min_y)
+    }
+
+    ///
+    /// Check whether the other box lies completely within the box.
+    ///
+    /// Boxes are closed, so the other box may touch the border. An empty box is contained in
+    /// every box.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::bbox;
+    ///
+    /// let bb = bbox::BBox::new(0., 0., 2., 2.);
+    ///
+    /// assert!(bb.contains_bbox(&bbox::BBox::new(0., 1., 1., 2.)));
+    /// assert!(!bb.contains_bbox(&bbox::BBox::new(1., 1., 3., 2.)));
+    /// assert!(bb.contains_bbox(&bbox::BBox::new_empty()));
+    /// ```
+    ///
+    pub fn contains_bbox(&self, other: &Self) -> bool {
+        if other.m_min_x > other.m_max_x || other.m_min_y > other.m_max_y {
+            return true;
+        }
+
+        let x_in = self.m_min_x <= other.m_min_x && other.m_max_x <= self.m_max_x;
+        let y_in = self.m_min_y <= other.m_min_y && other.m_max_y <= self.m_max_y;
+
+        x_in && y_in
+    }
+}
+
+///
+/// Output the given bounding box in a human readable form.
+///
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::primitives::bbox;
+///
+/// let bb = bbox::BBox::new(1., 2., 3., 4.);
+/// let s = bb.to_string();
+///
+/// assert!(s == "[x: 1 - 3, y: 2 - 4]".to_string());
+/// assert!(format!("{:?}", bb).starts_with("BBox"));
+/// ```
+///
+impl<'a, P: HasPosition + ?Sized + 'a> FromIterator<&'a P> for BBox {
+    fn from_iter<I: IntoIterator<Item = &'a P>>(labels: I) -> Self {
+        BBox::from_labels(labels)
+    }
+}
+
+impl fmt::Display for BBox {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "[x: {} - {}, y: {} - {}]",
+               self.m_min_x,
+               self.m_max_x,
+               self.m_min_y,
+               self.m_max_y)
     }
 }
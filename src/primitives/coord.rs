@@ -0,0 +1,84 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+///
+/// The type coordinates and t values of labels and 3D PST nodes are stored with.
+///
+#[cfg(feature = "f32-coordinates")]
+pub type Coord = f32;
+
+///
+/// The type coordinates and t values of labels and 3D PST nodes are stored with.
+///
+#[cfg(not(feature = "f32-coordinates"))]
+pub type Coord = f64;
+
+///
+/// Convert a value to the storage type. With the `f32-coordinates` feature the value is rounded
+/// to the nearest f32.
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::primitives::coord;
+///
+/// assert!(coord::from_coord(coord::to_coord(0.5)) == 0.5);
+/// ```
+///
+#[cfg(feature = "f32-coordinates")]
+#[inline]
+pub fn to_coord(v: f64) -> Coord {
+    v as f32
+}
+
+///
+/// Convert a value to the storage type. With the `f32-coordinates` feature the value is rounded
+/// to the nearest f32.
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::primitives::coord;
+///
+/// assert!(coord::from_coord(coord::to_coord(0.5)) == 0.5);
+/// ```
+///
+#[cfg(not(feature = "f32-coordinates"))]
+#[inline]
+pub fn to_coord(v: f64) -> Coord {
+    v
+}
+
+///
+/// Convert a stored value back to f64. The conversion is exact.
+///
+#[cfg(feature = "f32-coordinates")]
+#[inline]
+pub fn from_coord(v: Coord) -> f64 {
+    f64::from(v)
+}
+
+///
+/// Convert a stored value back to f64. The conversion is exact.
+///
+#[cfg(not(feature = "f32-coordinates"))]
+#[inline]
+pub fn from_coord(v: Coord) -> f64 {
+    v
+}
@@ -0,0 +1,175 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::f64;
+
+use primitives::bbox::BBox;
+
+///
+/// The equatorial radius of the WGS84 ellipsoid in meters used by the Web Mercator projection.
+///
+const EARTH_RADIUS: f64 = 6378137.;
+
+///
+/// The coordinate reference system of a geographic 3D PST.
+///
+/// The reference system defines the extent of the world in the coordinates of the labels and how
+/// longitude/latitude coordinates are converted into them, e.g. for tile queries. Queries are
+/// performed in the coordinates of the reference system.
+///
+#[derive(Clone, Debug, Default)]
+pub enum Crs {
+    ///
+    /// Longitude and latitude in degrees (EPSG:4326).
+    ///
+    #[default]
+    Wgs84,
+    ///
+    /// Spherical Web Mercator coordinates in meters (EPSG:3857).
+    ///
+    WebMercator,
+    ///
+    /// A user defined reference system given by the extent of the world and the transformation
+    /// of longitude/latitude coordinates into it.
+    ///
+    Custom {
+        extent: BBox,
+        from_wgs84: fn(f64, f64) -> (f64, f64),
+    },
+}
+
+impl Crs {
+    ///
+    /// Get the extent of the world in the coordinates of the reference system.
+    ///
+    /// The x coordinate wraps around at the left and right border of the extent.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::crs::Crs;
+    ///
+    /// let e = Crs::Wgs84.get_extent();
+    /// assert!(e.get_min_x() == -180. && e.get_max_y() == 90.);
+    ///
+    /// let e = Crs::WebMercator.get_extent();
+    /// assert!((e.get_max_x() - 20037508.34).abs() < 0.01);
+    /// assert!(e.get_max_y() == e.get_max_x());
+    /// ```
+    ///
+    pub fn get_extent(&self) -> BBox {
+        match *self {
+            Crs::Wgs84 => BBox::new(-180., -90., 180., 90.),
+            Crs::WebMercator => {
+                let max = EARTH_RADIUS * f64::consts::PI;
+                BBox::new(-max, -max, max, max)
+            }
+            Crs::Custom { ref extent, .. } => extent.clone(),
+        }
+    }
+
+    ///
+    /// Convert the given longitude and latitude in degrees into the reference system.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::crs::Crs;
+    ///
+    /// assert!(Crs::Wgs84.from_wgs84(9.18, 48.78) == (9.18, 48.78));
+    ///
+    /// let (x, y) = Crs::WebMercator.from_wgs84(180., 0.);
+    /// assert!((x - 20037508.34).abs() < 0.01 && y.abs() < 1e-6);
+    ///
+    /// let scaled = Crs::Custom {
+    ///     extent: rt_datastructure::primitives::bbox::BBox::new(-1., -1., 1., 1.),
+    ///     from_wgs84: |lon, lat| (lon / 180., lat / 90.),
+    /// };
+    /// assert!(scaled.from_wgs84(90., -45.) == (0.5, -0.5));
+    /// ```
+    ///
+    pub fn from_wgs84(&self, lon: f64, lat: f64) -> (f64, f64) {
+        match *self {
+            Crs::Wgs84 => (lon, lat),
+            Crs::WebMercator => {
+                let y = (f64::consts::FRAC_PI_4 + lat.to_radians() / 2.).tan().ln();
+                (EARTH_RADIUS * lon.to_radians(), EARTH_RADIUS * y)
+            }
+            Crs::Custom { from_wgs84, .. } => from_wgs84(lon, lat),
+        }
+    }
+
+    ///
+    /// Get a copy of the box expanded by margin_x on the left and right and by margin_y on the
+    /// bottom and top, clamped to the extent of the reference system.
+    ///
+    /// The y coordinates are clamped to the extent, e.g. the latitude of a WGS84 box to
+    /// [-90, 90]. The x coordinates wrap around the antimeridian and are kept, unless the box
+    /// spans at least the whole width of the extent, which is clamped to the extent then.
+    /// Compare `BBox::expanded`.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::bbox::BBox;
+    /// use rt_datastructure::primitives::crs::Crs;
+    ///
+    /// let bb = Crs::Wgs84.expand_bbox(&BBox::new(170., 80., 175., 85.), 10., 10.);
+    /// assert!(bb.get_min_x() == 160. && bb.get_max_x() == 185.);
+    /// assert!(bb.get_min_y() == 70. && bb.get_max_y() == 90.);
+    ///
+    /// let bb = Crs::Wgs84.expand_bbox(&BBox::new(-100., 0., 100., 10.), 100., 0.);
+    /// assert!(bb.get_min_x() == -180. && bb.get_max_x() == 180.);
+    /// ```
+    ///
+    pub fn expand_bbox(&self, bbox: &BBox, margin_x: f64, margin_y: f64) -> BBox {
+        self.clamp_bbox(&bbox.expanded(margin_x, margin_y))
+    }
+
+    ///
+    /// Get a copy of the box scaled by the factor around its center, clamped to the extent of the
+    /// reference system like `expand_bbox`. Compare `BBox::scaled`.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::bbox::BBox;
+    /// use rt_datastructure::primitives::crs::Crs;
+    ///
+    /// let bb = Crs::Wgs84.scale_bbox(&BBox::new(0., 60., 20., 80.), 2.);
+    /// assert!(bb.get_min_x() == -10. && bb.get_max_x() == 30.);
+    /// assert!(bb.get_min_y() == 50. && bb.get_max_y() == 90.);
+    /// ```
+    ///
+    pub fn scale_bbox(&self, bbox: &BBox, factor: f64) -> BBox {
+        self.clamp_bbox(&bbox.scaled(factor))
+    }
+
+    // Clamp the y coordinates of the box to the extent and limit its width to the extent.
+    fn clamp_bbox(&self, bbox: &BBox) -> BBox {
+        let extent = self.get_extent();
+        let clamp = |y: f64| y.clamp(extent.get_min_y(), extent.get_max_y());
+
+        let (mut min_x, mut max_x) = (bbox.get_min_x(), bbox.get_max_x());
+        if min_x <= max_x && max_x - min_x >= extent.get_max_x() - extent.get_min_x() {
+            min_x = extent.get_min_x();
+            max_x = extent.get_max_x();
+        }
+
+        BBox::new(min_x, clamp(bbox.get_min_y()), max_x, clamp(bbox.get_max_y()))
+    }
+}
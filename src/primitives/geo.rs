@@ -0,0 +1,115 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::f64;
+
+use primitives::bbox::BBox;
+use primitives::point::Point;
+use primitives::traits::HasPosition;
+
+///
+/// The mean radius of the earth in meters used for geodesic distances.
+///
+pub const EARTH_MEAN_RADIUS: f64 = 6371008.8;
+
+///
+/// Get the great circle distance in meters between two positions given as longitude (x) and
+/// latitude (y) in degrees.
+///
+/// The distance is computed with the haversine formula on a sphere, the error compared to the
+/// WGS84 ellipsoid is below 0.5%.
+///
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::primitives::geo;
+/// use rt_datastructure::primitives::label::Label;
+/// use rt_datastructure::primitives::point::Point;
+///
+/// let stuttgart = Label::new(9.18, 48.78, 0., 1, 1, 1.5, "Stuttgart".to_string());
+/// let berlin = Point::new(13.40, 52.52);
+///
+/// let d = geo::haversine_distance(&stuttgart, &berlin);
+/// assert!((d - 511000.).abs() < 1000.);
+///
+/// // the distance is measured across the antimeridian
+/// let d = geo::haversine_distance(&Point::new(179.5, 0.), &Point::new(-179.5, 0.));
+/// assert!((d - 111195.).abs() < 1.);
+/// ```
+///
+pub fn haversine_distance<P, Q>(first: &P, second: &Q) -> f64
+    where P: HasPosition + ?Sized,
+          Q: HasPosition + ?Sized
+{
+    let lat1 = first.get_y().to_radians();
+    let lat2 = second.get_y().to_radians();
+    let d_lat = lat2 - lat1;
+    let d_lon = (second.get_x() - first.get_x()).to_radians();
+
+    let a = (d_lat / 2.).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.).sin().powi(2);
+
+    2. * EARTH_MEAN_RADIUS * a.sqrt().min(1.).asin()
+}
+
+///
+/// Get a bounding box in longitude/latitude degrees containing all positions within the given
+/// radius in meters around the center.
+///
+/// The latitude is clamped to [-90, 90]. If the circle contains a pole, the box spans all
+/// longitudes. Otherwise the longitude range may exceed [-180, 180] around the antimeridian,
+/// GeoPst3d queries wrap such boxes.
+///
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::primitives::geo;
+/// use rt_datastructure::primitives::point::Point;
+///
+/// let bb = geo::bbox_around(&Point::new(0., 0.), 111195.);
+/// assert!((bb.get_max_x() - 1.).abs() < 1e-4 && (bb.get_min_y() + 1.).abs() < 1e-4);
+///
+/// // longitudes converge towards the poles
+/// let bb = geo::bbox_around(&Point::new(0., 60.), 111195.);
+/// assert!((bb.get_max_x() - 2.).abs() < 0.01);
+///
+/// let bb = geo::bbox_around(&Point::new(0., 89.5), 111195.);
+/// assert!(bb.get_min_x() == -180. && bb.get_max_x() == 180. && bb.get_max_y() == 90.);
+/// ```
+///
+pub fn bbox_around(center: &Point, radius: f64) -> BBox {
+    let angle = radius.max(0.) / EARTH_MEAN_RADIUS;
+    let lat = center.get_y().to_radians();
+
+    let min_lat = lat - angle;
+    let max_lat = lat + angle;
+    if min_lat <= -f64::consts::FRAC_PI_2 || max_lat >= f64::consts::FRAC_PI_2 {
+        return BBox::new(-180.,
+                         min_lat.to_degrees().max(-90.),
+                         180.,
+                         max_lat.to_degrees().min(90.));
+    }
+
+    let d_lon = (angle.sin() / lat.cos()).min(1.).asin().to_degrees();
+    BBox::new(center.get_x() - d_lon,
+              min_lat.to_degrees(),
+              center.get_x() + d_lon,
+              max_lat.to_degrees())
+}
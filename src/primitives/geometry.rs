@@ -0,0 +1,138 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use primitives::point::Point;
+
+///
+/// The geometry of a line or area feature a label belongs to.
+///
+/// Labels are indexed by a single representative position, the anchor. The geometry is stored
+/// along with the label, so a renderer can e.g. place the text along a road or river.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub enum Geometry {
+    ///
+    /// A line feature given by its vertices.
+    ///
+    Polyline(Vec<Point>),
+    ///
+    /// An area feature given by the vertices of its outer ring. The ring is closed implicitly,
+    /// the first vertex does not need to be repeated.
+    ///
+    Area(Vec<Point>),
+}
+
+impl Geometry {
+    ///
+    /// Get the vertices of the geometry.
+    ///
+    pub fn get_points(&self) -> &Vec<Point> {
+        match *self {
+            Geometry::Polyline(ref points) |
+            Geometry::Area(ref points) => points,
+        }
+    }
+
+    ///
+    /// Get the representative position of the geometry a label is indexed by.
+    ///
+    /// The anchor of a polyline is the point halfway along the line. The anchor of an area is
+    /// the centroid of its ring, which may lie outside of non convex areas. Areas without extent
+    /// are anchored at the mean of their vertices. Returns None if the geometry has no vertices.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::geometry::Geometry;
+    /// use rt_datastructure::primitives::point::Point;
+    ///
+    /// let road = Geometry::Polyline(vec![Point::new(0., 0.),
+    ///                                    Point::new(2., 0.),
+    ///                                    Point::new(2., 4.)]);
+    /// assert!(road.anchor() == Some(Point::new(2., 1.)));
+    ///
+    /// let lake = Geometry::Area(vec![Point::new(0., 0.),
+    ///                                Point::new(4., 0.),
+    ///                                Point::new(4., 2.),
+    ///                                Point::new(0., 2.)]);
+    /// assert!(lake.anchor() == Some(Point::new(2., 1.)));
+    ///
+    /// assert!(Geometry::Polyline(Vec::new()).anchor().is_none());
+    /// ```
+    ///
+    pub fn anchor(&self) -> Option<Point> {
+        match *self {
+            Geometry::Polyline(ref points) => polyline_midpoint(points),
+            Geometry::Area(ref points) => area_centroid(points),
+        }
+    }
+}
+
+///
+/// Get the point halfway along the polyline.
+///
+fn polyline_midpoint(points: &[Point]) -> Option<Point> {
+    let first = match points.first() {
+        Some(p) => *p,
+        None => return None,
+    };
+
+    let length: f64 = points.windows(2).map(|s| s[0].distance(&s[1])).sum();
+    let mut remaining = length / 2.;
+    for s in points.windows(2) {
+        let d = s[0].distance(&s[1]);
+        if d > 0. && remaining <= d {
+            let f = remaining / d;
+            return Some(Point::new(s[0].get_x() + f * (s[1].get_x() - s[0].get_x()),
+                                   s[0].get_y() + f * (s[1].get_y() - s[0].get_y())));
+        }
+        remaining -= d;
+    }
+
+    Some(first)
+}
+
+///
+/// Get the centroid of the implicitly closed ring, or the mean of its vertices if the ring
+/// encloses no area.
+///
+fn area_centroid(points: &[Point]) -> Option<Point> {
+    if points.is_empty() {
+        return None;
+    }
+
+    let (mut area, mut cx, mut cy) = (0., 0., 0.);
+    for (i, p) in points.iter().enumerate() {
+        let q = &points[(i + 1) % points.len()];
+        let cross = p.get_x() * q.get_y() - q.get_x() * p.get_y();
+        area += cross;
+        cx += (p.get_x() + q.get_x()) * cross;
+        cy += (p.get_y() + q.get_y()) * cross;
+    }
+
+    if area == 0. {
+        let n = points.len() as f64;
+        return Some(Point::new(points.iter().map(|p| p.get_x()).sum::<f64>() / n,
+                               points.iter().map(|p| p.get_y()).sum::<f64>() / n));
+    }
+
+    Some(Point::new(cx / (3. * area), cy / (3. * area)))
+}
@@ -20,28 +20,53 @@
 */
 
 use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use primitives::coord::{Coord, from_coord, to_coord};
+use primitives::geometry::Geometry;
+use primitives::point::Point;
+use primitives::traits::{HasPosition, HasT};
 
 ///
 /// The label class.
 ///
 /// For more information about the members compare the [module description](../index.html)
 ///
+#[derive(Debug)]
 pub struct Label {
-    m_x: f64,
-    m_y: f64,
-    m_t: f64,
+    m_x: Coord,
+    m_y: Coord,
+    m_t: Coord,
 
     m_osm_id: i64,
     m_prio: i32,
 
     m_lbl_fac: f64,
+    m_label_length: f64,
+    m_category: u16,
     m_label: String,
+    m_short_label: Option<String>,
+    m_names: Vec<(String, String)>,
+    m_tags: Vec<(String, String)>,
+    m_geometry: Option<Geometry>,
 }
 
 impl Label {
+    ///
+    /// Start building a label, compare `LabelBuilder`.
+    ///
+    pub fn builder() -> LabelBuilder {
+        LabelBuilder::new()
+    }
+
     ///
     /// Initialize a new label object with the given data.
     ///
+    /// The arguments are the position x and y, the elimination time t, the osm id, the priority,
+    /// the label (size) factor lbl_f and the label string. The label factor is read back by
+    /// `get_label_factor`. `Label::builder` sets the same data by name.
+    ///
     ///
     /// # Examples
     /// ```
@@ -52,13 +77,19 @@ impl Label {
     ///
     pub fn new(x: f64, y: f64, t: f64, osm_id: i64, prio: i32, lbl_f: f64, label: String) -> Label {
         Label {
-            m_x: x,
-            m_y: y,
-            m_t: t,
+            m_x: to_coord(x),
+            m_y: to_coord(y),
+            m_t: to_coord(t),
             m_osm_id: osm_id,
             m_prio: prio,
             m_lbl_fac: lbl_f,
+            m_label_length: 0.,
+            m_category: 0,
             m_label: label,
+            m_short_label: None,
+            m_names: Vec::new(),
+            m_tags: Vec::new(),
+            m_geometry: None,
         }
     }
 
@@ -79,6 +110,63 @@ impl Label {
         self.m_lbl_fac
     }
 
+    ///
+    /// Get the label length, e.g. the extent of the rendered label string used for collision
+    /// boxes.
+    ///
+    /// The label length is read from the input files. New labels have the label length 0.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    ///
+    /// let mut l = label::Label::new(90., 45., 0.9, 1234567, 16, 1.5, "Test".to_string());
+    /// assert!(l.get_label_length() == 0.);
+    ///
+    /// l.set_label_length(3300.);
+    /// assert!(l.get_label_length() == 3300.);
+    /// ```
+    ///
+    pub fn get_label_length(&self) -> f64 {
+        self.m_label_length
+    }
+
+    ///
+    /// Set the label length.
+    ///
+    pub fn set_label_length(&mut self, label_length: f64) {
+        self.m_label_length = label_length;
+    }
+
+    ///
+    /// Get the category (class) id of the label, e.g. to distinguish city names from peaks.
+    ///
+    /// New labels have the category 0.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    ///
+    /// let mut l = label::Label::new(90., 45., 0.9, 1234567, 16, 1.5, "Test".to_string());
+    /// assert!(l.get_category() == 0);
+    ///
+    /// l.set_category(3);
+    /// assert!(l.get_category() == 3);
+    /// ```
+    ///
+    pub fn get_category(&self) -> u16 {
+        self.m_category
+    }
+
+    ///
+    /// Set the category (class) id of the label.
+    ///
+    pub fn set_category(&mut self, category: u16) {
+        self.m_category = category;
+    }
+
     ///
     /// Get the label string.
     ///
@@ -96,6 +184,191 @@ impl Label {
         &self.m_label
     }
 
+    ///
+    /// Get the optional short label string, e.g. an abbreviation of the label string.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    ///
+    /// let mut l = label::Label::new(90., 45., 0.9, 1234567, 16, 1.5, "Test".to_string());
+    /// assert!(l.get_short_label().is_none());
+    ///
+    /// l.set_short_label(Some("T.".to_string()));
+    /// assert!(*l.get_short_label().unwrap() == "T.".to_string());
+    /// ```
+    ///
+    pub fn get_short_label(&self) -> Option<&String> {
+        self.m_short_label.as_ref()
+    }
+
+    ///
+    /// Set (or remove) the short label string.
+    ///
+    pub fn set_short_label(&mut self, short_label: Option<String>) {
+        self.m_short_label = short_label;
+    }
+
+    ///
+    /// Get the name stored for the given key, e.g. "name:de" for the german name.
+    ///
+    /// The key "name" refers to the label string.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    ///
+    /// let mut l = label::Label::new(90., 45., 0.9, 1234567, 16, 1.5, "München".to_string());
+    /// l.set_name("name:en".to_string(), "Munich".to_string());
+    ///
+    /// assert!(*l.get_name("name:en").unwrap() == "Munich".to_string());
+    /// assert!(*l.get_name("name").unwrap() == "München".to_string());
+    /// assert!(l.get_name("name:fr").is_none());
+    /// ```
+    ///
+    pub fn get_name(&self, key: &str) -> Option<&String> {
+        if key == "name" {
+            return Some(&self.m_label);
+        }
+
+        self.m_names.iter().find(|n| n.0 == key).map(|n| &n.1)
+    }
+
+    ///
+    /// Set the name for the given key, e.g. "name:de" for the german name. An existing name for
+    /// the key is replaced.
+    ///
+    pub fn set_name(&mut self, key: String, name: String) {
+        match self.m_names.iter_mut().find(|n| n.0 == key) {
+            Some(n) => n.1 = name,
+            None => self.m_names.push((key, name)),
+        }
+    }
+
+    ///
+    /// Get all names in further languages as (key, name) pairs.
+    ///
+    pub fn get_names(&self) -> &Vec<(String, String)> {
+        &self.m_names
+    }
+
+    ///
+    /// Remove the name for the given key and return it. The label string itself, i.e. the key
+    /// "name", cannot be removed.
+    ///
+    pub fn remove_name(&mut self, key: &str) -> Option<String> {
+        let idx = self.m_names.iter().position(|n| n.0 == key)?;
+        Some(self.m_names.remove(idx).1)
+    }
+
+    ///
+    /// Get the value of the tag with the given key, e.g. "place" or "wikidata".
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    ///
+    /// let mut l = label::Label::new(13.4, 52.5, 0.9, 62422, 16, 1.5, "Berlin".to_string());
+    /// l.set_tag("wikidata".to_string(), "Q64".to_string());
+    /// l.set_tag("place".to_string(), "city".to_string());
+    ///
+    /// assert!(*l.get_tag("place").unwrap() == "city".to_string());
+    /// assert!(l.get_tag("icon").is_none());
+    ///
+    /// // the tags are sorted by their keys
+    /// assert!(l.get_tags()[0].0 == "place");
+    ///
+    /// assert!(l.remove_tag("place") == Some("city".to_string()));
+    /// assert!(l.get_tags().len() == 1);
+    /// ```
+    ///
+    pub fn get_tag(&self, key: &str) -> Option<&String> {
+        self.m_tags
+            .binary_search_by(|t| t.0.as_str().cmp(key))
+            .ok()
+            .map(|idx| &self.m_tags[idx].1)
+    }
+
+    ///
+    /// Set the value of the tag with the given key. An existing value for the key is replaced.
+    ///
+    pub fn set_tag(&mut self, key: String, value: String) {
+        match self.m_tags.binary_search_by(|t| t.0.cmp(&key)) {
+            Ok(idx) => self.m_tags[idx].1 = value,
+            Err(idx) => self.m_tags.insert(idx, (key, value)),
+        }
+    }
+
+    ///
+    /// Remove the tag with the given key and return its value.
+    ///
+    pub fn remove_tag(&mut self, key: &str) -> Option<String> {
+        match self.m_tags.binary_search_by(|t| t.0.as_str().cmp(key)) {
+            Ok(idx) => Some(self.m_tags.remove(idx).1),
+            Err(_) => None,
+        }
+    }
+
+    ///
+    /// Get all tags as (key, value) pairs sorted by their keys.
+    ///
+    pub fn get_tags(&self) -> &Vec<(String, String)> {
+        &self.m_tags
+    }
+
+    ///
+    /// Initialize a new label of a line or area feature.
+    ///
+    /// The label is positioned at the anchor of the geometry (see `Geometry::anchor`) and keeps
+    /// the geometry, e.g. to place the text along a road. The remaining arguments are the same as
+    /// for `new`. Returns None if the geometry has no vertices.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::geometry::Geometry;
+    /// use rt_datastructure::primitives::label;
+    /// use rt_datastructure::primitives::point::Point;
+    ///
+    /// let river = Geometry::Polyline(vec![Point::new(8., 50.), Point::new(9., 50.)]);
+    /// let l = label::Label::new_anchored(river.clone(), 0.9, 123, 16, 1.5, "Main".to_string())
+    ///     .unwrap();
+    ///
+    /// assert!(l.get_position() == Point::new(8.5, 50.));
+    /// assert!(l.get_geometry() == Some(&river));
+    /// ```
+    ///
+    pub fn new_anchored(geometry: Geometry,
+                        t: f64,
+                        osm_id: i64,
+                        prio: i32,
+                        lbl_f: f64,
+                        label: String)
+                        -> Option<Label> {
+        geometry.anchor().map(|anchor| {
+            let mut l = Label::new(anchor.get_x(), anchor.get_y(), t, osm_id, prio, lbl_f, label);
+            l.m_geometry = Some(geometry);
+            l
+        })
+    }
+
+    ///
+    /// Get the geometry of the line or area feature the label belongs to, if any.
+    ///
+    pub fn get_geometry(&self) -> Option<&Geometry> {
+        self.m_geometry.as_ref()
+    }
+
+    ///
+    /// Set the geometry of the line or area feature the label belongs to.
+    ///
+    /// The position of the label is not changed, compare `new_anchored`.
+    ///
+    pub fn set_geometry(&mut self, geometry: Option<Geometry>) {
+        self.m_geometry = geometry;
+    }
+
 
     ///
     /// Get the osm_id.
@@ -114,6 +387,24 @@ impl Label {
         self.m_osm_id
     }
 
+    ///
+    /// Set the osm_id.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    ///
+    /// let mut l = label::Label::new(90., 45., 0.9, 1234567, 16, 1.5, "Test".to_string());
+    /// l.set_osm_id(42);
+    ///
+    /// assert!(l.get_osm_id() == 42);
+    /// ```
+    ///
+    pub fn set_osm_id(&mut self, osm_id: i64) {
+        self.m_osm_id = osm_id;
+    }
+
 
     ///
     /// Get the label priority.
@@ -141,13 +432,13 @@ impl Label {
     /// ```
     /// use rt_datastructure::primitives::label;
     ///
-    /// let l = label::Label::new(90., 45., 0.9, 1234567, 16, 1.5, "Test".to_string());
+    /// let l = label::Label::new(90., 45., 0.75, 1234567, 16, 1.5, "Test".to_string());
     ///
-    /// assert!(l.get_t() == 0.9);
+    /// assert!(l.get_t() == 0.75);
     /// ```
     ///
     pub fn get_t(&self) -> f64 {
-        self.m_t
+        from_coord(self.m_t)
     }
 
 
@@ -165,7 +456,7 @@ impl Label {
     /// ```
     ///
     pub fn get_x(&self) -> f64 {
-        self.m_x
+        from_coord(self.m_x)
     }
 
 
@@ -183,7 +474,45 @@ impl Label {
     /// ```
     ///
     pub fn get_y(&self) -> f64 {
-        self.m_y
+        from_coord(self.m_y)
+    }
+
+    ///
+    /// Get the position of the label.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    /// use rt_datastructure::primitives::point::Point;
+    ///
+    /// let l = label::Label::new(90., 45., 0.9, 1234567, 16, 1.5, "Test".to_string());
+    ///
+    /// assert!(l.get_position() == Point::new(90., 45.));
+    /// ```
+    ///
+    pub fn get_position(&self) -> Point {
+        Point::new(self.get_x(), self.get_y())
+    }
+
+    ///
+    /// Move the label to the given position.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    ///
+    /// let mut l = label::Label::new(90., 45., 0.9, 1234567, 16, 1.5, "Test".to_string());
+    /// l.set_position(10., 20.);
+    ///
+    /// assert!(l.get_x() == 10.);
+    /// assert!(l.get_y() == 20.);
+    /// ```
+    ///
+    pub fn set_position(&mut self, x: f64, y: f64) {
+        self.m_x = to_coord(x);
+        self.m_y = to_coord(y);
     }
 
     ///
@@ -270,41 +599,259 @@ impl Label {
             Ordering::Equal
         }
     }
+}
 
+///
+/// Output the label in a human readable form.
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::primitives::label;
+///
+/// let l = label::Label::new(90., 45., 0.9, 1234567, 16, 1.5, "Test".to_string());
+///
+/// let s = l.to_string();
+/// assert!(s == "Label [#1234567]: 'Test' at (90, 45) with prio 16, elim-t: 0.9 and label \
+///                                                                               factor: 1.5");
+/// assert!(format!("{}", l) == s);
+/// ```
+///
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "Label [#{}]: '{}' at ({}, {}) with prio {}, elim-t: {} and label factor: {}",
+               self.m_osm_id,
+               self.m_label,
+               self.m_x,
+               self.m_y,
+               self.m_prio,
+               self.m_t,
+               self.m_lbl_fac)
+    }
+}
+
+impl HasPosition for Label {
+    fn get_x(&self) -> f64 {
+        from_coord(self.m_x)
+    }
+
+    fn get_y(&self) -> f64 {
+        from_coord(self.m_y)
+    }
+}
+
+impl HasT for Label {
+    fn get_t(&self) -> f64 {
+        from_coord(self.m_t)
+    }
+}
+
+///
+/// Two labels are equal if they have the same osm id.
+///
+/// The osm id identifies the labeled object, so the other members (e.g. the position of a label
+/// candidate or its t value) are not compared. This allows to key labels by their object in hash
+/// sets and maps, e.g. to deduplicate query results.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashSet;
+/// use rt_datastructure::primitives::label;
+///
+/// let l1 = label::Label::new(1., 1., 5., 1, 1, 1.5, "Bremen".to_string());
+/// let l2 = label::Label::new(1.5, 1., 7., 1, 1, 1.5, "Bremen".to_string());
+/// let l3 = label::Label::new(2., 2., 6., 2, 1, 1.5, "Vegesack".to_string());
+///
+/// assert!(l1 == l2);
+/// assert!(l1 != l3);
+///
+/// let ids: HashSet<&label::Label> = vec![&l1, &l2, &l3].into_iter().collect();
+/// assert!(ids.len() == 2);
+/// ```
+///
+impl PartialEq for Label {
+    fn eq(&self, other: &Self) -> bool {
+        self.m_osm_id == other.m_osm_id
+    }
+}
+
+impl Eq for Label {}
+
+impl Hash for Label {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.m_osm_id.hash(state);
+    }
+}
+
+impl Clone for Label {
+    fn clone(&self) -> Self {
+        let mut l = Self::new(from_coord(self.m_x),
+                              from_coord(self.m_y),
+                              from_coord(self.m_t),
+                              self.m_osm_id,
+                              self.m_prio,
+                              self.m_lbl_fac,
+                              self.m_label.clone());
+        l.m_label_length = self.m_label_length;
+        l.m_category = self.m_category;
+        l.m_short_label = self.m_short_label.clone();
+        l.m_names = self.m_names.clone();
+        l.m_tags = self.m_tags.clone();
+        l.m_geometry = self.m_geometry.clone();
+
+        l
+    }
+}
+
+///
+/// A builder for labels with named settings instead of the positional arguments of `Label::new`.
+///
+/// Settings that are not given keep their defaults: position (0, 0), t 0, osm id 0, priority 0,
+/// label factor 1, label length 0, category 0 and an empty label string.
+///
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::primitives::label::Label;
+///
+/// let l = Label::builder()
+///     .pos(8.75, 53.125)
+///     .t(3.)
+///     .osm_id(62559)
+///     .prio(1)
+///     .text("Bremen")
+///     .tag("place", "city")
+///     .build();
+///
+/// assert!(l.get_x() == 8.75 && l.get_y() == 53.125);
+/// assert!(l.get_t() == 3.);
+/// assert!(l.get_osm_id() == 62559);
+/// assert!(l.get_label() == "Bremen");
+/// assert!(l.get_label_factor() == 1.);
+/// assert!(*l.get_tag("place").unwrap() == "city".to_string());
+/// ```
+///
+#[derive(Clone, Debug)]
+pub struct LabelBuilder {
+    m_label: Label,
+}
+
+impl LabelBuilder {
     ///
-    /// This function compares two pois with respect to their y coordinate
+    /// Initialize a builder with the default settings.
     ///
-    /// # Examples
-    /// ```
-    /// use rt_datastructure::primitives::label;
+    pub fn new() -> LabelBuilder {
+        LabelBuilder { m_label: Label::new(0., 0., 0., 0, 0, 1., String::new()) }
+    }
+
     ///
-    /// let l = label::Label::new(90., 45., 0.9, 1234567, 16, 1.5, "Test".to_string());
+    /// Set the position of the label.
     ///
-    /// let s = l.to_string();
-    /// assert!(s == "Label [#1234567]: 'Test' at (90, 45) with prio 16, elim-t: 0.9 and label \
-    ///                                                                               factor: 1.5")
-    /// ```
+    pub fn pos(mut self, x: f64, y: f64) -> LabelBuilder {
+        self.m_label.set_position(x, y);
+        self
+    }
+
+    ///
+    /// Set the elimination time of the label.
+    ///
+    pub fn t(mut self, t: f64) -> LabelBuilder {
+        self.m_label.m_t = to_coord(t);
+        self
+    }
+
+    ///
+    /// Set the osm id of the label.
+    ///
+    pub fn osm_id(mut self, osm_id: i64) -> LabelBuilder {
+        self.m_label.m_osm_id = osm_id;
+        self
+    }
+
+    ///
+    /// Set the priority of the label.
+    ///
+    pub fn prio(mut self, prio: i32) -> LabelBuilder {
+        self.m_label.m_prio = prio;
+        self
+    }
+
+    ///
+    /// Set the label (size) factor.
+    ///
+    pub fn factor(mut self, lbl_f: f64) -> LabelBuilder {
+        self.m_label.m_lbl_fac = lbl_f;
+        self
+    }
+
+    ///
+    /// Set the label length.
+    ///
+    pub fn length(mut self, label_length: f64) -> LabelBuilder {
+        self.m_label.m_label_length = label_length;
+        self
+    }
+
+    ///
+    /// Set the label string.
+    ///
+    pub fn text<S: Into<String>>(mut self, label: S) -> LabelBuilder {
+        self.m_label.m_label = label.into();
+        self
+    }
+
+    ///
+    /// Set the short label string, compare `Label::set_short_label`.
+    ///
+    pub fn short_text<S: Into<String>>(mut self, short_label: S) -> LabelBuilder {
+        self.m_label.set_short_label(Some(short_label.into()));
+        self
+    }
+
+    ///
+    /// Set the category of the label.
+    ///
+    pub fn category(mut self, category: u16) -> LabelBuilder {
+        self.m_label.set_category(category);
+        self
+    }
+
+    ///
+    /// Add the name with the given key, compare `Label::set_name`.
     ///
-    pub fn to_string(&self) -> String {
-        format!("Label [#{}]: '{}' at ({}, {}) with prio {}, elim-t: {} and label factor: {}",
-                self.m_osm_id,
-                self.m_label,
-                self.m_x,
-                self.m_y,
-                self.m_prio,
-                self.m_t,
-                self.m_lbl_fac)
+    pub fn name<K: Into<String>, N: Into<String>>(mut self, key: K, name: N) -> LabelBuilder {
+        self.m_label.set_name(key.into(), name.into());
+        self
+    }
+
+    ///
+    /// Add the tag with the given key, compare `Label::set_tag`.
+    ///
+    pub fn tag<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> LabelBuilder {
+        self.m_label.set_tag(key.into(), value.into());
+        self
+    }
+
+    ///
+    /// Set the geometry of the line or area feature of the label.
+    ///
+    /// The position is not changed, compare `Label::new_anchored`.
+    ///
+    pub fn geometry(mut self, geometry: Geometry) -> LabelBuilder {
+        self.m_label.set_geometry(Some(geometry));
+        self
+    }
+
+    ///
+    /// Build the label.
+    ///
+    pub fn build(self) -> Label {
+        self.m_label
     }
 }
 
-impl Clone for Label {
-    fn clone(&self) -> Self {
-        Self::new(self.m_x,
-                  self.m_y,
-                  self.m_t,
-                  self.m_osm_id,
-                  self.m_prio,
-                  self.m_lbl_fac,
-                  self.m_label.clone())
+impl Default for LabelBuilder {
+    fn default() -> Self {
+        Self::new()
     }
 }
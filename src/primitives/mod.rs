@@ -45,3 +45,61 @@ pub mod bbox;
 /// respect to one of their coordinates or t.
 ///
 pub mod label;
+
+///
+/// A module providing a simple 2 dimensional point class.
+///
+/// Positions, e.g. the focus of nearest neighbor queries, are passed as points instead of bare
+/// coordinate pairs. The module further provides distance helpers.
+///
+pub mod point;
+
+///
+/// A module providing geodesic distances of longitude/latitude positions.
+///
+/// The module provides the great circle distance in meters and the bounding box of a circle with
+/// a radius in meters, e.g. for radius queries on geographic 3D PSTs.
+///
+pub mod geo;
+
+///
+/// A module providing the geometries of line and area features.
+///
+/// Labels of roads, rivers or lakes are indexed by a representative anchor point and carry the
+/// geometry of their feature, so renderers can place the text along it.
+///
+pub mod geometry;
+
+///
+/// A module providing the traits of elements stored in a 3D PST.
+///
+/// An element must provide a 2 dimensional position (HasPosition) and an elimination time
+/// (HasT). The Label implements both traits, but any user type implementing them can be stored
+/// in a 3D PST.
+///
+pub mod traits;
+
+///
+/// A module providing hooks to abbreviate label strings depending on the query min_t.
+///
+/// A label may carry an optional short label. The Abbreviation trait allows clients to plug in
+/// their own rules which text is displayed for a given label at a given min_t.
+///
+pub mod abbreviation;
+
+///
+/// A module providing the type coordinates and t values are stored with.
+///
+/// With the `f32-coordinates` feature labels and 3D PST nodes store them as f32 instead of f64,
+/// which halves the memory required for them. The interfaces still use f64 values.
+///
+pub mod coord;
+
+///
+/// A module providing the coordinate reference systems of geographic 3D PSTs.
+///
+/// A geographic 3D PST stores labels either in longitude/latitude (WGS84), in Web Mercator
+/// meters or in a user defined reference system. The reference system defines the extent used
+/// for coordinate checks and for the wraparound at the antimeridian.
+///
+pub mod crs;
@@ -0,0 +1,136 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::fmt;
+
+use primitives::traits::HasPosition;
+
+///
+/// A position in 2D given by its x and y coordinate.
+///
+/// Positions are passed as points instead of bare coordinate pairs, so x and y cannot be swapped
+/// accidentally.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Point {
+    m_x: f64,
+    m_y: f64,
+}
+
+impl Point {
+    ///
+    /// Initialize a new point at the given coordinates.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::point::Point;
+    ///
+    /// let p = Point::new(9.18, 48.78);
+    ///
+    /// assert!(p.get_x() == 9.18);
+    /// assert!(p.get_y() == 48.78);
+    /// ```
+    ///
+    pub fn new(x: f64, y: f64) -> Point {
+        Point { m_x: x, m_y: y }
+    }
+
+    ///
+    /// Get the x coordinate of the point.
+    ///
+    pub fn get_x(&self) -> f64 {
+        self.m_x
+    }
+
+    ///
+    /// Get the y coordinate of the point.
+    ///
+    pub fn get_y(&self) -> f64 {
+        self.m_y
+    }
+
+    ///
+    /// Get the squared euclidean distance to the given position.
+    ///
+    /// Comparing squared distances avoids the square root, e.g. when searching the closest
+    /// element.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label::Label;
+    /// use rt_datastructure::primitives::point::Point;
+    ///
+    /// let l = Label::new(3., 4., 0., 0, 0, 1., "Label".to_string());
+    ///
+    /// assert!(Point::new(0., 0.).squared_distance(&l) == 25.);
+    /// ```
+    ///
+    pub fn squared_distance<P: HasPosition + ?Sized>(&self, other: &P) -> f64 {
+        let dx = other.get_x() - self.m_x;
+        let dy = other.get_y() - self.m_y;
+
+        dx * dx + dy * dy
+    }
+
+    ///
+    /// Get the euclidean distance to the given position.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::point::Point;
+    ///
+    /// assert!(Point::new(1., 1.).distance(&Point::new(4., 5.)) == 5.);
+    /// ```
+    ///
+    pub fn distance<P: HasPosition + ?Sized>(&self, other: &P) -> f64 {
+        self.squared_distance(other).sqrt()
+    }
+}
+
+impl HasPosition for Point {
+    fn get_x(&self) -> f64 {
+        self.m_x
+    }
+
+    fn get_y(&self) -> f64 {
+        self.m_y
+    }
+}
+
+///
+/// Print the point as (x, y).
+///
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::primitives::point::Point;
+///
+/// assert!(Point::new(1.5, -2.).to_string() == "(1.5, -2)");
+/// ```
+///
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}, {})", self.m_x, self.m_y)
+    }
+}
@@ -0,0 +1,79 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::sync::Arc;
+
+///
+/// An element with a position in 2D.
+///
+pub trait HasPosition {
+    ///
+    /// Get the x coordinate.
+    ///
+    fn get_x(&self) -> f64;
+
+    ///
+    /// Get the y coordinate.
+    ///
+    fn get_y(&self) -> f64;
+}
+
+///
+/// An element with an elimination time t.
+///
+pub trait HasT {
+    ///
+    /// Get the t value.
+    ///
+    fn get_t(&self) -> f64;
+}
+
+impl<P: HasPosition + ?Sized> HasPosition for &P {
+    fn get_x(&self) -> f64 {
+        (**self).get_x()
+    }
+
+    fn get_y(&self) -> f64 {
+        (**self).get_y()
+    }
+}
+
+impl<P: HasT + ?Sized> HasT for &P {
+    fn get_t(&self) -> f64 {
+        (**self).get_t()
+    }
+}
+
+impl<P: HasPosition + ?Sized> HasPosition for Arc<P> {
+    fn get_x(&self) -> f64 {
+        (**self).get_x()
+    }
+
+    fn get_y(&self) -> f64 {
+        (**self).get_y()
+    }
+}
+
+impl<P: HasT + ?Sized> HasT for Arc<P> {
+    fn get_t(&self) -> f64 {
+        (**self).get_t()
+    }
+}
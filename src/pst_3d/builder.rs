@@ -0,0 +1,241 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use error::Error;
+use primitives::traits::{HasPosition, HasT};
+use pst_3d::Pst3d;
+
+///
+/// The strategy to choose the split value of a tree node.
+///
+/// The elements of the subtree of a node are split into the left and the right subtree at the
+/// split value. Independent of the strategy both subtrees are non empty.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SplitStrategy {
+    /// Split at the median coordinate, i.e. both subtrees have the same size (up to one element).
+    /// The depth of the tree is logarithmic.
+    Median,
+    /// Split at the midpoint of the extent of the coordinates of the elements. Clustered data is
+    /// split at the gaps between the clusters.
+    Midpoint,
+    /// Split at the midpoint of the region of the node. If all elements lie on one side of the
+    /// midpoint, the split value slides to the closest element.
+    SlidingMidpoint,
+}
+
+///
+/// The settings of the construction of a 3D PST.
+///
+/// A default constructed Pst3dBuilder constructs the same tree as `Pst3d::new`. The settings are
+/// kept by the tree, so a rebuild uses the same settings.
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::primitives::{label, bbox};
+/// use rt_datastructure::pst_3d;
+/// use rt_datastructure::pst_3d::builder::Pst3dBuilder;
+///
+/// let mut v = Vec::new();
+/// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
+/// v.push(label::Label::new(2., 3., 9., 2, 1, 1.5, "T2".to_string()));
+/// v.push(label::Label::new(3., 4., 8., 3, 1, 1.5, "T3".to_string()));
+///
+/// let mut b = Pst3dBuilder::new();
+/// b.set_parallel(true);
+///
+/// let t = b.build(v.clone());
+/// assert!(t.get(&bbox::BBox::new(0., 0., 10., 10.), 0.).len() == 3);
+/// assert!(t.to_string() == pst_3d::Pst3d::new(v).to_string());
+/// ```
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pst3dBuilder {
+    m_parallel: bool,
+    m_split_strategy: SplitStrategy,
+    m_leaf_bucket_size: usize,
+}
+
+impl Pst3dBuilder {
+    ///
+    /// Initialize new settings that construct the same tree as `Pst3d::new`.
+    ///
+    pub fn new() -> Pst3dBuilder {
+        Pst3dBuilder {
+            m_parallel: false,
+            m_split_strategy: SplitStrategy::Median,
+            m_leaf_bucket_size: 1,
+        }
+    }
+
+    ///
+    /// Use multiple threads for the construction.
+    ///
+    /// The resulting tree is identical to the tree constructed by a single thread.
+    ///
+    pub fn set_parallel(&mut self, parallel: bool) {
+        self.m_parallel = parallel;
+    }
+
+    ///
+    /// Check if the construction uses multiple threads.
+    ///
+    pub fn get_parallel(&self) -> bool {
+        self.m_parallel
+    }
+
+    ///
+    /// Set the strategy to choose the split values, the default is the median.
+    ///
+    /// The midpoint strategies may result in a deeper tree, but the nodes of clustered data
+    /// (e.g. cities along a coast line) split at the gaps between the clusters.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d::builder::{Pst3dBuilder, SplitStrategy};
+    ///
+    /// let mut v = Vec::new();
+    /// for i in 0..100 {
+    ///     let x = if i % 2 == 0 { i as f64 / 1000. } else { 100. + i as f64 / 1000. };
+    ///     v.push(label::Label::new(x, 1., i as f64, i, 1, 1.5, "T".to_string()));
+    /// }
+    ///
+    /// for &s in [SplitStrategy::Median, SplitStrategy::Midpoint, SplitStrategy::SlidingMidpoint]
+    ///     .iter() {
+    ///     let mut b = Pst3dBuilder::new();
+    ///     b.set_split_strategy(s);
+    ///     assert!(b.get_split_strategy() == s);
+    ///
+    ///     let t = b.build(v.clone());
+    ///     assert!(t.validate().is_ok());
+    ///     assert!(t.get(&bbox::BBox::new(50., 0., 150., 2.), 49.).len() == 25);
+    /// }
+    /// ```
+    ///
+    pub fn set_split_strategy(&mut self, strategy: SplitStrategy) {
+        self.m_split_strategy = strategy;
+    }
+
+    ///
+    /// Get the strategy to choose the split values.
+    ///
+    pub fn get_split_strategy(&self) -> SplitStrategy {
+        self.m_split_strategy
+    }
+
+    ///
+    /// Store subtrees of at most the given number of labels in leaf buckets, the default of 1
+    /// disables the buckets.
+    ///
+    /// A bucket takes a single leaf node, which references its labels stored contiguously by
+    /// decreasing t. A query scans them linearly instead of comparing split values, which pays
+    /// off for buckets of about 8 to 32 labels. The tree has fewer nodes and a smaller depth, see
+    /// `Pst3d::get_node_count`. Results are identical to the tree without buckets.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    /// use rt_datastructure::pst_3d::builder::Pst3dBuilder;
+    ///
+    /// let mut v = Vec::new();
+    /// for i in 0..100 {
+    ///     let (x, y) = ((i * 37 % 101) as f64, (i * 61 % 103) as f64);
+    ///     v.push(label::Label::new(x, y, i as f64, i, 1, 1.5, "T".to_string()));
+    /// }
+    ///
+    /// let mut b = Pst3dBuilder::new();
+    /// b.set_leaf_bucket_size(16);
+    /// let t = b.build(v.clone());
+    /// assert!(t.validate().is_ok());
+    ///
+    /// let bb = bbox::BBox::new(20., 30., 70., 80.);
+    /// let mut found: Vec<i64> = t.get(&bb, 20.).iter().map(|l| l.get_osm_id()).collect();
+    /// let mut expected: Vec<i64> =
+    ///     pst_3d::Pst3d::new(v).get(&bb, 20.).iter().map(|l| l.get_osm_id()).collect();
+    /// found.sort();
+    /// expected.sort();
+    /// assert!(found == expected);
+    /// ```
+    ///
+    pub fn set_leaf_bucket_size(&mut self, size: usize) {
+        self.m_leaf_bucket_size = size;
+    }
+
+    ///
+    /// Get the maximum number of labels of a leaf bucket.
+    ///
+    pub fn get_leaf_bucket_size(&self) -> usize {
+        self.m_leaf_bucket_size
+    }
+
+    ///
+    /// Construct a 3D PST from the given labels, see `Pst3d::new`.
+    ///
+    pub fn build<T: HasPosition + HasT>(&self, labels: Vec<T>) -> Pst3d<T> {
+        self.build_by(labels, |l| l.get_t())
+    }
+
+    ///
+    /// Construct a 3D PST from the given labels using the given function to compute the priority
+    /// of a label, see `Pst3d::new_by`.
+    ///
+    pub fn build_by<T, F>(&self, labels: Vec<T>, priority: F) -> Pst3d<T>
+        where T: HasPosition + HasT,
+              F: Fn(&T) -> f64
+    {
+        let weighted = labels.into_iter().map(|l| (priority(&l), l)).collect();
+
+        Pst3d::new_weighted(weighted, self.clone())
+    }
+
+    ///
+    /// Construct a 3D PST from the given labels, if all labels are valid, see `Pst3d::try_new`.
+    ///
+    /// # Errors
+    ///   * Validation: see `Pst3d::try_new`
+    ///
+    pub fn try_build<T: HasPosition + HasT>(&self, labels: Vec<T>) -> Result<Pst3d<T>, Error> {
+        self.try_build_by(labels, |l| l.get_t())
+    }
+
+    ///
+    /// Construct a 3D PST like `build_by`, if all labels are valid.
+    ///
+    /// # Errors
+    ///   * Validation: see `Pst3d::try_new`
+    ///
+    pub fn try_build_by<T, F>(&self, labels: Vec<T>, priority: F) -> Result<Pst3d<T>, Error>
+        where T: HasPosition + HasT,
+              F: Fn(&T) -> f64
+    {
+        let weighted = labels.into_iter().map(|l| (priority(&l), l)).collect();
+
+        Pst3d::try_new_weighted(weighted, self.clone())
+    }
+}
+
+impl Default for Pst3dBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
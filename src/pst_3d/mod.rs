@@ -24,10 +24,72 @@
 ///
 mod root;
 
+///
+/// Options and result types of queries.
+///
+/// The QueryOptions allow to post process the result of a query, e.g. to merge visually
+/// identical labels.
+///
+pub mod query;
+
+///
+/// Editorial overrides of label priorities.
+///
+/// A PriorityOverlay raises or lowers the t value of labels matched by their osm id or by a
+/// region at query time without rebuilding the tree.
+///
+pub mod overlay;
+
+///
+/// Construction settings of a 3D PST.
+///
+/// The Pst3dBuilder collects the options of the tree construction, e.g. whether the
+/// construction uses multiple threads.
+///
+pub mod builder;
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::f64;
+use std::fmt;
+use std::io::{Read, Write};
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering as AtomicOrdering};
+
+use cache::ResultCache;
+use error::Error;
+use pack::codec;
 use primitives::label::Label;
 use primitives::bbox::BBox;
+use primitives::crs::Crs;
+use primitives::geo;
+use primitives::point::Point;
+use primitives::traits::{HasPosition, HasT};
+
+use self::builder::Pst3dBuilder;
+use self::overlay::PriorityOverlay;
+use self::query::{NameFallback, QueryBudget, QueryOptions, QueryResult, QueryStats,
+                  VersionedResult};
+use self::root::{MAX_ELEMENTS, NoStats, Root, Traversal, Tree};
+
+// The generation id of the next constructed tree.
+static NEXT_GENERATION: AtomicUsize = AtomicUsize::new(1);
+
+// The maximum number of offending labels listed in a validation error.
+const MAX_LISTED_LABELS: usize = 10;
 
-use self::root::Root;
+///
+/// The maximum zoom level of a Web Mercator tile query.
+///
+pub const MAX_TILE_ZOOM: u32 = 30;
+
+// The trees are shared between query threads, fail to compile if a member breaks Send or Sync.
+const _: fn() = || {
+    fn assert_send_sync<S: Send + Sync>() {}
+    assert_send_sync::<GeoPst3d>();
+    assert_send_sync::<Pst3d>();
+};
 
 ///
 /// A wrapper to the Pst3d providing some additional coordinate range checks and some functions
@@ -39,8 +101,43 @@ use self::root::Root;
 /// The getter function deals with "wrap arounds", i.e. a bounding box might range from lin -170
 /// to 170
 ///
+/// # Concurrency
+/// The tree is Send and Sync. All queries take the tree by shared reference, the hit counters are
+/// atomic and the result cache is protected by a mutex, so a single tree can serve concurrent
+/// queries, e.g. from a thread pool, when it is shared by an Arc.
+///
+/// # Examples
+/// ```
+/// use std::sync::Arc;
+/// use std::thread;
+/// use rt_datastructure::primitives::{label, bbox};
+/// use rt_datastructure::pst_3d;
+///
+/// let mut v = Vec::new();
+/// for i in 0..100 {
+///     let lon = (i % 10) as f64;
+///     let lat = (i / 10) as f64;
+///     v.push(label::Label::new(lon, lat, i as f64, i, 1, 1.5, "T".to_string()));
+/// }
+///
+/// let shared = Arc::new(pst_3d::GeoPst3d::new(v));
+///
+/// let workers: Vec<_> = (0..4)
+///     .map(|i| {
+///         let t = Arc::clone(&shared);
+///         thread::spawn(move || t.get(&bbox::BBox::new(0., 0., 9., 9.), (i * 10) as f64).len())
+///     })
+///     .collect();
+///
+/// let counts: Vec<usize> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+/// assert!(counts == vec![99, 89, 79, 69]);
+/// ```
+///
 pub struct GeoPst3d {
     m_pst: Pst3d,
+    m_crs: Crs,
+    m_name_fallback: NameFallback,
+    m_cache: Mutex<Option<ResultCache>>,
 }
 
 impl GeoPst3d {
@@ -52,6 +149,11 @@ impl GeoPst3d {
     /// # Panics
     /// * if lat not in range [-90, 90]
     /// * if lon not in range [-180, 180]
+    /// * if t is not finite
+    ///
+    /// With the `panic-free` feature the coordinates are clamped to the valid range instead and
+    /// labels with a non finite t are dropped. Use `try_new` to handle invalid labels without a
+    /// panic, e.g. in a server.
     ///
     /// # Examples
     /// ```
@@ -71,45 +173,200 @@ impl GeoPst3d {
     /// v.push(label::Label::new(180., 90., 1., 10, 1, 1.5, "T10".to_string()));
     ///
     /// let _ = pst_3d::GeoPst3d::new(v);
+    ///
+    /// // labels outside of the valid range are reported by try_new
+    /// let v = vec![label::Label::new(100., 100., 9., 1, 1, 1.5, "T1".to_string())];
+    /// assert!(pst_3d::GeoPst3d::try_new(v).is_err());
+    ///
+    /// let v = vec![label::Label::new(200., 90., 9., 1, 1, 1.5, "T1".to_string())];
+    /// assert!(pst_3d::GeoPst3d::try_new(v).is_err());
     /// ```
     ///
-    /// ```should_panic
-    /// use rt_datastructure::primitives::label;
+    pub fn new(labels: Vec<Label>) -> GeoPst3d {
+        GeoPst3d::new_with_crs(labels, Crs::Wgs84)
+    }
+
+    ///
+    /// Initialize a new 3D PST from the given label vector with coordinates in the given
+    /// coordinate reference system.
+    ///
+    /// Queries are performed in the coordinates of the reference system, the wraparound happens
+    /// at the left and right border of its extent.
+    ///
+    /// # Panics
+    /// * if a label is not within the extent of the reference system
+    /// * if t is not finite
+    ///
+    /// With the `panic-free` feature the coordinates are clamped to the extent instead and labels
+    /// with a non finite t are dropped.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::{bbox, label};
+    /// use rt_datastructure::primitives::crs::Crs;
     /// use rt_datastructure::pst_3d;
     ///
     /// let mut v = Vec::new();
-    /// v.push(label::Label::new(100., 100., 9., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(1021868., 6233722., 2., 1, 1, 1.5, "Stuttgart".to_string()));
+    /// v.push(label::Label::new(20037508., 0., 1., 2, 1, 1.5, "Dateline".to_string()));
     ///
-    /// let _ = pst_3d::GeoPst3d::new(v);
+    /// let t = pst_3d::GeoPst3d::new_with_crs(v, Crs::WebMercator);
+    ///
+    /// let bb = bbox::BBox::new(1e6, 6e6, 1.1e6, 6.3e6);
+    /// assert!(t.get(&bb, 0.).len() == 1);
+    ///
+    /// // wraparound at the border of the Web Mercator extent
+    /// let bb = bbox::BBox::new(2e7, -1e5, -2e7, 1e5);
+    /// assert!(t.get(&bb, 0.)[0].get_osm_id() == 2);
     /// ```
     ///
-    /// ```should_panic
+    pub fn new_with_crs(mut labels: Vec<Label>, crs: Crs) -> GeoPst3d {
+        // ensure that each Label has valid coordinates
+        let bbox = crs.get_extent();
+        for l in &mut labels {
+            let contained = bbox.is_contained(l);
+            validate!(contained,
+                      "Label coordinates out of bounds: osm id {}, x {}, y {}",
+                      l.get_osm_id(),
+                      l.get_x(),
+                      l.get_y());
+
+            // without panics the coordinates are clamped to the valid range
+            if !contained {
+                let x = l.get_x().max(bbox.get_min_x()).min(bbox.get_max_x());
+                let y = l.get_y().max(bbox.get_min_y()).min(bbox.get_max_y());
+                l.set_position(x, y);
+            }
+        }
+
+        GeoPst3d::from_pst(Pst3d::new(labels), crs)
+    }
+
+    ///
+    /// Initialize a new 3D PST from the given label vector, if all labels have valid
+    /// coordinates.
+    ///
+    /// # Errors
+    ///   * Validation: if a label is not within the bounds -180 <= x <= 180 and -90 <= y <= 90,
+    ///     e.g. due to a NaN coordinate, or has a non finite t value. The error lists the
+    ///     position in the input, the osm id and the values of the offending labels.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::f64;
     /// use rt_datastructure::primitives::label;
     /// use rt_datastructure::pst_3d;
     ///
     /// let mut v = Vec::new();
-    /// v.push(label::Label::new(200., 90., 9., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(10., 20., 9., 1, 1, 1.5, "T1".to_string()));
+    /// assert!(pst_3d::GeoPst3d::try_new(v.clone()).is_ok());
     ///
-    /// let _ = pst_3d::GeoPst3d::new(v);
+    /// v.push(label::Label::new(200., 20., 8., 2, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(f64::NAN, 20., 8., 3, 1, 1.5, "T3".to_string()));
+    /// let e = pst_3d::GeoPst3d::try_new(v).err().unwrap();
+    /// assert!(e.get_code() == 3);
+    /// assert!(e.to_string().ends_with("#1 (osm id 2, x 200, y 20, t 8), \
+    ///                                  #2 (osm id 3, x NaN, y 20, t 8)"));
     /// ```
     ///
-    pub fn new(mut labels: Vec<Label>) -> GeoPst3d {
-        // ensure that each Label has valid coordinates
-        let bbox = BBox::new(-180., -90., 180., 90.);
-        for l in &labels {
-            if !bbox.is_contained(&l) {
-                panic!("Label coordinates out of bounds");
-            }
+    pub fn try_new(labels: Vec<Label>) -> Result<GeoPst3d, Error> {
+        GeoPst3d::try_new_with_crs(labels, Crs::Wgs84)
+    }
+
+    ///
+    /// Initialize a new 3D PST from the given label vector with coordinates in the given
+    /// coordinate reference system, if all labels have valid coordinates. Compare
+    /// `new_with_crs`.
+    ///
+    /// # Errors
+    ///   * Validation: if a label is not within the extent of the reference system or has a non
+    ///     finite t value. The error lists the position in the input, the osm id and the values
+    ///     of the offending labels.
+    ///
+    pub fn try_new_with_crs(labels: Vec<Label>, crs: Crs) -> Result<GeoPst3d, Error> {
+        let bbox = crs.get_extent();
+        let invalid: Vec<String> = labels.iter()
+            .enumerate()
+            .filter(|&(_, l)| !bbox.is_contained(l) || !l.get_t().is_finite())
+            .map(|(idx, l)| {
+                format!("#{} (osm id {}, x {}, y {}, t {})",
+                        idx,
+                        l.get_osm_id(),
+                        l.get_x(),
+                        l.get_y(),
+                        l.get_t())
+            })
+            .collect();
+        if !invalid.is_empty() {
+            return Err(Error::Validation(format!("Invalid coordinates or t values of the labels {}",
+                                                 list_labels(&invalid))));
+        }
+
+        Pst3d::try_new(labels).map(|pst| GeoPst3d::from_pst(pst, crs))
+    }
+
+    ///
+    /// Wrap an existing 3D PST, e.g. a tree loaded by `Pst3d::read_tree`, if all labels have valid
+    /// coordinates.
+    ///
+    /// # Errors
+    ///   * Validation: if a label is not within the bounds -180 <= x <= 180 and -90 <= y <= 90
+    ///
+    pub fn try_from_pst(pst: Pst3d) -> Result<GeoPst3d, Error> {
+        GeoPst3d::try_from_pst_with_crs(pst, Crs::Wgs84)
+    }
+
+    ///
+    /// Wrap an existing 3D PST with coordinates in the given coordinate reference system, if all
+    /// labels are within the extent of the reference system. Compare `try_from_pst`.
+    ///
+    /// # Errors
+    ///   * Validation: if a label is not within the extent of the reference system
+    ///
+    pub fn try_from_pst_with_crs(pst: Pst3d, crs: Crs) -> Result<GeoPst3d, Error> {
+        let bbox = crs.get_extent();
+        let invalid = (0..pst.get_size() as u32)
+            .filter_map(|idx| pst.get_label(idx))
+            .find(|l| !bbox.is_contained(*l));
+        if let Some(l) = invalid {
+            return Err(Error::Validation(format!("Label {} coordinates out of bounds",
+                                                 l.get_osm_id())));
         }
 
-        GeoPst3d { m_pst: Pst3d::new(labels) }
+        Ok(GeoPst3d::from_pst(pst, crs))
+    }
+
+    ///
+    /// Get the wrapped 3D PST.
+    ///
+    pub fn get_pst(&self) -> &Pst3d {
+        &self.m_pst
+    }
+
+    ///
+    /// Get the coordinate reference system of the labels.
+    ///
+    pub fn get_crs(&self) -> &Crs {
+        &self.m_crs
+    }
+
+    // Wrap the given tree with the default settings.
+    fn from_pst(pst: Pst3d, crs: Crs) -> GeoPst3d {
+        GeoPst3d {
+            m_pst: pst,
+            m_crs: crs,
+            m_name_fallback: NameFallback::default(),
+            m_cache: Mutex::new(None),
+        }
     }
 
     ///
     /// Return the set of label in the given bounding box with a t >= min_t.
     ///
     /// The getter supports a wraparound. So a request for a bounding box with min lon = 170 and
-    /// max lon = -170 is valid
+    /// max lon = -170 is valid. Longitudes outside of [-180, 180] are wrapped around as well, a
+    /// box spanning at least 360 degrees of longitude covers the whole globe. Latitudes are
+    /// clamped to [-90, 90], so a box around a pole may exceed the valid latitude range.
     ///
     /// # Examples
     /// ```
@@ -170,99 +427,207 @@ impl GeoPst3d {
     /// assert!(r.len() == 4);
     /// ```
     ///
+    /// around the north pole:
+    ///
+    /// ```
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(-120., 85., 3., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(60., 90., 2., 2, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(175., 80., 1., 3, 1, 1.5, "T3".to_string()));
+    /// v.push(label::Label::new(0., 0., 4., 4, 1, 1.5, "T4".to_string()));
+    ///
+    /// let t = pst_3d::GeoPst3d::new(v);
+    ///
+    /// // the latitude is clamped, the longitude covers the whole globe
+    /// assert!(t.get(&bbox::BBox::new(-200., 75., 200., 95.), 0.).len() == 3);
+    ///
+    /// // unwrapped longitudes beyond the antimeridian
+    /// assert!(t.get(&bbox::BBox::new(170., 75., 250., 91.), 0.).len() == 2);
+    /// ```
+    ///
     pub fn get<'a>(&'a self, bbox: &BBox, min_t: f64) -> Vec<&'a Label> {
-        // Check if bbox includes the -180/180 meridian.
-        // If this is the case, split the request into two valid requests.
-        if bbox.get_max_x() < bbox.get_min_x() {
-            let mut res = self.m_pst
-                .get(&BBox::new(bbox.get_min_x(), bbox.get_min_y(), 180., bbox.get_max_y()),
-                     min_t);
-            res.append(&mut self.m_pst
-                                .get(&BBox::new(-180.,
-                                                bbox.get_min_y(),
-                                                bbox.get_max_x(),
-                                                bbox.get_max_y()),
-                                     min_t));
+        self.get_indices(bbox, min_t)
+            .into_iter()
+            .filter_map(|idx| self.m_pst.get_label(idx))
+            .collect()
+    }
 
-            return res;
-        }
+    ///
+    /// Return the indices of the labels in the given bounding box with a t >= min_t.
+    ///
+    /// Like the getter this function supports a wraparound. The indices can be resolved to the
+    /// corresponding labels by `get_label`.
+    ///
+    pub fn get_indices(&self, bbox: &BBox, min_t: f64) -> Vec<u32> {
+        self.query_parts(bbox, |part| self.m_pst.get_indices(part, min_t))
+    }
+
+    ///
+    /// Return the labels in the given bounding box with a t >= min_t together with statistics
+    /// about the traversal of the tree.
+    ///
+    /// Like the getter this function supports a wraparound, the statistics of both parts of the
+    /// query are added up.
+    ///
+    pub fn get_with_stats<'a>(&'a self, bbox: &BBox, min_t: f64) -> (Vec<&'a Label>, QueryStats) {
+        let mut stats = QueryStats::default();
+        let res: Vec<&Label> = self.query_parts(bbox, |part| {
+                let (indices, part_stats) = self.m_pst.get_indices_with_stats(part, min_t);
+                stats.add(&part_stats);
+                indices
+            })
+            .into_iter()
+            .filter_map(|idx| self.m_pst.get_label(idx))
+            .collect();
+
+        let stats = QueryStats::new(stats.get_nodes_visited(),
+                                    stats.get_subtrees_pruned(),
+                                    stats.get_labels_tested(),
+                                    res.len());
+        (res, stats)
+    }
+
+    ///
+    /// Return the set of labels in the given bounding box with t_low <= t <= t_high.
+    ///
+    /// Like the getter this function supports a wraparound. Compare
+    /// [Pst3d::get_t_interval](struct.Pst3d.html#method.get_t_interval).
+    ///
+    pub fn get_t_interval(&self, bbox: &BBox, t_low: f64, t_high: f64) -> Vec<&Label> {
+        self.get_t_interval_indices(bbox, t_low, t_high)
+            .into_iter()
+            .filter_map(|idx| self.m_pst.get_label(idx))
+            .collect()
+    }
 
-        self.m_pst.get(&bbox, min_t)
+    ///
+    /// Return the indices of the labels in the given bounding box with t_low <= t <= t_high.
+    /// Compare `get_t_interval`.
+    ///
+    pub fn get_t_interval_indices(&self, bbox: &BBox, t_low: f64, t_high: f64) -> Vec<u32> {
+        self.m_pst.retain_up_to(self.get_indices(bbox, t_low.next_down()), t_high)
     }
 
     ///
-    /// Create a human readable string representation of the tree.
+    /// Return the set of label in the given bounding box with a t >= min_t post processed by the
+    /// given query options.
     ///
-    /// The function returns a multiline string with one row for each tree node. Large trees will
-    /// produce a huge multiline string!
+    /// Like the getter this function supports a wraparound, also if the bounding box crosses the
+    /// antimeridian only after it has been expanded by the margin of the options. Compare
+    /// [Pst3d::get_with_options](struct.Pst3d.html#method.get_with_options).
     ///
     /// # Examples
     /// ```
-    /// use rt_datastructure::primitives::label;
+    /// use rt_datastructure::primitives::{label, bbox};
     /// use rt_datastructure::pst_3d;
+    /// use rt_datastructure::pst_3d::query;
     ///
     /// let mut v = Vec::new();
-    /// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
-    /// v.push(label::Label::new(2., 3., 9., 2, 1, 1.5, "T2".to_string()));
-    /// v.push(label::Label::new(3., 4., 8., 3, 1, 1.5, "T3".to_string()));
-    /// v.push(label::Label::new(4., 5., 7., 4, 1, 1.5, "T4".to_string()));
-    /// v.push(label::Label::new(5., 6., 6., 5, 1, 1.5, "T5".to_string()));
-    /// v.push(label::Label::new(6., 7., 5., 6, 1, 1.5, "T6".to_string()));
-    /// v.push(label::Label::new(7., 8., 4., 7, 1, 1.5, "T7".to_string()));
-    /// v.push(label::Label::new(8., 9., 3., 8, 1, 1.5, "T8".to_string()));
-    /// v.push(label::Label::new(9., 10., 2., 9, 1, 1.5, "T9".to_string()));
-    /// v.push(label::Label::new(10., 11., 1., 10, 1, 1.5, "T10".to_string()));
+    /// v.push(label::Label::new(179.5, 10., 3., 1, 1, 1.5, "Fiji".to_string()));
+    /// v.push(label::Label::new(-179.5, 10., 2., 2, 1, 1.5, "Samoa".to_string()));
+    /// v.push(label::Label::new(170., 10., 1., 3, 1, 1.5, "Vanuatu".to_string()));
     ///
-    /// let t = pst_3d::Pst3d::new(v);
+    /// let t = pst_3d::GeoPst3d::new(v);
+    /// let bb = bbox::BBox::new(175., 5., 179., 15.);
+    ///
+    /// let mut o = query::QueryOptions::new();
+    /// assert!(t.get_with_options(&bb, 0., &o).is_empty());
     ///
-    /// let res_string = "\
-    ///   x-node (split: 5): Label [#1]: 'T1' at (1, 2) with prio 1, elim-t: 10 and label \
-    ///                                                                              factor: 1.5\n\
-    ///   l    y-node (split: 4): Label [#2]: 'T2' at (2, 3) with prio 1, elim-t: 9 and label \
-    ///                                                                              factor: 1.5\n\
-    ///   l        x-node (split: NaN): Label [#3]: 'T3' at (3, 4) with prio 1, elim-t: 8 and \
-    ///                                                                        label factor: 1.5\n\
-    ///   r        x-node (split: 5): Label [#4]: 'T4' at (4, 5) with prio 1, elim-t: 7 and label \
-    ///                                                                              factor: 1.5\n\
-    ///   l            y-node (split: NaN): Label [#5]: 'T5' at (5, 6) with prio 1, elim-t: 6 and \
-    ///                                                                        label factor: 1.5\n\
-    ///   r    y-node (split: 9): Label [#6]: 'T6' at (6, 7) with prio 1, elim-t: 5 and label \
-    ///                                                                              factor: 1.5\n\
-    ///   l        x-node (split: 8): Label [#7]: 'T7' at (7, 8) with prio 1, elim-t: 4 and label \
-    ///                                                                              factor: 1.5\n\
-    ///   l            y-node (split: NaN): Label [#8]: 'T8' at (8, 9) with prio 1, elim-t: 3 and \
-    ///                                                                        label factor: 1.5\n\
-    ///   r        x-node (split: 10): Label [#9]: 'T9' at (9, 10) with prio 1, elim-t: 2 and \
-    ///                                                                        label factor: 1.5\n\
-    ///   l            y-node (split: NaN): Label [#10]: 'T10' at (10, 11) with prio 1, elim-t: 1 \
-    ///                                                                      and label factor: 1.5\
-    ///   ".to_string();
-    ///
-    /// println!("Tree after construction:\n{}", t.to_string());
-    ///
-    /// assert!(t.to_string() == res_string);
-    /// ```
-    ///
-    pub fn to_string(&self) -> String {
-        self.m_pst.to_string()
+    /// o.set_margin(1.5);
+    /// let r = t.get_with_options(&bb, 0., &o);
+    /// assert!(r.len() == 2);
+    /// assert!(r[1].get_label().get_osm_id() == 2);
+    /// ```
+    ///
+    pub fn get_with_options(&self,
+                            bbox: &BBox,
+                            min_t: f64,
+                            options: &QueryOptions)
+                            -> Vec<QueryResult<'_>> {
+        let bbox = options.expand_bbox(bbox);
+
+        // the limit is applied after the filters, so all labels are collected by decreasing t
+        let indices = match options.get_limit() {
+            Some(_) => self.get_limited_indices(&bbox, min_t, Some(usize::MAX)),
+            None => self.get_indices(&bbox, min_t),
+        };
+        query::apply_options_by(self.m_pst.get_prioritized(indices), min_t, options)
     }
-}
 
-///
-/// A struct to store the 3d PST and provide a basic interface
-///
-pub struct Pst3d {
-    m_bbox: BBox,
+    ///
+    /// Return the set of label in the given bounding box with a t >= min_t, but at most limit
+    /// labels.
+    ///
+    /// Like the getter this function supports a wraparound. Compare
+    /// [Pst3d::get_limited](struct.Pst3d.html#method.get_limited).
+    ///
+    pub fn get_limited(&self, bbox: &BBox, min_t: f64, limit: Option<usize>) -> Vec<&Label> {
+        self.get_limited_indices(bbox, min_t, limit)
+            .into_iter()
+            .filter_map(|idx| self.m_pst.get_label(idx))
+            .collect()
+    }
 
-    m_data: Vec<Root>,
-    m_root_idx: Option<usize>,
-}
+    ///
+    /// Return the indices of the labels in the given bounding box with a t >= min_t, but at most
+    /// limit indices. Compare `get_limited`.
+    ///
+    pub fn get_limited_indices(&self, bbox: &BBox, min_t: f64, limit: Option<usize>) -> Vec<u32> {
+        let limit = match limit {
+            Some(limit) => limit,
+            None => return self.get_indices(bbox, min_t),
+        };
+
+        let mut res = self.query_parts(bbox, |part| {
+            self.m_pst.get_limited_indices(part, min_t, Some(limit))
+        });
+
+        // keep the labels with the largest t values of both halves ordered by decreasing t
+        if res.len() > 1 {
+            res.sort_by(|first, second| {
+                            let t_first = self.m_pst.get_priority(*first);
+                            let t_second = self.m_pst.get_priority(*second);
+                            t_second.partial_cmp(&t_first).unwrap_or(Ordering::Equal)
+                        });
+            res.truncate(limit);
+        }
+
+        res
+    }
+
+    // Run the given query for each part of the bounding box split at the antimeridian and
+    // concatenate the results. A label on the common border of both parts is reported once.
+    fn query_parts<F>(&self, bbox: &BBox, mut query: F) -> Vec<u32>
+        where F: FnMut(&BBox) -> Vec<u32>
+    {
+        let parts = split_geo_box(bbox, &self.m_crs.get_extent());
+        let mut res = Vec::new();
+        for part in &parts {
+            res.append(&mut query(part));
+        }
+
+        if parts.len() > 1 {
+            let mut seen = HashSet::new();
+            res.retain(|idx| seen.insert(*idx));
+        }
+
+        res
+    }
 
-impl Pst3d {
     ///
-    /// Initialize a new 3D PST from the given label vector.
+    /// Return the set of labels in the Web Mercator tile z/x/y with a t >= min_t.
     ///
-    /// Take care: The procedure will consume the given vector!
+    /// The tile numbering follows the slippy map convention, i.e. the tile 0/0/0 covers the
+    /// whole map and y grows from north to south. The tile is converted into the coordinate
+    /// reference system of the tree. Both tile coordinates must be within the map, i.e. x and y
+    /// must be less than 2^z, copies of the world left and right of the map are not numbered.
+    /// Labels on the common border of two tiles are contained in both tiles.
+    ///
+    /// # Errors
+    ///   * Query: if z > MAX_TILE_ZOOM, x >= 2^z or y >= 2^z
     ///
     /// # Examples
     /// ```
@@ -270,140 +635,2305 @@ impl Pst3d {
     /// use rt_datastructure::pst_3d;
     ///
     /// let mut v = Vec::new();
-    /// v.push(label::Label::new(1., 2., 9., 1, 1, 1.5, "T1".to_string()));
-    /// v.push(label::Label::new(2., 3., 8., 2, 1, 1.5, "T2".to_string()));
-    /// v.push(label::Label::new(3., 4., 7., 3, 1, 1.5, "T3".to_string()));
-    /// v.push(label::Label::new(3., 5., 7., 4, 1, 1.5, "T4".to_string()));
-    /// v.push(label::Label::new(4., 6., 6., 5, 1, 1.5, "T5".to_string()));
-    /// v.push(label::Label::new(5., 7., 5., 6, 1, 1.5, "T6".to_string()));
-    /// v.push(label::Label::new(6., 8., 4., 7, 1, 1.5, "T7".to_string()));
-    /// v.push(label::Label::new(7., 9., 3., 8, 1, 1.5, "T8".to_string()));
-    /// v.push(label::Label::new(8., 10., 2., 9, 1, 1.5, "T9".to_string()));
-    /// v.push(label::Label::new(9., 11., 1., 10, 1, 1.5, "T10".to_string()));
+    /// v.push(label::Label::new(9.18, 48.78, 3., 1, 1, 1.5, "Stuttgart".to_string()));
+    /// v.push(label::Label::new(-74.01, 40.71, 2., 2, 1, 1.5, "New York".to_string()));
+    /// v.push(label::Label::new(151.21, -33.87, 1., 3, 1, 1.5, "Sydney".to_string()));
     ///
-    /// let t = pst_3d::Pst3d::new(v.clone());
+    /// let t = pst_3d::GeoPst3d::new(v);
+    ///
+    /// assert!(t.get_tile(0, 0, 0, 0.).unwrap().len() == 3);
+    ///
+    /// let r = t.get_tile(1, 1, 0, 0.).unwrap();
+    /// assert!(r.len() == 1);
+    /// assert!(r[0].get_osm_id() == 1);
+    ///
+    /// assert!(t.get_tile(1, 0, 0, 0.).unwrap()[0].get_osm_id() == 2);
+    ///
+    /// // tiles outside of the map
+    /// assert!(t.get_tile(1, 2, 0, 0.).is_err());
+    /// assert!(t.get_tile(1, 0, 2, 0.).is_err());
     /// ```
     ///
-    pub fn new(mut labels: Vec<Label>) -> Pst3d {
-        labels.sort_by(Label::order_t);
-        labels.reverse();
-
-        let mut v: Vec<Root> = Vec::with_capacity(labels.len());
-        let mut bbox = BBox::new_empty();
+    pub fn get_tile(&self, z: u32, x: u32, y: u32, min_t: f64) -> Result<Vec<&Label>, Error> {
+        let tile = GeoPst3d::get_tile_bbox(z, x, y)?;
+        let (x1, y1) = self.m_crs.from_wgs84(tile.get_min_x(), tile.get_min_y());
+        let (x2, y2) = self.m_crs.from_wgs84(tile.get_max_x(), tile.get_max_y());
 
-        for mut l in labels {
-            bbox.add_to_box(&mut l);
+        Ok(self.get(&BBox::new(x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2)), min_t))
+    }
 
-            v.push(Root::new(l));
+    ///
+    /// Get the bounding box in lon/lat coordinates of the Web Mercator tile z/x/y. Compare
+    /// `get_tile`.
+    ///
+    /// # Errors
+    ///   * Query: if z > MAX_TILE_ZOOM, x >= 2^z or y >= 2^z
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let bb = pst_3d::GeoPst3d::get_tile_bbox(1, 0, 1).unwrap();
+    /// assert!(bb.get_min_x() == -180. && bb.get_max_x() == 0.);
+    /// assert!(bb.get_max_y() == 0.);
+    /// assert!((bb.get_min_y() + 85.0511).abs() < 1e-4);
+    /// ```
+    ///
+    pub fn get_tile_bbox(z: u32, x: u32, y: u32) -> Result<BBox, Error> {
+        if z > MAX_TILE_ZOOM {
+            return Err(Error::Query(format!("Tile zoom level {} exceeds {}", z, MAX_TILE_ZOOM)));
         }
 
-        let tree_root = Root::init_pst3d(&mut v);
+        let n = 1u64 << z;
+        if u64::from(x) >= n {
+            return Err(Error::Query(format!("Tile column {} out of range at zoom level {}", x, z)));
+        }
+        if u64::from(y) >= n {
+            return Err(Error::Query(format!("Tile row {} out of range at zoom level {}", y, z)));
+        }
 
-        Pst3d {
-            m_bbox: bbox,
+        let x = u64::from(x);
+        let lon = |x: u64| x as f64 / n as f64 * 360. - 180.;
+        let lat = |y: u64| {
+            (f64::consts::PI * (1. - 2. * y as f64 / n as f64)).sinh().atan().to_degrees()
+        };
 
-            m_data: v,
-            m_root_idx: tree_root,
-        }
+        Ok(BBox::new(lon(x), lat(u64::from(y) + 1), lon(x + 1), lat(u64::from(y))))
     }
 
     ///
-    /// Return the set of label in the given bounding box with a t >= min_t.
+    /// Return the set of labels with a t >= min_t within the given radius in meters around the
+    /// center given in longitude/latitude degrees.
+    ///
+    /// The tree is queried with the bounding box of the circle (see `geo::bbox_around`),
+    /// afterwards the great circle distance of each result is checked. The query wraps around
+    /// the antimeridian.
+    ///
+    /// # Errors
+    ///   * Query: if the coordinate reference system of the tree is not WGS84
     ///
     /// # Examples
     /// ```
-    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::primitives::label;
+    /// use rt_datastructure::primitives::point::Point;
     /// use rt_datastructure::pst_3d;
     ///
     /// let mut v = Vec::new();
-    /// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
-    /// v.push(label::Label::new(2., 3., 9., 2, 1, 1.5, "T2".to_string()));
-    /// v.push(label::Label::new(3., 4., 8., 3, 1, 1.5, "T3".to_string()));
-    /// v.push(label::Label::new(4., 5., 7., 4, 1, 1.5, "T4".to_string()));
-    /// v.push(label::Label::new(5., 6., 6., 5, 1, 1.5, "T5".to_string()));
-    /// v.push(label::Label::new(6., 7., 5., 6, 1, 1.5, "T6".to_string()));
-    /// v.push(label::Label::new(7., 8., 4., 7, 1, 1.5, "T7".to_string()));
-    /// v.push(label::Label::new(8., 9., 3., 8, 1, 1.5, "T8".to_string()));
-    /// v.push(label::Label::new(9., 10., 2., 9, 1, 1.5, "T9".to_string()));
-    /// v.push(label::Label::new(10., 11., 1., 10, 1, 1.5, "T10".to_string()));
+    /// v.push(label::Label::new(9.18, 48.78, 3., 1, 1, 1.5, "Stuttgart".to_string()));
+    /// v.push(label::Label::new(8.40, 49.01, 2., 2, 1, 1.5, "Karlsruhe".to_string()));
+    /// v.push(label::Label::new(11.58, 48.14, 1., 3, 1, 1.5, "Munich".to_string()));
     ///
-    /// let t = pst_3d::Pst3d::new(v);
-    ///
-    /// let bb = bbox::BBox::new(4., 5., 7., 8.);
-    /// let r = t.get(&bb, 4.);
+    /// let t = pst_3d::GeoPst3d::new(v);
     ///
-    /// for e in &r {
-    ///   println!("{}, ", e.to_string());
-    /// }
+    /// let r = t.get_within_meters(&Point::new(9.18, 48.78), 100000., 0.).unwrap();
+    /// assert!(r.len() == 2);
     ///
-    /// // resulting labels are T5 to  T7
+    /// let r = t.get_within_meters(&Point::new(9.18, 48.78), 200000., 0.).unwrap();
     /// assert!(r.len() == 3);
     /// ```
     ///
-    pub fn get<'a>(&'a self, bbox: &BBox, min_t: f64) -> Vec<&'a Label> {
-        match self.m_root_idx {
-            Some(idx) => self.m_data[idx].get(&bbox, min_t, &self.m_data),
-            None => Vec::new(),
+    pub fn get_within_meters(&self,
+                             center: &Point,
+                             radius: f64,
+                             min_t: f64)
+                             -> Result<Vec<&Label>, Error> {
+        match self.m_crs {
+            Crs::Wgs84 => {}
+            _ => {
+                return Err(Error::Query("Geodesic queries require WGS84 coordinates".to_string()))
+            }
         }
+
+        Ok(self.get(&geo::bbox_around(center, radius), min_t)
+               .into_iter()
+               .filter(|l| geo::haversine_distance(center, *l) <= radius)
+               .collect())
+    }
+
+    ///
+    /// Resolve an index returned by `get_indices` to the corresponding label.
+    ///
+    /// Returns None if the index is out of range.
+    ///
+    pub fn get_label(&self, idx: u32) -> Option<&Label> {
+        self.m_pst.get_label(idx)
+    }
+
+    ///
+    /// Set the name fallback chain used to resolve the names of all query results.
+    ///
+    pub fn set_name_fallback(&mut self, fallback: NameFallback) {
+        self.m_name_fallback = fallback;
+    }
+
+    ///
+    /// Get the name fallback chain used to resolve the names of all query results.
+    ///
+    pub fn get_name_fallback(&self) -> &NameFallback {
+        &self.m_name_fallback
     }
 
     ///
-    /// Create a human readable string representation of the tree.
+    /// Resolve the name of the given label with the configured name fallback chain or the given
+    /// override.
     ///
-    /// The function returns a multiline string with one row for each tree node. Large trees will
-    /// produce a huge multiline string!
     ///
     /// # Examples
     /// ```
     /// use rt_datastructure::primitives::label;
     /// use rt_datastructure::pst_3d;
+    /// use rt_datastructure::pst_3d::query::NameFallback;
     ///
-    /// let mut v = Vec::new();
-    /// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
-    /// v.push(label::Label::new(2., 3., 9., 2, 1, 1.5, "T2".to_string()));
-    /// v.push(label::Label::new(3., 4., 8., 3, 1, 1.5, "T3".to_string()));
-    /// v.push(label::Label::new(4., 5., 7., 4, 1, 1.5, "T4".to_string()));
-    /// v.push(label::Label::new(5., 6., 6., 5, 1, 1.5, "T5".to_string()));
-    /// v.push(label::Label::new(6., 7., 5., 6, 1, 1.5, "T6".to_string()));
-    /// v.push(label::Label::new(7., 8., 4., 7, 1, 1.5, "T7".to_string()));
-    /// v.push(label::Label::new(8., 9., 3., 8, 1, 1.5, "T8".to_string()));
-    /// v.push(label::Label::new(9., 10., 2., 9, 1, 1.5, "T9".to_string()));
-    /// v.push(label::Label::new(10., 11., 1., 10, 1, 1.5, "T10".to_string()));
+    /// let mut l = label::Label::new(11.58, 48.14, 0.9, 1, 16, 1.5, "München".to_string());
+    /// l.set_name("name:en".to_string(), "Munich".to_string());
+    /// l.set_name("name:it".to_string(), "Monaco di Baviera".to_string());
     ///
-    /// let t = pst_3d::Pst3d::new(v);
+    /// let mut t = pst_3d::GeoPst3d::new(vec![l]);
+    /// t.set_name_fallback(NameFallback::from_list("name:en,name"));
     ///
-    /// let res_string = "\
-    ///   x-node (split: 5): Label [#1]: 'T1' at (1, 2) with prio 1, elim-t: 10 and label \
-    ///                                                                              factor: 1.5\n\
-    ///   l    y-node (split: 4): Label [#2]: 'T2' at (2, 3) with prio 1, elim-t: 9 and label \
-    ///                                                                              factor: 1.5\n\
-    ///   l        x-node (split: NaN): Label [#3]: 'T3' at (3, 4) with prio 1, elim-t: 8 and \
-    ///                                                                        label factor: 1.5\n\
-    ///   r        x-node (split: 5): Label [#4]: 'T4' at (4, 5) with prio 1, elim-t: 7 and label \
-    ///                                                                              factor: 1.5\n\
-    ///   l            y-node (split: NaN): Label [#5]: 'T5' at (5, 6) with prio 1, elim-t: 6 and \
-    ///                                                                        label factor: 1.5\n\
-    ///   r    y-node (split: 9): Label [#6]: 'T6' at (6, 7) with prio 1, elim-t: 5 and label \
-    ///                                                                              factor: 1.5\n\
-    ///   l        x-node (split: 8): Label [#7]: 'T7' at (7, 8) with prio 1, elim-t: 4 and label \
-    ///                                                                              factor: 1.5\n\
-    ///   l            y-node (split: NaN): Label [#8]: 'T8' at (8, 9) with prio 1, elim-t: 3 and \
-    ///                                                                        label factor: 1.5\n\
-    ///   r        x-node (split: 10): Label [#9]: 'T9' at (9, 10) with prio 1, elim-t: 2 and \
-    ///                                                                        label factor: 1.5\n\
-    ///   l            y-node (split: NaN): Label [#10]: 'T10' at (10, 11) with prio 1, elim-t: 1 \
-    ///                                                                      and label factor: 1.5\
-    ///   ".to_string();
-    ///
-    /// println!("Tree after construction:\n{}", t.to_string());
-    ///
-    /// assert!(t.to_string() == res_string);
-    /// ```
-    ///
-    pub fn to_string(&self) -> String {
-        match self.m_root_idx {
-            Some(idx) => self.m_data[idx].to_string(0, &self.m_data),
-            None => "PSKdT is empty!".to_string(),
-        }
+    /// let l = t.get_label(0).unwrap();
+    /// assert!(*t.get_name(l, None) == "Munich".to_string());
+    ///
+    /// let italian = NameFallback::from_list("name:it");
+    /// assert!(*t.get_name(l, Some(&italian)) == "Monaco di Baviera".to_string());
+    /// ```
+    ///
+    pub fn get_name<'a>(&self, l: &'a Label, fallback: Option<&NameFallback>) -> &'a String {
+        fallback.unwrap_or(&self.m_name_fallback).resolve(l)
+    }
+
+    ///
+    /// Iterate over all stored labels in increasing osm_id order, see
+    /// `Pst3d::iter_all_sorted_by_id`.
+    ///
+    pub fn iter_all_sorted_by_id(&self) -> impl Iterator<Item = &Label> {
+        self.m_pst.iter_all_sorted_by_id()
+    }
+
+    ///
+    /// Get the generation id of the tree.
+    ///
+    pub fn get_generation(&self) -> u64 {
+        self.m_pst.get_generation()
+    }
+
+    ///
+    /// Return the set of label in the given bounding box with a t >= min_t together with the
+    /// generation id of the tree.
+    ///
+    /// Like the getter this function supports a wraparound.
+    ///
+    pub fn get_versioned(&self, bbox: &BBox, min_t: f64) -> VersionedResult<'_> {
+        VersionedResult::new(self.get_generation(), self.get(bbox, min_t))
+    }
+
+    ///
+    /// Create a Graphviz DOT representation of the tree, see `Pst3d::to_dot`.
+    ///
+    pub fn to_dot(&self) -> String {
+        self.m_pst.to_dot()
+    }
+
+    ///
+    /// Reconstruct a balanced tree from the current label set.
+    ///
+    /// Indices returned by `get_indices` before the rebuild are invalidated.
+    ///
+    /// # Errors
+    /// * Shared: if the nodes are still shared with a snapshot, see `Pst3d::rebuild`
+    ///
+    pub fn rebuild(&mut self) -> Result<(), Error> {
+        self.m_pst.rebuild()?;
+        self.invalidate_cache();
+
+        Ok(())
+    }
+
+    ///
+    /// Set the default visibility hysteresis delta of the dataset.
+    ///
+    pub fn set_hysteresis(&mut self, delta: f64) {
+        self.m_pst.set_hysteresis(delta);
+    }
+
+    ///
+    /// Start counting how often each label is reported by a query, see
+    /// `Pst3d::enable_access_tracking`.
+    ///
+    pub fn enable_access_tracking(&mut self) {
+        self.m_pst.enable_access_tracking();
+    }
+
+    ///
+    /// Stop counting how often each label is reported and discard all counters.
+    ///
+    pub fn disable_access_tracking(&mut self) {
+        self.m_pst.disable_access_tracking();
+    }
+
+    ///
+    /// Return the bounding boxes of the maximal subtrees whose labels were reported at most
+    /// max_hits times, see `Pst3d::get_cold_regions`.
+    ///
+    pub fn get_cold_regions(&self, max_hits: u64) -> Vec<BBox> {
+        self.m_pst.get_cold_regions(max_hits)
+    }
+
+    ///
+    /// Flatten the cold regions of the tree into leaf buckets and return the number of released
+    /// tree nodes, see `Pst3d::flatten_cold_regions`. Cached results are discarded.
+    ///
+    /// # Errors
+    /// * Shared: if the nodes are still shared with a snapshot, the tree is unchanged then
+    ///
+    pub fn flatten_cold_regions(&mut self, max_hits: u64) -> Result<usize, Error> {
+        let released = self.m_pst.flatten_cold_regions(max_hits)?;
+        if released > 0 {
+            self.invalidate_cache();
+        }
+
+        Ok(released)
+    }
+
+    ///
+    /// Answer the queries of `get_cached` from the given cache. A previously enabled cache is
+    /// replaced.
+    ///
+    pub fn enable_cache(&mut self, cache: ResultCache) {
+        *self.m_cache.get_mut().unwrap_or_else(|e| e.into_inner()) = Some(cache);
+    }
+
+    ///
+    /// Disable the cache of `get_cached` and discard all cached results.
+    ///
+    pub fn disable_cache(&mut self) {
+        *self.m_cache.get_mut().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    ///
+    /// Invalidate all cached results.
+    ///
+    /// Modifying the tree, i.e. `rebuild` and `flatten_cold_regions`, invalidates the cache
+    /// automatically. Results cached for an older generation of the tree are never returned.
+    ///
+    pub fn invalidate_cache(&mut self) {
+        if let Some(ref mut c) = *self.m_cache.get_mut().unwrap_or_else(|e| e.into_inner()) {
+            c.invalidate();
+        }
+    }
+
+    ///
+    /// Return the set of label in the given bounding box with a t >= min_t using the cache
+    /// enabled by `enable_cache`.
+    ///
+    /// Without an enabled cache the result is the same as for `get`. Otherwise a valid cached
+    /// result of the query is returned or the tree is queried and the result is cached.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::Duration;
+    /// use rt_datastructure::cache::ResultCache;
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(2., 3., 9., 2, 1, 1.5, "T2".to_string()));
+    ///
+    /// let mut t = pst_3d::GeoPst3d::new(v);
+    ///
+    /// let mut c = ResultCache::new(64, Duration::from_secs(60));
+    /// c.set_key_grid(0.01);
+    /// t.enable_cache(c);
+    ///
+    /// assert!(t.get_cached(&bbox::BBox::new(0., 0., 10., 10.), 0.).len() == 2);
+    /// // answered from the cache
+    /// assert!(t.get_cached(&bbox::BBox::new(0.001, 0., 10., 10.), 0.).len() == 2);
+    /// ```
+    ///
+    pub fn get_cached(&self, bbox: &BBox, min_t: f64) -> Vec<&Label> {
+        // the lock is only held to access the cache, concurrent queries are not serialized
+        let cached = self.m_cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_mut()
+            .map(|c| {
+                     c.sync_data_generation(self.get_generation());
+                     c.get(bbox, min_t).cloned()
+                 });
+
+        let indices = match cached {
+            Some(Some(indices)) => indices,
+            None => return self.get(bbox, min_t),
+            Some(None) => {
+                let indices = self.get_indices(bbox, min_t);
+                if let Some(ref mut c) = *self.m_cache.lock().unwrap_or_else(|e| e.into_inner()) {
+                    c.insert(bbox, min_t, indices.clone());
+                }
+                indices
+            }
+        };
+
+        indices.into_iter().filter_map(|idx| self.get_label(idx)).collect()
+    }
+
+    ///
+    /// Return the set of label in the given bounding box with a t >= min_t after applying the
+    /// overrides of the given overlay.
+    ///
+    /// Like the getter this function supports a wraparound.
+    ///
+    pub fn get_with_overlay(&self,
+                            bbox: &BBox,
+                            min_t: f64,
+                            overlay: &PriorityOverlay)
+                            -> Vec<&Label> {
+        let indices = self.get_indices(bbox, min_t - overlay.get_max_boost());
+        overlay.apply_by(self.m_pst.get_prioritized(indices), min_t)
+    }
+
+    ///
+    /// Return the set of label in the given bounding box with a t >= min_t and the labels that
+    /// were visible before and have a t >= min_t - delta.
+    ///
+    /// Like the getter this function supports a wraparound. Compare
+    /// [Pst3d::get_with_hysteresis](struct.Pst3d.html#method.get_with_hysteresis).
+    ///
+    pub fn get_with_hysteresis(&self,
+                               bbox: &BBox,
+                               min_t: f64,
+                               visible: &HashSet<i64>,
+                               delta: Option<f64>)
+                               -> Vec<&Label> {
+        let delta = delta.unwrap_or(self.m_pst.get_hysteresis());
+        let labels = self.m_pst.get_prioritized(self.get_indices(bbox, min_t - delta));
+        query::apply_hysteresis_by(labels, min_t, visible)
+    }
+}
+
+///
+/// Create a human readable string representation of the tree.
+///
+/// The function returns a multiline string with one row for each tree node. Large trees will
+/// produce a huge multiline string!
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::primitives::label;
+/// use rt_datastructure::pst_3d;
+///
+/// let mut v = Vec::new();
+/// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
+/// v.push(label::Label::new(2., 3., 9., 2, 1, 1.5, "T2".to_string()));
+/// v.push(label::Label::new(3., 4., 8., 3, 1, 1.5, "T3".to_string()));
+/// v.push(label::Label::new(4., 5., 7., 4, 1, 1.5, "T4".to_string()));
+/// v.push(label::Label::new(5., 6., 6., 5, 1, 1.5, "T5".to_string()));
+/// v.push(label::Label::new(6., 7., 5., 6, 1, 1.5, "T6".to_string()));
+/// v.push(label::Label::new(7., 8., 4., 7, 1, 1.5, "T7".to_string()));
+/// v.push(label::Label::new(8., 9., 3., 8, 1, 1.5, "T8".to_string()));
+/// v.push(label::Label::new(9., 10., 2., 9, 1, 1.5, "T9".to_string()));
+/// v.push(label::Label::new(10., 11., 1., 10, 1, 1.5, "T10".to_string()));
+///
+/// let t = pst_3d::Pst3d::new(v);
+///
+/// let res_string = "\
+///   x-node (split: 5): Label [#1]: 'T1' at (1, 2) with prio 1, elim-t: 10 and label \
+///                                                                              factor: 1.5\n\
+///   l    y-node (split: 4): Label [#2]: 'T2' at (2, 3) with prio 1, elim-t: 9 and label \
+///                                                                              factor: 1.5\n\
+///   l        x-node (split: NaN): Label [#3]: 'T3' at (3, 4) with prio 1, elim-t: 8 and \
+///                                                                        label factor: 1.5\n\
+///   r        x-node (split: 5): Label [#4]: 'T4' at (4, 5) with prio 1, elim-t: 7 and label \
+///                                                                              factor: 1.5\n\
+///   l            y-node (split: NaN): Label [#5]: 'T5' at (5, 6) with prio 1, elim-t: 6 and \
+///                                                                        label factor: 1.5\n\
+///   r    y-node (split: 9): Label [#6]: 'T6' at (6, 7) with prio 1, elim-t: 5 and label \
+///                                                                              factor: 1.5\n\
+///   l        x-node (split: 8): Label [#7]: 'T7' at (7, 8) with prio 1, elim-t: 4 and label \
+///                                                                              factor: 1.5\n\
+///   l            y-node (split: NaN): Label [#8]: 'T8' at (8, 9) with prio 1, elim-t: 3 and \
+///                                                                        label factor: 1.5\n\
+///   r        x-node (split: 10): Label [#9]: 'T9' at (9, 10) with prio 1, elim-t: 2 and \
+///                                                                        label factor: 1.5\n\
+///   l            y-node (split: NaN): Label [#10]: 'T10' at (10, 11) with prio 1, elim-t: 1 \
+///                                                                      and label factor: 1.5\
+///   ".to_string();
+///
+/// println!("Tree after construction:\n{}", t.to_string());
+///
+/// assert!(t.to_string() == res_string);
+/// ```
+///
+impl fmt::Display for GeoPst3d {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.m_pst)
+    }
+}
+
+impl fmt::Debug for GeoPst3d {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GeoPst3d")
+            .field("pst", &self.m_pst)
+            .field("crs", &self.m_crs)
+            .finish()
+    }
+}
+
+///
+/// A struct to store the 3d PST and provide a basic interface
+///
+/// By default the tree stores labels. Any element type implementing HasPosition and HasT can be
+/// stored instead. The label specific queries (e.g. overlays or hysteresis) are only available
+/// for trees storing labels.
+///
+/// The tree is Send and Sync if the element type is, concurrent queries are supported. Compare
+/// [GeoPst3d](struct.GeoPst3d.html).
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::primitives::bbox;
+/// use rt_datastructure::primitives::traits::{HasPosition, HasT};
+/// use rt_datastructure::pst_3d;
+///
+/// struct Poi {
+///     x: f64,
+///     y: f64,
+///     importance: f64,
+/// }
+///
+/// impl HasPosition for Poi {
+///     fn get_x(&self) -> f64 { self.x }
+///     fn get_y(&self) -> f64 { self.y }
+/// }
+///
+/// impl HasT for Poi {
+///     fn get_t(&self) -> f64 { self.importance }
+/// }
+///
+/// let mut v = Vec::new();
+/// v.push(Poi { x: 1., y: 1., importance: 3. });
+/// v.push(Poi { x: 2., y: 2., importance: 2. });
+/// v.push(Poi { x: 5., y: 5., importance: 1. });
+///
+/// let t = pst_3d::Pst3d::new(v);
+///
+/// let bbox = bbox::BBox::new(0., 0., 3., 3.);
+/// let r = t.get(&bbox, 0.);
+///
+/// assert!(r.len() == 2);
+/// assert!(r.iter().all(|p| p.get_x() <= 3.));
+/// ```
+///
+pub struct Pst3d<T = Label> {
+    m_bbox: BBox,
+
+    m_data: Arc<Tree<T>>,
+    m_root_idx: Option<usize>,
+
+    m_generation: u64,
+    m_hysteresis: f64,
+
+    m_hits: Option<Vec<AtomicU32>>,
+    m_builder: Pst3dBuilder,
+}
+
+impl<T: HasPosition + HasT> Pst3d<T> {
+    ///
+    /// Initialize a new 3D PST from the given label vector.
+    ///
+    /// Take care: The procedure will consume the given vector!
+    ///
+    /// The construction is deterministic: The same label vector always results in the same tree
+    /// and thus in the same query results in the same order, independent of the thread the tree
+    /// is built on.
+    ///
+    /// # Panics
+    /// * if a coordinate or a t value is not finite
+    /// * if there are more than u32::MAX - 1 labels
+    ///
+    /// With the `panic-free` feature labels with non finite values are dropped instead. Use
+    /// `try_new` to handle invalid labels without a panic.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 9., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(2., 3., 8., 2, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(3., 4., 7., 3, 1, 1.5, "T3".to_string()));
+    /// v.push(label::Label::new(3., 5., 7., 4, 1, 1.5, "T4".to_string()));
+    /// v.push(label::Label::new(4., 6., 6., 5, 1, 1.5, "T5".to_string()));
+    /// v.push(label::Label::new(5., 7., 5., 6, 1, 1.5, "T6".to_string()));
+    /// v.push(label::Label::new(6., 8., 4., 7, 1, 1.5, "T7".to_string()));
+    /// v.push(label::Label::new(7., 9., 3., 8, 1, 1.5, "T8".to_string()));
+    /// v.push(label::Label::new(8., 10., 2., 9, 1, 1.5, "T9".to_string()));
+    /// v.push(label::Label::new(9., 11., 1., 10, 1, 1.5, "T10".to_string()));
+    ///
+    /// let t = pst_3d::Pst3d::new(v.clone());
+    /// ```
+    ///
+    pub fn new(labels: Vec<T>) -> Pst3d<T> {
+        Pst3d::new_by(labels, |l| l.get_t())
+    }
+
+    ///
+    /// Initialize a new 3D PST from the given label vector, if all labels have finite
+    /// coordinates and t values.
+    ///
+    /// A NaN coordinate breaks every comparison with the split values, so such a label would
+    /// silently corrupt the tree.
+    ///
+    /// # Errors
+    ///   * Validation: listing the positions of the offending labels in the given vector or if
+    ///     there are too many labels for a single tree
+    ///
+    /// # Examples
+    /// ```
+    /// use std::f64;
+    /// use rt_datastructure::primitives::label;
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(f64::NAN, 3., 9., 2, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(3., 4., f64::INFINITY, 3, 1, 1.5, "T3".to_string()));
+    ///
+    /// let e = pst_3d::Pst3d::try_new(v).err().unwrap();
+    /// assert!(e.to_string().ends_with("of the labels at positions 1, 2"));
+    /// ```
+    ///
+    pub fn try_new(labels: Vec<T>) -> Result<Pst3d<T>, Error> {
+        Pst3d::try_new_by(labels, |l| l.get_t())
+    }
+
+    ///
+    /// Initialize a new 3D PST like `new_by`, if all labels have finite coordinates and
+    /// priorities.
+    ///
+    /// # Errors
+    ///   * Validation: see `try_new`
+    ///
+    pub fn try_new_by<F>(labels: Vec<T>, priority: F) -> Result<Pst3d<T>, Error>
+        where F: Fn(&T) -> f64
+    {
+        let weighted = labels.into_iter().map(|l| (priority(&l), l)).collect();
+
+        Pst3d::try_new_weighted(weighted, Pst3dBuilder::new())
+    }
+
+    ///
+    /// Initialize a new 3D PST from the given label vector using the given function to compute
+    /// the priority of a label instead of its t value.
+    ///
+    /// All queries and the results of `max_t_in` refer to the computed priority.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 1., 1, 30, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(2., 3., 2., 2, 20, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(3., 4., 3., 3, 10, 1.5, "T3".to_string()));
+    ///
+    /// let t = pst_3d::Pst3d::new_by(v, |l| l.get_prio() as f64);
+    ///
+    /// let bbox = bbox::BBox::new(0., 0., 10., 10.);
+    /// let r = t.get(&bbox, 15.);
+    ///
+    /// assert!(r.len() == 2);
+    /// assert!(r.iter().all(|l| l.get_prio() > 15));
+    /// assert!(t.max_t_in(&bbox) == Some(30.));
+    /// ```
+    ///
+    pub fn new_by<F>(labels: Vec<T>, priority: F) -> Pst3d<T>
+        where F: Fn(&T) -> f64
+    {
+        let weighted = labels.into_iter().map(|l| (priority(&l), l)).collect();
+
+        Pst3d::new_weighted(weighted, Pst3dBuilder::new())
+    }
+
+    // Initialize a new 3D PST like new_weighted, if all labels are valid.
+    fn try_new_weighted(labels: Vec<(f64, T)>, builder: Pst3dBuilder) -> Result<Pst3d<T>, Error> {
+        if labels.len() > MAX_ELEMENTS {
+            return Err(Error::Validation(format!("{} labels exceed the maximum of {} labels of a \
+                                                  single tree",
+                                                 labels.len(),
+                                                 MAX_ELEMENTS)));
+        }
+
+        let invalid: Vec<usize> = labels.iter()
+            .enumerate()
+            .filter(|&(_, l)| !is_finite(l))
+            .map(|(idx, _)| idx)
+            .collect();
+        if !invalid.is_empty() {
+            return Err(Error::Validation(format!("Non finite coordinates or t values of the \
+                                                  labels at positions {}",
+                                                 list_labels(&invalid))));
+        }
+
+        Ok(Pst3d::new_weighted(labels, builder))
+    }
+
+    // Initialize a new 3D PST from the given labels with their precomputed priorities using the
+    // settings of the given builder.
+    //
+    // Labels with non finite coordinates or priorities are rejected. Without panics they are
+    // dropped, as they could not be found by any query anyway. The same holds for more than
+    // MAX_ELEMENTS labels, without panics only those with the largest priorities are kept.
+    fn new_weighted(mut labels: Vec<(f64, T)>, builder: Pst3dBuilder) -> Pst3d<T> {
+        let finite = labels.iter().all(is_finite);
+        validate!(finite, "Non finite label coordinates or t values");
+        if !finite {
+            labels.retain(is_finite);
+        }
+        validate!(labels.len() <= MAX_ELEMENTS, "Too many elements for a single tree");
+
+        labels.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+        labels.reverse();
+        labels.truncate(MAX_ELEMENTS);
+
+        let mut v: Vec<Root<T>> = Vec::with_capacity(labels.len());
+        let mut bbox = BBox::new_empty();
+
+        for (t, l) in labels {
+            bbox.add_to_box(&l);
+
+            v.push(Root::new(l, t));
+        }
+
+        let mut tree = Tree::new(v);
+        let tree_root = Root::init_pst3d(&mut tree, &builder);
+
+        Pst3d {
+            m_bbox: bbox,
+
+            m_data: Arc::new(tree),
+            m_root_idx: tree_root,
+
+            m_generation: NEXT_GENERATION.fetch_add(1, AtomicOrdering::SeqCst) as u64,
+            m_hysteresis: 0.,
+
+            m_hits: None,
+            m_builder: builder,
+        }
+    }
+
+    ///
+    /// Reconstruct a balanced tree from the current label set.
+    ///
+    /// The priorities of a tree constructed by `new_by` are kept.
+    ///
+    /// Indices returned by `get_indices` before the rebuild are invalidated.
+    ///
+    /// # Errors
+    /// * Shared: if the nodes are still shared with a snapshot, the tree is unchanged then
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(2., 3., 9., 2, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(3., 4., 8., 3, 1, 1.5, "T3".to_string()));
+    ///
+    /// let mut t = pst_3d::Pst3d::new(v);
+    /// let before = t.to_string();
+    ///
+    /// t.rebuild().unwrap();
+    ///
+    /// assert!(t.to_string() == before);
+    /// assert!(t.get(&bbox::BBox::new(0., 0., 10., 10.), 0.).len() == 3);
+    /// ```
+    ///
+    pub fn rebuild(&mut self) -> Result<(), Error> {
+        let hysteresis = self.m_hysteresis;
+
+        match Arc::try_unwrap(mem::take(&mut self.m_data)) {
+            Ok(data) => {
+                let labels = data.into_elements().collect();
+                *self = Pst3d::new_weighted(labels, self.m_builder.clone());
+            }
+            Err(shared) => {
+                self.m_data = shared;
+                return Err(Error::Shared("Cannot rebuild the tree".to_string()));
+            }
+        }
+        self.m_hysteresis = hysteresis;
+
+        Ok(())
+    }
+
+    ///
+    /// Create a cheap immutable snapshot of the tree.
+    ///
+    /// The snapshot shares all nodes with the tree, so creating it does not copy any label. It
+    /// does not borrow the tree and can be moved to other threads to be queried there. The nodes
+    /// are not copied on write: while a snapshot is alive, modifications of the tree that change
+    /// its nodes, i.e. `rebuild` and `flatten_cold_regions`, fail with a Shared error.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::thread;
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(2., 3., 9., 2, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(3., 4., 8., 3, 1, 1.5, "T3".to_string()));
+    ///
+    /// let mut t = pst_3d::Pst3d::new(v);
+    /// let snapshot = t.snapshot();
+    ///
+    /// let reader = thread::spawn(move || {
+    ///     let r = snapshot.get(&bbox::BBox::new(0., 0., 10., 10.), 8.5);
+    ///     (snapshot.get_generation(), r.len())
+    /// });
+    ///
+    /// let before = t.get_generation();
+    /// assert!(t.rebuild().unwrap_err().get_code() == 7);
+    ///
+    /// assert!(reader.join().unwrap() == (before, 2));
+    /// t.rebuild().unwrap();
+    /// assert!(t.get_generation() != before);
+    /// ```
+    ///
+    pub fn snapshot(&self) -> Pst3dSnapshot<T> {
+        Pst3dSnapshot {
+            m_pst: Pst3d {
+                m_bbox: self.m_bbox.clone(),
+
+                m_data: Arc::clone(&self.m_data),
+                m_root_idx: self.m_root_idx,
+
+                m_generation: self.m_generation,
+                m_hysteresis: self.m_hysteresis,
+
+                m_hits: None,
+                m_builder: self.m_builder.clone(),
+            },
+        }
+    }
+
+    ///
+    /// Return the set of label in the given bounding box with a t >= min_t.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(2., 3., 9., 2, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(3., 4., 8., 3, 1, 1.5, "T3".to_string()));
+    /// v.push(label::Label::new(4., 5., 7., 4, 1, 1.5, "T4".to_string()));
+    /// v.push(label::Label::new(5., 6., 6., 5, 1, 1.5, "T5".to_string()));
+    /// v.push(label::Label::new(6., 7., 5., 6, 1, 1.5, "T6".to_string()));
+    /// v.push(label::Label::new(7., 8., 4., 7, 1, 1.5, "T7".to_string()));
+    /// v.push(label::Label::new(8., 9., 3., 8, 1, 1.5, "T8".to_string()));
+    /// v.push(label::Label::new(9., 10., 2., 9, 1, 1.5, "T9".to_string()));
+    /// v.push(label::Label::new(10., 11., 1., 10, 1, 1.5, "T10".to_string()));
+    ///
+    /// let t = pst_3d::Pst3d::new(v);
+    ///
+    /// let bb = bbox::BBox::new(4., 5., 7., 8.);
+    /// let r = t.get(&bb, 4.);
+    ///
+    /// for e in &r {
+    ///   println!("{}, ", e.to_string());
+    /// }
+    ///
+    /// // resulting labels are T5 to  T7
+    /// assert!(r.len() == 3);
+    /// ```
+    ///
+    pub fn get<'a>(&'a self, bbox: &BBox, min_t: f64) -> Vec<&'a T> {
+        self.get_indices(bbox, min_t)
+            .into_iter()
+            .filter_map(|idx| self.get_label(idx))
+            .collect()
+    }
+
+    ///
+    /// Return the maximum t value of the labels in the given bounding box or None if the box
+    /// does not contain any label.
+    ///
+    /// No labels are collected. The search only descends into subtrees that may contain a label
+    /// with a larger t value than the best one found so far.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(2., 3., 9., 2, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(3., 4., 8., 3, 1, 1.5, "T3".to_string()));
+    /// v.push(label::Label::new(4., 5., 7., 4, 1, 1.5, "T4".to_string()));
+    ///
+    /// let t = pst_3d::Pst3d::new(v);
+    ///
+    /// assert!(t.max_t_in(&bbox::BBox::new(0., 0., 10., 10.)) == Some(10.));
+    /// assert!(t.max_t_in(&bbox::BBox::new(2.5, 3.5, 10., 10.)) == Some(8.));
+    /// assert!(t.max_t_in(&bbox::BBox::new(5., 5., 10., 10.)).is_none());
+    /// ```
+    ///
+    pub fn max_t_in(&self, bbox: &BBox) -> Option<f64> {
+        let mut best = None;
+        if let Some(idx) = self.m_root_idx {
+            self.m_data[idx].max_t_in(bbox, &self.m_data, &mut best);
+        }
+
+        best
+    }
+
+    ///
+    /// Set the default visibility hysteresis delta of the dataset.
+    ///
+    /// The default is used by `get_with_hysteresis` if no delta is given for the query. A delta
+    /// of 0 disables the hysteresis.
+    ///
+    pub fn set_hysteresis(&mut self, delta: f64) {
+        self.m_hysteresis = delta;
+    }
+
+    ///
+    /// Get the default visibility hysteresis delta of the dataset.
+    ///
+    pub fn get_hysteresis(&self) -> f64 {
+        self.m_hysteresis
+    }
+
+    ///
+    /// Get the generation id of the tree.
+    ///
+    /// Each constructed tree gets a unique generation id. Rebuilding the tree starts a new
+    /// generation, since the label indices change.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
+    ///
+    /// let mut t = pst_3d::Pst3d::new(v.clone());
+    /// let other = pst_3d::Pst3d::new(v);
+    /// assert!(t.get_generation() != other.get_generation());
+    ///
+    /// let before = t.get_generation();
+    /// t.rebuild().unwrap();
+    /// assert!(t.get_generation() != before);
+    /// ```
+    ///
+    pub fn get_generation(&self) -> u64 {
+        self.m_generation
+    }
+
+    ///
+    /// Get the size in bytes of a single tree node, excluding heap memory owned by the element.
+    ///
+    pub fn get_node_size() -> usize {
+        mem::size_of::<Root<T>>()
+    }
+
+    ///
+    /// Return the set of label in the given bounding box with a t >= min_t, but at most limit
+    /// labels.
+    ///
+    /// If a limit is given, the labels with the largest t values are returned ordered by
+    /// decreasing t and the traversal stops as soon as limit labels are found. Without a limit
+    /// the result is the same as for `get`.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(2., 3., 9., 2, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(3., 4., 8., 3, 1, 1.5, "T3".to_string()));
+    /// v.push(label::Label::new(4., 5., 7., 4, 1, 1.5, "T4".to_string()));
+    ///
+    /// let t = pst_3d::Pst3d::new(v);
+    /// let bb = bbox::BBox::new(1.5, 2.5, 10., 10.);
+    ///
+    /// let r = t.get_limited(&bb, 0., Some(2));
+    /// assert!(r.len() == 2);
+    /// assert!(r[0].get_osm_id() == 2);
+    /// assert!(r[1].get_osm_id() == 3);
+    ///
+    /// assert!(t.get_limited(&bb, 0., None).len() == 3);
+    /// ```
+    ///
+    pub fn get_limited(&self, bbox: &BBox, min_t: f64, limit: Option<usize>) -> Vec<&T> {
+        self.get_limited_indices(bbox, min_t, limit)
+            .into_iter()
+            .filter_map(|idx| self.get_label(idx))
+            .collect()
+    }
+
+    ///
+    /// Return the indices of the labels in the given bounding box with a t >= min_t, but at most
+    /// limit indices. Compare `get_limited`.
+    ///
+    pub fn get_limited_indices(&self, bbox: &BBox, min_t: f64, limit: Option<usize>) -> Vec<u32> {
+        match (self.m_root_idx, limit) {
+            (Some(idx), Some(limit)) => {
+                let r = Root::get_top_indices(idx, bbox, min_t, limit, &self.m_data);
+                self.record_hits(&r);
+                r
+            }
+            _ => self.get_indices(bbox, min_t),
+        }
+    }
+
+    ///
+    /// Return the set of label in the given bounding box with a t >= min_t, but descend at most
+    /// max_depth levels below the root of the tree.
+    ///
+    /// Every node stores the label with the highest t value of its subtree, so the result is a
+    /// coarse but representative subset of the result of `get` that is available after visiting
+    /// at most 2^(max_depth + 1) - 1 nodes, e.g. for low zoom levels or to display something
+    /// before the full query completes.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// for i in 1..11 {
+    ///     let (x, t) = (i as f64, 11. - i as f64);
+    ///     v.push(label::Label::new(x, x + 1., t, i, 1, 1.5, format!("T{}", i)));
+    /// }
+    ///
+    /// let t = pst_3d::Pst3d::new(v);
+    /// let bb = bbox::BBox::new(0., 0., 20., 20.);
+    ///
+    /// assert!(t.get_depth_limited(&bb, 0., 0).len() == 1);
+    ///
+    /// let r = t.get_depth_limited(&bb, 0., 1);
+    /// let mut ids: Vec<i64> = r.iter().map(|l| l.get_osm_id()).collect();
+    /// ids.sort();
+    /// assert!(ids == vec![1, 2, 6]);
+    ///
+    /// assert!(t.get_depth_limited(&bb, 0., 10).len() == 10);
+    /// ```
+    ///
+    pub fn get_depth_limited(&self, bbox: &BBox, min_t: f64, max_depth: usize) -> Vec<&T> {
+        self.get_depth_limited_indices(bbox, min_t, max_depth)
+            .into_iter()
+            .filter_map(|idx| self.get_label(idx))
+            .collect()
+    }
+
+    ///
+    /// Return the indices of the labels in the given bounding box with a t >= min_t found within
+    /// max_depth levels below the root. Compare `get_depth_limited`.
+    ///
+    pub fn get_depth_limited_indices(&self, bbox: &BBox, min_t: f64, max_depth: usize) -> Vec<u32> {
+        let mut r: Vec<u32> = Vec::new();
+        if let Some(idx) = self.m_root_idx {
+            let root = &self.m_data[idx];
+            root.get_depth_limited_indices(idx, bbox, min_t, max_depth, &self.m_data, &mut r);
+        }
+        self.record_hits(&r);
+
+        r
+    }
+
+    ///
+    /// Return a subset of the labels in the given bounding box with a t >= min_t that may miss
+    /// labels close to the border of the box.
+    ///
+    /// The tree is only descended into subtrees that are cut by the bounding box shrunk by margin
+    /// times its width and height on each side. All labels of the shrunk box are reported, labels
+    /// in the margin are reported if they are found on the way. The area searched exhaustively is
+    /// (1 - 2 margin)^2 times the area of the box, which bounds the number of visited nodes for
+    /// overview rendering where exactness is less important than the latency.
+    ///
+    /// The margin is clamped to [0, 0.5], a margin of 0 is equal to `get`.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// for i in 0..100 {
+    ///     let x = (i % 10) as f64;
+    ///     let y = (i / 10) as f64;
+    ///     v.push(label::Label::new(x, y, i as f64, i, 1, 1.5, "T".to_string()));
+    /// }
+    ///
+    /// let t = pst_3d::Pst3d::new(v);
+    /// let bb = bbox::BBox::new(-0.5, -0.5, 9.5, 9.5);
+    /// let inner = bbox::BBox::new(2., 2., 7., 7.);
+    ///
+    /// assert!(t.get_approximate(&bb, -1., 0.).len() == 100);
+    ///
+    /// let r = t.get_approximate(&bb, -1., 0.25);
+    /// assert!(r.len() <= 100);
+    /// assert!(r.iter().filter(|l| inner.is_contained(**l)).count() == 36);
+    /// ```
+    ///
+    pub fn get_approximate(&self, bbox: &BBox, min_t: f64, margin: f64) -> Vec<&T> {
+        self.get_approximate_indices(bbox, min_t, margin)
+            .into_iter()
+            .filter_map(|idx| self.get_label(idx))
+            .collect()
+    }
+
+    ///
+    /// Return the indices of a subset of the labels in the given bounding box with a t >= min_t
+    /// that may miss labels close to the border of the box. Compare `get_approximate`.
+    ///
+    pub fn get_approximate_indices(&self, bbox: &BBox, min_t: f64, margin: f64) -> Vec<u32> {
+        // a NaN margin is treated like an exact query
+        let margin = if margin > 0. { margin.min(0.5) } else { 0. };
+        let dx = (bbox.get_max_x() - bbox.get_min_x()) * margin;
+        let dy = (bbox.get_max_y() - bbox.get_min_y()) * margin;
+        let prune_bbox = BBox::new(bbox.get_min_x() + dx,
+                                   bbox.get_min_y() + dy,
+                                   bbox.get_max_x() - dx,
+                                   bbox.get_max_y() - dy);
+
+        let mut r: Vec<u32> = Vec::new();
+        if let Some(idx) = self.m_root_idx {
+            let root = &self.m_data[idx];
+            root.get_approximate_indices(idx, bbox, &prune_bbox, min_t, &self.m_data, &mut r);
+        }
+        self.record_hits(&r);
+
+        r
+    }
+
+    ///
+    /// Return the labels in the given bounding box with a t >= min_t found before the query
+    /// budget is exhausted, together with a flag that is false if the result is incomplete.
+    ///
+    /// The nodes are visited by decreasing t value, so a partial result contains the labels with
+    /// the largest t values ordered by decreasing t. Real-time renderers can thus bound the query
+    /// time and accept a slightly incomplete label set.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    /// use rt_datastructure::pst_3d::query::QueryBudget;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(2., 3., 9., 2, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(3., 4., 8., 3, 1, 1.5, "T3".to_string()));
+    /// v.push(label::Label::new(4., 5., 7., 4, 1, 1.5, "T4".to_string()));
+    ///
+    /// let t = pst_3d::Pst3d::new(v);
+    /// let bb = bbox::BBox::new(0., 0., 10., 10.);
+    ///
+    /// let mut b = QueryBudget::new();
+    /// b.set_max_visits(Some(2));
+    /// let (r, complete) = t.get_budgeted(&bb, 0., &b);
+    /// assert!(!complete);
+    /// assert!(r.len() == 2);
+    /// assert!(r[0].get_osm_id() == 1);
+    /// assert!(r[1].get_osm_id() == 2);
+    ///
+    /// let (r, complete) = t.get_budgeted(&bb, 0., &QueryBudget::new());
+    /// assert!(complete);
+    /// assert!(r.len() == 4);
+    /// ```
+    ///
+    pub fn get_budgeted(&self, bbox: &BBox, min_t: f64, budget: &QueryBudget) -> (Vec<&T>, bool) {
+        let (r, complete) = self.get_budgeted_indices(bbox, min_t, budget);
+        let labels = r.into_iter()
+            .filter_map(|idx| self.get_label(idx))
+            .collect();
+
+        (labels, complete)
+    }
+
+    ///
+    /// Return the indices of the labels in the given bounding box with a t >= min_t found before
+    /// the query budget is exhausted. Compare `get_budgeted`.
+    ///
+    pub fn get_budgeted_indices(&self,
+                                bbox: &BBox,
+                                min_t: f64,
+                                budget: &QueryBudget)
+                                -> (Vec<u32>, bool) {
+        match self.m_root_idx {
+            Some(idx) => {
+                let (r, complete) =
+                    Root::get_budgeted_indices(idx, bbox, min_t, budget, &self.m_data);
+                self.record_hits(&r);
+                (r, complete)
+            }
+            None => (Vec::new(), true),
+        }
+    }
+
+    ///
+    /// Return the indices of the labels in the given bounding box with a t >= min_t.
+    ///
+    /// The indices are stable for the lifetime of the tree and can be resolved to the
+    /// corresponding labels by `get_label`.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(2., 3., 9., 2, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(3., 4., 8., 3, 1, 1.5, "T3".to_string()));
+    ///
+    /// let t = pst_3d::Pst3d::new(v);
+    ///
+    /// let bb = bbox::BBox::new(1.5, 2.5, 3., 4.);
+    /// let r = t.get_indices(&bb, 0.);
+    /// assert!(r.len() == 2);
+    ///
+    /// let mut ids: Vec<i64> = r.iter()
+    ///     .map(|&idx| t.get_label(idx).unwrap().get_osm_id())
+    ///     .collect();
+    /// ids.sort();
+    /// assert!(ids == vec![2, 3]);
+    /// ```
+    ///
+    pub fn get_indices(&self, bbox: &BBox, min_t: f64) -> Vec<u32> {
+        let mut r: Vec<u32> = Vec::new();
+        if let Some(idx) = self.m_root_idx {
+            self.m_data[idx].get_indices(idx, bbox, min_t, &self.m_data, &mut r, &mut NoStats);
+        }
+        self.record_hits(&r);
+
+        r
+    }
+
+    ///
+    /// Return the set of labels in the given bounding box with a t >= min_t together with
+    /// statistics about the traversal of the tree.
+    ///
+    /// The result is equal to the result of `get`. The statistics help to understand why some
+    /// queries are more expensive than others, e.g. when comparing split strategies.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// for i in 0..100 {
+    ///     let x = (i % 10) as f64;
+    ///     let y = (i / 10) as f64;
+    ///     v.push(label::Label::new(x, y, i as f64, i, 1, 1.5, "T".to_string()));
+    /// }
+    ///
+    /// let t = pst_3d::Pst3d::new(v);
+    /// let bb = bbox::BBox::new(0.5, 0.5, 3.5, 3.5);
+    ///
+    /// let (r, stats) = t.get_with_stats(&bb, 0.);
+    /// assert!(r.len() == 9);
+    /// assert!(stats.get_labels_returned() == 9);
+    /// assert!(stats.get_labels_tested() <= stats.get_nodes_visited());
+    /// assert!(stats.get_nodes_visited() < 100);
+    /// assert!(stats.get_subtrees_pruned() > 0);
+    /// ```
+    ///
+    pub fn get_with_stats(&self, bbox: &BBox, min_t: f64) -> (Vec<&T>, QueryStats) {
+        let (r, stats) = self.get_indices_with_stats(bbox, min_t);
+        let labels = r.into_iter().filter_map(|idx| self.get_label(idx)).collect();
+
+        (labels, stats)
+    }
+
+    ///
+    /// Return the indices of the labels in the given bounding box with a t >= min_t together
+    /// with statistics about the traversal of the tree. Compare `get_with_stats`.
+    ///
+    pub fn get_indices_with_stats(&self, bbox: &BBox, min_t: f64) -> (Vec<u32>, QueryStats) {
+        let mut r: Vec<u32> = Vec::new();
+        let mut stats = Traversal::new();
+        if let Some(idx) = self.m_root_idx {
+            self.m_data[idx].get_indices(idx, bbox, min_t, &self.m_data, &mut r, &mut stats);
+        }
+        self.record_hits(&r);
+
+        let len = r.len();
+        (r, stats.into_stats(len))
+    }
+
+    ///
+    /// Return the set of labels in the given bounding box with t_low <= t <= t_high.
+    ///
+    /// Both bounds are contained, e.g. the labels disappearing during a zoom transition from t_low
+    /// to t_high including the labels with a t value of exactly t_low or t_high.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(2., 3., 9., 2, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(3., 4., 8., 3, 1, 1.5, "T3".to_string()));
+    /// v.push(label::Label::new(4., 5., 7., 4, 1, 1.5, "T4".to_string()));
+    ///
+    /// let t = pst_3d::Pst3d::new(v);
+    ///
+    /// let bb = bbox::BBox::new(0., 0., 10., 10.);
+    /// let r = t.get_t_interval(&bb, 7., 9.);
+    /// let mut ids: Vec<i64> = r.iter().map(|l| l.get_osm_id()).collect();
+    /// ids.sort();
+    ///
+    /// assert!(ids == vec![2, 3, 4]);
+    /// assert!(t.get_t_interval(&bb, 7., 7.).len() == 1);
+    /// ```
+    ///
+    pub fn get_t_interval(&self, bbox: &BBox, t_low: f64, t_high: f64) -> Vec<&T> {
+        self.get_t_interval_indices(bbox, t_low, t_high)
+            .into_iter()
+            .filter_map(|idx| self.get_label(idx))
+            .collect()
+    }
+
+    ///
+    /// Return the indices of the labels in the given bounding box with t_low <= t <= t_high.
+    /// Compare `get_t_interval`.
+    ///
+    pub fn get_t_interval_indices(&self, bbox: &BBox, t_low: f64, t_high: f64) -> Vec<u32> {
+        // the queries report the labels with t > min_t, start right below t_low to include it
+        self.retain_up_to(self.get_indices(bbox, t_low.next_down()), t_high)
+    }
+
+    // Remove the indices of labels with a t > t_high.
+    //
+    // The heap order only bounds the t values of a subtree from above, so the upper bound can not
+    // prune the traversal and is applied to its result.
+    fn retain_up_to(&self, mut indices: Vec<u32>, t_high: f64) -> Vec<u32> {
+        indices.retain(|idx| self.get_priority(*idx).is_some_and(|t| t <= t_high));
+
+        indices
+    }
+
+    ///
+    /// Answer several queries given as (bbox, min_t) pairs at once. The i-th result contains the
+    /// labels in the i-th bounding box with a t >= min_t.
+    ///
+    /// The queries share the traversal of the tree, so overlapping boxes (e.g. a neighborhood of
+    /// tiles) visit the upper levels of the tree only once instead of once per query.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(2., 3., 9., 2, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(3., 4., 8., 3, 1, 1.5, "T3".to_string()));
+    ///
+    /// let t = pst_3d::Pst3d::new(v);
+    ///
+    /// let queries = vec![(bbox::BBox::new(0., 0., 2., 3.), 0.),
+    ///                    (bbox::BBox::new(1.5, 2.5, 3., 4.), 0.),
+    ///                    (bbox::BBox::new(1.5, 2.5, 3., 4.), 8.5)];
+    /// let r = t.get_many(&queries);
+    /// assert!(r.len() == 3);
+    /// assert!(r[0].len() == 2);
+    /// assert!(r[1].len() == 2);
+    /// assert!(r[2].len() == 1);
+    /// ```
+    ///
+    pub fn get_many(&self, queries: &[(BBox, f64)]) -> Vec<Vec<&T>> {
+        self.get_many_indices(queries)
+            .into_iter()
+            .map(|r| {
+                     r.into_iter()
+                         .filter_map(|idx| self.get_label(idx))
+                         .collect()
+                 })
+            .collect()
+    }
+
+    ///
+    /// Return the indices of the labels matching each of the queries. Compare `get_many`.
+    ///
+    pub fn get_many_indices(&self, queries: &[(BBox, f64)]) -> Vec<Vec<u32>> {
+        let mut r: Vec<Vec<u32>> = vec![Vec::new(); queries.len()];
+        if let Some(idx) = self.m_root_idx {
+            let active: Vec<usize> = (0..queries.len()).collect();
+            self.m_data[idx].get_many_indices(idx, queries, &active, &self.m_data, &mut r);
+        }
+        for indices in &r {
+            self.record_hits(indices);
+        }
+
+        r
+    }
+
+    ///
+    /// Start counting how often each label is reported by a query.
+    ///
+    /// The counters identify rarely queried (cold) regions, see `get_cold_regions`. Enabling the
+    /// tracking again resets all counters. A rebuild disables the tracking.
+    ///
+    pub fn enable_access_tracking(&mut self) {
+        self.m_hits = Some((0..self.get_size()).map(|_| AtomicU32::new(0)).collect());
+    }
+
+    ///
+    /// Stop counting how often each label is reported and discard all counters.
+    ///
+    pub fn disable_access_tracking(&mut self) {
+        self.m_hits = None;
+    }
+
+    ///
+    /// Return the bounding boxes of the maximal subtrees whose labels were reported at most
+    /// max_hits times in total since the access tracking was enabled.
+    ///
+    /// The regions are candidates for compaction, e.g. oceans or remote areas of a global
+    /// dataset. If the access tracking is disabled no region is returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(0., 0., 10., 1, 1, 1.5, "Hot".to_string()));
+    /// v.push(label::Label::new(1., 1., 9., 2, 1, 1.5, "Hot".to_string()));
+    /// v.push(label::Label::new(50., 50., 8., 3, 1, 1.5, "Cold".to_string()));
+    ///
+    /// let mut t = pst_3d::Pst3d::new(v);
+    /// t.enable_access_tracking();
+    ///
+    /// for _ in 0..10 {
+    ///     t.get(&bbox::BBox::new(-1., -1., 2., 2.), 0.);
+    /// }
+    ///
+    /// let cold = t.get_cold_regions(0);
+    /// assert!(cold.len() == 1);
+    /// assert!(cold[0].get_min_x() == 50. && cold[0].get_max_x() == 50.);
+    /// ```
+    ///
+    pub fn get_cold_regions(&self, max_hits: u64) -> Vec<BBox> {
+        let mut r = Vec::new();
+        if let (Some(hits), Some(idx)) = (self.m_hits.as_ref(), self.m_root_idx) {
+            self.m_data[idx].collect_cold(idx, hits, max_hits, &self.m_data, &mut r);
+        }
+
+        r.iter().map(|idx| self.m_data[*idx].get_subtree_bbox()).collect()
+    }
+
+    ///
+    /// Flatten the cold regions of the tree into leaf buckets and return the number of released
+    /// tree nodes.
+    ///
+    /// Each maximal subtree whose labels were reported at most max_hits times in total since the
+    /// access tracking was enabled, compare `get_cold_regions`, is collapsed into a single leaf
+    /// bucket. Its labels are kept uncompressed as bucket elements, while the split values, child
+    /// indices and subtree bounding boxes of its inner nodes are released, see `get_memory_size`.
+    /// Queries reaching a flattened region test all of its labels linearly, so only flatten
+    /// regions that are rarely queried, e.g. oceans or remote areas of a global dataset.
+    ///
+    /// All query results stay the same, but the label indices change like on a rebuild, so the
+    /// generation changes and the access counters are reset. If the access tracking is disabled
+    /// nothing is flattened.
+    ///
+    /// # Errors
+    /// * Shared: if the nodes are still shared with a snapshot, the tree is unchanged then
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// for i in 0..100 {
+    ///     v.push(label::Label::new(i as f64, (i * 7 % 100) as f64, i as f64, i, 1, 1.5,
+    ///                              "T".to_string()));
+    /// }
+    ///
+    /// let mut t = pst_3d::Pst3d::new(v);
+    /// t.enable_access_tracking();
+    /// t.get(&bbox::BBox::new(0., 0., 10., 10.), 0.);
+    ///
+    /// let memory = t.get_memory_size();
+    /// assert!(t.flatten_cold_regions(0).unwrap() > 0);
+    /// assert!(t.get_memory_size() < memory);
+    /// assert!(t.get_size() == 100);
+    /// assert!(t.get(&bbox::BBox::new(0., 0., 100., 100.), -1.).len() == 100);
+    /// ```
+    ///
+    pub fn flatten_cold_regions(&mut self, max_hits: u64) -> Result<usize, Error> {
+        let (hits, root_idx) = match (self.m_hits.as_ref(), self.m_root_idx) {
+            (Some(hits), Some(idx)) => (hits, idx),
+            _ => return Ok(0),
+        };
+        let mut subtrees = Vec::new();
+        self.m_data[root_idx].collect_cold(root_idx, hits, max_hits, &self.m_data, &mut subtrees);
+        if subtrees.is_empty() {
+            return Ok(0);
+        }
+
+        let node_count = self.get_node_count();
+        match Arc::try_unwrap(mem::take(&mut self.m_data)) {
+            Ok(data) => {
+                let (data, root_idx) = Root::flatten_subtrees(data, root_idx, &subtrees);
+                self.m_data = Arc::new(data);
+                self.m_root_idx = Some(root_idx);
+                self.m_generation = NEXT_GENERATION.fetch_add(1, AtomicOrdering::SeqCst) as u64;
+                self.enable_access_tracking();
+            }
+            Err(shared) => {
+                self.m_data = shared;
+                return Err(Error::Shared("Cannot flatten the cold regions".to_string()));
+            }
+        }
+
+        Ok(node_count - self.get_node_count())
+    }
+
+    // Count the reported labels if the access tracking is enabled.
+    fn record_hits(&self, indices: &[u32]) {
+        if let Some(ref hits) = self.m_hits {
+            for idx in indices {
+                if let Some(h) = hits.get(*idx as usize) {
+                    h.fetch_add(1, AtomicOrdering::Relaxed);
+                }
+            }
+        }
+    }
+
+    ///
+    /// Resolve an index returned by `get_indices` to the corresponding label.
+    ///
+    /// Returns None if the index is out of range.
+    ///
+    pub fn get_label(&self, idx: u32) -> Option<&T> {
+        self.m_data.get_element(idx as usize).map(|(l, _)| l)
+    }
+
+
+    ///
+    /// Get the priority of the label with the given index, i.e. the t value the tree is ordered
+    /// by. For a tree constructed by `new_by` this is the value of the priority function.
+    ///
+    /// Returns None if the index is out of range.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let v = vec![label::Label::new(1., 2., 1., 1, 30, 1.5, "T1".to_string())];
+    /// let t = pst_3d::Pst3d::new_by(v, |l| l.get_prio() as f64);
+    ///
+    /// assert!(t.get_priority(0) == Some(30.));
+    /// assert!(t.get_priority(1).is_none());
+    /// ```
+    ///
+    pub fn get_priority(&self, idx: u32) -> Option<f64> {
+        self.m_data.get_element(idx as usize).map(|(_, t)| t)
+    }
+
+    // Resolve the given indices to the labels together with their priorities.
+    fn get_prioritized(&self, indices: Vec<u32>) -> Vec<(&T, f64)> {
+        indices.into_iter()
+            .filter_map(|idx| self.m_data.get_element(idx as usize))
+            .collect()
+    }
+
+    ///
+    /// Get the number of stored labels.
+    ///
+    pub fn get_size(&self) -> usize {
+        self.m_data.get_element_count()
+    }
+
+    ///
+    /// Get the number of tree nodes.
+    ///
+    /// Without leaf buckets each node stores one label. A leaf bucket stores up to the bucket
+    /// size of labels in a single node, see `Pst3dBuilder::set_leaf_bucket_size`.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    /// use rt_datastructure::pst_3d::builder::Pst3dBuilder;
+    ///
+    /// let mut v = Vec::new();
+    /// for i in 0..100 {
+    ///     v.push(label::Label::new(i as f64, (i * 7 % 100) as f64, i as f64, i, 1, 1.5,
+    ///                              "T".to_string()));
+    /// }
+    ///
+    /// let mut b = Pst3dBuilder::new();
+    /// assert!(b.build(v.clone()).get_node_count() == 100);
+    ///
+    /// b.set_leaf_bucket_size(16);
+    /// let t = b.build(v);
+    /// assert!(t.get_size() == 100);
+    /// assert!(t.get_node_count() < 25);
+    /// ```
+    ///
+    pub fn get_node_count(&self) -> usize {
+        self.m_data.len()
+    }
+
+    ///
+    /// Get the size in bytes of the tree nodes and leaf bucket elements, excluding heap memory
+    /// owned by the labels. Compare `get_node_size`.
+    ///
+    pub fn get_memory_size(&self) -> usize {
+        self.m_data.get_memory_size()
+    }
+
+    ///
+    /// Iterate over all stored labels in the order of their indices, i.e. the i-th label is the
+    /// one returned by `get_label(i)`.
+    ///
+    /// The nodes are stored in breadth first order, so each label is returned before the labels
+    /// of its subtree.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 8., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(2., 3., 10., 2, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(3., 4., 9., 3, 1, 1.5, "T3".to_string()));
+    ///
+    /// let t = pst_3d::Pst3d::new(v);
+    ///
+    /// assert!(t.iter().count() == 3);
+    /// assert!(t.iter().next().map(|l| l.get_osm_id()) == Some(2));
+    /// ```
+    ///
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        (0..self.get_size()).filter_map(move |idx| self.get_label(idx as u32))
+    }
+
+    ///
+    /// Iterate over all stored labels by decreasing t value.
+    ///
+    /// Labels with equal t values are returned in the order of their indices.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 8., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(2., 3., 10., 2, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(3., 4., 9., 3, 1, 1.5, "T3".to_string()));
+    ///
+    /// let t = pst_3d::Pst3d::new(v);
+    /// let ids: Vec<i64> = t.iter_by_t().map(|l| l.get_osm_id()).collect();
+    ///
+    /// assert!(ids == vec![2, 3, 1]);
+    /// ```
+    ///
+    pub fn iter_by_t(&self) -> impl Iterator<Item = &T> {
+        let mut order: Vec<u32> = (0..self.get_size() as u32).collect();
+        order.sort_by(|a, b| {
+                          let t_a = self.get_priority(*a);
+                          let t_b = self.get_priority(*b);
+                          t_b.partial_cmp(&t_a).unwrap_or(Ordering::Equal)
+                      });
+
+        order.into_iter().filter_map(move |idx| self.get_label(idx))
+    }
+
+    ///
+    /// Check the invariants of the tree: the heap order on t, the split values of both
+    /// dimensions, the subtree information, the child indices and that every node is reachable
+    /// from the root exactly once (i.e. there is no cycle).
+    ///
+    /// A tree constructed by the crate always satisfies the invariants. The check is meant for
+    /// trees changed or loaded by other means and takes linear time.
+    ///
+    /// # Errors
+    ///   * Invariant: describing the first violated invariant and the node it was found at
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(2., 3., 9., 2, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(3., 4., 8., 3, 1, 1.5, "T3".to_string()));
+    ///
+    /// let t = pst_3d::Pst3d::new(v);
+    /// assert!(t.validate().is_ok());
+    /// ```
+    ///
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.m_root_idx.is_none() && self.get_size() > 0 {
+            return Err(Error::Invariant {
+                           node: None,
+                           reason: "Non empty tree without a root".to_string(),
+                       });
+        }
+
+        Root::validate_tree(self.m_root_idx, &self.m_data)
+    }
+
+    ///
+    /// Write the structure of the tree without its labels.
+    ///
+    /// The labels must be stored separately in the order of their indices, i.e. the label
+    /// returned by `get_label(i)` at position i. Compare `read_tree`.
+    ///
+    pub fn write_tree<W: Write>(&self, w: &mut W) -> Result<(), Error> {
+        codec::write_f64(w, self.m_hysteresis)?;
+        codec::write_varint(w, self.m_root_idx.map_or(0, |idx| idx as u64 + 1))?;
+        Root::write_nodes(&self.m_data, w)
+    }
+
+    ///
+    /// Read the tree structure written by `write_tree` and attach the given labels. The tree is
+    /// not constructed again, so loading a tree is linear in the number of labels.
+    ///
+    /// # Errors
+    ///   * Io: if the reader fails or ends too early
+    ///   * Serialization: if the data does not describe a valid tree of the given labels
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(2., 3., 9., 2, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(3., 4., 8., 3, 1, 1.5, "T3".to_string()));
+    ///
+    /// let t = pst_3d::Pst3d::new(v);
+    /// let mut buf: Vec<u8> = Vec::new();
+    /// t.write_tree(&mut buf).unwrap();
+    ///
+    /// let labels = (0..t.get_size() as u32).map(|i| t.get_label(i).unwrap().clone()).collect();
+    /// let loaded: pst_3d::Pst3d = pst_3d::Pst3d::read_tree(&mut buf.as_slice(), labels).unwrap();
+    ///
+    /// let bb = bbox::BBox::new(1.5, 2.5, 10., 10.);
+    /// assert!(loaded.get(&bb, 8.5).len() == 1);
+    /// assert!(loaded.get(&bb, 0.).len() == 2);
+    /// ```
+    ///
+    pub fn read_tree<R: Read>(r: &mut R, labels: Vec<T>) -> Result<Pst3d<T>, Error> {
+        let hysteresis = codec::read_f64(r)?;
+        let root_idx = match codec::read_varint(r)? {
+            0 => None,
+            idx => Some((idx - 1) as usize),
+        };
+        if root_idx.map_or(!labels.is_empty(), |idx| idx >= labels.len()) {
+            return Err(Error::Serialization("Invalid root index".to_string()));
+        }
+
+        let mut bbox = BBox::new_empty();
+        for l in &labels {
+            bbox.add_to_box(l);
+        }
+
+        let data = Root::read_nodes(r, labels)?;
+        if root_idx.is_some_and(|idx| idx >= data.len()) {
+            return Err(Error::Serialization("Invalid root index".to_string()));
+        }
+
+        Ok(Pst3d {
+               m_bbox: bbox,
+
+               m_data: Arc::new(data),
+               m_root_idx: root_idx,
+
+               m_generation: NEXT_GENERATION.fetch_add(1, AtomicOrdering::SeqCst) as u64,
+               m_hysteresis: hysteresis,
+
+               m_hits: None,
+               m_builder: Pst3dBuilder::new(),
+           })
+    }
+
+    ///
+    /// Return the set of labels within the given radius around the center with a t >= min_t.
+    ///
+    /// The tree is queried with the bounding box of the circle, afterwards the exact euclidean
+    /// distance is checked for each result. Labels exactly at the given distance are contained.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    /// use rt_datastructure::primitives::point::Point;
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(0., 0., 10., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(1., 1., 9., 2, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(0., 1., 8., 3, 1, 1.5, "T3".to_string()));
+    /// v.push(label::Label::new(-1., 0., 2., 4, 1, 1.5, "T4".to_string()));
+    ///
+    /// let t = pst_3d::Pst3d::new(v);
+    ///
+    /// // T2 lies within the bounding box of the circle but not within the circle
+    /// let r = t.get_within_radius(&Point::new(0., 0.), 1., 4.);
+    /// assert!(r.len() == 2);
+    ///
+    /// let r = t.get_within_radius(&Point::new(0., 0.), 1., 1.);
+    /// assert!(r.len() == 3);
+    /// ```
+    ///
+    pub fn get_within_radius(&self, center: &Point, radius: f64, min_t: f64) -> Vec<&T> {
+        let bbox = BBox::new_from_point(center).expanded(radius, radius);
+
+        let squared_radius = radius * radius;
+        self.get(&bbox, min_t)
+            .into_iter()
+            .filter(|l| center.squared_distance(*l) <= squared_radius)
+            .collect()
+    }
+
+    ///
+    /// Return the k labels with a t >= min_t closest to the focus ordered by increasing distance.
+    ///
+    /// The children of each node are visited by increasing distance to the focus and subtrees that
+    /// cannot contain a label closer than the k-th closest label found so far are skipped.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    /// use rt_datastructure::primitives::point::Point;
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(0., 0., 10., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(3., 3., 9., 2, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(1., 1., 8., 3, 1, 1.5, "T3".to_string()));
+    /// v.push(label::Label::new(2., 2.5, 2., 4, 1, 1.5, "T4".to_string()));
+    ///
+    /// let t = pst_3d::Pst3d::new(v);
+    ///
+    /// let r = t.get_nearest(&Point::new(2.5, 2.5), 0., 2);
+    /// assert!(r.len() == 2);
+    /// assert!(r[0].get_osm_id() == 4);
+    /// assert!(r[1].get_osm_id() == 2);
+    ///
+    /// // T4 has a too small t value
+    /// let r = t.get_nearest(&Point::new(2.5, 2.5), 5., 2);
+    /// assert!(r[0].get_osm_id() == 2);
+    /// assert!(r[1].get_osm_id() == 3);
+    /// ```
+    ///
+    pub fn get_nearest(&self, focus: &Point, min_t: f64, k: usize) -> Vec<&T> {
+        self.get_nearest_indices(focus, min_t, k)
+            .into_iter()
+            .filter_map(|idx| self.get_label(idx))
+            .collect()
+    }
+
+    ///
+    /// Return the indices of the k labels with a t >= min_t closest to the focus ordered by
+    /// increasing distance. Compare `get_nearest`.
+    ///
+    pub fn get_nearest_indices(&self, focus: &Point, min_t: f64, k: usize) -> Vec<u32> {
+        match self.m_root_idx {
+            Some(idx) => {
+                let r = Root::get_nearest_indices(idx, focus, min_t, k, &self.m_data);
+                self.record_hits(&r);
+                r
+            }
+            None => Vec::new(),
+        }
+    }
+
+    ///
+    /// Return the label with a t >= min_t closest to the focus, e.g. to snap a position to the
+    /// nearest sufficiently important place.
+    ///
+    /// Returns None if no label has a sufficiently large t value.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    /// use rt_datastructure::primitives::point::Point;
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(0., 0., 10., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(3., 3., 9., 2, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(2., 2.5, 2., 3, 1, 1.5, "T3".to_string()));
+    ///
+    /// let t = pst_3d::Pst3d::new(v);
+    ///
+    /// let focus = Point::new(2.5, 2.5);
+    /// assert!(t.nearest_above(&focus, 0.).map(|l| l.get_osm_id()) == Some(3));
+    /// assert!(t.nearest_above(&focus, 5.).map(|l| l.get_osm_id()) == Some(2));
+    /// assert!(t.nearest_above(&focus, 10.).is_none());
+    /// ```
+    ///
+    pub fn nearest_above(&self, focus: &Point, min_t: f64) -> Option<&T> {
+        self.get_nearest(focus, min_t, 1).into_iter().next()
+    }
+}
+
+impl<P: HasPosition + HasT> Pst3d<Arc<P>> {
+    ///
+    /// Initialize a 3D PST storing the given labels with shared ownership.
+    ///
+    /// The results of `get_shared` own their labels, so they can outlive the borrow of the tree,
+    /// e.g. to hand them to another thread while the tree is queried further.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use std::thread;
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(2., 3., 9., 2, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(3., 4., 8., 3, 1, 1.5, "T3".to_string()));
+    ///
+    /// let t = pst_3d::Pst3d::new_shared(v);
+    /// let r = t.get_shared(&bbox::BBox::new(1.5, 2.5, 10., 10.), 0.);
+    ///
+    /// let layout = thread::spawn(move || r.len());
+    /// assert!(t.get(&bbox::BBox::new(0., 0., 10., 10.), 0.).len() == 3);
+    /// assert!(layout.join().unwrap() == 2);
+    /// ```
+    ///
+    pub fn new_shared(labels: Vec<P>) -> Pst3d<Arc<P>> {
+        Pst3d::new(labels.into_iter().map(Arc::new).collect())
+    }
+
+    ///
+    /// Return the set of labels in the given bounding box with a t >= min_t. In contrast to `get`
+    /// the result shares the ownership of the labels and does not borrow the tree.
+    ///
+    pub fn get_shared(&self, bbox: &BBox, min_t: f64) -> Vec<Arc<P>> {
+        self.get_indices(bbox, min_t)
+            .into_iter()
+            .filter_map(|idx| self.get_label(idx).map(Arc::clone))
+            .collect()
+    }
+}
+
+impl Pst3d<Label> {
+    ///
+    /// Iterate over all stored labels in increasing osm_id order.
+    ///
+    /// Labels with equal osm_ids are returned in the order they are stored in the tree. The order
+    /// is stable for the same label set.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 10., 30, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(2., 3., 9., 10, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(3., 4., 8., 20, 1, 1.5, "T3".to_string()));
+    ///
+    /// let t = pst_3d::Pst3d::new(v);
+    /// let ids: Vec<i64> = t.iter_all_sorted_by_id().map(|l| l.get_osm_id()).collect();
+    ///
+    /// assert!(ids == vec![10, 20, 30]);
+    /// ```
+    ///
+    pub fn iter_all_sorted_by_id(&self) -> impl Iterator<Item = &Label> {
+        let mut order: Vec<u32> = (0..self.get_size() as u32).collect();
+        order.sort_by_key(|idx| self.get_label(*idx).map(|l| l.get_osm_id()));
+
+        order.into_iter().filter_map(move |idx| self.get_label(idx))
+    }
+
+    ///
+    /// Return the set of label in the given bounding box with a t >= min_t after applying the
+    /// overrides of the given overlay.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    /// use rt_datastructure::pst_3d::overlay;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(2., 3., 9., 2, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(3., 4., 2., 3, 1, 1.5, "Sponsor".to_string()));
+    ///
+    /// let t = pst_3d::Pst3d::new(v);
+    /// let bb = bbox::BBox::new(0., 0., 10., 10.);
+    ///
+    /// let mut o = overlay::PriorityOverlay::new();
+    /// o.add_id(3, 10.);
+    /// o.add_region(bbox::BBox::new(1.5, 2.5, 2.5, 3.5), -5.);
+    ///
+    /// let r = t.get_with_overlay(&bb, 5., &o);
+    /// let mut ids: Vec<i64> = r.iter().map(|l| l.get_osm_id()).collect();
+    /// ids.sort();
+    /// assert!(ids == vec![1, 3]);
+    /// ```
+    ///
+    pub fn get_with_overlay(&self,
+                            bbox: &BBox,
+                            min_t: f64,
+                            overlay: &PriorityOverlay)
+                            -> Vec<&Label> {
+        let indices = self.get_indices(bbox, min_t - overlay.get_max_boost());
+        overlay.apply_by(self.get_prioritized(indices), min_t)
+    }
+
+    ///
+    /// Return the set of label in the given bounding box with a t >= min_t and the labels that
+    /// were visible before and have a t >= min_t - delta.
+    ///
+    /// The visible set contains the osm ids of the labels returned by the previous query. If no
+    /// delta is given the default hysteresis of the dataset is used. Compare
+    /// [apply_hysteresis](query/fn.apply_hysteresis.html).
+    ///
+    /// # Examples
+    /// ```
+    /// use std::collections::HashSet;
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(2., 3., 5., 2, 1, 1.5, "T2".to_string()));
+    ///
+    /// let mut t = pst_3d::Pst3d::new(v);
+    /// t.set_hysteresis(0.5);
+    ///
+    /// let bb = bbox::BBox::new(0., 0., 10., 10.);
+    /// let visible: HashSet<i64> = t.get(&bb, 4.9).iter().map(|l| l.get_osm_id()).collect();
+    /// assert!(visible.len() == 2);
+    ///
+    /// // T2 stays visible for a slightly larger min_t
+    /// assert!(t.get_with_hysteresis(&bb, 5.1, &visible, None).len() == 2);
+    /// assert!(t.get_with_hysteresis(&bb, 5.1, &visible, Some(0.)).len() == 1);
+    /// assert!(t.get_with_hysteresis(&bb, 5.1, &HashSet::new(), None).len() == 1);
+    /// ```
+    ///
+    pub fn get_with_hysteresis(&self,
+                               bbox: &BBox,
+                               min_t: f64,
+                               visible: &HashSet<i64>,
+                               delta: Option<f64>)
+                               -> Vec<&Label> {
+        let delta = delta.unwrap_or(self.m_hysteresis);
+        let labels = self.get_prioritized(self.get_indices(bbox, min_t - delta));
+        query::apply_hysteresis_by(labels, min_t, visible)
+    }
+
+    ///
+    /// Return the set of label in the given bounding box with a t >= min_t together with the
+    /// generation id of the tree.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
+    ///
+    /// let t = pst_3d::Pst3d::new(v);
+    /// let r = t.get_versioned(&bbox::BBox::new(0., 0., 10., 10.), 0.);
+    ///
+    /// assert!(r.get_generation() == t.get_generation());
+    /// assert!(r.get_labels().len() == 1);
+    /// ```
+    ///
+    pub fn get_versioned(&self, bbox: &BBox, min_t: f64) -> VersionedResult<'_> {
+        VersionedResult::new(self.m_generation, self.get(bbox, min_t))
+    }
+
+    ///
+    /// Return the set of label in the given bounding box with a t >= min_t post processed by the
+    /// given query options.
+    ///
+    /// If the options contain a margin, the bounding box is expanded by it. If the options
+    /// contain a limit, it is applied after the other options, i.e. the result contains the labels
+    /// with the largest t values that are kept by the filters.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::{label, bbox};
+    /// use rt_datastructure::pst_3d;
+    /// use rt_datastructure::pst_3d::query;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 1., 10., 1, 1, 1.5, "Bremen".to_string()));
+    /// v.push(label::Label::new(1.1, 1., 9., 2, 1, 1.5, "Bremen".to_string()));
+    /// v.push(label::Label::new(1.2, 1., 8., 3, 1, 1.5, "Vegesack".to_string()));
+    /// v.push(label::Label::new(5., 5., 7., 4, 1, 1.5, "Bremen".to_string()));
+    ///
+    /// let t = pst_3d::Pst3d::new(v);
+    /// let bb = bbox::BBox::new(0., 0., 10., 10.);
+    ///
+    /// let mut o = query::QueryOptions::new();
+    /// o.set_merge_distance(2., 0.1);
+    ///
+    /// let r = t.get_with_options(&bb, 0., &o);
+    /// assert!(r.len() == 3);
+    /// assert!(r[0].get_label().get_osm_id() == 1);
+    /// assert!(r[0].get_merged_count() == 2);
+    /// ```
+    ///
+    pub fn get_with_options(&self,
+                            bbox: &BBox,
+                            min_t: f64,
+                            options: &QueryOptions)
+                            -> Vec<QueryResult<'_>> {
+        let bbox = options.expand_bbox(bbox);
+
+        // the limit is applied after the filters, so all labels are collected by decreasing t
+        let indices = match options.get_limit() {
+            Some(_) => self.get_limited_indices(&bbox, min_t, Some(usize::MAX)),
+            None => self.get_indices(&bbox, min_t),
+        };
+        query::apply_options_by(self.get_prioritized(indices), min_t, options)
+    }
+
+    ///
+    /// Create a Graphviz DOT representation of the tree.
+    ///
+    /// Each node shows its split dimension and value, the label string and the t value. The
+    /// edges to the children are labeled l and r. Unlike `to_string` the result can be rendered
+    /// as a graph, e.g. by `dot -Tsvg tree.dot -o tree.svg`.
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    /// use rt_datastructure::pst_3d;
+    ///
+    /// let mut v = Vec::new();
+    /// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
+    /// v.push(label::Label::new(2., 3., 9., 2, 1, 1.5, "T2".to_string()));
+    /// v.push(label::Label::new(3., 4., 8., 3, 1, 1.5, "\"T3\"".to_string()));
+    ///
+    /// let t = pst_3d::Pst3d::new(v);
+    ///
+    /// let dot = t.to_dot();
+    /// assert!(dot.starts_with("digraph pst3d {"));
+    /// assert!(dot.contains("n0 [label=\"x-split: 2\\nT1 (t: 10)\"];"));
+    /// assert!(dot.contains("n2 [label=\"leaf\\n\\\"T3\\\" (t: 8)\"];"));
+    /// assert!(dot.contains("n0 -> n2 [label=\"r\"];"));
+    /// ```
+    ///
+    pub fn to_dot(&self) -> String {
+        let mut result = "digraph pst3d {\n    node [shape=box];\n".to_string();
+        for (idx, node) in self.m_data.iter().enumerate() {
+            result.push_str(&node.to_dot(idx, &self.m_data));
+        }
+        result.push_str("}\n");
+
+        result
+    }
+}
+
+///
+/// Create a human readable string representation of the tree.
+///
+/// The function returns a multiline string with one row for each tree node. Large trees will
+/// produce a huge multiline string!
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::primitives::label;
+/// use rt_datastructure::pst_3d;
+///
+/// let mut v = Vec::new();
+/// v.push(label::Label::new(1., 2., 10., 1, 1, 1.5, "T1".to_string()));
+/// v.push(label::Label::new(2., 3., 9., 2, 1, 1.5, "T2".to_string()));
+/// v.push(label::Label::new(3., 4., 8., 3, 1, 1.5, "T3".to_string()));
+/// v.push(label::Label::new(4., 5., 7., 4, 1, 1.5, "T4".to_string()));
+/// v.push(label::Label::new(5., 6., 6., 5, 1, 1.5, "T5".to_string()));
+/// v.push(label::Label::new(6., 7., 5., 6, 1, 1.5, "T6".to_string()));
+/// v.push(label::Label::new(7., 8., 4., 7, 1, 1.5, "T7".to_string()));
+/// v.push(label::Label::new(8., 9., 3., 8, 1, 1.5, "T8".to_string()));
+/// v.push(label::Label::new(9., 10., 2., 9, 1, 1.5, "T9".to_string()));
+/// v.push(label::Label::new(10., 11., 1., 10, 1, 1.5, "T10".to_string()));
+///
+/// let t = pst_3d::Pst3d::new(v);
+///
+/// let res_string = "\
+///   x-node (split: 5): Label [#1]: 'T1' at (1, 2) with prio 1, elim-t: 10 and label \
+///                                                                              factor: 1.5\n\
+///   l    y-node (split: 4): Label [#2]: 'T2' at (2, 3) with prio 1, elim-t: 9 and label \
+///                                                                              factor: 1.5\n\
+///   l        x-node (split: NaN): Label [#3]: 'T3' at (3, 4) with prio 1, elim-t: 8 and \
+///                                                                        label factor: 1.5\n\
+///   r        x-node (split: 5): Label [#4]: 'T4' at (4, 5) with prio 1, elim-t: 7 and label \
+///                                                                              factor: 1.5\n\
+///   l            y-node (split: NaN): Label [#5]: 'T5' at (5, 6) with prio 1, elim-t: 6 and \
+///                                                                        label factor: 1.5\n\
+///   r    y-node (split: 9): Label [#6]: 'T6' at (6, 7) with prio 1, elim-t: 5 and label \
+///                                                                              factor: 1.5\n\
+///   l        x-node (split: 8): Label [#7]: 'T7' at (7, 8) with prio 1, elim-t: 4 and label \
+///                                                                              factor: 1.5\n\
+///   l            y-node (split: NaN): Label [#8]: 'T8' at (8, 9) with prio 1, elim-t: 3 and \
+///                                                                        label factor: 1.5\n\
+///   r        x-node (split: 10): Label [#9]: 'T9' at (9, 10) with prio 1, elim-t: 2 and \
+///                                                                        label factor: 1.5\n\
+///   l            y-node (split: NaN): Label [#10]: 'T10' at (10, 11) with prio 1, elim-t: 1 \
+///                                                                      and label factor: 1.5\
+///   ".to_string();
+///
+/// println!("Tree after construction:\n{}", t.to_string());
+///
+/// assert!(t.to_string() == res_string);
+/// ```
+///
+impl fmt::Display for Pst3d<Label> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.m_root_idx {
+            Some(idx) => {
+                write!(f, "{}", self.m_data[idx].to_string(0, &self.m_data))
+            }
+            None => write!(f, "PSKdT is empty!"),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Pst3d<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Pst3d")
+            .field("size", &self.m_data.get_element_count())
+            .field("bbox", &self.m_bbox)
+            .field("generation", &self.m_generation)
+            .finish()
+    }
+}
+
+///
+/// A read only view of a 3D PST at the time the snapshot was taken, see `Pst3d::snapshot`.
+///
+/// The snapshot owns a share of the tree nodes, i.e. it stays valid and unchanged independent of
+/// what happens to the tree it was taken from.
+///
+pub struct Pst3dSnapshot<T = Label> {
+    m_pst: Pst3d<T>,
+}
+
+impl<T: HasPosition + HasT> Pst3dSnapshot<T> {
+    ///
+    /// Get the generation id of the tree the snapshot was taken from.
+    ///
+    pub fn get_generation(&self) -> u64 {
+        self.m_pst.get_generation()
+    }
+
+    ///
+    /// Return the set of label in the given bounding box with a t >= min_t, see `Pst3d::get`.
+    ///
+    pub fn get<'a>(&'a self, bbox: &BBox, min_t: f64) -> Vec<&'a T> {
+        self.m_pst.get(bbox, min_t)
+    }
+
+    ///
+    /// Return the indices of the labels in the given bounding box with a t >= min_t, see
+    /// `Pst3d::get_indices`.
+    ///
+    pub fn get_indices(&self, bbox: &BBox, min_t: f64) -> Vec<u32> {
+        self.m_pst.get_indices(bbox, min_t)
+    }
+
+    ///
+    /// Resolve an index returned by `get_indices` to the corresponding label.
+    ///
+    /// Returns None if the index is out of range.
+    ///
+    pub fn get_label(&self, idx: u32) -> Option<&T> {
+        self.m_pst.get_label(idx)
+    }
+
+    ///
+    /// Get the number of labels in the snapshot.
+    ///
+    pub fn get_size(&self) -> usize {
+        self.m_pst.get_size()
+    }
+
+    ///
+    /// Access the snapshot as a tree to use the remaining queries.
+    ///
+    pub fn get_pst(&self) -> &Pst3d<T> {
+        &self.m_pst
+    }
+}
+
+// Check if the coordinates and the priority of a weighted label are finite.
+fn is_finite<T: HasPosition>(l: &(f64, T)) -> bool {
+    l.0.is_finite() && l.1.get_x().is_finite() && l.1.get_y().is_finite()
+}
+
+// Split a geographic query box into at most two boxes within the given extent of the world.
+//
+// The y coordinates are clamped to the extent, the x coordinates are wrapped into it. A box
+// crossing the antimeridian, given either by min_x > max_x or by x coordinates beyond the border
+// of the extent, results in two boxes.
+fn split_geo_box(bbox: &BBox, extent: &BBox) -> Vec<BBox> {
+    let (left, right) = (extent.get_min_x(), extent.get_max_x());
+    let width = right - left;
+
+    let min_y = bbox.get_min_y().clamp(extent.get_min_y(), extent.get_max_y());
+    let max_y = bbox.get_max_y().clamp(extent.get_min_y(), extent.get_max_y());
+    if min_y > max_y {
+        return Vec::new();
+    }
+
+    let (mut min_x, mut max_x) = (bbox.get_min_x(), bbox.get_max_x());
+    if min_x <= max_x && max_x - min_x >= width {
+        return vec![BBox::new(left, min_y, right, max_y)];
+    }
+
+    let in_range = |x: f64| (left..=right).contains(&x);
+    if !in_range(min_x) || !in_range(max_x) {
+        min_x = (min_x - left).rem_euclid(width) + left;
+        max_x = (max_x - left).rem_euclid(width) + left;
+    }
+
+    if min_x <= max_x {
+        vec![BBox::new(min_x, min_y, max_x, max_y)]
+    } else {
+        vec![BBox::new(min_x, min_y, right, max_y), BBox::new(left, min_y, max_x, max_y)]
+    }
+}
+
+// List the given label identifiers for an error message, at most MAX_LISTED_LABELS of them.
+fn list_labels<I: ToString>(ids: &[I]) -> String {
+    let listed: Vec<String> = ids.iter().take(MAX_LISTED_LABELS).map(|id| id.to_string()).collect();
+    if ids.len() > MAX_LISTED_LABELS {
+        format!("{} and {} more", listed.join(", "), ids.len() - MAX_LISTED_LABELS)
+    } else {
+        listed.join(", ")
     }
 }
@@ -0,0 +1,172 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+
+use primitives::bbox::BBox;
+use primitives::label::Label;
+
+///
+/// A set of editorial overrides that raise or lower the t value of labels at query time.
+///
+/// An override either matches a single label by its osm id or all labels within a region. The t
+/// value of a label is shifted by the sum of the deltas of all matching overrides. A positive
+/// delta promotes a label, i.e. it stays visible for larger min_t, a negative delta demotes it.
+///
+pub struct PriorityOverlay {
+    m_ids: HashMap<i64, f64>,
+    m_regions: Vec<(BBox, f64)>,
+
+    m_max_id_boost: f64,
+    m_region_boost: f64,
+}
+
+impl PriorityOverlay {
+    ///
+    /// Initialize a new overlay without any overrides.
+    ///
+    pub fn new() -> PriorityOverlay {
+        PriorityOverlay {
+            m_ids: HashMap::new(),
+            m_regions: Vec::new(),
+
+            m_max_id_boost: 0.,
+            m_region_boost: 0.,
+        }
+    }
+
+    ///
+    /// Shift the t value of the label with the given osm id by delta.
+    ///
+    /// Adding the same osm id twice replaces the previous delta.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::pst_3d::overlay;
+    ///
+    /// let mut o = overlay::PriorityOverlay::new();
+    /// o.add_id(1, 5.);
+    /// o.add_id(2, 3.);
+    /// assert!(o.get_max_boost() == 5.);
+    ///
+    /// o.add_id(1, 1.);
+    /// assert!(o.get_max_boost() == 3.);
+    /// ```
+    ///
+    pub fn add_id(&mut self, osm_id: i64, delta: f64) {
+        let previous = self.m_ids.insert(osm_id, delta);
+        if delta >= self.m_max_id_boost {
+            self.m_max_id_boost = delta;
+        } else if previous == Some(self.m_max_id_boost) {
+            // the largest delta was lowered, which is rare enough to scan all deltas again
+            self.m_max_id_boost = self.m_ids.values().fold(0., |m: f64, &d| m.max(d));
+        }
+    }
+
+    ///
+    /// Shift the t value of all labels within the given region by delta.
+    ///
+    pub fn add_region(&mut self, region: BBox, delta: f64) {
+        if delta > 0. {
+            self.m_region_boost += delta;
+        }
+        self.m_regions.push((region, delta));
+    }
+
+    ///
+    /// Get an upper bound of the increase of the t value of any label.
+    ///
+    pub fn get_max_boost(&self) -> f64 {
+        self.m_max_id_boost + self.m_region_boost
+    }
+
+    ///
+    /// Get the t value of the label after applying all matching overrides.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::{bbox, label};
+    /// use rt_datastructure::pst_3d::overlay;
+    ///
+    /// let mut o = overlay::PriorityOverlay::new();
+    /// o.add_id(1, 2.);
+    /// o.add_region(bbox::BBox::new(0., 0., 1., 1.), -1.);
+    ///
+    /// let l1 = label::Label::new(0.5, 0.5, 5., 1, 1, 1.5, "T1".to_string());
+    /// let l2 = label::Label::new(0.5, 0.5, 5., 2, 1, 1.5, "T2".to_string());
+    /// let l3 = label::Label::new(2., 2., 5., 3, 1, 1.5, "T3".to_string());
+    ///
+    /// assert!(o.get_t(&l1) == 6.);
+    /// assert!(o.get_t(&l2) == 4.);
+    /// assert!(o.get_t(&l3) == 5.);
+    /// assert!(o.get_max_boost() == 2.);
+    /// ```
+    ///
+    pub fn get_t(&self, l: &Label) -> f64 {
+        self.get_priority(l, l.get_t())
+    }
+
+    ///
+    /// Get the given priority of the label after applying all matching overrides, e.g. the
+    /// priority of the label in a tree constructed by `Pst3d::new_by`.
+    ///
+    pub fn get_priority(&self, l: &Label, priority: f64) -> f64 {
+        let mut t = priority;
+        if let Some(delta) = self.m_ids.get(&l.get_osm_id()) {
+            t += *delta;
+        }
+        for &(ref region, delta) in &self.m_regions {
+            if region.is_contained(l) {
+                t += delta;
+            }
+        }
+
+        t
+    }
+
+    ///
+    /// Keep the labels whose t value after applying the overrides is > min_t.
+    ///
+    /// The labels must be the result of a query with min_t - get_max_boost().
+    ///
+    pub fn apply<'a>(&self, labels: Vec<&'a Label>, min_t: f64) -> Vec<&'a Label> {
+        labels.into_iter().filter(|l| self.get_t(l) > min_t).collect()
+    }
+
+    ///
+    /// Keep the labels whose priority after applying the overrides is > min_t, each label given
+    /// together with its priority. Compare `apply`.
+    ///
+    pub fn apply_by<'a>(&self, labels: Vec<(&'a Label, f64)>, min_t: f64) -> Vec<&'a Label> {
+        labels.into_iter()
+            .filter(|&(l, priority)| self.get_priority(l, priority) > min_t)
+            .map(|(l, _)| l)
+            .collect()
+    }
+}
+
+impl Default for PriorityOverlay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
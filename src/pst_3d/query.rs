@@ -0,0 +1,948 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use primitives::bbox::BBox;
+use primitives::label::Label;
+
+///
+/// Options to refine the result of a query.
+///
+/// A default constructed QueryOptions object does not change the query result.
+///
+pub struct QueryOptions {
+    m_merge_distance: Option<f64>,
+    m_limit: Option<usize>,
+    m_sample_fraction: Option<f64>,
+    m_dedup_by_id: bool,
+    m_margin: f64,
+    m_categories: Option<CategorySet>,
+}
+
+impl QueryOptions {
+    ///
+    /// Initialize new query options that do not alter the query result.
+    ///
+    pub fn new() -> QueryOptions {
+        QueryOptions {
+            m_merge_distance: None,
+            m_limit: None,
+            m_sample_fraction: None,
+            m_dedup_by_id: false,
+            m_margin: 0.,
+            m_categories: None,
+        }
+    }
+
+    ///
+    /// Merge labels with identical label strings that are at most pixel_distance pixels apart.
+    ///
+    /// The pixel distance is converted into a coordinate distance by the size of a pixel in
+    /// coordinate units (e.g. degrees per pixel at the current zoom level).
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::pst_3d::query;
+    ///
+    /// let mut o = query::QueryOptions::new();
+    /// assert!(o.get_merge_distance().is_none());
+    ///
+    /// o.set_merge_distance(4., 0.5);
+    /// assert!(o.get_merge_distance() == Some(2.));
+    /// ```
+    ///
+    pub fn set_merge_distance(&mut self, pixel_distance: f64, pixel_size: f64) {
+        self.m_merge_distance = Some(pixel_distance * pixel_size);
+    }
+
+    ///
+    /// Get the maximum distance in coordinate units of two merged labels if merging is enabled.
+    ///
+    pub fn get_merge_distance(&self) -> Option<f64> {
+        self.m_merge_distance
+    }
+
+    ///
+    /// Limit the number of results of the query.
+    ///
+    /// The limit is applied after all other options, so the results are the labels with the
+    /// largest t values among the labels kept by the filters. Compare
+    /// [Pst3d::get_limited](../struct.Pst3d.html#method.get_limited).
+    ///
+    pub fn set_limit(&mut self, limit: Option<usize>) {
+        self.m_limit = limit;
+    }
+
+    ///
+    /// Get the maximum number of labels collected by the query.
+    ///
+    pub fn get_limit(&self) -> Option<usize> {
+        self.m_limit
+    }
+
+    ///
+    /// Keep only about the given fraction of the labels. Compare [is_sampled](fn.is_sampled.html).
+    ///
+    /// The sampling is deterministic: the same query on the same dataset returns identical
+    /// results in every process, independent of any random number generator state.
+    ///
+    pub fn set_sample_fraction(&mut self, fraction: Option<f64>) {
+        self.m_sample_fraction = fraction;
+    }
+
+    ///
+    /// Get the fraction of labels kept by the sampling if sampling is enabled.
+    ///
+    pub fn get_sample_fraction(&self) -> Option<f64> {
+        self.m_sample_fraction
+    }
+
+    ///
+    /// Collapse labels with the same osm id into the one with the highest t value.
+    ///
+    /// Elimination sequences may contain several label candidates of the same object, usually
+    /// only one of them is displayed.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    /// use rt_datastructure::pst_3d::query;
+    ///
+    /// let l1 = label::Label::new(1., 1., 5., 1, 1, 1.5, "Bremen".to_string());
+    /// let l2 = label::Label::new(1.5, 1., 7., 1, 1, 1.5, "Bremen".to_string());
+    /// let l3 = label::Label::new(2., 2., 6., 2, 1, 1.5, "Vegesack".to_string());
+    ///
+    /// let mut o = query::QueryOptions::new();
+    /// o.set_dedup_by_id(true);
+    ///
+    /// let r = query::apply_options(vec![&l1, &l2, &l3], 0., &o);
+    /// assert!(r.len() == 2);
+    /// assert!(r[0].get_label().get_t() == 7.);
+    /// assert!(r[1].get_label().get_osm_id() == 2);
+    /// ```
+    ///
+    pub fn set_dedup_by_id(&mut self, dedup: bool) {
+        self.m_dedup_by_id = dedup;
+    }
+
+    ///
+    /// Check if labels with the same osm id are collapsed.
+    ///
+    pub fn get_dedup_by_id(&self) -> bool {
+        self.m_dedup_by_id
+    }
+
+    ///
+    /// Expand the query bounding box by the given margin in coordinate units (e.g. degrees) on
+    /// each side, so labels whose text overlaps the border of the viewport are included.
+    ///
+    /// Negative margins are ignored.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::bbox;
+    /// use rt_datastructure::pst_3d::query;
+    ///
+    /// let mut o = query::QueryOptions::new();
+    /// o.set_margin(0.5);
+    ///
+    /// let bb = o.expand_bbox(&bbox::BBox::new(0., 0., 1., 1.));
+    /// assert!(bb.get_min_x() == -0.5 && bb.get_max_y() == 1.5);
+    /// ```
+    ///
+    pub fn set_margin(&mut self, margin: f64) {
+        self.m_margin = if margin > 0. { margin } else { 0. };
+    }
+
+    ///
+    /// Expand the query bounding box by the given number of label sizes on each side.
+    ///
+    /// The label sizes are converted into a coordinate distance by the size of a label with
+    /// label factor 1 in coordinate units at the current zoom level, e.g. the width of its text.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::pst_3d::query;
+    ///
+    /// let mut o = query::QueryOptions::new();
+    /// assert!(o.get_margin() == 0.);
+    ///
+    /// o.set_margin_in_label_sizes(1.5, 0.2);
+    /// assert!((o.get_margin() - 0.3).abs() < 1e-12);
+    /// ```
+    ///
+    pub fn set_margin_in_label_sizes(&mut self, label_sizes: f64, label_size: f64) {
+        self.set_margin(label_sizes * label_size);
+    }
+
+    ///
+    /// Get the margin in coordinate units the query bounding box is expanded by.
+    ///
+    pub fn get_margin(&self) -> f64 {
+        self.m_margin
+    }
+
+    ///
+    /// Keep only labels whose category is contained in the given set. Compare
+    /// [in_categories](fn.in_categories.html).
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    /// use rt_datastructure::pst_3d::query;
+    ///
+    /// let mut city = label::Label::new(1., 1., 5., 1, 1, 1.5, "Innsbruck".to_string());
+    /// let mut peak = label::Label::new(1.5, 1., 7., 2, 1, 1.5, "Zugspitze".to_string());
+    /// city.set_category(1);
+    /// peak.set_category(2);
+    ///
+    /// let mut o = query::QueryOptions::new();
+    /// o.set_categories(Some(query::CategorySet::from_list(&[2])));
+    ///
+    /// let r = query::apply_options(vec![&city, &peak], 0., &o);
+    /// assert!(r.len() == 1);
+    /// assert!(r[0].get_label().get_osm_id() == 2);
+    /// ```
+    ///
+    pub fn set_categories(&mut self, categories: Option<CategorySet>) {
+        self.m_categories = categories;
+    }
+
+    ///
+    /// Get the set of categories kept by the query if filtering is enabled.
+    ///
+    pub fn get_categories(&self) -> Option<&CategorySet> {
+        self.m_categories.as_ref()
+    }
+
+    ///
+    /// Expand the given bounding box by the margin on each side.
+    ///
+    /// A box crossing the antimeridian (min x > max x) keeps crossing it, the wraparound is
+    /// handled by the geographic queries.
+    ///
+    pub fn expand_bbox(&self, bbox: &BBox) -> BBox {
+        bbox.expanded(self.m_margin, self.m_margin)
+    }
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+///
+/// A budget limiting the work of a single query.
+///
+/// A query exceeding its budget stops the traversal and returns the labels found so far. As the
+/// nodes are visited by decreasing t value, the partial result contains the most important
+/// labels. A default constructed QueryBudget is unlimited.
+///
+pub struct QueryBudget {
+    m_max_visits: Option<usize>,
+    m_max_duration: Option<Duration>,
+}
+
+impl QueryBudget {
+    ///
+    /// Initialize a new unlimited budget.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::Duration;
+    /// use rt_datastructure::pst_3d::query;
+    ///
+    /// let mut b = query::QueryBudget::new();
+    /// assert!(b.get_max_visits().is_none());
+    ///
+    /// b.set_max_visits(Some(1000));
+    /// b.set_max_duration(Some(Duration::from_millis(2)));
+    /// assert!(b.get_max_visits() == Some(1000));
+    /// assert!(b.get_max_duration() == Some(Duration::from_millis(2)));
+    /// ```
+    ///
+    pub fn new() -> QueryBudget {
+        QueryBudget {
+            m_max_visits: None,
+            m_max_duration: None,
+        }
+    }
+
+    ///
+    /// Limit the number of tree nodes visited by the query.
+    ///
+    pub fn set_max_visits(&mut self, max_visits: Option<usize>) {
+        self.m_max_visits = max_visits;
+    }
+
+    ///
+    /// Get the maximum number of tree nodes visited by the query.
+    ///
+    pub fn get_max_visits(&self) -> Option<usize> {
+        self.m_max_visits
+    }
+
+    ///
+    /// Limit the time spent on the traversal of the tree.
+    ///
+    pub fn set_max_duration(&mut self, max_duration: Option<Duration>) {
+        self.m_max_duration = max_duration;
+    }
+
+    ///
+    /// Get the maximum time spent on the traversal of the tree.
+    ///
+    pub fn get_max_duration(&self) -> Option<Duration> {
+        self.m_max_duration
+    }
+}
+
+impl Default for QueryBudget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+///
+/// The precision of coordinates in the output of query results.
+///
+/// Reducing the precision reduces the size of serialized results without a visible placement
+/// error.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CoordinatePrecision {
+    /// Output the coordinates unchanged
+    Full,
+    /// Round the coordinates to the given number of decimal places
+    Decimals(u32),
+    /// Snap the coordinates to the nearest multiple of the given grid size (e.g. tile units)
+    Grid(f64),
+}
+
+impl CoordinatePrecision {
+    ///
+    /// Apply the precision to a single coordinate value.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::pst_3d::query::CoordinatePrecision;
+    ///
+    /// assert!(CoordinatePrecision::Full.apply(8.9351249) == 8.9351249);
+    /// assert!(CoordinatePrecision::Decimals(2).apply(8.9351249) == 8.94);
+    /// assert!(CoordinatePrecision::Decimals(0).apply(-8.9351249) == -9.);
+    /// assert!(CoordinatePrecision::Grid(0.25).apply(8.9351249) == 9.);
+    /// ```
+    ///
+    pub fn apply(&self, v: f64) -> f64 {
+        match *self {
+            CoordinatePrecision::Full => v,
+            CoordinatePrecision::Decimals(d) => {
+                let f = 10f64.powi(d as i32);
+                (v * f).round() / f
+            }
+            CoordinatePrecision::Grid(size) => {
+                if size > 0. {
+                    (v / size).round() * size
+                } else {
+                    v
+                }
+            }
+        }
+    }
+}
+
+///
+/// An ordered list of name keys used to resolve the displayed name of a label.
+///
+/// The first key the label has a name for is used, e.g. for ["name:de", "name:en", "name"] the
+/// german name is preferred over the english name and the label string. If the label has no
+/// name for any of the keys, the label string is used.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct NameFallback {
+    m_keys: Vec<String>,
+}
+
+impl NameFallback {
+    ///
+    /// Initialize a new fallback chain from the given keys in the order of preference.
+    ///
+    pub fn new(keys: Vec<String>) -> NameFallback {
+        NameFallback { m_keys: keys }
+    }
+
+    ///
+    /// Initialize a new fallback chain from a comma separated list of keys, e.g.
+    /// "name:de,name:en,name".
+    ///
+    pub fn from_list(keys: &str) -> NameFallback {
+        NameFallback::new(keys.split(',')
+                              .map(|k| k.trim().to_string())
+                              .filter(|k| !k.is_empty())
+                              .collect())
+    }
+
+    ///
+    /// Get the keys in the order of preference.
+    ///
+    pub fn get_keys(&self) -> &Vec<String> {
+        &self.m_keys
+    }
+
+    ///
+    /// Resolve the name of the given label.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::primitives::label;
+    /// use rt_datastructure::pst_3d::query::NameFallback;
+    ///
+    /// let mut l = label::Label::new(11.58, 48.14, 0.9, 1, 16, 1.5, "München".to_string());
+    /// l.set_name("name:en".to_string(), "Munich".to_string());
+    ///
+    /// let fallback = NameFallback::from_list("name:fr, name:en, name");
+    /// assert!(*fallback.resolve(&l) == "Munich".to_string());
+    ///
+    /// let fallback = NameFallback::from_list("name:fr");
+    /// assert!(*fallback.resolve(&l) == "München".to_string());
+    /// ```
+    ///
+    pub fn resolve<'a>(&self, l: &'a Label) -> &'a String {
+        self.m_keys
+            .iter()
+            .filter_map(|k| l.get_name(k))
+            .next()
+            .unwrap_or_else(|| l.get_label())
+    }
+}
+
+impl Default for NameFallback {
+    fn default() -> Self {
+        Self::new(vec!["name".to_string()])
+    }
+}
+
+///
+/// A set of label categories stored as a bitset, e.g. to filter query results by category.
+///
+/// Any category a label can have, i.e. 0 to u16::MAX, can be contained. The bitset only grows
+/// up to the largest contained category.
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::pst_3d::query::CategorySet;
+///
+/// let mut s = CategorySet::from_mask(0b1010);
+/// s.insert(300);
+///
+/// assert!(s.contains(1) && s.contains(3) && s.contains(300));
+/// assert!(!s.contains(2) && !s.contains(64) && !s.contains(u16::MAX));
+/// ```
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CategorySet {
+    m_bits: Vec<u64>,
+}
+
+impl CategorySet {
+    ///
+    /// Initialize a new empty set.
+    ///
+    pub fn new() -> CategorySet {
+        CategorySet { m_bits: Vec::new() }
+    }
+
+    ///
+    /// Initialize a new set containing the given categories.
+    ///
+    pub fn from_list(categories: &[u16]) -> CategorySet {
+        let mut s = CategorySet::new();
+        for c in categories {
+            s.insert(*c);
+        }
+
+        s
+    }
+
+    ///
+    /// Initialize a new set of the categories 0 to 63 given by a bitmask, i.e. the set contains
+    /// the category c if bit c of the mask is set.
+    ///
+    pub fn from_mask(mask: u64) -> CategorySet {
+        CategorySet { m_bits: vec![mask] }
+    }
+
+    ///
+    /// Add the category to the set.
+    ///
+    pub fn insert(&mut self, category: u16) {
+        let word = usize::from(category / 64);
+        if self.m_bits.len() <= word {
+            self.m_bits.resize(word + 1, 0);
+        }
+        self.m_bits[word] |= 1 << (category % 64);
+    }
+
+    ///
+    /// Check if the set contains the category.
+    ///
+    pub fn contains(&self, category: u16) -> bool {
+        self.m_bits
+            .get(usize::from(category / 64))
+            .is_some_and(|w| w & (1 << (category % 64)) != 0)
+    }
+}
+
+///
+/// The labels of a query result together with the generation id of the queried dataset.
+///
+/// Every constructed or rebuilt tree gets a new generation id. Clients mixing cached and fresh
+/// results can compare the generation ids to detect results of outdated datasets.
+///
+pub struct VersionedResult<'a> {
+    m_generation: u64,
+    m_labels: Vec<&'a Label>,
+}
+
+impl<'a> VersionedResult<'a> {
+    ///
+    /// Initialize a new result of the dataset with the given generation id.
+    ///
+    pub fn new(generation: u64, labels: Vec<&'a Label>) -> VersionedResult<'a> {
+        VersionedResult {
+            m_generation: generation,
+            m_labels: labels,
+        }
+    }
+
+    ///
+    /// Get the generation id of the queried dataset.
+    ///
+    pub fn get_generation(&self) -> u64 {
+        self.m_generation
+    }
+
+    ///
+    /// Get the labels of the result.
+    ///
+    pub fn get_labels(&self) -> &Vec<&'a Label> {
+        &self.m_labels
+    }
+
+    ///
+    /// Consume the result and return its labels.
+    ///
+    pub fn into_labels(self) -> Vec<&'a Label> {
+        self.m_labels
+    }
+}
+
+///
+/// A single element of a query result.
+///
+/// If labels have been merged the result element references the label with the highest t value
+/// and the merged count contains the number of labels it represents (including itself).
+///
+pub struct QueryResult<'a> {
+    m_label: &'a Label,
+    m_merged_count: usize,
+}
+
+impl<'a> QueryResult<'a> {
+    ///
+    /// Initialize a new result element representing a single label.
+    ///
+    pub fn new(l: &'a Label) -> QueryResult<'a> {
+        QueryResult {
+            m_label: l,
+            m_merged_count: 1,
+        }
+    }
+
+    ///
+    /// Get the label of the result element.
+    ///
+    pub fn get_label(&self) -> &'a Label {
+        self.m_label
+    }
+
+    ///
+    /// Get the number of labels represented by the result element.
+    ///
+    pub fn get_merged_count(&self) -> usize {
+        self.m_merged_count
+    }
+}
+
+///
+/// Statistics about the traversal of the tree by a single query.
+///
+/// A subtree is pruned if its elements are skipped without testing them individually, either
+/// because none of them has a sufficiently large t value or because the subtree lies on the other
+/// side of a split line.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct QueryStats {
+    m_nodes_visited: usize,
+    m_subtrees_pruned: usize,
+    m_labels_tested: usize,
+    m_labels_returned: usize,
+}
+
+impl QueryStats {
+    ///
+    /// Initialize new statistics with the given counters.
+    ///
+    ///
+    /// # Examples
+    /// ```
+    /// use rt_datastructure::pst_3d::query;
+    ///
+    /// let mut s = query::QueryStats::new(10, 2, 8, 5);
+    /// s.add(&query::QueryStats::new(1, 1, 1, 1));
+    ///
+    /// assert!(s.get_nodes_visited() == 11);
+    /// assert!(s.get_subtrees_pruned() == 3);
+    /// assert!(s.get_labels_tested() == 9);
+    /// assert!(s.get_labels_returned() == 6);
+    /// ```
+    ///
+    pub fn new(nodes_visited: usize,
+               subtrees_pruned: usize,
+               labels_tested: usize,
+               labels_returned: usize)
+               -> QueryStats {
+        QueryStats {
+            m_nodes_visited: nodes_visited,
+            m_subtrees_pruned: subtrees_pruned,
+            m_labels_tested: labels_tested,
+            m_labels_returned: labels_returned,
+        }
+    }
+
+    ///
+    /// Add the counters of other to the counters of self, e.g. to combine the statistics of the
+    /// two parts of a query crossing the antimeridian.
+    ///
+    pub fn add(&mut self, other: &QueryStats) {
+        self.m_nodes_visited += other.m_nodes_visited;
+        self.m_subtrees_pruned += other.m_subtrees_pruned;
+        self.m_labels_tested += other.m_labels_tested;
+        self.m_labels_returned += other.m_labels_returned;
+    }
+
+    ///
+    /// Get the number of tree nodes visited by the query.
+    ///
+    pub fn get_nodes_visited(&self) -> usize {
+        self.m_nodes_visited
+    }
+
+    ///
+    /// Get the number of subtrees that have been skipped by the query.
+    ///
+    pub fn get_subtrees_pruned(&self) -> usize {
+        self.m_subtrees_pruned
+    }
+
+    ///
+    /// Get the number of labels that have been tested against the bounding box.
+    ///
+    pub fn get_labels_tested(&self) -> usize {
+        self.m_labels_tested
+    }
+
+    ///
+    /// Get the number of labels returned by the query.
+    ///
+    pub fn get_labels_returned(&self) -> usize {
+        self.m_labels_returned
+    }
+}
+
+///
+/// Apply the given query options to the labels returned by a query with the given min_t.
+///
+/// The limit of the options is applied last. The labels are expected to be ordered by decreasing
+/// t value, e.g. as returned by `get_limited`, so that the labels with the largest t values are
+/// kept.
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::primitives::label;
+/// use rt_datastructure::pst_3d::query;
+///
+/// let mut a = label::Label::new(1., 1., 10., 1, 1, 1.5, "A".to_string());
+/// let b = label::Label::new(2., 1., 9., 2, 1, 1.5, "B".to_string());
+/// let mut c = label::Label::new(3., 1., 8., 3, 1, 1.5, "C".to_string());
+/// a.set_category(1);
+/// c.set_category(1);
+///
+/// let mut o = query::QueryOptions::new();
+/// o.set_categories(Some(query::CategorySet::from_list(&[1])));
+/// o.set_limit(Some(2));
+///
+/// let r = query::apply_options(vec![&a, &b, &c], 0., &o);
+/// assert!(r.len() == 2);
+/// assert!(r[1].get_label().get_osm_id() == 3);
+/// ```
+///
+pub fn apply_options<'a>(labels: Vec<&'a Label>,
+                         min_t: f64,
+                         options: &QueryOptions)
+                         -> Vec<QueryResult<'a>> {
+    apply_options_by(labels.into_iter().map(|l| (l, l.get_t())).collect(), min_t, options)
+}
+
+///
+/// Apply the given query options to the labels returned by a query with the given min_t, each
+/// label given together with its priority.
+///
+/// Like `apply_options`, but duplicates are merged and deduplicated by the given priorities
+/// instead of the t values of the labels, e.g. for a tree constructed by `Pst3d::new_by`.
+/// Compare [Pst3d::get_priority](../struct.Pst3d.html#method.get_priority).
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::primitives::label;
+/// use rt_datastructure::pst_3d::query;
+///
+/// let a = label::Label::new(1., 1., 10., 1, 1, 1.5, "A".to_string());
+/// let b = label::Label::new(1.1, 1., 9., 2, 1, 1.5, "A".to_string());
+///
+/// let mut o = query::QueryOptions::new();
+/// o.set_merge_distance(1., 1.);
+///
+/// let r = query::apply_options_by(vec![(&a, 1.), (&b, 2.)], 0., &o);
+/// assert!(r.len() == 1);
+/// assert!(r[0].get_label().get_osm_id() == 2);
+/// ```
+///
+pub fn apply_options_by<'a>(mut labels: Vec<(&'a Label, f64)>,
+                            min_t: f64,
+                            options: &QueryOptions)
+                            -> Vec<QueryResult<'a>> {
+    if let Some(categories) = options.get_categories() {
+        labels.retain(|&(l, _)| in_categories(l, categories));
+    }
+    if options.get_dedup_by_id() {
+        labels = dedup_by_id(labels);
+    }
+    if let Some(fraction) = options.get_sample_fraction() {
+        labels.retain(|&(l, _)| is_sampled(l, min_t, fraction));
+    }
+
+    let mut result = match options.get_merge_distance() {
+        Some(dist) => merge_duplicates(labels, dist),
+        None => labels.into_iter().map(|(l, _)| QueryResult::new(l)).collect(),
+    };
+    if let Some(limit) = options.get_limit() {
+        result.truncate(limit);
+    }
+
+    result
+}
+
+///
+/// Check if the category of the label is contained in the given set.
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::primitives::label;
+/// use rt_datastructure::pst_3d::query::{self, CategorySet};
+///
+/// let mut l = label::Label::new(1., 1., 10., 1, 1, 1.5, "Bremen".to_string());
+/// l.set_category(3);
+///
+/// assert!(query::in_categories(&l, &CategorySet::from_mask(0b1000)));
+/// assert!(!query::in_categories(&l, &CategorySet::from_mask(0b0111)));
+///
+/// l.set_category(64);
+/// assert!(!query::in_categories(&l, &CategorySet::from_mask(u64::MAX)));
+/// assert!(query::in_categories(&l, &CategorySet::from_list(&[64, 1000])));
+/// ```
+///
+pub fn in_categories(l: &Label, categories: &CategorySet) -> bool {
+    categories.contains(l.get_category())
+}
+
+///
+/// Check if the label is kept when sampling the given fraction of the labels of a query with the
+/// given min_t.
+///
+/// The decision only depends on the osm id of the label and on min_t. No random number generator
+/// is involved, so distributed renderers produce identical results. The queried bounding box is
+/// deliberately ignored: a label is kept or dropped consistently in all tiles of the same min_t.
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::primitives::label;
+/// use rt_datastructure::pst_3d::query;
+///
+/// let l = label::Label::new(1., 1., 10., 1, 1, 1.5, "Bremen".to_string());
+/// assert!(query::is_sampled(&l, 0.5, 1.));
+/// assert!(!query::is_sampled(&l, 0.5, 0.));
+/// assert!(query::is_sampled(&l, 0.5, 0.3) == query::is_sampled(&l, 0.5, 0.3));
+///
+/// let kept = (0..1000)
+///     .map(|id| label::Label::new(1., 1., 10., id, 1, 1.5, "A".to_string()))
+///     .filter(|l| query::is_sampled(l, 0.5, 0.3))
+///     .count();
+/// assert!(kept > 250 && kept < 350);
+/// ```
+///
+pub fn is_sampled(l: &Label, min_t: f64, fraction: f64) -> bool {
+    let h = mix(l.get_osm_id() as u64 ^ mix(min_t.to_bits()));
+
+    // map the upper 53 bits of the hash to [0, 1)
+    ((h >> 11) as f64) / ((1u64 << 53) as f64) < fraction
+}
+
+// The finalizer of the splitmix64 generator, a fast and well distributed 64 bit mix function.
+fn mix(v: u64) -> u64 {
+    let mut z = v.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+
+    z ^ (z >> 31)
+}
+
+///
+/// Keep only the label with the highest priority of each osm id.
+///
+/// The result keeps the position of the first label of each osm id.
+///
+fn dedup_by_id<'a>(labels: Vec<(&'a Label, f64)>) -> Vec<(&'a Label, f64)> {
+    let mut result: Vec<(&'a Label, f64)> = Vec::with_capacity(labels.len());
+    let mut by_id: HashMap<i64, usize> = HashMap::new();
+
+    for (l, priority) in labels {
+        match by_id.get(&l.get_osm_id()) {
+            Some(&idx) => {
+                if priority > result[idx].1 {
+                    result[idx] = (l, priority);
+                }
+            }
+            None => {
+                by_id.insert(l.get_osm_id(), result.len());
+                result.push((l, priority));
+            }
+        }
+    }
+
+    result
+}
+
+///
+/// Merge labels with identical label strings within the given distance.
+///
+/// The labels are processed by decreasing priority, so every label is merged into the
+/// representative with the highest priority. The result is ordered by decreasing priority.
+///
+fn merge_duplicates<'a>(mut labels: Vec<(&'a Label, f64)>, dist: f64) -> Vec<QueryResult<'a>> {
+    labels.sort_by(|first, second| second.1.partial_cmp(&first.1).unwrap_or(Ordering::Equal));
+
+    let squared_dist = dist * dist;
+    let mut result: Vec<QueryResult<'a>> = Vec::with_capacity(labels.len());
+    let mut by_text: HashMap<&'a str, Vec<usize>> = HashMap::new();
+
+    for (l, _) in labels {
+        let candidates = by_text.entry(l.get_label().as_str()).or_default();
+
+        let representative = candidates.iter().cloned().find(|&idx| {
+            let r = result[idx].m_label;
+            let dx = r.get_x() - l.get_x();
+            let dy = r.get_y() - l.get_y();
+            dx * dx + dy * dy <= squared_dist
+        });
+
+        match representative {
+            Some(idx) => result[idx].m_merged_count += 1,
+            None => {
+                candidates.push(result.len());
+                result.push(QueryResult::new(l));
+            }
+        }
+    }
+
+    result
+}
+
+///
+/// Filter the labels of a query with min_t - delta by a visibility hysteresis.
+///
+/// A label with t > min_t is always kept. A label with min_t - delta < t <= min_t is only kept if
+/// it was visible before, i.e. its osm id is contained in the visible set. This way a label
+/// appears at t but disappears only at t - delta and labels do not flicker if the min_t of
+/// consecutive queries oscillates around t.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashSet;
+/// use rt_datastructure::primitives::label;
+/// use rt_datastructure::pst_3d::query;
+///
+/// let l1 = label::Label::new(1., 1., 5., 1, 1, 1.5, "T1".to_string());
+/// let l2 = label::Label::new(1., 1., 4.9, 2, 1, 1.5, "T2".to_string());
+/// let l3 = label::Label::new(1., 1., 4.8, 3, 1, 1.5, "T3".to_string());
+///
+/// let mut visible = HashSet::new();
+/// visible.insert(2);
+///
+/// let r = query::apply_hysteresis(vec![&l1, &l2, &l3], 4.95, &visible);
+/// assert!(r.len() == 2);
+/// ```
+///
+pub fn apply_hysteresis<'a>(labels: Vec<&'a Label>,
+                            min_t: f64,
+                            visible: &HashSet<i64>)
+                            -> Vec<&'a Label> {
+    apply_hysteresis_by(labels.into_iter().map(|l| (l, l.get_t())).collect(), min_t, visible)
+}
+
+///
+/// Filter the labels of a query with min_t - delta by a visibility hysteresis, each label given
+/// together with its priority.
+///
+/// Like `apply_hysteresis`, but the given priorities are compared with min_t instead of the t
+/// values of the labels, e.g. for a tree constructed by `Pst3d::new_by`.
+///
+pub fn apply_hysteresis_by<'a>(labels: Vec<(&'a Label, f64)>,
+                               min_t: f64,
+                               visible: &HashSet<i64>)
+                               -> Vec<&'a Label> {
+    labels.into_iter()
+        .filter(|&(l, priority)| priority > min_t || visible.contains(&l.get_osm_id()))
+        .map(|(l, _)| l)
+        .collect()
+}
@@ -21,14 +21,31 @@
 
 use std::f64;
 use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::io::{Read, Write};
+use std::mem;
+use std::ops::{Deref, DerefMut, Range};
+use std::sync::atomic::{AtomicU32, Ordering as AtomicOrdering};
+use std::thread;
+use std::time::Instant;
 
+use error::Error;
+use pack::codec;
+use primitives::coord::{Coord, from_coord, to_coord};
 use primitives::label::Label;
 use primitives::bbox::BBox;
+use primitives::point::Point;
+use primitives::traits::{HasPosition, HasT};
+use pst_3d::builder::{Pst3dBuilder, SplitStrategy};
+use pst_3d::query::{QueryBudget, QueryStats};
 
 ///
 /// Represent the possible split dimensions.
 ///
+/// The split dimension is stored in a single byte flag.
+///
 #[derive(PartialEq)]
+#[repr(u8)]
 enum SplitDimension {
     X,
     Y,
@@ -36,372 +53,2043 @@ enum SplitDimension {
 }
 
 ///
-/// The struct defines a tree node.
+/// The child index of a node without the corresponding child.
 ///
-/// The tree nodes members are the labels t value, the label itself, the split type (X, Y or UNDEF
-/// in case the node is a leaf node).
+const NO_CHILD: u32 = u32::MAX;
+
 ///
-/// The split value indicates the maximum value of the left children in the corresponding
-/// dimension. The split value is guaranteed to be less than the corresponding coordinate of the
-/// right children.
+/// The maximum number of elements of a single tree.
 ///
-/// Left and right child are some indices, if there is a left or right subtree and none otherwise.
-pub struct Root {
-    m_t: f64,
-    m_data: Label,
-    m_type: SplitDimension,
-    m_split: f64,
-    m_left_child: Option<usize>,
-    m_right_child: Option<usize>,
+pub const MAX_ELEMENTS: usize = NO_CHILD as usize - 1;
+
+///
+/// The number of visited nodes between two checks of the time budget of a query.
+///
+const TIME_CHECK_INTERVAL: usize = 64;
+
+///
+/// A collector of the counters of a traversal of the tree by a query.
+///
+/// The traversal is generic over the collector, so a query without statistics uses `NoStats`
+/// and the counting is compiled out.
+///
+pub trait TraversalStats {
+    ///
+    /// Count a visited node.
+    ///
+    fn visit(&mut self);
+
+    ///
+    /// Count the given number of pruned subtrees.
+    ///
+    fn prune(&mut self, count: usize);
+
+    ///
+    /// Count an element tested against the bounding box.
+    ///
+    fn test(&mut self);
+}
+
+///
+/// A collector ignoring all counters.
+///
+pub struct NoStats;
+
+impl TraversalStats for NoStats {
+    #[inline]
+    fn visit(&mut self) {}
+
+    #[inline]
+    fn prune(&mut self, _count: usize) {}
+
+    #[inline]
+    fn test(&mut self) {}
+}
+
+///
+/// The counters collected during the traversal of the tree by a query.
+///
+#[derive(Default)]
+pub struct Traversal {
+    m_visited: usize,
+    m_pruned: usize,
+    m_tested: usize,
 }
 
-impl Root {
+impl Traversal {
     ///
-    /// Construct a new root from the given label
+    /// Initialize new counters.
     ///
-    /// Note: The function only contains the given label. No subtrees of connenctions to other
-    /// tree nodes are constructed.
+    pub fn new() -> Traversal {
+        Traversal::default()
+    }
+
     ///
-    /// To construct a single tree from a forest of root nodes use the Root::init_pst3d(...)
-    /// function.
+    /// Convert the counters into the statistics of a query returning the given number of labels.
     ///
-    pub fn new(l: Label) -> Root {
-        Root {
-            m_t: l.get_t(),
-            m_data: l,
+    pub fn into_stats(self, returned: usize) -> QueryStats {
+        QueryStats::new(self.m_visited, self.m_pruned, self.m_tested, returned)
+    }
+}
 
-            m_type: SplitDimension::UNDEF,
-            m_split: f64::NAN,
-            m_left_child: None,
-            m_right_child: None,
-        }
+impl TraversalStats for Traversal {
+    fn visit(&mut self) {
+        self.m_visited += 1;
+    }
+
+    fn prune(&mut self, count: usize) {
+        self.m_pruned += count;
+    }
+
+    fn test(&mut self) {
+        self.m_tested += 1;
     }
+}
 
+///
+/// A bounding box stored with f32 precision.
+///
+/// The bounds are rounded outwards, so the box contains the exact box it was created from.
+///
+struct CompactBox {
+    m_min_x: f32,
+    m_min_y: f32,
+    m_max_x: f32,
+    m_max_y: f32,
+}
+
+impl CompactBox {
     ///
-    /// Initialize a single 3D PST out of a forest of root nodes and return the root node index.
+    /// Initialize the smallest f32 box containing the given bounding box.
     ///
-    /// The function will mutate the given root nodes and set the corresponding split type, split
-    /// value and left and right subtree indices.
+    fn new(bbox: &BBox) -> CompactBox {
+        CompactBox {
+            m_min_x: round_down(bbox.get_min_x()),
+            m_min_y: round_down(bbox.get_min_y()),
+            m_max_x: round_up(bbox.get_max_x()),
+            m_max_y: round_up(bbox.get_max_y()),
+        }
+    }
+
     ///
-    /// The function returns the index of the root node in the data array.
+    /// Get the box as bounding box, the conversion is exact.
     ///
-    pub fn init_pst3d(mut data: &mut Vec<Root>) -> Option<usize> {
-        let mut refs: Vec<RootRef> = Vec::with_capacity(data.len());
+    #[inline]
+    fn get_bbox(&self) -> BBox {
+        BBox::new(f64::from(self.m_min_x),
+                  f64::from(self.m_min_y),
+                  f64::from(self.m_max_x),
+                  f64::from(self.m_max_y))
+    }
+}
 
-        data.sort_by(|first, second| if first.m_t < second.m_t {
-                         Ordering::Less
-                     } else if first.m_t > second.m_t {
-            Ordering::Greater
+///
+/// The struct defines a tree node.
+///
+/// The tree nodes members are the elements t value, the element itself, the split type (X, Y or
+/// UNDEF in case the node is a leaf bucket).
+///
+/// A leaf bucket stores the element with the maximum t value itself and its remaining elements in
+/// a separate bucket vector. Instead of children it stores the range of its elements in the
+/// bucket vector, sorted by decreasing t value. Thus a bucket of k elements takes a single node.
+///
+/// The split value indicates the maximum value of the left children in the corresponding
+/// dimension. The split value is guaranteed to be less than or equal to the corresponding
+/// coordinate of the right children. Elements with equal coordinates may end up in both subtrees,
+/// which keeps the tree balanced for duplicate positions.
+///
+/// Left and right child are the indices of the left and right subtree or NO_CHILD, if there is
+/// no such subtree. For a leaf bucket they are the start and the end of its range instead.
+/// Storing u32 indices instead of Option<usize> keeps the nodes small. Thus a tree stores less
+/// than u32::MAX nodes.
+///
+/// Additionally each node stores the maximum t value of its descendants and the bounding box
+/// spanned by all labels in its subtree. Both allow to prune or to report whole subtrees without
+/// testing each node against the query box. They are only used to prune, so they are stored as
+/// f32 rounded outwards: the stored values are conservative bounds of the exact values and a
+/// node without an element takes 48 bytes instead of 72 bytes.
+pub struct Root<T> {
+    m_t: Coord,
+    m_data: T,
+    m_type: SplitDimension,
+    m_split: Coord,
+    m_left_child: u32,
+    m_right_child: u32,
+    m_subtree_max_t: f32,
+    m_subtree_bbox: CompactBox,
+}
+
+impl<T> Root<T> {
+    ///
+    /// Get the index of the left child or None if there is no left subtree.
+    ///
+    fn get_left_child(&self) -> Option<usize> {
+        if self.is_bucket() {
+            None
         } else {
-            Ordering::Equal
-        });
-        data.reverse();
+            from_child(self.m_left_child)
+        }
+    }
 
-        for (idx, d) in data.iter().enumerate() {
-            refs.push(RootRef::new(d, idx));
+    ///
+    /// Get the index of the right child or None if there is no right subtree.
+    ///
+    fn get_right_child(&self) -> Option<usize> {
+        if self.is_bucket() {
+            None
+        } else {
+            from_child(self.m_right_child)
         }
+    }
 
-        let initial_dimension = SplitDimension::X;
-        create_root(refs, &mut data, &initial_dimension)
+    ///
+    /// Get the priority the node is ordered by.
+    ///
+    pub fn get_t(&self) -> f64 {
+        from_coord(self.m_t)
     }
 
     ///
-    /// Get a vector of references to the elements in the 3d PST with t >= min_t and that are
-    /// contained in bbox.
+    /// Get the element stored in the node.
     ///
-    pub fn get<'a>(&'a self, bbox: &BBox, min_t: f64, data: &'a Vec<Root>) -> Vec<&'a Label> {
-        let mut r: Vec<&Label> = Vec::new();
+    pub fn get_label(&self) -> &T {
+        &self.m_data
+    }
 
-        if self.m_t <= min_t {
-            return r;
-        }
+    ///
+    /// Get the bounding box of all elements of the subtree rooted at the node.
+    ///
+    pub fn get_subtree_bbox(&self) -> BBox {
+        self.m_subtree_bbox.get_bbox()
+    }
 
-        if bbox.is_contained(&self.m_data) {
-            r.push(&self.m_data);
-        }
+    ///
+    /// Check if the node is a leaf bucket, i.e. it has no children but a range of bucket elements.
+    ///
+    fn is_bucket(&self) -> bool {
+        self.m_type == SplitDimension::UNDEF && self.m_left_child != NO_CHILD
+    }
 
-        // append the left child if it exists and is cut by the bounding box
-        if let Some(idx) = self.m_left_child {
-            let append = match self.m_type {
-                SplitDimension::X => bbox.get_min_x() <= self.m_split,
-                SplitDimension::Y => bbox.get_min_y() <= self.m_split,
-                SplitDimension::UNDEF => false,
-            };
+    ///
+    /// Iterate over the remaining elements of a leaf bucket by decreasing t value together with
+    /// their element indices, see `Tree::get_element`.
+    ///
+    fn get_bucket<'a>(&self,
+                      data: &'a Tree<T>)
+                      -> impl Iterator<Item = (usize, &'a BucketEntry<T>)> {
+        let range = self.get_bucket_range();
+        let offset = data.len() + range.start;
+        let entries = data.m_bucket.get(range).unwrap_or(&[]);
 
-            if append {
-                assert!(idx < data.len());
-                let mut res = data[idx].get(&bbox, min_t, &data);
-                r.append(&mut res);
-            }
+        entries.iter().enumerate().map(move |(i, e)| (offset + i, e))
+    }
+
+    ///
+    /// Get the range of the bucket vector holding the remaining elements of a leaf bucket. The
+    /// range is empty for all other nodes.
+    ///
+    fn get_bucket_range(&self) -> Range<usize> {
+        if self.is_bucket() {
+            self.m_left_child as usize..self.m_right_child as usize
+        } else {
+            0..0
         }
-        // append the right child if it exists and is cut by the bounding box
-        if let Some(idx) = self.m_right_child {
-            let append = match self.m_type {
-                SplitDimension::X => bbox.get_max_x() > self.m_split,
-                SplitDimension::Y => bbox.get_max_y() > self.m_split,
-                SplitDimension::UNDEF => false,
-            };
+    }
 
-            if append {
-                assert!(idx < data.len());
-                let mut res = data[idx].get(&bbox, min_t, &data);
-                r.append(&mut res);
-            }
+    ///
+    /// Write the nodes of a 3D PST without their elements. The elements are stored separately
+    /// in the order of their indices, see `Tree::get_element`.
+    ///
+    /// Each node is written as its t value, split dimension, split value, child indices, subtree
+    /// max t and subtree bounding box. The nodes are followed by the t values of the bucket
+    /// elements.
+    ///
+    pub fn write_nodes<W: Write>(data: &Tree<T>, w: &mut W) -> Result<(), Error> {
+        codec::write_varint(w, data.len() as u64)?;
+        for r in data.iter() {
+            codec::write_f64(w, from_coord(r.m_t))?;
+            codec::write_u8(w,
+                            match r.m_type {
+                                SplitDimension::X => 0,
+                                SplitDimension::Y => 1,
+                                SplitDimension::UNDEF => 2,
+                            })?;
+            codec::write_f64(w, from_coord(r.m_split))?;
+            codec::write_u32(w, r.m_left_child)?;
+            codec::write_u32(w, r.m_right_child)?;
+            codec::write_f64(w, f64::from(r.m_subtree_max_t))?;
+            codec::write_f64(w, r.m_subtree_bbox.get_bbox().get_min_x())?;
+            codec::write_f64(w, r.m_subtree_bbox.get_bbox().get_min_y())?;
+            codec::write_f64(w, r.m_subtree_bbox.get_bbox().get_max_x())?;
+            codec::write_f64(w, r.m_subtree_bbox.get_bbox().get_max_y())?;
+        }
+        for e in &data.m_bucket {
+            codec::write_f64(w, from_coord(e.m_t))?;
         }
 
-        r
+        Ok(())
     }
 
     ///
-    /// Get a human readable string representation of the tree rooted at self.
-    ///
-    /// A such string will look like:
+    /// Read the nodes written by `write_nodes` and attach the given elements in the order of
+    /// their indices. The elements not stored in a node form the bucket vector.
     ///
-    /// ```text
-    ///   x-node (split: 4.5): Label [#1]: 'T1' at (1, 2) with prio 1,elim-t: 9 and label factor: \
-    ///                                                                                         1.5
-    ///   l    y-node (split: 4.5): Label [#2]: 'T2' at (2, 3) with prio 1, elim-t: 8 and label \
-    ///                                                                                 factor: 1.5
-    ///   l        x-node (split: NaN): Label [#3]: 'T3' at (3, 4) with prio 1, elim-t: 7 and \
-    ///                                                                           label factor: 1.5
-    /// ```
+    /// # Errors
+    ///   * Serialization: if there are more nodes than elements or the nodes do not form a valid
+    ///     tree. The children of a node must be stored after the node and the ranges of the leaf
+    ///     buckets must lie within the bucket vector.
     ///
-    pub fn to_string(&self, level: i32, data: &Vec<Root>) -> String {
-        // prefix is level x p
-        let p = "    ";
-        let mut prefix = String::new();
-        for _ in 0..level {
-            prefix = format!("{}{}", prefix, p);
+    pub fn read_nodes<R: Read>(r: &mut R, mut elements: Vec<T>) -> Result<Tree<T>, Error> {
+        let count = codec::read_count(r, elements.len() as u64)?;
+        if count > elements.len() {
+            return Err(Error::Serialization(format!("Found {} nodes for {} elements",
+                                                    count,
+                                                    elements.len())));
         }
+        let bucket_elements = elements.split_off(count);
 
-        let mut result = match self.m_type {
-            SplitDimension::X => {
-                format!("{}x-node (split: {}): {}",
-                        prefix,
-                        self.m_split,
-                        self.m_data.to_string())
-            }
-            SplitDimension::Y => {
-                format!("{}y-node (split: {}): {}",
-                        prefix,
-                        self.m_split,
-                        self.m_data.to_string())
-            }
-            SplitDimension::UNDEF => {
-                format!("{}leaf-node (split: {}): {}",
-                        prefix,
-                        self.m_split,
-                        self.m_data.to_string())
+        let mut data: Vec<Root<T>> = Vec::with_capacity(count);
+        for (idx, element) in elements.into_iter().enumerate() {
+            let t = codec::read_f64(r)?;
+            let split_type = match codec::read_u8(r)? {
+                0 => SplitDimension::X,
+                1 => SplitDimension::Y,
+                2 => SplitDimension::UNDEF,
+                v => return Err(Error::Serialization(format!("Invalid split dimension {}", v))),
+            };
+            let split = codec::read_f64(r)?;
+            let left = codec::read_u32(r)?;
+            let right = codec::read_u32(r)?;
+
+            // children stored after their parents guarantee a cycle free tree
+            if split_type == SplitDimension::UNDEF && left != NO_CHILD {
+                if left > right || right as usize > bucket_elements.len() {
+                    return Err(Error::Serialization(format!("Invalid bucket {}..{} of node {}",
+                                                            left,
+                                                            right,
+                                                            idx)));
+                }
+            } else {
+                for &child in &[left, right] {
+                    if child != NO_CHILD && (child as usize <= idx || child as usize >= count) {
+                        return Err(Error::Serialization(format!("Invalid child {} of node {}",
+                                                                child,
+                                                                idx)));
+                    }
+                }
             }
-        };
 
-        // append the left subtree
-        if let Some(idx) = self.m_left_child {
-            assert!(idx < data.len());
-            result = format!("{}\nl{}", result, data[idx].to_string(level + 1, &data));
+            let subtree_max_t = codec::read_f64(r)?;
+            let min_x = codec::read_f64(r)?;
+            let min_y = codec::read_f64(r)?;
+            let max_x = codec::read_f64(r)?;
+            let max_y = codec::read_f64(r)?;
+
+            data.push(Root {
+                          m_t: to_coord(t),
+                          m_data: element,
+                          m_type: split_type,
+                          m_split: to_coord(split),
+                          m_left_child: left,
+                          m_right_child: right,
+                          m_subtree_max_t: round_up(subtree_max_t),
+                          m_subtree_bbox: CompactBox::new(&BBox::new(min_x, min_y, max_x, max_y)),
+                      });
         }
-        // append the right subtree
-        if let Some(idx) = self.m_right_child {
-            assert!(idx < data.len());
-            result = format!("{}\nr{}", result, data[idx].to_string(level + 1, &data));
+
+        let mut bucket: Vec<BucketEntry<T>> = Vec::with_capacity(bucket_elements.len());
+        for element in bucket_elements {
+            bucket.push(BucketEntry {
+                            m_t: to_coord(codec::read_f64(r)?),
+                            m_data: element,
+                        });
         }
 
-        result
+        Ok(Tree {
+               m_nodes: data,
+               m_bucket: bucket,
+           })
     }
 }
 
 ///
-/// The struct represents a reference to a root node and contains all the information required to
-/// construct the 3D PST.
+/// The nodes of a 3D PST and the remaining elements of its leaf buckets.
 ///
-#[derive(Debug)]
-struct RootRef {
-    m_x: f64,
-    m_y: f64,
-    m_t: f64,
-
-    m_idx: usize,
+/// The tree dereferences to its nodes. Each element has an index: the first indices refer to the
+/// elements of the nodes, the following ones to the bucket elements.
+///
+pub struct Tree<T> {
+    m_nodes: Vec<Root<T>>,
+    m_bucket: Vec<BucketEntry<T>>,
 }
 
-impl RootRef {
+impl<T> Tree<T> {
     ///
-    /// Initialize a new root ref
+    /// Initialize a tree of the given unconnected nodes without bucket elements, see
+    /// `Root::init_pst3d`.
     ///
-    fn new(r: &Root, idx: usize) -> RootRef {
-        RootRef {
-            m_t: r.m_data.get_t(),
-            m_x: r.m_data.get_x(),
-            m_y: r.m_data.get_y(),
-
-            m_idx: idx,
+    pub fn new(nodes: Vec<Root<T>>) -> Tree<T> {
+        Tree {
+            m_nodes: nodes,
+            m_bucket: Vec::new(),
         }
     }
 
     ///
-    /// Compare two Root refs with respect to the t value.
+    /// Get the number of elements, i.e. the number of nodes and bucket elements.
     ///
-    fn order_by_t(first: &Self, second: &Self) -> Ordering {
-        if first.m_t < second.m_t {
-            Ordering::Less
-        } else if first.m_t > second.m_t {
-            Ordering::Greater
-        } else {
-            Ordering::Equal
-        }
+    pub fn get_element_count(&self) -> usize {
+        self.m_nodes.len() + self.m_bucket.len()
     }
 
     ///
-    /// Compare two Root refs with respect to the x value.
+    /// Get the size in bytes of the nodes and the bucket elements, excluding heap memory owned by
+    /// the elements.
     ///
-    fn order_by_x(first: &Self, second: &Self) -> Ordering {
-        if first.m_x < second.m_x {
-            Ordering::Less
-        } else if first.m_x > second.m_x {
-            Ordering::Greater
-        } else {
-            Ordering::Equal
-        }
+    pub fn get_memory_size(&self) -> usize {
+        self.m_nodes.len() * mem::size_of::<Root<T>>() +
+        self.m_bucket.len() * mem::size_of::<BucketEntry<T>>()
     }
 
     ///
-    /// Compare two Root refs with respect to the y value.
+    /// Get the element with the given index and its t value or None if the index is out of range.
     ///
-    fn order_by_y(first: &Self, second: &Self) -> Ordering {
-        if first.m_y < second.m_y {
-            Ordering::Less
-        } else if first.m_y > second.m_y {
-            Ordering::Greater
-        } else {
-            Ordering::Equal
+    pub fn get_element(&self, idx: usize) -> Option<(&T, f64)> {
+        match self.m_nodes.get(idx) {
+            Some(r) => Some((r.get_label(), r.get_t())),
+            None => {
+                idx.checked_sub(self.m_nodes.len())
+                    .and_then(|idx| self.m_bucket.get(idx))
+                    .map(|e| (&e.m_data, from_coord(e.m_t)))
+            }
         }
     }
+
+    ///
+    /// Consume the tree and return its elements with their t values in the order of their
+    /// indices.
+    ///
+    pub fn into_elements(self) -> impl Iterator<Item = (f64, T)> {
+        self.m_nodes
+            .into_iter()
+            .map(|r| (from_coord(r.m_t), r.m_data))
+            .chain(self.m_bucket.into_iter().map(|e| (from_coord(e.m_t), e.m_data)))
+    }
 }
 
-///
-/// In the RootRef vector find the index of the root with the maximum t value.
-///
-fn find_root_idx(refs: &mut Vec<RootRef>) -> usize {
-    let mut max_t = 0.;
-    let mut max_idx = 0;
-    for (idx, e) in refs.iter().enumerate() {
-        if e.m_t > max_t {
-            max_t = e.m_t;
-            max_idx = idx;
-        }
+impl<T> Default for Tree<T> {
+    fn default() -> Tree<T> {
+        Tree::new(Vec::new())
     }
+}
+
+impl<T> Deref for Tree<T> {
+    type Target = [Root<T>];
 
-    let r = refs.swap_remove(max_idx);
-    assert!(r.m_t == max_t);
+    fn deref(&self) -> &[Root<T>] {
+        &self.m_nodes
+    }
+}
 
-    r.m_idx
+impl<T> DerefMut for Tree<T> {
+    fn deref_mut(&mut self) -> &mut [Root<T>] {
+        &mut self.m_nodes
+    }
 }
 
 ///
-/// From the given RootRef vector construct the subtree and update the corresponding root nodes in
-/// the data vector.
-///
-/// The element with the maximum t value will be set as root with the corresponding split
-/// dimension. The remaining elements will sorted by the split dimension. The split value is the
-/// corresponding coordinate of item floor(|root_refs| / 2) and the elements are splitted into <=
-/// and >.
-///
-/// From the <= elements the left subtree is constructed recursively with swapped split dimension.
-/// Same for the > elements as the right subtree.
-///
-/// For the nodes in data that are referenced by RootRefs in root_refs the  corresponding Roots are
-/// updated accordingly.
+/// A remaining element of a leaf bucket, i.e. any element of the bucket except for the one with
+/// the maximum t value, which is stored in the leaf node itself.
 ///
+pub struct BucketEntry<T> {
+    m_t: Coord,
+    m_data: T,
+}
 
-fn create_root(mut root_refs: Vec<RootRef>,
-               mut data: &mut Vec<Root>,
-               dim: &SplitDimension)
-               -> Option<usize> {
-    if root_refs.is_empty() {
-        return None;
+impl<T> BucketEntry<T> {
+    ///
+    /// Get the priority the element is ordered by.
+    ///
+    pub fn get_t(&self) -> f64 {
+        from_coord(self.m_t)
     }
+}
 
+impl<T: HasPosition + HasT> Root<T> {
+    ///
+    /// Construct a new root from the given element with the priority t.
+    ///
+    /// Note: The function only contains the given element. No subtrees of connenctions to other
+    /// tree nodes are constructed.
+    ///
+    /// To construct a single tree from a forest of root nodes use the Root::init_pst3d(...)
+    /// function.
+    ///
+    pub fn new(l: T, t: f64) -> Root<T> {
+        Root {
+            m_t: to_coord(t),
+            m_data: l,
 
-    let size1 = root_refs.len();
-
-    assert!(*dim != SplitDimension::UNDEF);
-    let is_x = *dim == SplitDimension::X;
+            m_type: SplitDimension::UNDEF,
+            m_split: Coord::NAN,
+            m_left_child: NO_CHILD,
+            m_right_child: NO_CHILD,
+            m_subtree_max_t: f32::NEG_INFINITY,
+            m_subtree_bbox: CompactBox::new(&BBox::new_empty()),
+        }
+    }
 
-    // find the element with the maximum t value, remove the corresonding RootRef
-    let root_idx = find_root_idx(&mut root_refs);
+    ///
+    /// Initialize a single 3D PST out of a forest of root nodes and return the root node index.
+    ///
+    /// The function will mutate the given root nodes and set the corresponding split type, split
+    /// value and left and right subtree indices.
+    ///
+    /// The function returns the index of the root node in the data array.
+    ///
+    /// Finally the nodes are stored in breadth first order, i.e. the root node is the first
+    /// element and the children of a node are adjacent. Queries visit the nodes of the upper
+    /// levels first, which are thus close in memory.
+    ///
+    /// The settings of the builder are applied, e.g. the elements are sorted by x and by y in
+    /// parallel if the construction is parallel. If the builder allows leaf buckets of more than
+    /// one element, the remaining elements of the buckets are moved from the nodes to the bucket
+    /// elements of the tree.
+    ///
+    pub fn init_pst3d(tree: &mut Tree<T>, builder: &Pst3dBuilder) -> Option<usize> {
+        let (mut data, bucket) = (&mut tree.m_nodes, &mut tree.m_bucket);
+        if data.len() > MAX_ELEMENTS {
+            validate!(false, "Too many elements for a single tree");
+            return None;
+        }
 
-    // the sub dimension flips from X to Y or from Y to X
-    let sub_dim = if is_x {
-        SplitDimension::Y
+        data.sort_by(|first, second| if first.m_t < second.m_t {
+                         Ordering::Less
+                     } else if first.m_t > second.m_t {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        });
+        data.reverse();
+
+        // the elements are sorted by x and y once, the subtrees keep the order
+        let mut by_x: Vec<RootRef> = data.iter()
+            .enumerate()
+            .map(|(idx, d)| RootRef::new(d, idx))
+            .collect();
+        let mut by_y = by_x.clone();
+        if builder.get_parallel() {
+            thread::scope(|s| {
+                              s.spawn(|| by_x.sort_by(RootRef::order_by_x));
+                              by_y.sort_by(RootRef::order_by_y);
+                          });
+        } else {
+            by_x.sort_by(RootRef::order_by_x);
+            by_y.sort_by(RootRef::order_by_y);
+        }
+
+        let mut is_left = vec![false; data.len()];
+
+        // the cell of the root spans all elements
+        let cell = match (by_x.first(), by_x.last(), by_y.first(), by_y.last()) {
+            (Some(min_x), Some(max_x), Some(min_y), Some(max_y)) => {
+                BBox::new(min_x.m_x, min_y.m_y, max_x.m_x, max_y.m_y)
+            }
+            _ => BBox::new_empty(),
+        };
+
+        let initial_dimension = SplitDimension::X;
+        let root_idx = create_root(by_x,
+                                   by_y,
+                                   &mut data,
+                                   &initial_dimension,
+                                   &mut is_left,
+                                   builder,
+                                   &cell);
+
+        root_idx.map(|idx| reorder_breadth_first(data, bucket, idx))
+    }
+
+    ///
+    /// Collapse each subtree rooted at one of the given node indices into a single leaf bucket
+    /// and return the flattened tree and the index of its root node.
+    ///
+    /// The root node of a subtree keeps its element, i.e. the one with the maximum t value, and
+    /// its subtree information. All remaining elements of the subtree become its bucket elements,
+    /// the split values, child indices and subtree information of the inner nodes are dropped.
+    /// The subtrees must not be nested.
+    ///
+    /// Finally the nodes are stored in breadth first order again, so the element indices change.
+    ///
+    pub fn flatten_subtrees(tree: Tree<T>,
+                            root_idx: usize,
+                            subtrees: &[usize])
+                            -> (Tree<T>, usize) {
+        let Tree { m_nodes: mut data, m_bucket: bucket } = tree;
+        let node_count = data.len();
+
+        // chain the existing bucket elements behind their leaf nodes, compare create_bucket
+        let ranges: Vec<(usize, Range<usize>)> = data.iter()
+            .enumerate()
+            .filter(|&(_, r)| r.is_bucket())
+            .map(|(idx, r)| (idx, r.get_bucket_range()))
+            .collect();
+        data.extend(bucket.into_iter().map(|e| Root::new(e.m_data, from_coord(e.m_t))));
+        for (idx, range) in ranges {
+            if node_count + range.end > data.len() {
+                validate!(false, "Trying to access element at not existing vector position");
+                continue;
+            }
+            let mut next = NO_CHILD;
+            for bucket_idx in range.rev() {
+                data[node_count + bucket_idx].m_left_child = next;
+                next = to_child(Some(node_count + bucket_idx));
+            }
+            data[idx].m_left_child = next;
+            data[idx].m_right_child = NO_CHILD;
+        }
+
+        for subtree in subtrees {
+            if *subtree >= data.len() {
+                validate!(false, "Trying to access element at not existing vector position");
+                continue;
+            }
+
+            // inner nodes link their children, leaf buckets chain their elements
+            let mut elements = Vec::new();
+            let mut stack = vec![*subtree];
+            while let Some(idx) = stack.pop() {
+                let r = match data.get(idx) {
+                    Some(r) => r,
+                    None => {
+                        validate!(false,
+                                  "Trying to access element at not existing vector position");
+                        continue;
+                    }
+                };
+                stack.extend(from_child(r.m_left_child).iter().chain(from_child(r.m_right_child)
+                    .iter()));
+                if idx != *subtree {
+                    elements.push(idx);
+                }
+            }
+            elements.sort_by(|first, second| {
+                                 data[*second]
+                                     .m_t
+                                     .partial_cmp(&data[*first].m_t)
+                                     .unwrap_or(Ordering::Equal)
+                                     .then(first.cmp(second))
+                             });
+
+            let mut next = NO_CHILD;
+            for idx in elements.iter().rev() {
+                let r = &mut data[*idx];
+                r.m_type = SplitDimension::UNDEF;
+                r.m_left_child = next;
+                r.m_right_child = NO_CHILD;
+                next = to_child(Some(*idx));
+            }
+            let r = &mut data[*subtree];
+            r.m_type = SplitDimension::UNDEF;
+            r.m_split = to_coord(f64::NAN);
+            r.m_left_child = next;
+            r.m_right_child = NO_CHILD;
+        }
+
+        let mut bucket = Vec::new();
+        let root_idx = reorder_breadth_first(&mut data, &mut bucket, root_idx);
+
+        (Tree {
+             m_nodes: data,
+             m_bucket: bucket,
+         },
+         root_idx)
+    }
+
+
+    ///
+    /// Append the indices of the elements in the 3d PST with t >= min_t and that are contained in
+    /// bbox to the result.
+    ///
+    /// The given own_idx must be the index of self in the data vector. The visited nodes, the
+    /// pruned subtrees and the elements tested against the bounding box are counted in stats.
+    ///
+    pub fn get_indices<S: TraversalStats>(&self,
+                                          own_idx: usize,
+                                          bbox: &BBox,
+                                          min_t: f64,
+                                          data: &Tree<T>,
+                                          r: &mut Vec<u32>,
+                                          stats: &mut S) {
+        if self.get_t() <= min_t {
+            stats.visit();
+            stats.prune(1);
+            return;
+        }
+
+        // the whole subtree lies within the bounding box, no further bbox tests are required
+        if box_contains_box(bbox, &self.m_subtree_bbox.get_bbox()) {
+            self.report_subtree(own_idx, min_t, data, r, stats);
+            return;
+        }
+
+        stats.visit();
+        stats.test();
+        if point_in_box(bbox, &self.m_data) {
+            r.push(own_idx as u32);
+        }
+
+        // none of the descendants has a sufficiently large t value
+        if f64::from(self.m_subtree_max_t) <= min_t {
+            stats.prune(self.get_left_child().iter().count() +
+                        self.get_right_child().iter().count());
+            return;
+        }
+
+        // scan the elements of a leaf bucket until the first one with a too small t value
+        for (idx, e) in self.get_bucket(data) {
+            if e.get_t() <= min_t {
+                break;
+            }
+            stats.test();
+            if point_in_box(bbox, &e.m_data) {
+                r.push(idx as u32);
+            }
+        }
+
+        // the split value is compared in storage precision, rounding preserves the order
+        // append the left child if it exists and is cut by the bounding box
+        if let Some(idx) = self.get_left_child() {
+            let append = match self.m_type {
+                SplitDimension::X => to_coord(bbox.get_min_x()) <= self.m_split,
+                SplitDimension::Y => to_coord(bbox.get_min_y()) <= self.m_split,
+                // a leaf bucket has no children
+                SplitDimension::UNDEF => false,
+            };
+
+            if append {
+                if let Some(child) = get_node(data, idx) {
+                    child.get_indices(idx, bbox, min_t, data, r, stats);
+                }
+            } else {
+                stats.prune(1);
+            }
+        }
+        // append the right child if it exists and is cut by the bounding box
+        if let Some(idx) = self.get_right_child() {
+            let append = match self.m_type {
+                SplitDimension::X => to_coord(bbox.get_max_x()) >= self.m_split,
+                SplitDimension::Y => to_coord(bbox.get_max_y()) >= self.m_split,
+                SplitDimension::UNDEF => false,
+            };
+
+            if append {
+                if let Some(child) = get_node(data, idx) {
+                    child.get_indices(idx, bbox, min_t, data, r, stats);
+                }
+            } else {
+                stats.prune(1);
+            }
+        }
+    }
+
+    ///
+    /// Append the indices of the elements in the 3d PST that match each of the queries to the
+    /// corresponding result. A query consists of a bounding box and a min_t value, an element
+    /// matches it if it is contained in the box and has a t > min_t.
+    ///
+    /// active contains the indices of the queries that may match elements in the subtree rooted
+    /// at self. The queries share the traversal: each node is visited once for all active queries
+    /// and a subtree is only entered if at least one query may match elements in it.
+    ///
+    pub fn get_many_indices(&self,
+                            own_idx: usize,
+                            queries: &[(BBox, f64)],
+                            active: &[usize],
+                            data: &Tree<T>,
+                            r: &mut Vec<Vec<u32>>) {
+        let mut left: Vec<usize> = Vec::new();
+        let mut right: Vec<usize> = Vec::new();
+
+        for &q in active {
+            let (ref bbox, min_t) = queries[q];
+            if self.get_t() <= min_t {
+                continue;
+            }
+
+            // the whole subtree lies within the bounding box, no further bbox tests are required
+            if box_contains_box(bbox, &self.m_subtree_bbox.get_bbox()) {
+                self.report_subtree(own_idx, min_t, data, &mut r[q], &mut NoStats);
+                continue;
+            }
+
+            if point_in_box(bbox, &self.m_data) {
+                r[q].push(own_idx as u32);
+            }
+
+            if f64::from(self.m_subtree_max_t) <= min_t {
+                continue;
+            }
+
+            for (idx, e) in self.get_bucket(data) {
+                if e.get_t() <= min_t {
+                    break;
+                }
+                if point_in_box(bbox, &e.m_data) {
+                    r[q].push(idx as u32);
+                }
+            }
+
+            let (to_left, to_right) = match self.m_type {
+                SplitDimension::X => {
+                    (to_coord(bbox.get_min_x()) <= self.m_split,
+                     to_coord(bbox.get_max_x()) >= self.m_split)
+                }
+                SplitDimension::Y => {
+                    (to_coord(bbox.get_min_y()) <= self.m_split,
+                     to_coord(bbox.get_max_y()) >= self.m_split)
+                }
+                SplitDimension::UNDEF => (false, false),
+            };
+            if to_left {
+                left.push(q);
+            }
+            if to_right {
+                right.push(q);
+            }
+        }
+
+        if let Some(idx) = self.get_left_child() {
+            if !left.is_empty() {
+                if let Some(child) = get_node(data, idx) {
+                    child.get_many_indices(idx, queries, &left, data, r);
+                }
+            }
+        }
+        if let Some(idx) = self.get_right_child() {
+            if !right.is_empty() {
+                if let Some(child) = get_node(data, idx) {
+                    child.get_many_indices(idx, queries, &right, data, r);
+                }
+            }
+        }
+    }
+
+    ///
+    /// Get the indices of at most limit elements in the 3d PST rooted at root_idx with t > min_t
+    /// and that are contained in bbox.
+    ///
+    /// The nodes are visited by decreasing t value, so the result contains the elements with the
+    /// largest t values ordered by decreasing t. The traversal stops as soon as limit elements
+    /// are found.
+    ///
+    pub fn get_top_indices(root_idx: usize,
+                           bbox: &BBox,
+                           min_t: f64,
+                           limit: usize,
+                           data: &Tree<T>)
+                           -> Vec<u32> {
+        Self::visit_top(root_idx, bbox, min_t, limit, &QueryBudget::new(), data).0
+    }
+
+    ///
+    /// Get the indices of the elements in the 3d PST rooted at root_idx with t > min_t and that
+    /// are contained in bbox, visiting the nodes until the budget is exhausted.
+    ///
+    /// The nodes are visited by decreasing t value, so a partial result contains the elements
+    /// with the largest t values. The returned flag is false if the traversal was stopped by the
+    /// budget.
+    ///
+    pub fn get_budgeted_indices(root_idx: usize,
+                                bbox: &BBox,
+                                min_t: f64,
+                                budget: &QueryBudget,
+                                data: &Tree<T>)
+                                -> (Vec<u32>, bool) {
+        Self::visit_top(root_idx, bbox, min_t, usize::MAX, budget, data)
+    }
+
+    ///
+    /// Visit the nodes by decreasing t value until limit elements are found, all elements are
+    /// found or the budget is exhausted. Returns the found indices and whether the traversal ran
+    /// to completion.
+    ///
+    /// The contained elements of a visited leaf bucket are queued by their t value as well, the
+    /// queue distinguishes them from the nodes by their index, see `Tree::get_element`.
+    ///
+    fn visit_top(root_idx: usize,
+                 bbox: &BBox,
+                 min_t: f64,
+                 limit: usize,
+                 budget: &QueryBudget,
+                 data: &Tree<T>)
+                 -> (Vec<u32>, bool) {
+        let start = Instant::now();
+        let mut visits: usize = 0;
+
+        let mut r: Vec<u32> = Vec::new();
+        let mut heap = BinaryHeap::new();
+        heap.push(HeapEntry::new(data[root_idx].get_t(), root_idx));
+
+        while let Some(e) = heap.pop() {
+            if r.len() >= limit || e.m_key <= min_t {
+                break;
+            }
+
+            // reading the clock is expensive compared to visiting a node, so check it rarely
+            let out_of_time = visits.is_multiple_of(TIME_CHECK_INTERVAL) &&
+                              budget.get_max_duration().is_some_and(|d| start.elapsed() >= d);
+            if out_of_time || budget.get_max_visits().is_some_and(|m| visits >= m) {
+                return (r, false);
+            }
+            visits += 1;
+
+            // a bucket element queued as contained element
+            let node = match data.get(e.m_idx) {
+                Some(node) => node,
+                None => {
+                    r.push(e.m_idx as u32);
+                    continue;
+                }
+            };
+            if point_in_box(bbox, &node.m_data) {
+                r.push(e.m_idx as u32);
+            }
+
+            for (idx, b) in node.get_bucket(data) {
+                if b.get_t() <= min_t {
+                    break;
+                }
+                if point_in_box(bbox, &b.m_data) {
+                    heap.push(HeapEntry::new(b.get_t(), idx));
+                }
+            }
+
+            for child in node.get_left_child().iter().chain(node.get_right_child().iter()) {
+                if let Some(c) = get_node(data, *child) {
+                    if box_intersects_box(bbox, &c.m_subtree_bbox.get_bbox()) {
+                        heap.push(HeapEntry::new(c.get_t(), *child));
+                    }
+                }
+            }
+        }
+
+        (r, true)
+    }
+
+    ///
+    /// Append the indices of all elements of the subtree rooted at self with t > min_t to the
+    /// result.
+    ///
+    /// No bounding box tests are performed. Only subtrees with a sufficiently large t value are
+    /// visited, so the cost is linear in the number of reported elements.
+    ///
+    fn report_subtree<S: TraversalStats>(&self,
+                                         own_idx: usize,
+                                         min_t: f64,
+                                         data: &Tree<T>,
+                                         r: &mut Vec<u32>,
+                                         stats: &mut S) {
+        stats.visit();
+        if self.get_t() <= min_t {
+            stats.prune(1);
+            return;
+        }
+
+        r.push(own_idx as u32);
+
+        if f64::from(self.m_subtree_max_t) <= min_t {
+            stats.prune(self.get_left_child().iter().count() +
+                        self.get_right_child().iter().count());
+            return;
+        }
+
+        r.extend(self.get_bucket(data)
+                     .take_while(|&(_, e)| e.get_t() > min_t)
+                     .map(|(idx, _)| idx as u32));
+
+        if let Some(idx) = self.get_left_child() {
+            if let Some(child) = get_node(data, idx) {
+                child.report_subtree(idx, min_t, data, r, stats);
+            }
+        }
+        if let Some(idx) = self.get_right_child() {
+            if let Some(child) = get_node(data, idx) {
+                child.report_subtree(idx, min_t, data, r, stats);
+            }
+        }
+    }
+
+    ///
+    /// Get the indices of the k elements in the 3d PST rooted at root_idx with t > min_t that are
+    /// closest to the focus, ordered by increasing distance.
+    ///
+    /// In contrast to the fixed left-then-right order of `get_indices` the children are visited
+    /// by increasing distance of their subtree bounding box to the focus. Subtrees that cannot
+    /// contain an element closer than the current k-th best distance are pruned.
+    ///
+    pub fn get_nearest_indices(root_idx: usize,
+                               focus: &Point,
+                               min_t: f64,
+                               k: usize,
+                               data: &Tree<T>)
+                               -> Vec<u32> {
+        let mut best = BinaryHeap::new();
+        if k > 0 {
+            data[root_idx].collect_nearest(root_idx, focus, min_t, k, data, &mut best);
+        }
+
+        best.into_sorted_vec().into_iter().map(|e| e.m_idx as u32).collect()
+    }
+
+    ///
+    /// Add the elements of the subtree rooted at self that are closer to the focus than the current
+    /// k-th best element to best. best is a max heap of squared distances of at most k entries.
+    ///
+    fn collect_nearest(&self,
+                       own_idx: usize,
+                       focus: &Point,
+                       min_t: f64,
+                       k: usize,
+                       data: &Tree<T>,
+                       best: &mut BinaryHeap<HeapEntry>) {
+        // due to the heap property no descendant has a sufficiently large t value
+        if self.get_t() <= min_t {
+            return;
+        }
+
+        let worst = |best: &BinaryHeap<HeapEntry>| match best.peek() {
+            Some(e) if best.len() >= k => e.m_key,
+            _ => f64::INFINITY,
+        };
+
+        if squared_box_distance(&self.m_subtree_bbox.get_bbox(), focus) > worst(best) {
+            return;
+        }
+
+        let offer = |d: f64, idx: usize, best: &mut BinaryHeap<HeapEntry>| {
+            if d < worst(best) {
+                if best.len() >= k {
+                    best.pop();
+                }
+                best.push(HeapEntry::new(d, idx));
+            }
+        };
+
+        offer(focus.squared_distance(&self.m_data), own_idx, best);
+
+        if f64::from(self.m_subtree_max_t) <= min_t {
+            return;
+        }
+
+        for (idx, e) in self.get_bucket(data).take_while(|&(_, e)| e.get_t() > min_t) {
+            offer(focus.squared_distance(&e.m_data), idx, best);
+        }
+
+        // visit the child closer to the query focus first to tighten the bound early
+        let mut children: Vec<(f64, usize, &Root<T>)> = self.get_left_child()
+            .iter()
+            .chain(self.get_right_child().iter())
+            .filter_map(|&idx| get_node(data, idx).map(|c| (c, idx)))
+            .map(|(c, idx)| (squared_box_distance(&c.m_subtree_bbox.get_bbox(), focus), idx, c))
+            .collect();
+        if children.len() == 2 && children[1].0 < children[0].0 {
+            children.swap(0, 1);
+        }
+
+        for (_, idx, child) in children {
+            child.collect_nearest(idx, focus, min_t, k, data, best);
+        }
+    }
+
+    ///
+    /// Update best with the maximum t value of the elements in the subtree rooted at self that
+    /// are contained in bbox.
+    ///
+    /// Subtrees are skipped if their root t value is not larger than the best t value found so
+    /// far. Due to the heap property the search stops at the first contained element of each
+    /// path.
+    ///
+    pub fn max_t_in(&self,
+                    bbox: &BBox,
+                    data: &Tree<T>,
+                    best: &mut Option<f64>) {
+        if let Some(t) = *best {
+            if self.get_t() <= t {
+                return;
+            }
+        }
+
+        if !box_intersects_box(bbox, &self.m_subtree_bbox.get_bbox()) {
+            return;
+        }
+
+        // self has the maximum t of the subtree
+        if point_in_box(bbox, &self.m_data) {
+            *best = Some(self.get_t());
+            return;
+        }
+
+        // the bucket is sorted by decreasing t, the first contained element has the maximum t
+        let contained = self.get_bucket(data)
+            .take_while(|&(_, e)| best.is_none_or(|t| e.get_t() > t))
+            .find(|&(_, e)| point_in_box(bbox, &e.m_data));
+        if let Some((_, e)) = contained {
+            *best = Some(e.get_t());
+        }
+
+        for child in self.get_left_child().iter().chain(self.get_right_child().iter()) {
+            if let Some(node) = get_node(data, *child) {
+                node.max_t_in(bbox, data, best);
+            }
+        }
+    }
+
+    ///
+    /// Append the indices of the elements in the 3d PST rooted at self with t > min_t that are
+    /// contained in bbox, but descend at most max_depth levels below self.
+    ///
+    /// Due to the heap property the reported elements are the ones with the highest t values of
+    /// the visited subtrees.
+    ///
+    pub fn get_depth_limited_indices(&self,
+                                     own_idx: usize,
+                                     bbox: &BBox,
+                                     min_t: f64,
+                                     max_depth: usize,
+                                     data: &Tree<T>,
+                                     r: &mut Vec<u32>) {
+        if self.get_t() <= min_t || !box_intersects_box(bbox, &self.m_subtree_bbox.get_bbox()) {
+            return;
+        }
+
+        if point_in_box(bbox, &self.m_data) {
+            r.push(own_idx as u32);
+        }
+
+        if f64::from(self.m_subtree_max_t) <= min_t {
+            return;
+        }
+
+        // the elements of a leaf bucket belong to the level of the leaf
+        for (idx, e) in self.get_bucket(data).take_while(|&(_, e)| e.get_t() > min_t) {
+            if point_in_box(bbox, &e.m_data) {
+                r.push(idx as u32);
+            }
+        }
+
+        if max_depth == 0 {
+            return;
+        }
+
+        for child in self.get_left_child().iter().chain(self.get_right_child().iter()) {
+            if let Some(node) = get_node(data, *child) {
+                node.get_depth_limited_indices(*child, bbox, min_t, max_depth - 1, data, r);
+            }
+        }
+    }
+
+    ///
+    /// Append the indices of elements in the 3d PST with t > min_t and that are contained in bbox
+    /// to the result, but only descend into children that are cut by prune_bbox.
+    ///
+    /// prune_bbox must be contained in bbox. All elements of prune_bbox are reported, elements of
+    /// bbox outside of prune_bbox are only reported if they are found on the way. The given own_idx
+    /// must be the index of self in the data vector.
+    ///
+    pub fn get_approximate_indices(&self,
+                                   own_idx: usize,
+                                   bbox: &BBox,
+                                   prune_bbox: &BBox,
+                                   min_t: f64,
+                                   data: &Tree<T>,
+                                   r: &mut Vec<u32>) {
+        if self.get_t() <= min_t {
+            return;
+        }
+
+        if box_contains_box(bbox, &self.m_subtree_bbox.get_bbox()) {
+            self.report_subtree(own_idx, min_t, data, r, &mut NoStats);
+            return;
+        }
+
+        if point_in_box(bbox, &self.m_data) {
+            r.push(own_idx as u32);
+        }
+
+        if f64::from(self.m_subtree_max_t) <= min_t {
+            return;
+        }
+
+        for (idx, e) in self.get_bucket(data).take_while(|&(_, e)| e.get_t() > min_t) {
+            if point_in_box(bbox, &e.m_data) {
+                r.push(idx as u32);
+            }
+        }
+
+        let (to_left, to_right) = match self.m_type {
+            SplitDimension::X => {
+                (to_coord(prune_bbox.get_min_x()) <= self.m_split,
+                 to_coord(prune_bbox.get_max_x()) >= self.m_split)
+            }
+            SplitDimension::Y => {
+                (to_coord(prune_bbox.get_min_y()) <= self.m_split,
+                 to_coord(prune_bbox.get_max_y()) >= self.m_split)
+            }
+            SplitDimension::UNDEF => (false, false),
+        };
+
+        if let Some(idx) = self.get_left_child().filter(|_| to_left) {
+            if let Some(child) = get_node(data, idx) {
+                child.get_approximate_indices(idx, bbox, prune_bbox, min_t, data, r);
+            }
+        }
+        if let Some(idx) = self.get_right_child().filter(|_| to_right) {
+            if let Some(child) = get_node(data, idx) {
+                child.get_approximate_indices(idx, bbox, prune_bbox, min_t, data, r);
+            }
+        }
+    }
+
+    ///
+    /// Append the root indices of the maximal subtrees below self whose elements were reported at
+    /// most max_hits times in total to the result and return the total hits of the tree rooted at
+    /// self.
+    ///
+    /// The index of self is not appended, even if the whole tree is cold.
+    ///
+    /// The given own_idx must be the index of self in the data vector and hits must contain one
+    /// counter per element, see `Tree::get_element`.
+    ///
+    pub fn collect_cold(&self,
+                        own_idx: usize,
+                        hits: &[AtomicU32],
+                        max_hits: u64,
+                        data: &Tree<T>,
+                        r: &mut Vec<usize>)
+                        -> u64 {
+        let count = |idx: usize| {
+            hits.get(idx).map_or(0, |h| h.load(AtomicOrdering::Relaxed) as u64)
+        };
+
+        let mut cold_children = Vec::new();
+        let mut total = count(own_idx);
+        total += self.get_bucket(data).map(|(idx, _)| count(idx)).sum::<u64>();
+        for child in self.get_left_child().iter().chain(self.get_right_child().iter()) {
+            let child_hits = match get_node(data, *child) {
+                Some(node) => node.collect_cold(*child, hits, max_hits, data, r),
+                None => continue,
+            };
+            if child_hits <= max_hits {
+                cold_children.push(*child);
+            }
+            total += child_hits;
+        }
+
+        // a cold tree is reported by the parent as a whole
+        if total > max_hits {
+            r.extend(cold_children);
+        }
+
+        total
+    }
+
+    ///
+    /// Check the invariants of the 3d PST rooted at root_idx.
+    ///
+    /// The check is iterative and marks visited nodes, so it terminates on corrupted trees with
+    /// cycles as well.
+    ///
+    /// # Errors
+    ///   * Invariant: describing the first violated invariant, i.e. a child index or a bucket
+    ///     range out of range, an element reachable twice or not at all, a child or a bucket
+    ///     element with a larger t value than its parent, an element on the wrong side of a split
+    ///     value or wrong subtree information.
+    ///
+    pub fn validate_tree(root_idx: Option<usize>,
+                         data: &Tree<T>)
+                         -> Result<(), Error> {
+        // the bucket elements are marked by their element index, see Tree::get_element
+        let mut visited = vec![false; data.get_element_count()];
+        let mut count = 0;
+        let in_range = |p: &T, range: &[Coord; 4]| {
+            let (x, y) = (to_coord(p.get_x()), to_coord(p.get_y()));
+            range[0] <= x && x <= range[1] && range[2] <= y && y <= range[3]
+        };
+
+        // each entry holds a node index and the ranges [min_x, max_x], [min_y, max_y] the
+        // splits of its ancestors allow for the elements of its subtree
+        let all = [Coord::NEG_INFINITY, Coord::INFINITY, Coord::NEG_INFINITY, Coord::INFINITY];
+        let mut stack: Vec<(usize, [Coord; 4])> =
+            root_idx.into_iter().map(|idx| (idx, all)).collect();
+
+        while let Some((idx, range)) = stack.pop() {
+            let node = match data.get(idx) {
+                Some(node) => node,
+                None => return Err(violated(idx, "The node does not exist".to_string())),
+            };
+            if visited[idx] {
+                return Err(violated(idx, "The node is reachable twice".to_string()));
+            }
+            visited[idx] = true;
+            count += 1;
+
+            if !in_range(&node.m_data, &range) {
+                return Err(violated(idx, "The node violates the split value of an ancestor"
+                                             .to_string()));
+            }
+            if !point_in_box(&node.m_subtree_bbox.get_bbox(), &node.m_data) {
+                return Err(violated(idx, "The node is not contained in its subtree bounding box"
+                                             .to_string()));
+            }
+
+            let bucket_range = node.get_bucket_range();
+            if bucket_range.start > bucket_range.end || bucket_range.end > data.m_bucket.len() {
+                return Err(violated(idx,
+                                    format!("The bucket references the missing elements {}..{}",
+                                            bucket_range.start,
+                                            bucket_range.end)));
+            }
+            let mut max_t = node.m_t;
+            for (e_idx, e) in node.get_bucket(data) {
+                if visited[e_idx] {
+                    return Err(violated(e_idx,
+                                        "The bucket element is reachable twice".to_string()));
+                }
+                visited[e_idx] = true;
+                count += 1;
+
+                if e.m_t > max_t || from_coord(e.m_t) > f64::from(node.m_subtree_max_t) {
+                    return Err(violated(e_idx,
+                                        format!("The bucket element of node {} is not ordered \
+                                                 by decreasing t value",
+                                                idx)));
+                }
+                max_t = e.m_t;
+                if !in_range(&e.m_data, &range) ||
+                   !point_in_box(&node.m_subtree_bbox.get_bbox(), &e.m_data) {
+                    return Err(violated(e_idx,
+                                        format!("The bucket element lies outside of node {}",
+                                                idx)));
+                }
+            }
+
+            let children = [(node.get_left_child(), true), (node.get_right_child(), false)];
+            for &(child, is_left) in children.iter() {
+                let child_idx = match child {
+                    Some(child_idx) => child_idx,
+                    None => continue,
+                };
+                let c = match data.get(child_idx) {
+                    Some(c) => c,
+                    None => {
+                        return Err(violated(idx,
+                                            format!("The node references the missing child {}",
+                                                    child_idx)))
+                    }
+                };
+
+                if c.m_t > node.m_t || from_coord(c.m_t) > f64::from(node.m_subtree_max_t) {
+                    return Err(violated(child_idx,
+                                        format!("The child has a larger t value than its parent \
+                                                 {}",
+                                                idx)));
+                }
+                if !box_contains_box(&node.m_subtree_bbox.get_bbox(), &c.m_subtree_bbox.get_bbox()) {
+                    return Err(violated(idx,
+                                        format!("The subtree bounding box does not contain \
+                                                 child {}",
+                                                child_idx)));
+                }
+
+                let mut child_range = range;
+                match (&node.m_type, is_left) {
+                    (&SplitDimension::X, true) => child_range[1] = range[1].min(node.m_split),
+                    (&SplitDimension::X, false) => child_range[0] = range[0].max(node.m_split),
+                    (&SplitDimension::Y, true) => child_range[3] = range[3].min(node.m_split),
+                    (&SplitDimension::Y, false) => child_range[2] = range[2].max(node.m_split),
+                    (&SplitDimension::UNDEF, _) => {
+                        return Err(violated(idx, "The leaf node has a child".to_string()))
+                    }
+                }
+                stack.push((child_idx, child_range));
+            }
+        }
+
+        if let Some(idx) = visited.iter().position(|v| !v) {
+            return Err(violated(idx,
+                                format!("{} of {} elements are not reachable from the root",
+                                        visited.len() - count,
+                                        visited.len())));
+        }
+
+        Ok(())
+    }
+}
+
+impl Root<Label> {
+    ///
+    /// Get a human readable string representation of the tree rooted at self.
+    ///
+    /// A such string will look like:
+    ///
+    /// ```text
+    ///   x-node (split: 4.5): Label [#1]: 'T1' at (1, 2) with prio 1,elim-t: 9 and label factor: \
+    ///                                                                                         1.5
+    ///   l    y-node (split: 4.5): Label [#2]: 'T2' at (2, 3) with prio 1, elim-t: 8 and label \
+    ///                                                                                 factor: 1.5
+    ///   l        x-node (split: NaN): Label [#3]: 'T3' at (3, 4) with prio 1, elim-t: 7 and \
+    ///                                                                           label factor: 1.5
+    /// ```
+    ///
+    /// The remaining elements of a leaf bucket follow the leaf in rows starting with b.
+    ///
+    pub fn to_string(&self,
+                     level: i32,
+                     data: &Tree<Label>)
+                     -> String {
+        // prefix is level x p
+        let p = "    ";
+        let mut prefix = String::new();
+        for _ in 0..level {
+            prefix = format!("{}{}", prefix, p);
+        }
+
+        let mut result = match self.m_type {
+            SplitDimension::X => {
+                format!("{}x-node (split: {}): {}",
+                        prefix,
+                        self.m_split,
+                        self.m_data)
+            }
+            SplitDimension::Y => {
+                format!("{}y-node (split: {}): {}",
+                        prefix,
+                        self.m_split,
+                        self.m_data)
+            }
+            SplitDimension::UNDEF => {
+                format!("{}leaf-node (split: {}): {}",
+                        prefix,
+                        self.m_split,
+                        self.m_data)
+            }
+        };
+
+        // append the bucket elements
+        for (_, e) in self.get_bucket(data) {
+            result = format!("{}\nb{}{}{}", result, prefix, p, e.m_data);
+        }
+        // append the left subtree
+        if let Some(idx) = self.get_left_child() {
+            if let Some(child) = get_node(data, idx) {
+                result = format!("{}\nl{}", result, child.to_string(level + 1, data));
+            }
+        }
+        // append the right subtree
+        if let Some(idx) = self.get_right_child() {
+            if let Some(child) = get_node(data, idx) {
+                result = format!("{}\nr{}", result, child.to_string(level + 1, data));
+            }
+        }
+
+        result
+    }
+
+    ///
+    /// Get the DOT statements of the node and of the edges to its children.
+    ///
+    /// The node is named n<own_idx> and labeled with its split dimension and value, the label
+    /// string and the t value, followed by the label strings and t values of the remaining
+    /// elements of a leaf bucket. The edges are labeled l and r for the left and the right child.
+    ///
+    pub fn to_dot(&self,
+                  own_idx: usize,
+                  data: &Tree<Label>)
+                  -> String {
+        let split = if self.is_bucket() {
+            "bucket".to_string()
+        } else if self.get_left_child().is_none() && self.get_right_child().is_none() {
+            "leaf".to_string()
+        } else {
+            match self.m_type {
+                SplitDimension::X => format!("x-split: {}", self.m_split),
+                SplitDimension::Y => format!("y-split: {}", self.m_split),
+                SplitDimension::UNDEF => "leaf".to_string(),
+            }
+        };
+        let escape = |l: &Label| l.get_label().replace('\\', "\\\\").replace('"', "\\\"");
+
+        let mut text = format!("{} (t: {})", escape(&self.m_data), self.m_t);
+        for (_, e) in self.get_bucket(data) {
+            text.push_str(&format!("\\n{} (t: {})", escape(&e.m_data), e.m_t));
+        }
+
+        let mut result = format!("    n{} [label=\"{}\\n{}\"];\n", own_idx, split, text);
+        let children = [(self.get_left_child(), "l"), (self.get_right_child(), "r")];
+        for &(child, side) in children.iter() {
+            if let Some(idx) = child {
+                result.push_str(&format!("    n{} -> n{} [label=\"{}\"];\n", own_idx, idx, side));
+            }
+        }
+
+        result
+    }
+}
+
+///
+/// The struct represents a reference to a root node and contains all the information required to
+/// construct the 3D PST.
+///
+#[derive(Debug, Clone, Copy)]
+struct RootRef {
+    m_x: f64,
+    m_y: f64,
+    m_t: f64,
+
+    m_idx: usize,
+}
+
+impl RootRef {
+    ///
+    /// Initialize a new root ref
+    ///
+    fn new<T: HasPosition + HasT>(r: &Root<T>, idx: usize) -> RootRef {
+        RootRef {
+            m_t: r.get_t(),
+            m_x: r.m_data.get_x(),
+            m_y: r.m_data.get_y(),
+
+            m_idx: idx,
+        }
+    }
+
+    ///
+    /// Compare two Root refs with respect to the t value.
+    ///
+    fn order_by_t(first: &Self, second: &Self) -> Ordering {
+        if first.m_t < second.m_t {
+            Ordering::Less
+        } else if first.m_t > second.m_t {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    }
+
+    ///
+    /// Compare two Root refs with respect to the x value.
+    ///
+    fn order_by_x(first: &Self, second: &Self) -> Ordering {
+        if first.m_x < second.m_x {
+            Ordering::Less
+        } else if first.m_x > second.m_x {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    }
+
+    ///
+    /// Compare two Root refs with respect to the y value.
+    ///
+    fn order_by_y(first: &Self, second: &Self) -> Ordering {
+        if first.m_y < second.m_y {
+            Ordering::Less
+        } else if first.m_y > second.m_y {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    }
+}
+
+///
+/// An entry of the priority queues used by the best-first traversals.
+///
+/// The key is the t value when visiting the nodes by decreasing t value and the squared distance
+/// to the query focus when collecting the nearest elements.
+///
+struct HeapEntry {
+    m_key: f64,
+    m_idx: usize,
+}
+
+impl HeapEntry {
+    fn new(key: f64, idx: usize) -> HeapEntry {
+        HeapEntry {
+            m_key: key,
+            m_idx: idx,
+        }
+    }
+}
+
+impl PartialEq for HeapEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for HeapEntry {}
+
+impl PartialOrd for HeapEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for HeapEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.m_key.partial_cmp(&other.m_key).unwrap_or(Ordering::Equal)
+    }
+}
+
+///
+/// Check if the inner bounding box lies completely within the outer bounding box.
+///
+fn box_contains_box(outer: &BBox, inner: &BBox) -> bool {
+    all_less_equal([outer.get_min_x(), outer.get_min_y(), inner.get_max_x(), inner.get_max_y()],
+                   [inner.get_min_x(), inner.get_min_y(), outer.get_max_x(), outer.get_max_y()])
+}
+
+///
+/// Check if the two bounding boxes have at least one point in common.
+///
+fn box_intersects_box(first: &BBox, second: &BBox) -> bool {
+    all_less_equal([first.get_min_x(), second.get_min_x(), first.get_min_y(), second.get_min_y()],
+                   [second.get_max_x(), first.get_max_x(), second.get_max_y(), first.get_max_y()])
+}
+
+///
+/// Get the squared distance of p to the closest point of the bounding box, 0 if the point
+/// lies within the box.
+///
+fn squared_box_distance(bbox: &BBox, p: &Point) -> f64 {
+    let (x, y) = (p.get_x(), p.get_y());
+    let dx = (bbox.get_min_x() - x).max(x - bbox.get_max_x()).max(0.);
+    let dy = (bbox.get_min_y() - y).max(y - bbox.get_max_y()).max(0.);
+
+    dx * dx + dy * dy
+}
+
+///
+/// Check if the element lies within the bounding box, equivalent to BBox::is_contained.
+///
+fn point_in_box<P: HasPosition>(bbox: &BBox, p: &P) -> bool {
+    all_less_equal([bbox.get_min_x(), bbox.get_min_y(), p.get_x(), p.get_y()],
+                   [p.get_x(), p.get_y(), bbox.get_max_x(), bbox.get_max_y()])
+}
+
+///
+/// Check if each of the first values is less than or equal to the corresponding second value.
+///
+#[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+fn all_less_equal(first: [f64; 4], second: [f64; 4]) -> bool {
+    first[0] <= second[0] && first[1] <= second[1] && first[2] <= second[2] &&
+    first[3] <= second[3]
+}
+
+///
+/// Check if each of the first values is less than or equal to the corresponding second value.
+///
+/// The four comparisons are evaluated pairwise by SSE2 instructions without branches.
+///
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+fn all_less_equal(first: [f64; 4], second: [f64; 4]) -> bool {
+    use std::arch::x86_64::{_mm_and_pd, _mm_cmple_pd, _mm_loadu_pd, _mm_movemask_pd};
+
+    // SSE2 is available on every x86_64 target, the loads stay within the arrays
+    unsafe {
+        let low = _mm_cmple_pd(_mm_loadu_pd(first.as_ptr()), _mm_loadu_pd(second.as_ptr()));
+        let high = _mm_cmple_pd(_mm_loadu_pd(first[2..].as_ptr()),
+                                _mm_loadu_pd(second[2..].as_ptr()));
+        _mm_movemask_pd(_mm_and_pd(low, high)) == 0b11
+    }
+}
+
+///
+/// Round the value to the largest f32 less than or equal to it.
+///
+fn round_down(v: f64) -> f32 {
+    let r = v as f32;
+    if f64::from(r) > v { r.next_down() } else { r }
+}
+
+///
+/// Round the value to the smallest f32 greater than or equal to it.
+///
+fn round_up(v: f64) -> f32 {
+    let r = v as f32;
+    if f64::from(r) < v { r.next_up() } else { r }
+}
+
+///
+/// Convert a stored child index into an index of the data vector.
+///
+fn from_child(child: u32) -> Option<usize> {
+    if child == NO_CHILD {
+        None
+    } else {
+        Some(child as usize)
+    }
+}
+
+///
+/// Convert an index of the data vector into a stored child index.
+///
+fn to_child(idx: Option<usize>) -> u32 {
+    idx.map_or(NO_CHILD, |idx| idx as u32)
+}
+
+///
+/// Reorder the nodes of the tree rooted at root_idx in breadth first order, update the child
+/// indices accordingly and return the new index of the root node, i.e. 0.
+///
+/// The remaining elements of each leaf bucket, chained by their left child, are moved to the
+/// bucket vector, where they are stored contiguously in the breadth first order of their leaves.
+/// The leaf references the range of its elements instead, so a bucket is scanned sequentially.
+///
+fn reorder_breadth_first<T>(data: &mut Vec<Root<T>>,
+                            bucket: &mut Vec<BucketEntry<T>>,
+                            root_idx: usize)
+                            -> usize {
+    // determine the breadth first order of the old indices, buckets are not entered
+    let mut order = vec![root_idx];
+    let mut next = 0;
+    while next < order.len() {
+        let r = &data[order[next]];
+        if r.m_type != SplitDimension::UNDEF {
+            order.extend(r.get_left_child().iter().chain(r.get_right_child().iter()));
+        }
+        next += 1;
+    }
+
+    let mut new_idx = vec![0; data.len()];
+    for (idx, old_idx) in order.iter().enumerate() {
+        new_idx[*old_idx] = idx;
+    }
+
+    let mut old: Vec<Option<Root<T>>> = data.drain(..).map(Some).collect();
+    for old_idx in order {
+        if let Some(mut r) = old[old_idx].take() {
+            if r.m_type == SplitDimension::UNDEF {
+                let start = bucket.len();
+                let mut chained = from_child(r.m_left_child);
+                while let Some(e) = chained.and_then(|idx| old[idx].take()) {
+                    chained = from_child(e.m_left_child);
+                    bucket.push(BucketEntry {
+                                    m_t: e.m_t,
+                                    m_data: e.m_data,
+                                });
+                }
+                r.m_left_child = start as u32;
+                r.m_right_child = bucket.len() as u32;
+            } else {
+                r.m_left_child = to_child(r.get_left_child().map(|c| new_idx[c]));
+                r.m_right_child = to_child(r.get_right_child().map(|c| new_idx[c]));
+            }
+            data.push(r);
+        }
+    }
+    validate!(old.iter().all(|r| r.is_none()));
+
+    0
+}
+
+///
+/// Get the node with the given index. Trees built by init_pst3d only contain valid child
+/// indices, an invalid one fails the validation or, if validation is compiled out, is skipped
+/// like a missing subtree.
+///
+fn get_node<T>(data: &Tree<T>, idx: usize) -> Option<&Root<T>> {
+    validate!(idx < data.len());
+    data.get(idx)
+}
+
+///
+/// Create the error of an invariant violated at the node or bucket element with the given index.
+///
+fn violated(idx: usize, reason: String) -> Error {
+    Error::Invariant {
+        node: Some(idx),
+        reason,
+    }
+}
+
+///
+/// In the RootRef vector find the index of the root with the maximum t value.
+///
+/// Of several roots with the maximum t value the one with the smallest index is chosen.
+///
+fn find_root_idx(refs: &[RootRef]) -> usize {
+    refs.iter()
+        .max_by(|first, second| {
+                    RootRef::order_by_t(first, second).then(second.m_idx.cmp(&first.m_idx))
+                })
+        .map_or(0, |r| r.m_idx)
+}
+
+///
+/// Choose the split of elements with the given coordinates, sorted increasingly, with the given
+/// strategy. Return the number of elements of the left subtree and the split value.
+///
+/// The first returned number of elements are <= the split value, the remaining elements are >=
+/// the split value and both subtrees are non empty. The cell is the range [cell_min, cell_max]
+/// of the subtree in the split dimension. If all elements share a single coordinate the median
+/// is used independent of the strategy, which keeps the tree balanced.
+///
+fn choose_split(coordinates: &[f64],
+                strategy: SplitStrategy,
+                cell_min: f64,
+                cell_max: f64)
+                -> (usize, f64) {
+    let count = coordinates.len();
+    let median_idx = count / 2;
+    let median = (median_idx, coordinates[median_idx - 1]);
+
+    let (min, max) = (coordinates[0], coordinates[count - 1]);
+    if min >= max {
+        return median;
+    }
+
+    match strategy {
+        SplitStrategy::Median => median,
+        SplitStrategy::Midpoint => {
+            let mid = min + (max - min) / 2.;
+            let left = coordinates.partition_point(|c| *c <= mid);
+
+            // adjacent floating point values may have no value in between
+            if left == count { median } else { (left, mid) }
+        }
+        SplitStrategy::SlidingMidpoint => {
+            let mid = cell_min + (cell_max - cell_min) / 2.;
+            let left = coordinates.partition_point(|c| *c <= mid);
+            if left == 0 {
+                // slide the split to the smallest coordinate
+                (coordinates.partition_point(|c| *c <= min), min)
+            } else if left == count {
+                // slide the split just below the largest coordinate
+                let left = coordinates.partition_point(|c| *c < max);
+                (left, coordinates[left - 1])
+            } else {
+                (left, mid)
+            }
+        }
+    }
+}
+
+///
+/// Split the cell of a subtree at the given split value in the x or y dimension into the cells of
+/// the left and of the right subtree.
+///
+fn split_cell(cell: &BBox, is_x: bool, split_value: f64) -> (BBox, BBox) {
+    let (min_x, min_y, max_x, max_y) =
+        (cell.get_min_x(), cell.get_min_y(), cell.get_max_x(), cell.get_max_y());
+    if is_x {
+        (BBox::new(min_x, min_y, split_value, max_y), BBox::new(split_value, min_y, max_x, max_y))
+    } else {
+        (BBox::new(min_x, min_y, max_x, split_value), BBox::new(min_x, split_value, max_x, max_y))
+    }
+}
+
+///
+/// Construct a leaf bucket of the referenced elements and return the index of its leaf node.
+///
+/// The leaf holds the element with the maximum t value. During the construction the remaining
+/// elements are chained by their left child in the order of decreasing t, until
+/// `reorder_breadth_first` moves them to the bucket vector. Queries scan the bucket linearly and
+/// stop at the first element with a too small t value.
+///
+fn create_bucket<T: HasPosition + HasT>(mut refs: Vec<RootRef>,
+                                        data: &mut [Root<T>])
+                                        -> Option<usize> {
+    // the same order as find_root_idx, the first element is the root of the subtree
+    refs.sort_by(|first, second| {
+                     RootRef::order_by_t(second, first).then(first.m_idx.cmp(&second.m_idx))
+                 });
+
+    let mut next: Option<usize> = None;
+    for r in refs.iter().rev() {
+        let (subtree_max_t, subtree_bbox) = match next {
+            Some(idx) => {
+                let mut bbox = data[idx].m_subtree_bbox.get_bbox();
+                bbox.add_to_box(&data[r.m_idx].m_data);
+                (data[idx].m_t, bbox)
+            }
+            None => (Coord::NEG_INFINITY, BBox::new_from_point(&data[r.m_idx].m_data)),
+        };
+
+        let node = match data.get_mut(r.m_idx) {
+            Some(node) => node,
+            None => {
+                validate!(false, "Trying to access element at not existing vector position");
+                return None;
+            }
+        };
+        node.m_type = SplitDimension::UNDEF;
+        node.m_split = to_coord(f64::NAN);
+        node.m_left_child = to_child(next);
+        node.m_right_child = NO_CHILD;
+        node.m_subtree_max_t = round_up(from_coord(subtree_max_t));
+        node.m_subtree_bbox = CompactBox::new(&subtree_bbox);
+
+        next = Some(r.m_idx);
+    }
+
+    next
+}
+
+///
+/// From the given RootRef vectors construct the subtree and update the corresponding root nodes
+/// in the data vector.
+///
+/// Both vectors reference the same elements, by_x sorted by the x and by_y sorted by the y
+/// coordinate.
+///
+/// The element with the maximum t value will be set as root with the corresponding split
+/// dimension. The split value is chosen by the given strategy, see `choose_split`. For the median
+/// strategy it is the corresponding coordinate of item floor(|root_refs| / 2) - 1 of the
+/// remaining elements sorted by the split dimension. The first floor(|root_refs| / 2) elements
+/// (all <= the split value) form the left subtree and the remaining elements (all >= the split
+/// value) form the right subtree.
+///
+/// The cell is the region of the subtree bounded by the split values of its ancestors and by
+/// the bounding box of all elements.
+///
+/// The subtrees are constructed recursively with swapped split dimension. Splitting the sorted
+/// vectors takes linear time, so the construction takes O(n log n) time in total.
+///
+/// For the nodes in data that are referenced by the RootRefs the corresponding Roots are updated
+/// accordingly. The is_left vector is a scratch buffer with one entry per data element that must
+/// be false everywhere.
+///
+fn create_root<T: HasPosition + HasT>(mut by_x: Vec<RootRef>,
+                                      mut by_y: Vec<RootRef>,
+                                      mut data: &mut Vec<Root<T>>,
+                                      dim: &SplitDimension,
+                                      is_left: &mut Vec<bool>,
+                                      builder: &Pst3dBuilder,
+                                      cell: &BBox)
+                                      -> Option<usize> {
+    if by_x.is_empty() {
+        return None;
+    }
+    if by_x.len() <= builder.get_leaf_bucket_size() && builder.get_leaf_bucket_size() > 1 {
+        return create_bucket(by_x, data);
+    }
+
+    validate!(by_x.len() == by_y.len());
+    validate!(*dim != SplitDimension::UNDEF);
+    let is_x = *dim == SplitDimension::X;
+
+    // find the element with the maximum t value, remove the corresonding RootRefs
+    let root_idx = find_root_idx(&by_x);
+    by_x.retain(|r| r.m_idx != root_idx);
+    by_y.retain(|r| r.m_idx != root_idx);
+
+    // the sub dimension flips from X to Y or from Y to X
+    let sub_dim = if is_x {
+        SplitDimension::Y
     } else {
         SplitDimension::X
     };
 
+    let coordinate = |r: &RootRef| if is_x { r.m_x } else { r.m_y };
+
     let mut split_value = f64::NAN;
     let mut left_child_idx: Option<usize> = None;
     let mut right_child_idx: Option<usize> = None;
 
-    let order_asc = if is_x {
-        RootRef::order_by_x
-    } else {
-        RootRef::order_by_y
-    };
-    if root_refs.len() == 1 {
-        split_value = if is_x {
-            root_refs[0].m_x
-        } else {
-            root_refs[0].m_y
-        };
-        left_child_idx = create_root(root_refs, &mut data, &sub_dim);
-    } else if root_refs.len() > 1 {
-        root_refs.sort_by(order_asc);
-
-        // take the x value of the median element as the new split value
-        let mut median_idx = root_refs.len() / 2 - 1;
-        split_value = if is_x {
-            root_refs[median_idx].m_x
-        } else {
-            root_refs[median_idx].m_y
+    if by_x.len() == 1 {
+        split_value = coordinate(&by_x[0]);
+        let left_cell = split_cell(cell, is_x, split_value).0;
+        left_child_idx =
+            create_root(by_x, by_y, &mut data, &sub_dim, is_left, builder, &left_cell);
+    } else if by_x.len() > 1 {
+        let (mut sorted, other) = if is_x { (by_x, by_y) } else { (by_y, by_x) };
+
+        let (split_idx, value) = {
+            let coordinates: Vec<f64> = sorted.iter().map(&coordinate).collect();
+            let (cell_min, cell_max) = if is_x {
+                (cell.get_min_x(), cell.get_max_x())
+            } else {
+                (cell.get_min_y(), cell.get_max_y())
+            };
+            choose_split(&coordinates, builder.get_split_strategy(), cell_min, cell_max)
         };
+        split_value = value;
 
-        // ensure that the right children realy have a value > m_split
-        if is_x {
-            while median_idx < root_refs.len() && root_refs[median_idx].m_x == split_value {
-                median_idx = median_idx + 1;
-            }
-        } else {
-            while median_idx < root_refs.len() && root_refs[median_idx].m_y == split_value {
-                median_idx = median_idx + 1;
-            }
+        // split the data at the split index, the other vector is split keeping its order
+        let sorted_right = sorted.split_off(split_idx);
+        for r in &sorted {
+            is_left[r.m_idx] = true;
+        }
+        let (other_left, other_right): (Vec<RootRef>, Vec<RootRef>) =
+            other.into_iter().partition(|r| is_left[r.m_idx]);
+        for r in &sorted {
+            is_left[r.m_idx] = false;
         }
 
-        let size2 = root_refs.len();
-        assert!(size1 == size2 + 1);
-        // split the data at the median point:
-        let last = root_refs.split_off(median_idx);
-        assert!(size2 == root_refs.len() + last.len());
+        let ((left_x, left_y), (right_x, right_y)) = if is_x {
+            ((sorted, other_left), (sorted_right, other_right))
+        } else {
+            ((other_left, sorted), (other_right, sorted_right))
+        };
+
+        let (left_cell, right_cell) = split_cell(cell, is_x, split_value);
+        left_child_idx =
+            create_root(left_x, left_y, &mut data, &sub_dim, is_left, builder, &left_cell);
+        right_child_idx =
+            create_root(right_x, right_y, &mut data, &sub_dim, is_left, builder, &right_cell);
+    }
 
-        left_child_idx = create_root(root_refs, &mut data, &sub_dim);
-        right_child_idx = create_root(last, &mut data, &sub_dim);
+    // collect the subtree information of the children
+    let mut subtree_max_t = Coord::NEG_INFINITY;
+    let mut subtree_bbox = BBox::new_from_point(&data[root_idx].m_data);
+    for child_idx in left_child_idx.iter().chain(right_child_idx.iter()) {
+        let child = &data[*child_idx];
+        subtree_max_t = subtree_max_t.max(child.m_t);
+        subtree_bbox.add_box(&child.m_subtree_bbox.get_bbox());
     }
 
-    let r = data.get_mut(root_idx)
-        .expect("Trying to access element at not existing vector position");
+    let r = match data.get_mut(root_idx) {
+        Some(r) => r,
+        None => {
+            validate!(false, "Trying to access element at not existing vector position");
+            return None;
+        }
+    };
 
     r.m_type = if is_x {
         SplitDimension::X
     } else {
         SplitDimension::Y
     };
-    r.m_split = split_value;
-    r.m_left_child = left_child_idx;
-    r.m_right_child = right_child_idx;
+    r.m_split = to_coord(split_value);
+    r.m_left_child = to_child(left_child_idx);
+    r.m_right_child = to_child(right_child_idx);
+    r.m_subtree_max_t = round_up(from_coord(subtree_max_t));
+    r.m_subtree_bbox = CompactBox::new(&subtree_bbox);
 
     Some(root_idx)
 }
 
+#[test]
+fn test_compact_node_layout() {
+    assert!(std::mem::size_of::<Root<()>>() <= 48);
+    assert!(std::mem::size_of::<Root<Label>>() <= std::mem::size_of::<Label>() + 48);
+
+    // the stored subtree information bounds the exact values
+    let bbox = CompactBox::new(&BBox::new(0.1, -0.1, 0.3, 1e300)).get_bbox();
+    assert!(bbox.get_min_x() <= 0.1 && bbox.get_min_y() <= -0.1);
+    assert!(bbox.get_max_x() >= 0.3 && bbox.get_max_y() >= 1e300);
+    assert!(f64::from(round_up(0.1)) >= 0.1 && f64::from(round_down(0.1)) <= 0.1);
+    assert!(round_up(f64::NEG_INFINITY) == f32::NEG_INFINITY);
+}
+
 #[test]
 fn test_root_new() {
-    let r = Root::new(Label::new(1., 2., 9., 1, 1, 1.5, "A".to_string()));
+    let r = Root::new(Label::new(1., 2., 9., 1, 1, 1.5, "A".to_string()), 9.);
 
     assert!(r.m_t == 9.);
     assert!(*r.m_data.get_label() == "A".to_string());
@@ -410,20 +2098,439 @@ fn test_root_new() {
 
 #[test]
 fn test_pst_init() {
-    let mut f: Vec<Root> = Vec::new();
-    f.push(Root::new(Label::new(1., 2., 9., 1, 1, 1.5, "A".to_string())));
-    f.push(Root::new(Label::new(2., 3., 8., 2, 1, 1.5, "B".to_string())));
-    f.push(Root::new(Label::new(3., 4., 7., 3, 1, 1.5, "C".to_string())));
+    let mut f = Tree::new(vec![Root::new(Label::new(1., 2., 9., 1, 1, 1.5, "A".to_string()), 9.),
+                               Root::new(Label::new(2., 3., 8., 2, 1, 1.5, "B".to_string()), 8.),
+                               Root::new(Label::new(3., 4., 7., 3, 1, 1.5, "C".to_string()), 7.)]);
 
-    let root = Root::init_pst3d(&mut f);
+    let root = Root::init_pst3d(&mut f, &Pst3dBuilder::new());
     let root_idx = root.unwrap();
     println!("{}", f[root_idx].to_string(0, &f));
     assert!(root_idx == 0);
 
     assert!(f[root_idx].m_type == SplitDimension::X);
-    assert!(f[root_idx].m_left_child.is_some());
-    assert!(f[root_idx].m_right_child.is_some());
+    assert!(f[root_idx].get_left_child().is_some());
+    assert!(f[root_idx].get_right_child().is_some());
+
+    assert!(f[root_idx].get_left_child().unwrap() == 1);
+    assert!(f[root_idx].get_right_child().unwrap() == 2);
+}
+
+#[test]
+fn test_subtree_information() {
+    let mut f = Tree::new(vec![Root::new(Label::new(1., 2., 9., 1, 1, 1.5, "A".to_string()), 9.),
+                               Root::new(Label::new(2., 3., 8., 2, 1, 1.5, "B".to_string()), 8.),
+                               Root::new(Label::new(3., 4., 7., 3, 1, 1.5, "C".to_string()), 7.)]);
+
+    let root_idx = Root::init_pst3d(&mut f, &Pst3dBuilder::new()).unwrap();
+
+    assert!(f[root_idx].m_subtree_max_t == 8.);
+    assert!(f[root_idx].m_subtree_bbox.get_bbox().get_min_x() == 1.);
+    assert!(f[root_idx].m_subtree_bbox.get_bbox().get_max_x() == 3.);
+    assert!(f[root_idx].m_subtree_bbox.get_bbox().get_min_y() == 2.);
+    assert!(f[root_idx].m_subtree_bbox.get_bbox().get_max_y() == 4.);
+
+    // a query box containing all elements is answered by reporting the whole subtree
+    let bb = BBox::new(0., 0., 10., 10.);
+    let mut r = Vec::new();
+    f[root_idx].get_indices(root_idx, &bb, 7.5, &f, &mut r, &mut NoStats);
+    assert!(r.len() == 2);
+
+    r.clear();
+    f[root_idx].get_indices(root_idx, &bb, 0., &f, &mut r, &mut NoStats);
+    assert!(r.len() == 3);
+}
+
+#[test]
+fn test_duplicate_positions_balanced() {
+    fn depth(idx: Option<usize>, data: &[Root<Label>]) -> usize {
+        match idx {
+            Some(idx) => {
+                let left = depth(data[idx].get_left_child(), data);
+                1 + left.max(depth(data[idx].get_right_child(), data))
+            }
+            None => 0,
+        }
+    }
+
+    let mut f: Vec<Root<Label>> = Vec::new();
+    for i in 0..1023 {
+        f.push(Root::new(Label::new(1., 1., i as f64, i, 1, 1.5, "A".to_string()), i as f64));
+    }
+    f.push(Root::new(Label::new(5., 5., -0.5, 1023, 1, 1.5, "B".to_string()), -0.5));
+
+    let mut f = Tree::new(f);
+    let root_idx = Root::init_pst3d(&mut f, &Pst3dBuilder::new());
+    assert!(depth(root_idx, &f) == 11);
+
+    // the elements on both sides of the split value are found
+    let root_idx = root_idx.unwrap();
+    let bb = BBox::new(1., 1., 1., 1.);
+    let mut r = Vec::new();
+    f[root_idx].get_indices(root_idx, &bb, -1., &f, &mut r, &mut NoStats);
+    assert!(r.len() == 1023);
+}
+
+#[test]
+fn test_duplicate_heavy_input() {
+    fn depth(idx: Option<usize>, data: &[Root<Label>]) -> usize {
+        match idx {
+            Some(idx) => {
+                let left = depth(data[idx].get_left_child(), data);
+                1 + left.max(depth(data[idx].get_right_child(), data))
+            }
+            None => 0,
+        }
+    }
+
+    // most labels share one of three positions, the remaining ones share an x or a y coordinate
+    let positions = [(1., 1.), (1., 2.), (2., 1.)];
+    let mut f: Vec<Root<Label>> = Vec::new();
+    for i in 0..2047 {
+        let (x, y) = match i % 10 {
+            8 => (1., (i % 7) as f64),
+            9 => ((i % 5) as f64, 2.),
+            k => positions[(k % 3) as usize],
+        };
+        let t = ((i * 37) % 101) as f64;
+        f.push(Root::new(Label::new(x, y, t, i, 1, 1.5, "A".to_string()), t));
+    }
+
+    let mut f = Tree::new(f);
+    let root_idx = Root::init_pst3d(&mut f, &Pst3dBuilder::new());
+    assert!(depth(root_idx, &f) == 11);
+    assert!(Root::validate_tree(root_idx, &f).is_ok());
+
+    let root_idx = root_idx.unwrap();
+    for &(x, y) in positions.iter() {
+        let bb = BBox::new(x, y, x, y);
+        let mut r = Vec::new();
+        f[root_idx].get_indices(root_idx, &bb, 50., &f, &mut r, &mut NoStats);
+
+        let expected = f.iter()
+            .filter(|n| n.m_data.get_x() == x && n.m_data.get_y() == y && n.get_t() > 50.)
+            .count();
+        assert!(r.len() == expected);
+    }
+}
+
+#[test]
+fn test_breadth_first_layout() {
+    let mut f: Vec<Root<Label>> = Vec::new();
+    for i in 0..100 {
+        let x = ((i * 37) % 100) as f64;
+        let y = ((i * 61) % 100) as f64;
+        f.push(Root::new(Label::new(x, y, i as f64, i, 1, 1.5, "A".to_string()), i as f64));
+    }
+
+    let mut f = Tree::new(f);
+    let root_idx = Root::init_pst3d(&mut f, &Pst3dBuilder::new());
+    assert!(root_idx == Some(0));
+
+    // the children are stored in breadth first order
+    let mut next = 1;
+    for r in f.iter() {
+        for child in r.get_left_child().iter().chain(r.get_right_child().iter()) {
+            assert!(*child == next);
+            next += 1;
+        }
+    }
+    assert!(next == f.len());
+}
+
+#[test]
+fn test_containment() {
+    let values = [-1., 0., 1., f64::NAN];
+    for a in values.iter() {
+        for b in values.iter() {
+            let outer = BBox::new(-0.5, -0.5, 0.5, 0.5);
+            let inner = BBox::new(*a, *b, 0.25, 0.25);
+            let l = Label::new(*a, *b, 1., 1, 1, 1.5, "A".to_string());
+
+            let contains = inner.get_min_x() >= -0.5 && inner.get_min_y() >= -0.5;
+            assert!(box_contains_box(&outer, &inner) == contains);
+            assert!(box_intersects_box(&outer, &inner) == (*a <= 0.5 && *b <= 0.5));
+            assert!(point_in_box(&outer, &l) == outer.is_contained(&l));
+        }
+    }
+}
 
-    assert!(f[root_idx].m_left_child.unwrap() == 1);
-    assert!(f[root_idx].m_right_child.unwrap() == 2);
+#[test]
+fn test_nearest_matches_brute_force() {
+    let mut f: Vec<Root<Label>> = Vec::new();
+    for i in 0..200 {
+        let x = ((i * 37) % 101) as f64;
+        let y = ((i * 61) % 103) as f64;
+        f.push(Root::new(Label::new(x, y, i as f64, i, 1, 1.5, "A".to_string()), i as f64));
+    }
+
+    let mut f = Tree::new(f);
+    let root_idx = Root::init_pst3d(&mut f, &Pst3dBuilder::new()).unwrap();
+
+    let (focus, min_t) = (Point::new(40.5, 60.25), 50.);
+    let distance = |r: &Root<Label>| focus.squared_distance(r.get_label());
+
+    let mut expected: Vec<f64> = f.iter().filter(|r| r.get_t() > min_t).map(&distance).collect();
+    expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    expected.truncate(10);
+
+    let r = Root::get_nearest_indices(root_idx, &focus, min_t, 10, &f);
+    let found: Vec<f64> = r.iter().map(|&idx| distance(&f[idx as usize])).collect();
+    assert!(found == expected);
+}
+
+#[test]
+fn test_validate_detects_corruption() {
+    let mut f: Vec<Root<Label>> = Vec::new();
+    for i in 0..20 {
+        let (x, y) = ((i * 7 % 20) as f64, (i * 13 % 20) as f64);
+        let l = Label::new(x, y, i as f64, i, 1, 1.5, "A".to_string());
+        f.push(Root::new(l, i as f64));
+    }
+    let mut f = Tree::new(f);
+    let root_idx = Root::init_pst3d(&mut f, &Pst3dBuilder::new());
+    assert!(Root::validate_tree(root_idx, &f).is_ok());
+
+    // heap order
+    let last = f.len() - 1;
+    f[last].m_t = 100.;
+    assert!(Root::validate_tree(root_idx, &f).is_err());
+    f[last].m_t = 0.;
+    assert!(Root::validate_tree(root_idx, &f).is_ok());
+
+    // split value
+    let split = f[0].m_split;
+    f[0].m_split = split - 5.;
+    assert!(Root::validate_tree(root_idx, &f).is_err());
+    f[0].m_split = split;
+
+    // cycle
+    let left = f[1].m_left_child;
+    f[1].m_left_child = 0;
+    assert!(Root::validate_tree(root_idx, &f).is_err());
+
+    // index bounds
+    f[1].m_left_child = 1000;
+    match Root::validate_tree(root_idx, &f) {
+        Err(Error::Invariant { node, .. }) => assert!(node == Some(1)),
+        r => panic!("Unexpected result {:?}", r),
+    }
+    f[1].m_left_child = left;
+    assert!(Root::validate_tree(root_idx, &f).is_ok());
+}
+
+#[test]
+fn test_many_matches_single_queries() {
+    let mut f: Vec<Root<Label>> = Vec::new();
+    for i in 0..200 {
+        let x = ((i * 37) % 101) as f64;
+        let y = ((i * 61) % 103) as f64;
+        f.push(Root::new(Label::new(x, y, i as f64, i, 1, 1.5, "A".to_string()), i as f64));
+    }
+
+    let mut f = Tree::new(f);
+    let root_idx = Root::init_pst3d(&mut f, &Pst3dBuilder::new()).unwrap();
+
+    // a 3x3 neighborhood of overlapping tiles
+    let mut queries: Vec<(BBox, f64)> = Vec::new();
+    for i in 0..9 {
+        let (x, y) = ((i % 3) as f64 * 30., (i / 3) as f64 * 30.);
+        queries.push((BBox::new(x, y, x + 40., y + 40.), (i * 10) as f64));
+    }
+
+    let active: Vec<usize> = (0..queries.len()).collect();
+    let mut r: Vec<Vec<u32>> = vec![Vec::new(); queries.len()];
+    f[root_idx].get_many_indices(root_idx, &queries, &active, &f, &mut r);
+
+    for (q, found) in queries.iter().zip(r.iter_mut()) {
+        let mut expected: Vec<u32> = Vec::new();
+        let mut stats = Traversal::new();
+        f[root_idx].get_indices(root_idx, &q.0, q.1, &f, &mut expected, &mut stats);
+        expected.sort();
+        found.sort();
+        assert!(*found == expected);
+    }
+}
+
+#[test]
+fn test_split_strategies_and_buckets_match_brute_force() {
+    // two dense clusters, a sparse background and duplicate positions
+    let mut labels: Vec<Label> = Vec::new();
+    for i in 0..300 {
+        let (x, y) = match i % 3 {
+            0 => (((i * 37) % 101) as f64 / 100., ((i * 61) % 103) as f64 / 100.),
+            1 => (50. + ((i * 17) % 7) as f64 / 10., 50.),
+            _ => (((i * 37) % 101) as f64, ((i * 61) % 103) as f64),
+        };
+        labels.push(Label::new(x, y, ((i * 7) % 300) as f64, i, 1, 1.5, "A".to_string()));
+    }
+
+    let queries = [BBox::new(0., 0., 1., 1.), BBox::new(49., 49., 51., 51.),
+                   BBox::new(20., 10., 70., 60.), BBox::new(0., 0., 110., 110.)];
+    let strategies = [SplitStrategy::Median, SplitStrategy::Midpoint,
+                      SplitStrategy::SlidingMidpoint];
+
+    for (&strategy, &bucket_size) in strategies.iter().flat_map(|s| [(s, &1), (s, &8)]) {
+        let mut builder = Pst3dBuilder::new();
+        builder.set_split_strategy(strategy);
+        builder.set_leaf_bucket_size(bucket_size);
+
+        let mut f = Tree::new(labels.iter().map(|l| Root::new(l.clone(), l.get_t())).collect());
+        let root_idx = Root::init_pst3d(&mut f, &builder);
+        assert!(Root::validate_tree(root_idx, &f).is_ok());
+        assert!(f.get_element_count() == labels.len());
+
+        let root_idx = root_idx.unwrap();
+        for bb in queries.iter() {
+            let mut found: Vec<i64> = Vec::new();
+            let mut r: Vec<u32> = Vec::new();
+            f[root_idx].get_indices(root_idx, bb, 100., &f, &mut r, &mut NoStats);
+            for idx in r {
+                let (l, t) = f.get_element(idx as usize).unwrap();
+                assert!(point_in_box(bb, l) && t > 100.);
+                found.push(l.get_osm_id());
+            }
+
+            let mut expected: Vec<i64> = labels.iter()
+                .filter(|l| point_in_box(bb, *l) && l.get_t() > 100.)
+                .map(|l| l.get_osm_id())
+                .collect();
+            found.sort();
+            expected.sort();
+            assert!(found == expected);
+        }
+
+        let batch: Vec<(BBox, f64)> = queries.iter().map(|bb| (bb.clone(), 100.)).collect();
+        let mut r: Vec<Vec<u32>> = vec![Vec::new(); batch.len()];
+        f[root_idx].get_many_indices(root_idx, &batch, &[0, 1, 2, 3], &f, &mut r);
+        for (bb, found) in queries.iter().zip(r.iter()) {
+            let mut expected: Vec<u32> = Vec::new();
+            let mut stats = Traversal::new();
+            f[root_idx].get_indices(root_idx, bb, 100., &f, &mut expected, &mut stats);
+            assert!(found.len() == expected.len());
+        }
+    }
+}
+
+#[test]
+fn test_leaf_buckets_reduce_nodes() {
+    fn depth(idx: Option<usize>, data: &[Root<Label>]) -> usize {
+        match idx {
+            Some(idx) => {
+                let left = depth(data[idx].get_left_child(), data);
+                1 + left.max(depth(data[idx].get_right_child(), data))
+            }
+            None => 0,
+        }
+    }
+
+    let labels: Vec<Label> = (0..1000)
+        .map(|i| {
+                 let (x, y) = (((i * 37) % 101) as f64, ((i * 61) % 103) as f64);
+                 Label::new(x, y, ((i * 7) % 1000) as f64, i, 1, 1.5, "A".to_string())
+             })
+        .collect();
+    let build = |bucket_size: usize| {
+        let mut builder = Pst3dBuilder::new();
+        builder.set_leaf_bucket_size(bucket_size);
+        let mut f = Tree::new(labels.iter().map(|l| Root::new(l.clone(), l.get_t())).collect());
+        let root_idx = Root::init_pst3d(&mut f, &builder);
+        (f, root_idx)
+    };
+
+    let (single, single_root) = build(1);
+    let (f, root_idx) = build(16);
+    assert!(single.len() == 1000 && single.get_element_count() == 1000);
+    assert!(f.get_element_count() == 1000);
+    assert!(f.len() < 1000 / 4);
+    assert!(depth(root_idx, &f) + 3 <= depth(single_root, &single));
+    assert!(Root::validate_tree(root_idx, &f).is_ok());
+
+    // the best-first traversals return the same t values with and without buckets
+    let root_idx = root_idx.unwrap();
+    let bb = BBox::new(10., 20., 60., 70.);
+    let t_values = |r: Vec<u32>, f: &Tree<Label>| -> Vec<f64> {
+        r.iter().map(|idx| f.get_element(*idx as usize).unwrap().1).collect()
+    };
+    let top = Root::get_top_indices(root_idx, &bb, 100., 20, &f);
+    let expected = Root::get_top_indices(0, &bb, 100., 20, &single);
+    assert!(t_values(top, &f) == t_values(expected, &single));
+
+    let focus = Point::new(40.5, 60.25);
+    let distance = |r: Vec<u32>, f: &Tree<Label>| -> Vec<f64> {
+        r.iter()
+            .map(|idx| focus.squared_distance(f.get_element(*idx as usize).unwrap().0))
+            .collect()
+    };
+    let nearest = Root::get_nearest_indices(root_idx, &focus, 100., 10, &f);
+    let expected = Root::get_nearest_indices(0, &focus, 100., 10, &single);
+    assert!(distance(nearest, &f) == distance(expected, &single));
+
+    // the nodes and the bucket elements are restored from their serialization
+    let mut buf: Vec<u8> = Vec::new();
+    Root::write_nodes(&f, &mut buf).unwrap();
+    let elements: Vec<Label> = (0..f.get_element_count())
+        .map(|idx| f.get_element(idx).unwrap().0.clone())
+        .collect();
+    let loaded = Root::read_nodes(&mut buf.as_slice(), elements).unwrap();
+    assert!(loaded.len() == f.len() && loaded.get_element_count() == 1000);
+    assert!(Root::validate_tree(Some(root_idx), &loaded).is_ok());
+    assert!(loaded[root_idx].to_string(0, &loaded) == f[root_idx].to_string(0, &f));
+}
+
+#[test]
+fn test_flatten_subtrees() {
+    let labels: Vec<Label> = (0..1000)
+        .map(|i| {
+                 let (x, y) = (((i * 37) % 101) as f64, ((i * 61) % 103) as f64);
+                 Label::new(x, y, ((i * 7) % 1000) as f64, i, 1, 1.5, "A".to_string())
+             })
+        .collect();
+    let mut builder = Pst3dBuilder::new();
+    builder.set_leaf_bucket_size(4);
+    let mut f = Tree::new(labels.iter().map(|l| Root::new(l.clone(), l.get_t())).collect());
+    let root_idx = Root::init_pst3d(&mut f, &builder).unwrap();
+
+    let ids = |root_idx: usize, f: &Tree<Label>, bb: &BBox, min_t: f64| -> Vec<i64> {
+        let mut r = Vec::new();
+        f[root_idx].get_indices(root_idx, bb, min_t, f, &mut r, &mut NoStats);
+        let mut ids: Vec<i64> = r.iter()
+            .map(|idx| f.get_element(*idx as usize).unwrap().0.get_osm_id())
+            .collect();
+        ids.sort();
+        ids
+    };
+    let queries = [(BBox::new(10., 20., 60., 70.), 100.),
+                   (BBox::new(0., 0., 101., 103.), 0.),
+                   (BBox::new(70., 0., 101., 40.), 500.),
+                   (BBox::new(-10., -10., 5., 5.), -1.)];
+    let expected: Vec<Vec<i64>> = queries.iter()
+        .map(|q| ids(root_idx, &f, &q.0, q.1))
+        .collect();
+
+    // collapse the right subtree of the root and both subtrees of its left child
+    let left = f[root_idx].get_left_child().unwrap();
+    let mut subtrees = vec![f[root_idx].get_right_child().unwrap()];
+    subtrees.extend(f[left].get_left_child().iter().chain(f[left].get_right_child().iter()));
+    let (node_count, memory) = (f.len(), f.get_memory_size());
+
+    let (f, root_idx) = Root::flatten_subtrees(f, root_idx, &subtrees);
+    assert!(f.get_element_count() == 1000);
+    assert!(f.len() == 5);
+    assert!(node_count > 100 && f.get_memory_size() < memory);
+    assert!(Root::validate_tree(Some(root_idx), &f).is_ok());
+    for (q, expected) in queries.iter().zip(expected.iter()) {
+        assert!(ids(root_idx, &f, &q.0, q.1) == *expected);
+    }
+
+    // the flattened leaf buckets are restored from their serialization
+    let mut buf: Vec<u8> = Vec::new();
+    Root::write_nodes(&f, &mut buf).unwrap();
+    let elements: Vec<Label> = (0..f.get_element_count())
+        .map(|idx| f.get_element(idx).unwrap().0.clone())
+        .collect();
+    let loaded = Root::read_nodes(&mut buf.as_slice(), elements).unwrap();
+    assert!(Root::validate_tree(Some(root_idx), &loaded).is_ok());
+    for (q, expected) in queries.iter().zip(expected.iter()) {
+        assert!(ids(root_idx, &loaded, &q.0, q.1) == *expected);
+    }
 }
@@ -0,0 +1,229 @@
+/*
+    The library provides a simple datastructure to access geolocated labels with an additional
+    elimination time t and a label size factor. The library provides method to query a set of
+    such labels with a bounding box and a minimum elimination time.
+
+    Copyright (C) {2017}  {Filip Krumpe <filip.krumpe@fmi.uni-stuttgart.de}
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use primitives::label::Label;
+
+///
+/// The possible ways to anonymize the osm ids of a label set.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IdAnonymization {
+    /// Keep the osm ids unchanged
+    Keep,
+    /// Replace the osm ids by running numbers 1, 2, ... in the order of the label set
+    Renumber,
+    /// Replace the osm ids by a salted hash of the original id
+    Hash(u64),
+}
+
+///
+/// Options of the sanitize pass.
+///
+/// A default constructed SanitizeOptions object keeps the osm ids and all tags and names.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct SanitizeOptions {
+    m_ids: IdAnonymization,
+    m_fields: Vec<String>,
+}
+
+impl SanitizeOptions {
+    ///
+    /// Initialize new options keeping the osm ids and all tags and names.
+    ///
+    pub fn new() -> SanitizeOptions {
+        SanitizeOptions {
+            m_ids: IdAnonymization::Keep,
+            m_fields: Vec::new(),
+        }
+    }
+
+    ///
+    /// Set the way the osm ids are anonymized.
+    ///
+    pub fn set_id_anonymization(&mut self, mode: IdAnonymization) {
+        self.m_ids = mode;
+    }
+
+    ///
+    /// Get the way the osm ids are anonymized.
+    ///
+    pub fn get_id_anonymization(&self) -> IdAnonymization {
+        self.m_ids
+    }
+
+    ///
+    /// Remove the tags and names with the given key, e.g. "wikidata" or "name:de". A key ending
+    /// with `*` removes all tags and names starting with the key, e.g. "name:*" removes the names
+    /// in all further languages and "*" removes all tags and names.
+    ///
+    pub fn add_stripped_field(&mut self, key: String) {
+        self.m_fields.push(key);
+    }
+
+    ///
+    /// Get the keys of the removed tags and names.
+    ///
+    pub fn get_stripped_fields(&self) -> &[String] {
+        &self.m_fields
+    }
+
+    ///
+    /// Check if the options change any label, i.e. anonymize the ids or remove any field.
+    ///
+    pub fn is_enabled(&self) -> bool {
+        self.m_ids != IdAnonymization::Keep || !self.m_fields.is_empty()
+    }
+}
+
+impl Default for SanitizeOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+///
+/// Sanitize the given labels: anonymize the osm ids and remove the configured tag and name fields.
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::primitives::label;
+/// use rt_datastructure::sanitize;
+///
+/// let mut l = label::Label::new(1., 2., 9., 3627273522, 1, 1.5, "Bremen".to_string());
+/// l.set_tag("wikidata".to_string(), "Q24879".to_string());
+/// l.set_tag("place".to_string(), "city".to_string());
+/// l.set_name("name:en".to_string(), "Bremen".to_string());
+/// let mut v = vec![l];
+///
+/// let mut o = sanitize::SanitizeOptions::new();
+/// o.set_id_anonymization(sanitize::IdAnonymization::Renumber);
+/// o.add_stripped_field("wikidata".to_string());
+/// o.add_stripped_field("name:*".to_string());
+/// sanitize::sanitize(&mut v, &o);
+///
+/// assert!(v[0].get_osm_id() == 1);
+/// assert!(v[0].get_tag("wikidata").is_none());
+/// assert!(v[0].get_tag("place").is_some());
+/// assert!(v[0].get_names().is_empty());
+/// assert!(v[0].get_label() == "Bremen");
+/// ```
+///
+pub fn sanitize(labels: &mut [Label], options: &SanitizeOptions) {
+    anonymize(labels, options.get_id_anonymization());
+
+    let fields = options.get_stripped_fields();
+    if fields.is_empty() {
+        return;
+    }
+    for l in labels.iter_mut() {
+        let tags: Vec<String> = l.get_tags()
+            .iter()
+            .filter(|t| is_stripped(&t.0, fields))
+            .map(|t| t.0.clone())
+            .collect();
+        for key in tags {
+            l.remove_tag(&key);
+        }
+
+        let names: Vec<String> = l.get_names()
+            .iter()
+            .filter(|n| is_stripped(&n.0, fields))
+            .map(|n| n.0.clone())
+            .collect();
+        for key in names {
+            l.remove_name(&key);
+        }
+    }
+}
+
+// Check if the tag or name key matches any of the stripped fields.
+fn is_stripped(key: &str, fields: &[String]) -> bool {
+    fields.iter().any(|f| match f.strip_suffix('*') {
+                          Some(prefix) => key.starts_with(prefix),
+                          None => key == f,
+                      })
+}
+
+///
+/// Anonymize the osm ids of the given labels.
+///
+/// Hashed ids are non negative and stable for the same salt, so two datasets anonymized with the
+/// same salt can still be joined on the hashed id.
+///
+/// # Examples
+/// ```
+/// use rt_datastructure::primitives::label;
+/// use rt_datastructure::sanitize;
+///
+/// let mut v = Vec::new();
+/// v.push(label::Label::new(1., 2., 9., 3627273522, 1, 1.5, "T1".to_string()));
+/// v.push(label::Label::new(2., 3., 8., 253042611, 1, 1.5, "T2".to_string()));
+///
+/// let mut renumbered = v.clone();
+/// sanitize::anonymize(&mut renumbered, sanitize::IdAnonymization::Renumber);
+/// assert!(renumbered[0].get_osm_id() == 1);
+/// assert!(renumbered[1].get_osm_id() == 2);
+///
+/// let mut hashed = v.clone();
+/// sanitize::anonymize(&mut hashed, sanitize::IdAnonymization::Hash(42));
+/// assert!(hashed[0].get_osm_id() != 3627273522);
+/// assert!(hashed[0].get_osm_id() >= 0);
+/// assert!(hashed[0].get_osm_id() == sanitize::hash_id(3627273522, 42));
+/// ```
+///
+pub fn anonymize(labels: &mut [Label], mode: IdAnonymization) {
+    match mode {
+        IdAnonymization::Keep => {}
+        IdAnonymization::Renumber => {
+            for (idx, l) in labels.iter_mut().enumerate() {
+                l.set_osm_id(idx as i64 + 1);
+            }
+        }
+        IdAnonymization::Hash(salt) => {
+            for l in labels.iter_mut() {
+                let id = hash_id(l.get_osm_id(), salt);
+                l.set_osm_id(id);
+            }
+        }
+    }
+}
+
+///
+/// Compute the salted hash of an osm id.
+///
+/// The hash is a 64 bit FNV-1a hash of the salt and the id truncated to a non negative i64. It
+/// does not depend on the platform or the compiler version.
+///
+pub fn hash_id(osm_id: i64, salt: u64) -> i64 {
+    const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+
+    let mut hash = FNV_OFFSET;
+    let salt_bytes = salt.to_le_bytes();
+    let id_bytes = osm_id.to_le_bytes();
+    for b in salt_bytes.iter().chain(id_bytes.iter()) {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+
+    (hash & (i64::MAX as u64)) as i64
+}
@@ -16,11 +16,14 @@ class C_Label(Structure):
                 ("t", c_double),
                 ("osm_id", c_long),
                 ("prio", c_int),
+                ("lbl_fac", c_double),
+                ("label_length", c_double),
                 ("label", c_char_p)]
     
 class C_Result(Structure):
     _fields_ = [("size", c_uint64),
-                ("data", POINTER(C_Label))]
+                ("data", POINTER(C_Label)),
+                ("generation", c_uint64)]
     
 class Label:
     def __init__(self, l):
@@ -30,6 +33,8 @@ class Label:
         
         self.osm_id = l.osm_id
         self.prio = l.prio
+        self.lbl_fac = l.lbl_fac
+        self.label_length = l.label_length
         self.label = l.label
         
     def to_string(self):